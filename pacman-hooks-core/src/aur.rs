@@ -0,0 +1,231 @@
+//! AUR RPC client, with a cache of responses shared by all clients of the process, and saved on disk
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+
+use crate::{
+    baseline::STATE_DIR,
+    cache::LookupCache,
+    http::{self, url_encode},
+    json::{self, Value},
+};
+
+/// AUR RPC interface base URL
+const RPC_URL: &str = "https://aur.archlinux.org/rpc/v5";
+
+/// Maximum number of packages per info request, to keep URLs short
+const INFO_BATCH_SIZE: usize = 100;
+
+/// Minimum time between requests, to be gentle with the AUR
+const MIN_REQUEST_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Default time responses are cached
+const DEFAULT_CACHE_TTL: Duration = Duration::from_hours(6);
+
+/// Cache of default clients, so that the AUR check and rebuild suggestions share responses
+static SHARED_CACHE: OnceLock<Arc<LookupCache>> = OnceLock::new();
+
+/// Time of the last request of all clients of the process, for rate limiting
+static LAST_REQUEST: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Package metadata from the AUR
+#[derive(Clone, Debug, PartialEq)]
+pub struct AurPackage {
+    name: String,
+    version: String,
+    maintainer: Option<String>,
+    /// When the package was flagged out of date, as a Unix timestamp
+    out_of_date: Option<u64>,
+}
+
+impl AurPackage {
+    fn from_json(value: &Value) -> Option<Self> {
+        let timestamp = match value.get("OutOfDate") {
+            #[expect(
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss,
+                reason = "timestamps are positive integers"
+            )]
+            Some(Value::Number(t)) => Some(*t as u64),
+            _ => None,
+        };
+        Some(Self {
+            name: value.get("Name")?.as_str()?.to_owned(),
+            version: value.get("Version")?.as_str()?.to_owned(),
+            maintainer: value
+                .get("Maintainer")
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned),
+            out_of_date: timestamp,
+        })
+    }
+
+    /// Package name
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Latest version
+    #[must_use]
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Maintainer, `None` if the package is orphaned
+    #[must_use]
+    pub fn maintainer(&self) -> Option<&str> {
+        self.maintainer.as_deref()
+    }
+
+    /// When the package was flagged out of date, as a Unix timestamp
+    #[must_use]
+    pub fn out_of_date(&self) -> Option<u64> {
+        self.out_of_date
+    }
+}
+
+/// Client of the AUR RPC interface
+pub struct AurClient {
+    cache: Arc<LookupCache>,
+    cache_ttl: Duration,
+}
+
+impl Default for AurClient {
+    fn default() -> Self {
+        let cache = SHARED_CACHE
+            .get_or_init(|| Arc::new(LookupCache::new(PathBuf::from(STATE_DIR).join("aur"))));
+        Self {
+            cache: Arc::clone(cache),
+            cache_ttl: DEFAULT_CACHE_TTL,
+        }
+    }
+}
+
+impl AurClient {
+    /// Create a client caching responses in `cache_dir` for `cache_ttl`
+    #[must_use]
+    pub fn new(cache_dir: PathBuf, cache_ttl: Duration) -> Self {
+        Self {
+            cache: Arc::new(LookupCache::new(cache_dir)),
+            cache_ttl,
+        }
+    }
+
+    /// Send a request, and parse the response
+    fn request(path: &str) -> anyhow::Result<Value> {
+        // Clients run concurrently, like the AUR check and rebuild suggestions
+        if let Ok(mut last_request) = LAST_REQUEST.lock() {
+            if let Some(wait) =
+                last_request.and_then(|t| MIN_REQUEST_INTERVAL.checked_sub(t.elapsed()))
+            {
+                thread::sleep(wait);
+            }
+            *last_request = Some(Instant::now());
+        }
+        let response =
+            http::download(&format!("{RPC_URL}/{path}")).context("AUR request failed")?;
+        let response =
+            json::parse(&String::from_utf8_lossy(&response)).context("Invalid AUR response")?;
+        if let Some(error) = response.get("error").and_then(Value::as_str) {
+            anyhow::bail!("AUR error: {error}");
+        }
+        Ok(response)
+    }
+
+    /// Get metadata of packages, packages missing from the result do not exist in the AUR
+    ///
+    /// # Errors
+    ///
+    /// Fails if the AUR can not be queried, or returns an invalid response
+    pub fn info(&self, names: &[String]) -> anyhow::Result<HashMap<String, AurPackage>> {
+        let mut packages = HashMap::new();
+        let mut uncached = Vec::new();
+        for name in names {
+            match self.cache.get("info", name, self.cache_ttl) {
+                Some(Value::Null) => {}
+                Some(value) => {
+                    if let Some(package) = AurPackage::from_json(&value) {
+                        packages.insert(name.to_owned(), package);
+                    }
+                }
+                None => uncached.push(name),
+            }
+        }
+        for batch in uncached.chunks(INFO_BATCH_SIZE) {
+            let query = batch
+                .iter()
+                .map(|n| format!("arg[]={}", url_encode(n)))
+                .collect::<Vec<_>>()
+                .join("&");
+            let response = Self::request(&format!("info?{query}"))?;
+            let Some(Value::Array(results)) = response.get("results") else {
+                anyhow::bail!("Missing results in AUR response");
+            };
+            for result in results {
+                if let Some(package) = AurPackage::from_json(result) {
+                    self.cache.set("info", &package.name, result.clone());
+                    packages.insert(package.name.clone(), package);
+                }
+            }
+            // Also cache that packages do not exist
+            for name in batch.iter().filter(|n| !packages.contains_key(n.as_str())) {
+                self.cache.set("info", name, Value::Null);
+            }
+        }
+        Ok(packages)
+    }
+
+    /// Search packages by name
+    ///
+    /// # Errors
+    ///
+    /// Fails if the AUR can not be queried, or returns an invalid response
+    pub fn search(&self, term: &str) -> anyhow::Result<Vec<AurPackage>> {
+        let response = if let Some(value) = self.cache.get("search", term, self.cache_ttl) {
+            value
+        } else {
+            let response = Self::request(&format!("search/{}?by=name", url_encode(term)))?;
+            self.cache.set("search", term, response.clone());
+            response
+        };
+        let Some(Value::Array(results)) = response.get("results") else {
+            anyhow::bail!("Missing results in AUR response");
+        };
+        Ok(results.iter().filter_map(AurPackage::from_json).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_info_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = AurClient::new(dir.path().to_owned(), Duration::from_mins(1));
+        let response = json::parse(
+            r#"{"Name":"foo-git","Version":"1.0.r2-1","Maintainer":null,"OutOfDate":1700000000}"#,
+        )
+        .unwrap();
+        client.cache.set("info", "foo-git", response);
+        client.cache.set("info", "bar", Value::Null);
+
+        // Everything is cached, so no request is made
+        let packages = client
+            .info(&["foo-git".to_owned(), "bar".to_owned()])
+            .unwrap();
+        assert_eq!(packages.len(), 1);
+        let foo = &packages["foo-git"];
+        assert_eq!(foo.version(), "1.0.r2-1");
+        assert_eq!(foo.maintainer(), None);
+        assert_eq!(foo.out_of_date(), Some(1_700_000_000));
+    }
+}
@@ -0,0 +1,1032 @@
+//! Package database queries
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs,
+    io::{self, BufRead, BufReader},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    cancel,
+    error::{Classify as _, ErrorKind},
+    localdb::{dependency_name, LocalDb},
+    privileges, root, syncdb,
+};
+
+/// Maximum number of attempts of a query failing because the database is busy
+const MAX_QUERY_ATTEMPTS: u32 = 3;
+
+/// Delay before retrying a failed query, doubled after each attempt
+const FIRST_QUERY_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// File of an installed package
+pub struct PackageFile {
+    path: PathBuf,
+    /// Whether the package records the file as executable, if known
+    executable: Option<bool>,
+    /// Size the package records for the file, if known
+    size: Option<u64>,
+}
+
+impl PackageFile {
+    /// Create a package file, with its executable state recorded by the package if known
+    #[must_use]
+    pub fn new(path: PathBuf, executable: Option<bool>) -> Self {
+        Self {
+            path,
+            executable,
+            size: None,
+        }
+    }
+
+    /// Set the size the package records for the file
+    #[must_use]
+    pub fn with_size(mut self, size: Option<u64>) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Installed file path
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Size the package records for the file, if known
+    #[must_use]
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    /// Get the installed file path, consuming the file
+    #[must_use]
+    pub fn into_path(self) -> PathBuf {
+        self.path
+    }
+}
+
+/// Source of installed package information
+pub trait PackageBackend: Send + Sync {
+    /// Get names of foreign packages, typically installed from the AUR
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package database can not be queried
+    fn foreign_packages(&self) -> anyhow::Result<Vec<String>>;
+
+    /// Get names of all installed packages
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package database can not be queried
+    fn installed_packages(&self) -> anyhow::Result<Vec<String>>;
+
+    /// Stream the files of an installed package to a callback
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package is not installed, or its file list can not be read
+    fn for_each_file(
+        &self,
+        package: &str,
+        callback: &mut dyn FnMut(PackageFile),
+    ) -> anyhow::Result<()>;
+
+    /// Get names of packages owning a path
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package database can not be queried
+    fn owners(&self, path: &Path) -> anyhow::Result<Vec<String>>;
+
+    /// Get installed version of a package, `None` if it is not installed
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package database can not be queried
+    fn version(&self, package: &str) -> anyhow::Result<Option<String>>;
+
+    /// Get the Unix time an installed package was built at, `None` if it is not installed or unknown
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package database can not be queried
+    fn build_date(&self, package: &str) -> anyhow::Result<Option<u64>>;
+
+    /// Get the Unix time a package was installed or last upgraded at, `None` if it is not installed or unknown
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package database can not be queried
+    fn install_date(&self, package: &str) -> anyhow::Result<Option<u64>>;
+
+    /// Get configuration files of all installed packages, that pacman backs up on upgrade
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package database can not be queried
+    fn backup_files(&self) -> anyhow::Result<Vec<PathBuf>>;
+
+    /// Get names of dependencies of an installed package
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package database can not be queried
+    fn dependencies(&self, package: &str) -> anyhow::Result<Vec<String>>;
+
+    /// Get names of optional dependencies of an installed package
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package database can not be queried
+    fn optional_dependencies(&self, package: &str) -> anyhow::Result<Vec<String>>;
+
+    /// Get names an installed package provides besides its own, like virtual packages and sonames
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package database can not be queried
+    fn provides(&self, package: &str) -> anyhow::Result<Vec<String>>;
+}
+
+/// Open the local database, or fall back to querying pacman if it can not be read
+#[must_use]
+pub fn open() -> Box<dyn PackageBackend> {
+    match LocalDb::open() {
+        Ok(db) => Box::new(db),
+        Err(err) => {
+            log::warn!("Failed to read local database, falling back to pacman: {err}");
+            Box::new(PacmanCli)
+        }
+    }
+}
+
+/// Whether a path of the checked system is a regular file with any execute bit set, not following symlinks
+#[must_use]
+pub fn is_executable_file(path: &Path) -> bool {
+    fs::symlink_metadata(root::resolve(path))
+        .is_ok_and(|m| m.file_type().is_file() && ((m.permissions().mode() & 0o111) != 0))
+}
+
+/// Stream the executable files of a package to a callback.
+///
+/// Files the package records as not executable are skipped, the others are checked on disk,
+/// because the installed file may have been modified since.
+///
+/// # Errors
+///
+/// Fails if the file list of the package can not be read
+pub fn for_each_package_executable_file<F>(
+    backend: &dyn PackageBackend,
+    package: &str,
+    mut callback: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(PackageFile),
+{
+    backend.for_each_file(package, &mut |file| {
+        if file.executable != Some(false) && is_executable_file(&file.path) {
+            callback(file);
+        }
+    })
+}
+
+/// Get installed packages depending on any of `packages`, optionally or not, sorted.
+///
+/// Dependencies on names `packages` provide match too, those of installed ones from `backend`, and `provides`
+/// for the others, like removed ones.
+///
+/// # Errors
+///
+/// Fails if the package database can not be queried
+pub fn reverse_dependencies(
+    backend: &dyn PackageBackend,
+    packages: &[String],
+    provides: &[String],
+) -> anyhow::Result<Vec<String>> {
+    let mut names: HashSet<&str> = packages
+        .iter()
+        .chain(provides)
+        .map(String::as_str)
+        .collect();
+    let mut installed_provides = Vec::new();
+    for package in packages {
+        if backend.version(package)?.is_some() {
+            installed_provides.extend(backend.provides(package)?);
+        }
+    }
+    names.extend(installed_provides.iter().map(String::as_str));
+
+    let mut dependents = Vec::new();
+    for package in backend.installed_packages()? {
+        if packages.contains(&package) {
+            continue;
+        }
+        let mut dependencies = backend.dependencies(&package)?;
+        dependencies.extend(backend.optional_dependencies(&package)?);
+        if dependencies.iter().any(|d| names.contains(d.as_str())) {
+            dependents.push(package);
+        }
+    }
+    dependents.sort_unstable();
+    Ok(dependents)
+}
+
+/// Whether a failed query may succeed later, because a pacman transaction was writing the database
+fn is_transient(err: &anyhow::Error) -> bool {
+    ErrorKind::of(err) == Some(ErrorKind::DbBusy)
+        || err
+            .chain()
+            .filter_map(|e| e.downcast_ref::<io::Error>())
+            .any(|e| {
+                matches!(
+                    e.kind(),
+                    io::ErrorKind::Interrupted
+                        | io::ErrorKind::WouldBlock
+                        | io::ErrorKind::ResourceBusy
+                        | io::ErrorKind::TimedOut
+                )
+            })
+}
+
+/// Delay before query attempt number `attempt`, counting from 1
+fn query_retry_delay(attempt: u32) -> Duration {
+    FIRST_QUERY_RETRY_DELAY * 2_u32.pow(attempt.saturating_sub(2))
+}
+
+/// Queries of another backend, retried when the database is momentarily busy.
+///
+/// Packages whose queries still fail after the last attempt are recorded as skipped, so that reports tell they are
+/// incomplete.
+pub struct RetryingBackend {
+    inner: Arc<dyn PackageBackend>,
+    /// Packages whose queries failed because the database stayed busy
+    skipped: Mutex<BTreeSet<String>>,
+}
+
+impl RetryingBackend {
+    /// Retry the queries of `inner`
+    pub fn new(inner: Arc<dyn PackageBackend>) -> Self {
+        Self {
+            inner,
+            skipped: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// Get the packages skipped because the database stayed busy, sorted
+    pub fn skipped(&self) -> Vec<String> {
+        self.skipped
+            .lock()
+            .map(|s| s.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Run a query with [`retry_while_busy`], recording `package` as skipped if its attempts are exhausted
+    fn retry<T>(
+        &self,
+        package: Option<&str>,
+        query: impl FnMut() -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        retry_while_busy(query).inspect_err(|err| {
+            // Errors that are not transient are not retried, and are not classified as busy
+            if let Some(package) = package.filter(|_| ErrorKind::of(err) == Some(ErrorKind::DbBusy))
+            {
+                log::warn!("Skipping package {package:?}, the package database is busy: {err:#}");
+                if let Ok(mut skipped) = self.skipped.lock() {
+                    skipped.insert(package.to_owned());
+                }
+            }
+        })
+    }
+}
+
+/// Run a query until it succeeds, fails with an error that is not transient, or its attempts are exhausted, in which
+/// case the error is classified as [`ErrorKind::DbBusy`]
+///
+/// # Errors
+///
+/// Fails with the error of the last attempt, or the first one that is not transient
+pub fn retry_while_busy<T, F>(mut query: F) -> anyhow::Result<T>
+where
+    F: FnMut() -> anyhow::Result<T>,
+{
+    let mut attempt = 1;
+    loop {
+        let error = match query() {
+            Ok(value) => return Ok(value),
+            Err(err) if !is_transient(&err) => return Err(err),
+            Err(err) => err,
+        };
+        if attempt == MAX_QUERY_ATTEMPTS || cancel::requested() {
+            return Err(error.context(format!(
+                "Package database still busy after {attempt} attempt(s)"
+            )))
+            .classify(ErrorKind::DbBusy);
+        }
+        attempt += 1;
+        let delay = query_retry_delay(attempt);
+        log::debug!(
+            "Package database query failed: {error:#}, retrying in {}ms",
+            delay.as_millis()
+        );
+        thread::sleep(delay);
+    }
+}
+
+impl PackageBackend for RetryingBackend {
+    fn foreign_packages(&self) -> anyhow::Result<Vec<String>> {
+        self.retry(None, || self.inner.foreign_packages())
+    }
+
+    fn installed_packages(&self) -> anyhow::Result<Vec<String>> {
+        self.retry(None, || self.inner.installed_packages())
+    }
+
+    fn for_each_file(
+        &self,
+        package: &str,
+        callback: &mut dyn FnMut(PackageFile),
+    ) -> anyhow::Result<()> {
+        // Files are listed in the same order by each attempt, the ones streamed by a failed attempt are skipped by the
+        // next one, so that they are not streamed twice without buffering file lists
+        let mut streamed = 0;
+        self.retry(Some(package), || {
+            let mut index = 0;
+            self.inner.for_each_file(package, &mut |f| {
+                if index == streamed {
+                    callback(f);
+                    streamed += 1;
+                }
+                index += 1;
+            })
+        })
+    }
+
+    fn owners(&self, path: &Path) -> anyhow::Result<Vec<String>> {
+        self.retry(None, || self.inner.owners(path))
+    }
+
+    fn version(&self, package: &str) -> anyhow::Result<Option<String>> {
+        self.retry(Some(package), || self.inner.version(package))
+    }
+
+    fn build_date(&self, package: &str) -> anyhow::Result<Option<u64>> {
+        self.retry(Some(package), || self.inner.build_date(package))
+    }
+
+    fn install_date(&self, package: &str) -> anyhow::Result<Option<u64>> {
+        self.retry(Some(package), || self.inner.install_date(package))
+    }
+
+    fn backup_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        self.retry(None, || self.inner.backup_files())
+    }
+
+    fn dependencies(&self, package: &str) -> anyhow::Result<Vec<String>> {
+        self.retry(Some(package), || self.inner.dependencies(package))
+    }
+
+    fn optional_dependencies(&self, package: &str) -> anyhow::Result<Vec<String>> {
+        self.retry(Some(package), || self.inner.optional_dependencies(package))
+    }
+
+    fn provides(&self, package: &str) -> anyhow::Result<Vec<String>> {
+        self.retry(Some(package), || self.inner.provides(package))
+    }
+}
+
+/// Create a pacman command running an operation on the checked system
+fn pacman(args: &[&str]) -> Command {
+    let mut command = privileges::command("pacman");
+    command.args(args).args(root::pacman_args());
+    command
+}
+
+/// Queries by running the pacman command
+pub struct PacmanCli;
+
+impl PackageBackend for PacmanCli {
+    fn foreign_packages(&self) -> anyhow::Result<Vec<String>> {
+        let output = pacman(&["-Qqm"])
+            .env("LANG", "C")
+            .output()
+            .classify(ErrorKind::PacmanQuery)?;
+
+        output
+            .stdout
+            .lines()
+            .collect::<Result<Vec<String>, _>>()
+            .classify(ErrorKind::PacmanQuery)
+    }
+
+    fn installed_packages(&self) -> anyhow::Result<Vec<String>> {
+        let output = pacman(&["-Qq"])
+            .env("LANG", "C")
+            .output()
+            .classify(ErrorKind::PacmanQuery)?;
+
+        output
+            .stdout
+            .lines()
+            .collect::<Result<Vec<String>, _>>()
+            .classify(ErrorKind::PacmanQuery)
+    }
+
+    fn for_each_file(
+        &self,
+        package: &str,
+        callback: &mut dyn FnMut(PackageFile),
+    ) -> anyhow::Result<()> {
+        let mut child = pacman(&["-Ql", package])
+            .env("LANG", "C")
+            .stdout(Stdio::piped())
+            .spawn()
+            .classify(ErrorKind::PacmanQuery)?;
+
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get pacman stdout"))
+            .classify(ErrorKind::PacmanQuery)?;
+        let mut unparsed = Vec::new();
+        for line in BufReader::new(stdout).lines() {
+            let line = line.classify(ErrorKind::PacmanQuery)?;
+            match file_list_path(package, &line) {
+                Some(path) => callback(PackageFile::new(PathBuf::from(path), None)),
+                None => unparsed.push(line),
+            }
+        }
+        if let Some(first) = unparsed.first() {
+            log::warn!(
+                "Ignored {} unexpected line(s) listing files of package {package:?} with pacman, first one: {first:?}",
+                unparsed.len()
+            );
+        }
+
+        if !child.wait().classify(ErrorKind::PacmanQuery)?.success() {
+            return Err(anyhow::anyhow!(
+                "Failed to list files for package {package:?} with pacman"
+            ))
+            .classify(ErrorKind::PacmanQuery);
+        }
+
+        Ok(())
+    }
+
+    fn owners(&self, path: &Path) -> anyhow::Result<Vec<String>> {
+        // pacman expects paths below its root
+        let output = pacman(&["-Qoq"])
+            .arg(root::resolve(path))
+            .env("LANG", "C")
+            .output()
+            .classify(ErrorKind::PacmanQuery)?;
+
+        output
+            .stdout
+            .lines()
+            .collect::<Result<Vec<String>, _>>()
+            .classify(ErrorKind::PacmanQuery)
+    }
+
+    fn version(&self, package: &str) -> anyhow::Result<Option<String>> {
+        let output = pacman(&["-Qi", package])
+            .env("LANG", "C")
+            .output()
+            .classify(ErrorKind::PacmanQuery)?;
+
+        if !output.status.success() {
+            return Ok(None);
+        }
+
+        let version = output
+            .stdout
+            .lines()
+            .map_while(Result::ok)
+            .find_map(|l| {
+                let (key, value) = l.split_once(':')?;
+                (key.trim_end() == "Version").then(|| value.trim().to_owned())
+            })
+            .ok_or_else(|| anyhow::anyhow!("Unexpected pacman output: unable to find version line"))
+            .classify(ErrorKind::PacmanQuery)?;
+        Ok(Some(version))
+    }
+
+    // Dates are printed in the local time zone and format, which can not be parsed reliably
+    fn build_date(&self, _package: &str) -> anyhow::Result<Option<u64>> {
+        Ok(None)
+    }
+
+    fn install_date(&self, _package: &str) -> anyhow::Result<Option<u64>> {
+        Ok(None)
+    }
+
+    fn backup_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let output = pacman(&["-Qii"])
+            .env("LANG", "C")
+            .output()
+            .classify(ErrorKind::PacmanQuery)?;
+
+        // Backup files are listed after the package information, as "<STATUS>\t<path>"
+        let mut files = Vec::new();
+        for line in output.stdout.lines() {
+            if let Some((_status, path)) = line
+                .classify(ErrorKind::PacmanQuery)?
+                .split_once('\t')
+                .filter(|(s, _)| matches!(*s, "MODIFIED" | "UNMODIFIED" | "MISSING" | "UNREADABLE"))
+            {
+                files.push(PathBuf::from(path));
+            }
+        }
+        Ok(files)
+    }
+
+    fn dependencies(&self, package: &str) -> anyhow::Result<Vec<String>> {
+        // Dependencies are separated by spaces
+        Ok(info_field(package, "Depends On")?
+            .iter()
+            .flat_map(|l| l.split_whitespace())
+            .map(|d| dependency_name(d).to_owned())
+            .collect())
+    }
+
+    fn optional_dependencies(&self, package: &str) -> anyhow::Result<Vec<String>> {
+        // Optional dependencies are one per line
+        Ok(info_field(package, "Optional Deps")?
+            .iter()
+            .map(|l| dependency_name(l.trim_end_matches(" [installed]")).to_owned())
+            .collect())
+    }
+
+    fn provides(&self, package: &str) -> anyhow::Result<Vec<String>> {
+        // Provided names are separated by spaces, and may have a version like dependencies
+        Ok(info_field(package, "Provides")?
+            .iter()
+            .flat_map(|l| l.split_whitespace())
+            .map(|p| dependency_name(p).to_owned())
+            .collect())
+    }
+}
+
+/// Get the path of a `pacman -Ql` output line, `None` if it is not a file of `package`.
+///
+/// Lines are the package name and the path, which may contain spaces.
+fn file_list_path<'a>(package: &str, line: &'a str) -> Option<&'a str> {
+    line.strip_prefix(package)?
+        .strip_prefix(' ')
+        .filter(|p| p.starts_with('/'))
+}
+
+/// Get value lines of a `pacman -Qi` field of an installed package, empty if the field is "None"
+fn info_field(package: &str, field: &str) -> anyhow::Result<Vec<String>> {
+    let output = pacman(&["-Qi", package])
+        .env("LANG", "C")
+        .output()
+        .classify(ErrorKind::PacmanQuery)?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("Package {package:?} is not installed"))
+            .classify(ErrorKind::PacmanQuery);
+    }
+
+    // The value is continued on indented lines
+    let mut lines = Vec::new();
+    let mut in_field = false;
+    for line in output.stdout.lines().map_while(Result::ok) {
+        let value = match line.split_once(':').filter(|(k, _)| !k.starts_with(' ')) {
+            Some((key, value)) => {
+                in_field = key.trim_end() == field;
+                value
+            }
+            None => &line,
+        };
+        let value = value.trim();
+        if in_field && !value.is_empty() && value != "None" {
+            lines.push(value.to_owned());
+        }
+    }
+    Ok(lines)
+}
+
+/// Direct reading of the database files, as libalpm does
+impl PackageBackend for LocalDb {
+    fn foreign_packages(&self) -> anyhow::Result<Vec<String>> {
+        let sync_packages = match syncdb::package_names() {
+            Ok(names) => names,
+            Err(err) => {
+                log::debug!("Failed to read sync databases, falling back to pacman: {err}");
+                return PacmanCli.foreign_packages();
+            }
+        };
+        let mut packages: Vec<String> = self
+            .packages()
+            .filter(|p| !sync_packages.contains(*p))
+            .map(ToOwned::to_owned)
+            .collect();
+        packages.sort_unstable();
+        Ok(packages)
+    }
+
+    fn installed_packages(&self) -> anyhow::Result<Vec<String>> {
+        let mut packages: Vec<String> = self.packages().map(ToOwned::to_owned).collect();
+        packages.sort_unstable();
+        Ok(packages)
+    }
+
+    fn for_each_file(
+        &self,
+        package: &str,
+        callback: &mut dyn FnMut(PackageFile),
+    ) -> anyhow::Result<()> {
+        if let Some(entries) = self.mtree(package)? {
+            for entry in entries {
+                let executable = entry.is_executable_file();
+                callback(
+                    PackageFile::new(entry.path().to_owned(), Some(executable))
+                        .with_size(entry.size()),
+                );
+            }
+            return Ok(());
+        }
+        let files = self
+            .files(package)?
+            .ok_or_else(|| anyhow::anyhow!("Package {package:?} is not installed"))?;
+        for file in files {
+            callback(PackageFile::new(PathBuf::from(file), None));
+        }
+        Ok(())
+    }
+
+    fn owners(&self, path: &Path) -> anyhow::Result<Vec<String>> {
+        let path = path.to_string_lossy();
+        let path = path.trim_end_matches('/');
+        let mut owners = Vec::new();
+        for package in self.packages() {
+            if self
+                .files(package)?
+                .unwrap_or_default()
+                .iter()
+                .any(|f| f.trim_end_matches('/') == path)
+            {
+                owners.push(package.to_owned());
+            }
+        }
+        owners.sort_unstable();
+        Ok(owners)
+    }
+
+    fn version(&self, package: &str) -> anyhow::Result<Option<String>> {
+        self.package_version(package)
+    }
+
+    fn build_date(&self, package: &str) -> anyhow::Result<Option<u64>> {
+        self.package_date(package, "BUILDDATE")
+    }
+
+    fn install_date(&self, package: &str) -> anyhow::Result<Option<u64>> {
+        self.package_date(package, "INSTALLDATE")
+    }
+
+    fn dependencies(&self, package: &str) -> anyhow::Result<Vec<String>> {
+        self.package_dependencies(package)
+    }
+
+    fn optional_dependencies(&self, package: &str) -> anyhow::Result<Vec<String>> {
+        self.package_optional_dependencies(package)
+    }
+
+    fn provides(&self, package: &str) -> anyhow::Result<Vec<String>> {
+        self.package_provides(package)
+    }
+
+    fn backup_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for package in self.packages() {
+            files.extend(self.package_backup_files(package)?);
+        }
+        files.sort_unstable();
+        Ok(files)
+    }
+}
+
+/// In memory package database, for tests
+#[derive(Default)]
+pub struct MemoryBackend {
+    packages: BTreeMap<String, MemoryPackage>,
+    backup_files: Vec<PathBuf>,
+}
+
+/// Package of a [`MemoryBackend`]
+struct MemoryPackage {
+    version: String,
+    foreign: bool,
+    files: Vec<PathBuf>,
+    dependencies: Vec<String>,
+    optional_dependencies: Vec<String>,
+    provides: Vec<String>,
+    build_date: Option<u64>,
+    install_date: Option<u64>,
+}
+
+impl MemoryBackend {
+    /// Add an installed package
+    #[must_use]
+    pub fn with_package(
+        mut self,
+        name: &str,
+        version: &str,
+        foreign: bool,
+        files: &[&str],
+    ) -> Self {
+        self.packages.insert(
+            name.to_owned(),
+            MemoryPackage {
+                version: version.to_owned(),
+                foreign,
+                files: files.iter().map(PathBuf::from).collect(),
+                dependencies: Vec::new(),
+                optional_dependencies: Vec::new(),
+                provides: Vec::new(),
+                build_date: None,
+                install_date: None,
+            },
+        );
+        self
+    }
+
+    /// Add a dependency to a package added before
+    #[must_use]
+    pub fn with_dependency(mut self, package: &str, dependency: &str) -> Self {
+        if let Some(p) = self.packages.get_mut(package) {
+            p.dependencies.push(dependency.to_owned());
+        }
+        self
+    }
+
+    /// Add an optional dependency to a package added before
+    #[must_use]
+    pub fn with_optional_dependency(mut self, package: &str, dependency: &str) -> Self {
+        if let Some(p) = self.packages.get_mut(package) {
+            p.optional_dependencies.push(dependency.to_owned());
+        }
+        self
+    }
+
+    /// Add a name provided by a package added before
+    #[must_use]
+    pub fn with_provide(mut self, package: &str, name: &str) -> Self {
+        if let Some(p) = self.packages.get_mut(package) {
+            p.provides.push(name.to_owned());
+        }
+        self
+    }
+
+    /// Set the build and install Unix times of a package added before
+    #[must_use]
+    pub fn with_dates(mut self, package: &str, build_date: u64, install_date: u64) -> Self {
+        if let Some(p) = self.packages.get_mut(package) {
+            p.build_date = Some(build_date);
+            p.install_date = Some(install_date);
+        }
+        self
+    }
+
+    /// Add a configuration file backed up on upgrade
+    #[must_use]
+    pub fn with_backup_file(mut self, path: &Path) -> Self {
+        self.backup_files.push(path.to_owned());
+        self
+    }
+}
+
+impl PackageBackend for MemoryBackend {
+    fn foreign_packages(&self) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .packages
+            .iter()
+            .filter(|(_, p)| p.foreign)
+            .map(|(n, _)| n.to_owned())
+            .collect())
+    }
+
+    fn installed_packages(&self) -> anyhow::Result<Vec<String>> {
+        Ok(self.packages.keys().cloned().collect())
+    }
+
+    fn for_each_file(
+        &self,
+        package: &str,
+        callback: &mut dyn FnMut(PackageFile),
+    ) -> anyhow::Result<()> {
+        let package = self
+            .packages
+            .get(package)
+            .ok_or_else(|| anyhow::anyhow!("Package {package:?} is not installed"))?;
+        for file in &package.files {
+            callback(PackageFile::new(file.to_owned(), None));
+        }
+        Ok(())
+    }
+
+    fn owners(&self, path: &Path) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .packages
+            .iter()
+            .filter(|(_, p)| p.files.iter().any(|f| f == path))
+            .map(|(n, _)| n.to_owned())
+            .collect())
+    }
+
+    fn version(&self, package: &str) -> anyhow::Result<Option<String>> {
+        Ok(self.packages.get(package).map(|p| p.version.clone()))
+    }
+
+    fn build_date(&self, package: &str) -> anyhow::Result<Option<u64>> {
+        Ok(self.packages.get(package).and_then(|p| p.build_date))
+    }
+
+    fn install_date(&self, package: &str) -> anyhow::Result<Option<u64>> {
+        Ok(self.packages.get(package).and_then(|p| p.install_date))
+    }
+
+    fn backup_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        Ok(self.backup_files.clone())
+    }
+
+    fn dependencies(&self, package: &str) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .packages
+            .get(package)
+            .map(|p| p.dependencies.clone())
+            .unwrap_or_default())
+    }
+
+    fn optional_dependencies(&self, package: &str) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .packages
+            .get(package)
+            .map(|p| p.optional_dependencies.clone())
+            .unwrap_or_default())
+    }
+
+    fn provides(&self, package: &str) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .packages
+            .get(package)
+            .map(|p| p.provides.clone())
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reverse_dependencies() {
+        let backend = MemoryBackend::default()
+            .with_package("icu", "75.1-1", false, &[])
+            .with_package("foo", "1.0-1", true, &[])
+            .with_dependency("foo", "icu")
+            .with_package("bar", "1.0-1", true, &[])
+            .with_optional_dependency("bar", "libbaz")
+            .with_package("qux", "1.0-1", true, &[])
+            .with_dependency("qux", "glibc")
+            .with_provide("icu", "libicuuc.so")
+            .with_package("quux", "1.0-1", true, &[])
+            .with_dependency("quux", "libicuuc.so")
+            .with_package("corge", "1.0-1", true, &[])
+            .with_dependency("corge", "java-runtime");
+        assert_eq!(
+            reverse_dependencies(&backend, &["icu".to_owned(), "libbaz".to_owned()], &[]).unwrap(),
+            ["bar", "foo", "quux"]
+        );
+        // Names provided by removed packages
+        assert_eq!(
+            reverse_dependencies(&backend, &["jre".to_owned()], &["java-runtime".to_owned()])
+                .unwrap(),
+            ["corge"]
+        );
+    }
+
+    /// Backend whose file lists fail while a transaction writes the entries of some packages
+    struct BusyBackend {
+        inner: MemoryBackend,
+        /// Package to number of file list queries still failing
+        busy: Mutex<BTreeMap<String, u32>>,
+    }
+
+    impl PackageBackend for BusyBackend {
+        fn foreign_packages(&self) -> anyhow::Result<Vec<String>> {
+            self.inner.foreign_packages()
+        }
+
+        fn installed_packages(&self) -> anyhow::Result<Vec<String>> {
+            self.inner.installed_packages()
+        }
+
+        fn for_each_file(
+            &self,
+            package: &str,
+            callback: &mut dyn FnMut(PackageFile),
+        ) -> anyhow::Result<()> {
+            // Files are streamed before the failure, like when an entry is read while being rewritten
+            self.inner.for_each_file(package, callback)?;
+            match self.busy.lock().unwrap().get_mut(package) {
+                Some(failures) if *failures > 0 => {
+                    *failures -= 1;
+                    Err(anyhow::anyhow!("Entry of {package:?} is being written"))
+                        .classify(ErrorKind::DbBusy)
+                }
+                _ => Ok(()),
+            }
+        }
+
+        fn owners(&self, path: &Path) -> anyhow::Result<Vec<String>> {
+            self.inner.owners(path)
+        }
+
+        fn version(&self, package: &str) -> anyhow::Result<Option<String>> {
+            self.inner.version(package)
+        }
+
+        fn build_date(&self, package: &str) -> anyhow::Result<Option<u64>> {
+            self.inner.build_date(package)
+        }
+
+        fn install_date(&self, package: &str) -> anyhow::Result<Option<u64>> {
+            self.inner.install_date(package)
+        }
+
+        fn backup_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+            self.inner.backup_files()
+        }
+
+        fn dependencies(&self, package: &str) -> anyhow::Result<Vec<String>> {
+            self.inner.dependencies(package)
+        }
+
+        fn optional_dependencies(&self, package: &str) -> anyhow::Result<Vec<String>> {
+            self.inner.optional_dependencies(package)
+        }
+
+        fn provides(&self, package: &str) -> anyhow::Result<Vec<String>> {
+            self.inner.provides(package)
+        }
+    }
+
+    #[test]
+    fn test_retrying_backend() {
+        let backend = RetryingBackend::new(Arc::new(BusyBackend {
+            inner: MemoryBackend::default()
+                .with_package("foo", "1.0-1", false, &["/usr/bin/foo"])
+                .with_package("bar", "1.0-1", false, &["/usr/bin/bar"]),
+            busy: Mutex::new(BTreeMap::from([
+                ("foo".to_owned(), 1),
+                ("bar".to_owned(), MAX_QUERY_ATTEMPTS),
+            ])),
+        }));
+        let mut files = Vec::new();
+        backend
+            .for_each_file("foo", &mut |f| files.push(f.into_path()))
+            .unwrap();
+        assert_eq!(files, [PathBuf::from("/usr/bin/foo")]);
+        assert!(backend.skipped().is_empty());
+
+        let mut count = 0;
+        let err = backend
+            .for_each_file("bar", &mut |_| count += 1)
+            .unwrap_err();
+        // Streamed once, before the first failure
+        assert_eq!(count, 1);
+        assert_eq!(ErrorKind::of(&err), Some(ErrorKind::DbBusy));
+        // Not transient
+        assert!(backend.for_each_file("baz", &mut |_| {}).is_err());
+        assert_eq!(backend.skipped(), ["bar"]);
+
+        assert_eq!(query_retry_delay(2), FIRST_QUERY_RETRY_DELAY);
+        assert_eq!(query_retry_delay(3), FIRST_QUERY_RETRY_DELAY * 2);
+    }
+
+    #[test]
+    fn test_file_list_path() {
+        assert_eq!(
+            file_list_path("foo", "foo /usr/bin/foo"),
+            Some("/usr/bin/foo")
+        );
+        assert_eq!(
+            file_list_path("foo", "foo /usr/share/foo/a file"),
+            Some("/usr/share/foo/a file")
+        );
+        assert_eq!(file_list_path("foo", "foobar /usr/bin/foobar"), None);
+        assert_eq!(
+            file_list_path("foo", "warning: foo: unexpected output"),
+            None
+        );
+        assert_eq!(file_list_path("foo", ""), None);
+    }
+}
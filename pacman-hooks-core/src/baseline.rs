@@ -0,0 +1,113 @@
+//! Findings of the previous run, to only report new ones
+
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{finding::Finding, root};
+
+/// Directory for persistent state
+pub const STATE_DIR: &str = "/var/lib/pacman-hooks";
+
+/// Directory in the state directory of the state of other root directories
+const ROOTS_DIRNAME: &str = "roots";
+
+/// Get the name of the state directory of another root directory, escaping its path like systemd
+fn escape_root(root: &Path) -> String {
+    root.to_string_lossy()
+        .trim_matches('/')
+        .replace('-', "\\x2d")
+        .replace('/', "-")
+}
+
+/// Get the directory of the baseline, reports and health history of the checked system, so that checks of other root
+/// directories, like containers, do not share them with the host
+#[must_use]
+pub fn root_state_dir() -> PathBuf {
+    let dir = PathBuf::from(STATE_DIR);
+    if root::is_alternate() {
+        dir.join(ROOTS_DIRNAME).join(escape_root(root::dir()))
+    } else {
+        dir
+    }
+}
+
+/// Baseline file name in state directory
+const BASELINE_FILENAME: &str = "check-broken-packages.baseline";
+
+/// Findings known from the previous run
+#[derive(Default)]
+pub struct Baseline {
+    known: HashSet<String>,
+}
+
+impl Baseline {
+    fn path() -> PathBuf {
+        root_state_dir().join(BASELINE_FILENAME)
+    }
+
+    /// Load baseline, which is empty on first run
+    ///
+    /// # Errors
+    ///
+    /// Fails if the baseline file exists but can not be read
+    pub fn load() -> anyhow::Result<Self> {
+        let known = match fs::read_to_string(Self::path()) {
+            Ok(content) => content.lines().map(ToOwned::to_owned).collect(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => HashSet::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self { known })
+    }
+
+    /// Whether a finding was already reported by the previous run
+    #[must_use]
+    pub fn contains(&self, finding: &Finding) -> bool {
+        self.known.contains(&finding.to_string())
+    }
+
+    /// Messages of the known findings, sorted
+    pub(crate) fn messages(&self) -> Vec<&str> {
+        let mut messages: Vec<_> = self.known.iter().map(String::as_str).collect();
+        messages.sort_unstable();
+        messages
+    }
+
+    /// Add the message of a known finding, returns whether it was not known yet
+    pub(crate) fn add(&mut self, message: String) -> bool {
+        self.known.insert(message)
+    }
+
+    /// Replace baseline with current findings
+    ///
+    /// # Errors
+    ///
+    /// Fails if the baseline file can not be written
+    pub fn save(findings: &[Finding]) -> anyhow::Result<()> {
+        Self::write(findings)
+    }
+
+    /// Replace baseline with these known findings
+    pub(crate) fn save_known(&self) -> anyhow::Result<()> {
+        Self::write(&self.messages())
+    }
+
+    fn write(lines: &[impl Display]) -> anyhow::Result<()> {
+        let path = Self::path();
+        fs::create_dir_all(root_state_dir())?;
+        let tmp_path = path.with_extension("tmp");
+        let mut file = io::BufWriter::new(fs::File::create(&tmp_path)?);
+        for line in lines {
+            writeln!(file, "{line}")?;
+        }
+        file.into_inner()
+            .map_err(io::IntoInnerError::into_error)?
+            .sync_all()?;
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+//! Cache of lookups in memory and on disk, so that the commands and checks of a transaction,
+//! like `predict` before it and the checks after it, do each network or files database lookup once
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    http,
+    json::{self, Value},
+};
+
+/// Values by kind and key, stored in a directory per kind, in files named after the percent encoded key
+pub(crate) struct LookupCache {
+    dir: PathBuf,
+    /// Values already read or written, with the time they were written
+    memory: Mutex<HashMap<(String, String), (SystemTime, Value)>>,
+}
+
+impl LookupCache {
+    /// Create a cache storing values in `dir`
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            memory: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn path(&self, kind: &str, key: &str) -> PathBuf {
+        self.dir.join(kind).join(http::url_encode(key))
+    }
+
+    /// Get a value, if it was written less than `ttl` ago
+    pub(crate) fn get(&self, kind: &str, key: &str, ttl: Duration) -> Option<Value> {
+        let fresh = |written: SystemTime| {
+            SystemTime::now()
+                .duration_since(written)
+                .is_ok_and(|age| age < ttl)
+        };
+        let mut memory = self.memory.lock().unwrap_or_else(PoisonError::into_inner);
+        let entry = (kind.to_owned(), key.to_owned());
+        if let Some((written, value)) = memory.get(&entry) {
+            return fresh(*written).then(|| value.clone());
+        }
+        let path = self.path(kind, key);
+        let written = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+        if !fresh(written) {
+            return None;
+        }
+        let value = json::parse(&fs::read_to_string(path).ok()?).ok()?;
+        memory.insert(entry, (written, value.clone()));
+        Some(value)
+    }
+
+    /// Set a value, failing to write it to disk is not fatal since the directory may not be writable
+    pub(crate) fn set(&self, kind: &str, key: &str, value: Value) {
+        let path = self.path(kind, key);
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&path, value.to_string()));
+        if let Err(err) = written {
+            log::debug!("Failed to cache {kind} lookup in {}: {err}", path.display());
+        }
+        self.memory
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                (kind.to_owned(), key.to_owned()),
+                (SystemTime::now(), value),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let ttl = Duration::from_mins(1);
+        let cache = LookupCache::new(dir.path().to_owned());
+        assert_eq!(cache.get("info", "foo/bar", ttl), None);
+        cache.set("info", "foo/bar", Value::Null);
+        assert_eq!(cache.get("info", "foo/bar", ttl), Some(Value::Null));
+        assert_eq!(cache.get("info", "foo/bar", Duration::ZERO), None);
+        assert!(dir.path().join("info/foo%2Fbar").is_file());
+
+        // Another cache in the same directory, like in a later command, reads it from disk
+        let later = LookupCache::new(dir.path().to_owned());
+        assert_eq!(later.get("info", "foo/bar", ttl), Some(Value::Null));
+        assert_eq!(later.get("search", "foo/bar", ttl), None);
+    }
+}
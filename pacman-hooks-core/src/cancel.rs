@@ -0,0 +1,50 @@
+//! Cancellation on SIGINT/SIGTERM
+
+use std::{
+    io,
+    sync::atomic::{AtomicI32, Ordering},
+};
+
+/// Number of the received signal, or 0
+static SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn handle_signal(signum: libc::c_int) {
+    SIGNAL.store(signum, Ordering::Relaxed);
+    // Restore default handler, so that a second signal terminates immediately
+    // SAFETY: signal is async-signal-safe
+    unsafe {
+        libc::signal(signum, libc::SIG_DFL);
+    }
+}
+
+/// Install SIGINT and SIGTERM handlers requesting cancellation
+///
+/// # Errors
+///
+/// Fails if a signal handler can not be installed
+pub fn install_handlers() -> io::Result<()> {
+    for signum in [libc::SIGINT, libc::SIGTERM] {
+        // libc represents handlers as integers
+        #[expect(clippy::fn_to_numeric_cast_any)]
+        let handler = handle_signal as *const () as libc::sighandler_t;
+        // SAFETY: the handler only does async-signal-safe operations
+        let prev = unsafe { libc::signal(signum, handler) };
+        if prev == libc::SIG_ERR {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Whether cancellation was requested
+pub fn requested() -> bool {
+    SIGNAL.load(Ordering::Relaxed) != 0
+}
+
+/// Signal that requested cancellation, if any
+pub fn signal() -> Option<i32> {
+    match SIGNAL.load(Ordering::Relaxed) {
+        0 => None,
+        signum => Some(signum),
+    }
+}
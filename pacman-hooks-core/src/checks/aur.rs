@@ -0,0 +1,82 @@
+//! Foreign packages deleted, orphaned or out of date in the AUR
+
+use super::{Check, CheckDoc, Context, Input};
+use crate::{
+    aur::AurClient,
+    backend::PackageBackend,
+    finding::{AurStatus, CheckKind, Finding},
+};
+
+/// Check foreign packages status in the AUR
+pub(super) struct AurPackages;
+
+impl Check for AurPackages {
+    fn name(&self) -> &str {
+        CheckKind::AurPackages.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::Packages]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Foreign packages deleted, orphaned or flagged out of date in the AUR".to_owned(),
+            caveats: vec!["Packages built locally, under a name that is not in the AUR, are reported as not found"],
+            config_keys: vec!["Offline", "NetworkProbeTimeout"],
+            examples: vec![Finding::AurPackage {
+                package: "foo-git".to_owned(),
+                status: AurStatus::Orphaned,
+            }],
+        }
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        // Needs network access
+        false
+    }
+
+    fn needs_network(&self) -> bool {
+        true
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        packages: &[String],
+    ) -> anyhow::Result<u64> {
+        Ok(packages.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        let aur_packages = match AurClient::default().info(ctx.packages()) {
+            Ok(aur_packages) => aur_packages,
+            Err(err) => {
+                log::error!("Failed to get AUR package information: {err:#}");
+                return Vec::new();
+            }
+        };
+        let mut findings = Vec::new();
+        for package in ctx.packages() {
+            let statuses = match aur_packages.get(package) {
+                None => vec![AurStatus::NotFound],
+                Some(aur_package) => [
+                    aur_package
+                        .maintainer()
+                        .is_none()
+                        .then_some(AurStatus::Orphaned),
+                    aur_package.out_of_date().map(|_| AurStatus::OutOfDate),
+                ]
+                .into_iter()
+                .flatten()
+                .collect(),
+            };
+            findings.extend(statuses.into_iter().map(|status| Finding::AurPackage {
+                package: package.to_owned(),
+                status,
+            }));
+            ctx.item_done();
+        }
+        findings
+    }
+}
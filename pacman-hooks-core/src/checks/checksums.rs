@@ -0,0 +1,126 @@
+//! Installed files not matching the checksums recorded in package mtree files
+
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::Context as _;
+use rayon::prelude::*;
+
+use super::{Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    cancel,
+    finding::{CheckKind, Finding},
+    localdb::LocalDb,
+    logging::LogContext,
+    root, sha256,
+};
+
+/// Check the regular files of a package against the digests of its mtree
+fn check_package(db: &LocalDb, package: &str) -> Vec<Finding> {
+    let entries = match db.mtree(package) {
+        Ok(Some(entries)) => entries,
+        Ok(None) => return Vec::new(),
+        Err(err) => {
+            log::error!("Failed to read mtree of package {package:?}: {err}");
+            return Vec::new();
+        }
+    };
+    // Configuration files are expected to be modified
+    let backup_files = db.package_backup_files(package).unwrap_or_default();
+    entries
+        .iter()
+        .filter(|e| !backup_files.iter().any(|b| b == e.path()))
+        .filter_map(|e| Some((e.path(), e.sha256()?)))
+        .take_while(|_| !cancel::requested())
+        .filter(|(path, expected)| {
+            match sha256::file_digest(&root::resolve(path)) {
+                Ok(digest) => digest != *expected,
+                Err(err) => {
+                    // Missing files are reported by pacman -Qk
+                    log::debug!("Failed to compute checksum of {path:?}: {err}");
+                    false
+                }
+            }
+        })
+        .map(|(path, _)| Finding::ChecksumMismatch {
+            package: package.to_owned(),
+            path: path.to_owned(),
+        })
+        .collect()
+}
+
+/// Check files of all installed packages for silent corruption, by computing their checksums
+#[derive(Default)]
+pub(super) struct FileChecksums {
+    db: Option<LocalDb>,
+    packages: Vec<String>,
+}
+
+impl Check for FileChecksums {
+    fn name(&self) -> &str {
+        CheckKind::FileChecksums.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Installed files whose SHA-256 checksum differs from the one recorded by their package, like after silent corruption on a failing disk".to_owned(),
+            caveats: vec![
+                "Files modified on purpose are also reported, except configuration files backed up on upgrade",
+                "All installed files are read, which is why the check is disabled by default",
+            ],
+            examples: vec![Finding::ChecksumMismatch {
+                package: "glibc".to_owned(),
+                path: PathBuf::from("/usr/lib/libc.so.6"),
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn progress_weight(&self) -> u64 {
+        // All files of each package are hashed
+        50
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        // Reads every installed file
+        false
+    }
+
+    fn timeout(&self) -> Duration {
+        // Hashing all files takes a while on slow disks
+        Duration::from_hours(2)
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        let db = LocalDb::open().context("Checksums are read from the local database")?;
+        self.packages = db.packages().map(ToOwned::to_owned).collect();
+        self.packages.sort_unstable();
+        self.db = Some(db);
+        Ok(self.packages.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        let Some(db) = &self.db else {
+            return Vec::new();
+        };
+        let log_context = LogContext::current();
+        self.packages
+            .par_iter()
+            .filter(|_| !cancel::requested())
+            .flat_map_iter(|p| {
+                let _log = log_context.clone().with_package(p).enter();
+                let findings = check_package(db, p);
+                ctx.item_done();
+                findings
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,497 @@
+//! Kernel command lines and boot loader entries referencing files that are no longer installed, like microcode
+//! images or Plymouth themes, which only break at the next boot
+
+use std::{
+    collections::HashSet,
+    fs, io,
+    os::unix::fs::MetadataExt as _,
+    path::{Path, PathBuf},
+};
+
+use glob::glob;
+
+use super::{mounts, Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    cancel,
+    finding::{BootReference, CheckKind, Finding},
+    root,
+};
+
+/// Command line of the running kernel
+const PROC_CMDLINE_PATH: &str = "/proc/cmdline";
+
+/// Command line embedded in unified kernel images
+const KERNEL_CMDLINE_PATH: &str = "/etc/kernel/cmdline";
+
+/// GRUB settings, with the command line of generated entries
+const GRUB_DEFAULT_PATH: &str = "/etc/default/grub";
+
+/// Generated GRUB configuration
+const GRUB_CONFIG_PATH: &str = "/boot/grub/grub.cfg";
+
+/// GRUB settings holding kernel parameters
+const GRUB_CMDLINE_KEYS: [&str; 2] = ["GRUB_CMDLINE_LINUX", "GRUB_CMDLINE_LINUX_DEFAULT"];
+
+/// Mount points of the filesystems that the GRUB entries of the checked system set as root
+const GRUB_ROOT_TARGETS: [&str; 2] = ["/", "/boot"];
+
+/// Usual mount points of the EFI system partition, or of the partition with the kernels
+const ESP_DIRS: [&str; 3] = ["/boot", "/efi", "/boot/efi"];
+
+/// Directories of boot loaders in a boot partition
+const BOOT_LOADER_DIRS: [&str; 3] = ["loader", "EFI", "grub"];
+
+/// Directory of Plymouth themes
+const PLYMOUTH_THEMES_DIR: &str = "/usr/share/plymouth/themes";
+
+/// File of an installed package that a boot configuration refers to
+#[derive(Debug, Eq, PartialEq)]
+struct Reference {
+    kind: BootReference,
+    /// Path or name, as written in the configuration, without the btrfs subvolume that GRUB image paths start with
+    value: String,
+}
+
+/// Menu entry of a generated GRUB configuration
+#[derive(Default)]
+struct GrubEntry<'a> {
+    /// UUID of the filesystem set as root with `search`, the one of the configuration file if not set
+    root_uuid: Option<&'a str>,
+    /// btrfs subvolume of the root filesystem, that image paths start with
+    subvolume: Option<&'a str>,
+    references: Vec<Reference>,
+}
+
+impl GrubEntry<'_> {
+    /// Get the references of the entry, `None` if it sets another filesystem than the ones of `uuids` as root
+    fn into_references(self, uuids: &[String]) -> Option<Vec<Reference>> {
+        if self
+            .root_uuid
+            .is_some_and(|u| !uuids.iter().any(|o| o == u))
+        {
+            return None;
+        }
+        let prefix = self.subvolume.map(|s| format!("/{s}/"));
+        Some(
+            self.references
+                .into_iter()
+                .map(|mut reference| {
+                    let stripped = prefix
+                        .as_deref()
+                        .filter(|_| reference.kind == BootReference::Image)
+                        .and_then(|p| reference.value.strip_prefix(p));
+                    if let Some(path) = stripped {
+                        reference.value = format!("/{path}");
+                    }
+                    reference
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Boot configuration file and its references
+#[derive(Debug, Eq, PartialEq)]
+struct BootConfig {
+    path: PathBuf,
+    /// Resolved directories that image paths are relative to, the image existing in any of them
+    image_dirs: Vec<PathBuf>,
+    references: Vec<Reference>,
+}
+
+/// Get the references of kernel parameters
+fn parameter_references(parameters: &str) -> Vec<Reference> {
+    parameters
+        .split_whitespace()
+        .filter_map(|p| {
+            let (key, value) = p.split_once('=')?;
+            let kind = match key {
+                // Set by the EFI stub of systemd-boot, with paths of the EFI system partition
+                "initrd" => BootReference::Image,
+                "init" => BootReference::Init,
+                "plymouth.theme" => BootReference::PlymouthTheme,
+                _ => return None,
+            };
+            Some(Reference {
+                kind,
+                value: value.replace('\\', "/"),
+            })
+        })
+        .collect()
+}
+
+/// Get the reference of an image path, like the ones of `initrd /intel-ucode.img /initramfs-linux.img` lines
+fn image_reference(path: &str) -> Reference {
+    Reference {
+        kind: BootReference::Image,
+        value: path.to_owned(),
+    }
+}
+
+/// Parse the GRUB settings with kernel parameters
+fn parse_grub_default(content: &str) -> Vec<Reference> {
+    content
+        .lines()
+        .filter_map(|l| l.trim().split_once('='))
+        .filter(|(key, _)| GRUB_CMDLINE_KEYS.contains(key))
+        .flat_map(|(_, value)| parameter_references(value.trim_matches(['"', '\''])))
+        .collect()
+}
+
+/// Get the references of a `linux` or `initrd` command of a GRUB entry, or of a key of a systemd-boot entry
+fn line_references(line: &str) -> Vec<Reference> {
+    let mut words = line.split_whitespace();
+    match words.next() {
+        Some("linux" | "linux16" | "linuxefi") => {
+            words.next().map(image_reference).into_iter().collect()
+        }
+        Some("initrd" | "initrd16" | "initrdefi") => words.map(image_reference).collect(),
+        // Parameters of systemd-boot entries
+        Some("options") => parameter_references(&words.collect::<Vec<_>>().join(" ")),
+        _ => Vec::new(),
+    }
+}
+
+/// Parse the keys of a systemd-boot entry
+fn parse_loader_lines(content: &str) -> Vec<Reference> {
+    content.lines().flat_map(line_references).collect()
+}
+
+/// Get the btrfs subvolume of the root filesystem set by kernel parameters, like `rootflags=subvol=@`
+fn root_subvolume(parameters: &str) -> Option<&str> {
+    parameters
+        .split_whitespace()
+        .filter_map(|p| p.strip_prefix("rootflags="))
+        .flat_map(|f| f.split(','))
+        .find_map(|o| o.strip_prefix("subvol="))
+        .map(|s| s.trim_matches('/'))
+        .filter(|s| !s.is_empty())
+}
+
+/// Parse the `linux` and `initrd` commands of the menu entries of a generated GRUB configuration, skipping the entries
+/// of other systems, like the ones added by os-prober, whose root is not one of the filesystems of `uuids`
+fn parse_grub_config(content: &str, uuids: &[String]) -> Vec<Reference> {
+    let mut references = Vec::new();
+    let mut entry: Option<GrubEntry<'_>> = None;
+    for line in content.lines().map(str::trim) {
+        if line.starts_with("menuentry ") {
+            entry = Some(GrubEntry::default());
+            continue;
+        }
+        let Some(current) = entry.as_mut() else {
+            continue;
+        };
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("}") => {
+                references.extend(
+                    entry
+                        .take()
+                        .and_then(|e| e.into_references(uuids))
+                        .into_iter()
+                        .flatten(),
+                );
+            }
+            Some("search") if line.contains("--set=root") => current.root_uuid = words.last(),
+            Some("linux" | "linux16" | "linuxefi") => {
+                current.subvolume = root_subvolume(line);
+                current.references.extend(line_references(line));
+            }
+            Some(_) => current.references.extend(line_references(line)),
+            None => {}
+        }
+    }
+    references
+}
+
+/// Read a boot configuration file, `None` if it does not exist or only root can read it
+fn read_config(path: &Path) -> Option<String> {
+    match fs::read_to_string(path) {
+        Ok(content) => Some(content),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => {
+            log::debug!("Failed to read {}: {err}", path.display());
+            None
+        }
+    }
+}
+
+/// Find the boot configurations of the checked system
+fn boot_configs() -> Vec<BootConfig> {
+    let resolve = |p: &str| root::resolve(Path::new(p));
+    let esp_dirs: Vec<_> = ESP_DIRS.map(resolve).to_vec();
+    let mut configs = Vec::new();
+    let mut add = |path: &Path, image_dirs: Vec<PathBuf>, parse: fn(&str) -> Vec<Reference>| {
+        if let Some(content) = read_config(path) {
+            configs.push(BootConfig {
+                path: root::unresolve(path),
+                image_dirs,
+                references: parse(&content),
+            });
+        }
+    };
+    // The running kernel is not the one of another root
+    if !root::is_alternate() {
+        add(
+            Path::new(PROC_CMDLINE_PATH),
+            esp_dirs.clone(),
+            parameter_references,
+        );
+    }
+    add(
+        &resolve(KERNEL_CMDLINE_PATH),
+        esp_dirs.clone(),
+        parameter_references,
+    );
+    add(
+        &resolve(GRUB_DEFAULT_PATH),
+        esp_dirs.clone(),
+        parse_grub_default,
+    );
+    // Paths are relative to the partition of /boot, which is often the root partition
+    add(
+        &resolve(GRUB_CONFIG_PATH),
+        vec![resolve("/boot"), resolve("/")],
+        |content| parse_grub_config(content, &mounts::filesystem_uuids(&GRUB_ROOT_TARGETS)),
+    );
+    for esp_dir in &esp_dirs {
+        let pattern = esp_dir.join("loader/entries/*.conf");
+        for entry in glob(&pattern.to_string_lossy())
+            .into_iter()
+            .flatten()
+            .flatten()
+        {
+            add(&entry, vec![esp_dir.clone()], parse_loader_lines);
+        }
+    }
+    configs
+}
+
+/// Get the existing image files of the references of `configs`
+fn config_images(configs: &[BootConfig]) -> HashSet<PathBuf> {
+    let mut images = HashSet::new();
+    for config in configs {
+        for reference in config
+            .references
+            .iter()
+            .filter(|r| r.kind == BootReference::Image)
+        {
+            let path = reference.value.trim_start_matches('/');
+            images.extend(
+                config
+                    .image_dirs
+                    .iter()
+                    .map(|d| d.join(path))
+                    .filter(|p| p.is_file()),
+            );
+        }
+    }
+    images
+}
+
+/// Get the resolved paths of the images that boot configurations of the checked system load, like kernels
+pub(super) fn referenced_images() -> HashSet<PathBuf> {
+    config_images(&boot_configs())
+}
+
+/// Whether a directory holds a boot partition: a mounted filesystem, or a directory with boot loader files, unlike the
+/// empty mount point of an ESP mounted on demand
+fn is_boot_partition(dir: &Path) -> bool {
+    BOOT_LOADER_DIRS.iter().any(|d| dir.join(d).is_dir())
+        || match (fs::metadata(dir), dir.parent().map(fs::metadata)) {
+            (Ok(metadata), Some(Ok(parent))) => metadata.dev() != parent.dev(),
+            (Ok(_), None) => true,
+            _ => false,
+        }
+}
+
+/// Whether the file a reference points to exists
+fn reference_exists(reference: &Reference, image_dirs: &[PathBuf]) -> bool {
+    match reference.kind {
+        BootReference::Image => {
+            let path = reference.value.trim_start_matches('/');
+            image_dirs.iter().any(|d| d.join(path).is_file())
+        }
+        BootReference::Init => root::resolve(Path::new(&reference.value)).is_file(),
+        BootReference::PlymouthTheme => root::resolve(Path::new(PLYMOUTH_THEMES_DIR))
+            .join(&reference.value)
+            .is_dir(),
+    }
+}
+
+/// Check the references of a boot configuration, each missing file being reported once
+fn check_config(config: &BootConfig) -> Vec<Finding> {
+    let mut reported = HashSet::new();
+    // Images can not be checked without their partition
+    let images_mounted = config.image_dirs.iter().any(|d| is_boot_partition(d));
+    config
+        .references
+        .iter()
+        .filter(|r| images_mounted || r.kind != BootReference::Image)
+        .filter(|r| !reference_exists(r, &config.image_dirs))
+        .filter(|r| reported.insert(&r.value))
+        .map(|r| Finding::BrokenBootReference {
+            path: config.path.clone(),
+            missing: r.value.clone(),
+            reference: r.kind,
+        })
+        .collect()
+}
+
+/// Check that kernel command lines and boot loader entries only reference installed files
+#[derive(Default)]
+pub(super) struct KernelCmdline {
+    configs: Vec<BootConfig>,
+}
+
+impl Check for KernelCmdline {
+    fn name(&self) -> &str {
+        CheckKind::KernelCmdline.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Kernel command lines and boot loader entries referencing files that are not installed anymore, like microcode, kernel and initramfs images, the init program or the Plymouth theme".to_owned(),
+            caveats: vec![
+                "Boot loader entries only readable by root are skipped when checking without privileges",
+                "Images are not checked when none of the partitions they can be on is mounted, like an ESP mounted on demand",
+                "GRUB entries of other systems, whose root is not a filesystem of fstab, are skipped",
+            ],
+            examples: vec![Finding::BrokenBootReference {
+                path: PathBuf::from("/boot/loader/entries/arch.conf"),
+                missing: "/boot/intel-ucode.img".to_owned(),
+                reference: BootReference::Image,
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        self.configs = boot_configs();
+        Ok(self.configs.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for config in self.configs.iter().take_while(|_| !cancel::requested()) {
+            findings.extend(check_config(config));
+            ctx.item_done();
+        }
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernel_cmdline() {
+        let esp = tempfile::tempdir().unwrap();
+        fs::write(esp.path().join("initramfs-linux.img"), "").unwrap();
+        fs::write(esp.path().join("vmlinuz-linux"), "").unwrap();
+        fs::create_dir(esp.path().join("loader")).unwrap();
+        let entry = BootConfig {
+            path: PathBuf::from("/boot/loader/entries/arch.conf"),
+            image_dirs: vec![esp.path().to_owned()],
+            references: parse_loader_lines(
+                "title Arch Linux
+linux /vmlinuz-linux
+initrd /intel-ucode.img
+initrd /initramfs-linux.img
+options root=UUID=1234 rw init=/usr/lib/missing-init plymouth.theme=missing-theme quiet
+",
+            ),
+        };
+        assert_eq!(entry.references.len(), 5);
+        assert_eq!(
+            check_config(&entry)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "Boot configuration /boot/loader/entries/arch.conf loads missing image /intel-ucode.img, install the package providing it or update the configuration, the next boot may fail",
+                "Boot configuration /boot/loader/entries/arch.conf sets missing init /usr/lib/missing-init, install the package providing it or update the configuration, the next boot may fail",
+                r#"Boot configuration /boot/loader/entries/arch.conf selects missing Plymouth theme "missing-theme", install the package providing it or update the configuration"#,
+            ]
+        );
+        // Without its partition
+        let unmounted = tempfile::tempdir().unwrap();
+        let cmdline = BootConfig {
+            path: PathBuf::from(PROC_CMDLINE_PATH),
+            image_dirs: vec![unmounted.path().to_owned()],
+            references: parameter_references(r"initrd=\intel-ucode.img rw"),
+        };
+        assert!(check_config(&cmdline).is_empty());
+
+        assert_eq!(
+            config_images(&[entry, cmdline]),
+            ["vmlinuz-linux", "initramfs-linux.img"]
+                .map(|f| esp.path().join(f))
+                .into()
+        );
+
+        assert_eq!(
+            parameter_references(r"initrd=\intel-ucode.img initrd=\initramfs-linux.img rw"),
+            ["/intel-ucode.img", "/initramfs-linux.img"].map(image_reference)
+        );
+        assert_eq!(
+            parse_grub_default(
+                "GRUB_TIMEOUT=5\nGRUB_CMDLINE_LINUX_DEFAULT=\"loglevel=3 plymouth.theme=bgrt\"\n"
+            ),
+            [Reference {
+                kind: BootReference::PlymouthTheme,
+                value: "bgrt".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_grub_config() {
+        let content = "search --no-floppy --fs-uuid --set=root 1111
+menuentry 'Arch Linux' --class arch {
+\tsearch --no-floppy --fs-uuid --set=root 1111
+\tlinux /@/boot/vmlinuz-linux root=UUID=1111 rw rootflags=subvol=@ loglevel=3
+\tinitrd /@/boot/intel-ucode.img /@/boot/initramfs-linux.img
+}
+submenu 'Advanced options for Arch Linux' {
+\tmenuentry 'Arch Linux, with Linux linux' {
+\t\tlinux /vmlinuz-linux root=UUID=1111 rw
+\t}
+}
+menuentry 'Windows Boot Manager (on /dev/nvme0n1p1)' {
+\tsearch --fs-uuid --set=root 2222
+\tchainloader /EFI/Microsoft/Boot/bootmgfw.efi
+}
+menuentry 'Other Linux (on /dev/sdb2)' {
+\tsearch --no-floppy --fs-uuid --set=root 3333
+\tlinux /boot/vmlinuz-linux-lts root=/dev/sdb2
+\tinitrd /boot/initramfs-linux-lts.img
+}
+";
+        assert_eq!(
+            parse_grub_config(content, &["1111".to_owned()]),
+            [
+                "/boot/vmlinuz-linux",
+                "/boot/intel-ucode.img",
+                "/boot/initramfs-linux.img",
+                "/vmlinuz-linux"
+            ]
+            .map(image_reference)
+        );
+        // Without any known filesystem, only the entries not setting their root are checked
+        assert_eq!(
+            parse_grub_config(content, &[]),
+            [image_reference("/vmlinuz-linux")]
+        );
+    }
+}
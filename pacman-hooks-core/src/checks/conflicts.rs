@@ -0,0 +1,153 @@
+//! Files owned by several installed packages, left by installs with `--overwrite`, which removing one of the
+//! packages deletes from under the others
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    os::unix::ffi::OsStrExt as _,
+    path::PathBuf,
+};
+
+use super::{Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    cancel,
+    finding::{CheckKind, Finding},
+    root,
+};
+
+/// Get the files listed by several of `packages`, with their owners in the order of `packages`.
+///
+/// Directories are shared by packages on purpose, and skipped.
+fn conflicting_files(ctx: &Context<'_>, packages: &[String]) -> BTreeMap<PathBuf, Vec<String>> {
+    let mut first_owners: HashMap<PathBuf, usize> = HashMap::new();
+    let mut conflicts: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    for (index, package) in packages
+        .iter()
+        .enumerate()
+        .take_while(|_| !cancel::requested())
+    {
+        let result = ctx.backend().for_each_file(package, &mut |file| {
+            // Directories of file lists end with a slash
+            if file.path().as_os_str().as_bytes().ends_with(b"/") {
+                return;
+            }
+            let path = file.into_path();
+            if let Some(first) = first_owners.get(&path) {
+                conflicts
+                    .entry(path)
+                    .or_insert_with(|| vec![packages[*first].clone()])
+                    .push(package.clone());
+            } else {
+                first_owners.insert(path, index);
+            }
+        });
+        if let Err(err) = result {
+            log::warn!("Failed to list files of package {package:?}: {err}");
+        }
+        ctx.item_done();
+    }
+    // Directories of mtree files have no trailing slash
+    conflicts.retain(|path, _| {
+        !fs::symlink_metadata(root::resolve(path)).is_ok_and(|m| m.file_type().is_dir())
+    });
+    conflicts
+}
+
+/// Check for files owned by more than one installed package
+#[derive(Default)]
+pub(super) struct FileConflicts {
+    packages: Vec<String>,
+}
+
+impl Check for FileConflicts {
+    fn name(&self) -> &str {
+        CheckKind::FileConflicts.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Files owned by more than one installed package, like after an installation forced with --overwrite, which removing one of the packages deletes from under the others".to_owned(),
+            caveats: vec![
+                "Directories, which packages share on purpose, are skipped",
+                "The file lists of all installed packages are read, which is why the check is disabled by default",
+            ],
+            examples: vec![Finding::FileConflict {
+                path: PathBuf::from("/usr/bin/foo"),
+                packages: vec!["foo".to_owned(), "foo-git".to_owned()],
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        // Pacman refuses new conflicts without --overwrite, and the file lists of all packages are read
+        false
+    }
+
+    fn prepare(
+        &mut self,
+        backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        self.packages = backend.installed_packages()?;
+        Ok(self.packages.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        conflicting_files(ctx, &self.packages)
+            .into_iter()
+            .map(|(path, packages)| Finding::FileConflict { path, packages })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::MemoryBackend, metadata::Metadata, progress::Progress};
+
+    #[test]
+    fn test_file_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let shared_dir = dir.path().join("share");
+        fs::create_dir(&shared_dir).unwrap();
+        let shared_dir = shared_dir.to_str().unwrap();
+        let backend = MemoryBackend::default()
+            .with_package(
+                "foo",
+                "1.0-1",
+                false,
+                &["/usr/", "/usr/bin/foo", "/usr/lib/libfoo.so", shared_dir],
+            )
+            .with_package(
+                "foo-git",
+                "1.0.r10-1",
+                true,
+                &["/usr/", "/usr/bin/foo", shared_dir],
+            )
+            .with_package("libfoo", "1.0-1", false, &["/usr/lib/libfoo.so"])
+            .with_package("libfoo-compat", "1.0-1", true, &["/usr/lib/libfoo.so"])
+            .with_package("bar", "1.0-1", false, &["/usr/bin/bar"]);
+        let progress = Progress::new(5, false).unwrap();
+        let metadata = Metadata::default();
+        let ctx = Context::new(&backend, &[], &metadata, &progress, None);
+        let packages = backend.installed_packages().unwrap();
+        let findings: Vec<_> = conflicting_files(&ctx, &packages)
+            .into_iter()
+            .map(|(path, packages)| Finding::FileConflict { path, packages })
+            .map(|f| f.to_string())
+            .collect();
+        assert_eq!(
+            findings,
+            [
+                r#"File /usr/bin/foo is owned by packages "foo" and "foo-git", removing one of them deletes it for the other"#,
+                r#"File /usr/lib/libfoo.so is owned by packages "foo", "libfoo" and "libfoo-compat", removing one of them deletes it for the others"#,
+            ]
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! Libraries of the same soname installed by several packages in the library search path,
+//! or installed manually and hiding a packaged one
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    os::unix::fs::MetadataExt as _,
+    path::{Path, PathBuf},
+};
+
+use super::{Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    cancel, elf,
+    finding::{CheckKind, Finding},
+    root,
+};
+
+/// Directories of software installed manually, outside of pacman
+const UNMANAGED_DIRS: [&str; 2] = ["/usr/local", "/home"];
+
+/// Check if a file name is the one of a shared library
+pub(super) fn is_library_name(name: &str) -> bool {
+    name.contains(".so.") || Path::new(name).extension().is_some_and(|e| e == "so")
+}
+
+/// Get library search directories, without the ones that are links to a previous one
+pub(super) fn unique_dirs(dirs: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    dirs.into_iter()
+        .filter(|d| fs::canonicalize(d).is_ok_and(|c| seen.insert(c)))
+        .collect()
+}
+
+/// Get the package owning a library, if any
+pub(super) fn owner(backend: &dyn PackageBackend, path: &Path) -> Option<String> {
+    backend
+        .owners(&root::unresolve(path))
+        .unwrap_or_else(|err| {
+            log::debug!("Failed to get owner of {path:?}: {err}");
+            Vec::new()
+        })
+        .into_iter()
+        .next()
+}
+
+/// Check if a library or its directory can be modified by users other than root
+fn is_user_writable(path: &Path) -> bool {
+    [Some(path), path.parent()]
+        .into_iter()
+        .flatten()
+        .any(|p| fs::metadata(p).is_ok_and(|m| m.uid() != 0 || m.mode() & 0o022 != 0))
+}
+
+/// Check libraries of the search directories, in search order, for sonames found in several of them
+fn check_duplicate_libraries(ctx: &Context<'_>, dirs: &[PathBuf]) -> Vec<Finding> {
+    let mut libraries: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for dir in dirs {
+        if cancel::requested() {
+            return Vec::new();
+        }
+        for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+            if let Some(name) = entry.file_name().to_str().filter(|n| is_library_name(n)) {
+                libraries
+                    .entry(name.to_owned())
+                    .or_default()
+                    .push(entry.path());
+            }
+        }
+        ctx.item_done();
+    }
+
+    let backend = ctx.backend();
+    let mut findings = Vec::new();
+    for (soname, paths) in libraries.into_iter().filter(|(_, p)| p.len() > 1) {
+        let Some((loaded, others)) = paths.split_first() else {
+            continue;
+        };
+        // Linker scripts are not loaded at runtime
+        let Some(class) = elf::elf_class(loaded) else {
+            continue;
+        };
+        let target = fs::canonicalize(loaded).ok();
+        let mut shadowed = others.iter().filter(|o| {
+            // 32-bit libraries are skipped by the loader of 64-bit programs, and the other way around
+            elf::elf_class(o) == Some(class) && fs::canonicalize(o).ok() != target
+        });
+        let path = root::unresolve(loaded);
+        if let Some(package) = owner(backend, loaded) {
+            for other in shadowed {
+                let Some(shadowed_package) = owner(backend, other).filter(|p| *p != package) else {
+                    continue;
+                };
+                findings.push(Finding::ShadowedLibrary {
+                    soname: soname.clone(),
+                    package: package.clone(),
+                    path: path.clone(),
+                    shadowed_package,
+                    shadowed_path: root::unresolve(other),
+                });
+            }
+        } else {
+            let writable = is_user_writable(loaded);
+            if !writable && !UNMANAGED_DIRS.iter().any(|d| path.starts_with(d)) {
+                continue;
+            }
+            let Some((other, shadowed_package)) =
+                shadowed.find_map(|o| owner(backend, o).map(|p| (o, p)))
+            else {
+                continue;
+            };
+            findings.push(Finding::UnpackagedLibrary {
+                soname,
+                path,
+                writable,
+                shadowed_package,
+                shadowed_path: root::unresolve(other),
+            });
+        }
+    }
+    findings
+}
+
+/// Check for libraries hiding the ones of a package in the library search path
+#[derive(Default)]
+pub(super) struct DuplicateLibraries {
+    dirs: Vec<PathBuf>,
+}
+
+impl Check for DuplicateLibraries {
+    fn name(&self) -> &str {
+        CheckKind::DuplicateLibraries.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Libraries of a package shadowing the library of the same soname of another package, because they come first in the loader search path, and libraries owned by no package shadowing a packaged one".to_owned(),
+            caveats: vec![
+                "Packages replacing a library on purpose, like a patched build, are also reported, SeverityOverride can lower their severity",
+                "32-bit copies of 64-bit libraries are not reported",
+            ],
+            examples: vec![Finding::ShadowedLibrary {
+                soname: "libfoo.so.1".to_owned(),
+                package: "foo-git".to_owned(),
+                path: PathBuf::from("/usr/lib/foo-git/libfoo.so.1"),
+                shadowed_package: "foo".to_owned(),
+                shadowed_path: PathBuf::from("/usr/lib/libfoo.so.1"),
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn progress_weight(&self) -> u64 {
+        // Each directory has thousands of libraries, compared with the ones of the other directories
+        100
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        self.dirs = unique_dirs(elf::default_search_dirs());
+        Ok(self.dirs.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        check_duplicate_libraries(ctx, &self.dirs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    use super::*;
+    use crate::{backend::MemoryBackend, metadata::Metadata, progress::Progress};
+
+    #[test]
+    fn test_duplicate_libraries() {
+        let dir = tempfile::tempdir().unwrap();
+        let elf = fs::read("/proc/self/exe").unwrap();
+        let dirs = [
+            dir.path().join("local"),
+            dir.path().join("opt"),
+            dir.path().join("lib"),
+        ];
+        for d in &dirs {
+            fs::create_dir(d).unwrap();
+        }
+        fs::set_permissions(&dirs[0], fs::Permissions::from_mode(0o777)).unwrap();
+        fs::write(dirs[0].join("libbaz.so.1"), &elf).unwrap();
+        for d in &dirs[1..] {
+            fs::write(d.join("libfoo.so.1"), &elf).unwrap();
+            fs::write(d.join("libbar.so.1"), &elf).unwrap();
+            fs::write(d.join("libbaz.so.1"), &elf).unwrap();
+            // Linker script
+            fs::write(d.join("libc.so"), "GROUP ( libc.so.6 )").unwrap();
+        }
+        let owned = |name: &str| {
+            dirs[1..]
+                .iter()
+                .map(|d| d.join(name).to_str().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+        let foo = owned("libfoo.so.1");
+        let bar = owned("libbar.so.1");
+        let libc = owned("libc.so");
+        let baz = owned("libbaz.so.1");
+        let backend = MemoryBackend::default()
+            .with_package(
+                "foo-git",
+                "1.0-1",
+                true,
+                &[foo[0].as_str(), libc[0].as_str()],
+            )
+            .with_package("foo", "1.0-1", false, &[foo[1].as_str(), libc[1].as_str()])
+            .with_package("bar", "1.0-1", false, &[bar[0].as_str(), bar[1].as_str()])
+            .with_package("baz", "1.0-1", false, &[baz[1].as_str()]);
+
+        let progress = Progress::new(3, false).unwrap();
+        let findings = check_duplicate_libraries(
+            &Context::new(&backend, &[], &Metadata::default(), &progress, None),
+            &dirs,
+        );
+        assert_eq!(
+            findings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                format!(
+                    r#"Library libbaz.so.1 in {}, not installed by a package, shadows the one from package "baz" in {}, and is writable by other users than root"#,
+                    dirs[0].display(),
+                    dirs[2].display()
+                ),
+                format!(
+                    r#"Library libfoo.so.1 from package "foo-git" in {} shadows the one from package "foo" in {}"#,
+                    dirs[1].display(),
+                    dirs[2].display()
+                )
+            ]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Custom checks running external commands
+
+use std::{process::Stdio, time::Instant};
+
+use super::{Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    cancel,
+    config::{CustomCheck, OutputFormat},
+    finding::{Finding, Severity},
+    json, privileges,
+    timings::Phase,
+};
+
+/// Check running a command, and reporting its output as findings
+pub(super) struct ExternalCheck {
+    name: String,
+    command: Vec<String>,
+    output: OutputFormat,
+    severity: Severity,
+}
+
+impl ExternalCheck {
+    pub(super) fn new(custom: &CustomCheck) -> Self {
+        Self {
+            name: custom.name().to_owned(),
+            command: custom.command().to_vec(),
+            output: custom.output(),
+            severity: custom.severity(),
+        }
+    }
+
+    /// Run the command and parse its output
+    fn findings(&self) -> anyhow::Result<Vec<Finding>> {
+        let (program, args) = self
+            .command
+            .split_first()
+            .ok_or_else(|| anyhow::anyhow!("Empty command"))?;
+        let output = privileges::command(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .output()?;
+        // Exit code 1 is allowed, to signal findings were reported
+        if !matches!(output.status.code(), Some(0 | 1)) {
+            anyhow::bail!("Command failed with {}", output.status);
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        match self.output {
+            OutputFormat::Lines => Ok(stdout
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(|l| self.finding(l.to_owned(), self.severity))
+                .collect()),
+            OutputFormat::Json => self.parse_json(&stdout),
+        }
+    }
+
+    fn parse_json(&self, stdout: &str) -> anyhow::Result<Vec<Finding>> {
+        if stdout.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let json::Value::Array(items) = json::parse(stdout)? else {
+            anyhow::bail!("Output is not a JSON array");
+        };
+        items
+            .iter()
+            .map(|item| {
+                let message = item
+                    .get("message")
+                    .and_then(json::Value::as_str)
+                    .ok_or_else(|| anyhow::anyhow!("Missing finding message"))?;
+                let severity = item
+                    .get("severity")
+                    .and_then(json::Value::as_str)
+                    .map_or(Ok(self.severity), str::parse)?;
+                Ok(self.finding(message.to_owned(), severity))
+            })
+            .collect()
+    }
+
+    fn finding(&self, message: String, severity: Severity) -> Finding {
+        Finding::External {
+            check: self.name.clone(),
+            severity,
+            message,
+        }
+    }
+}
+
+impl Check for ExternalCheck {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: format!(
+                "Findings printed by the custom command {:?}",
+                self.command.join(" ")
+            ),
+            caveats: vec!["Findings are only as accurate as the command, and are not analyzed"],
+            config_keys: vec!["Command", "Output", "Severity"],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        Ok(1)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        if cancel::requested() {
+            return Vec::new();
+        }
+        let start = Instant::now();
+        let findings = self.findings().unwrap_or_else(|err| {
+            if !cancel::requested() {
+                log::error!("Custom check {:?} failed: {err}", self.name);
+            }
+            Vec::new()
+        });
+        if let Some(timings) = ctx.timings() {
+            timings.record_phase(Phase::External, start.elapsed());
+        }
+        ctx.item_done();
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json() {
+        let check = ExternalCheck {
+            name: "foo".to_owned(),
+            command: vec!["foo".to_owned()],
+            output: OutputFormat::Json,
+            severity: Severity::Warning,
+        };
+        let findings = check
+            .parse_json(
+                r#"[{"message": "bar is broken", "severity": "error"}, {"message": "baz"}]"#,
+            )
+            .unwrap();
+        assert_eq!(
+            findings
+                .iter()
+                .map(|f| (f.to_string(), f.severity()))
+                .collect::<Vec<_>>(),
+            [
+                ("foo: bar is broken".to_owned(), Severity::Error),
+                ("foo: baz".to_owned(), Severity::Warning),
+            ]
+        );
+        assert!(check.parse_json(r#"[{"severity": "error"}]"#).is_err());
+        assert!(check.parse_json(r#"{"message": "bar"}"#).is_err());
+    }
+}
@@ -0,0 +1,310 @@
+//! Firmware files the running kernel failed to load that are still not installed, like after the split of
+//! `linux-firmware` into vendor packages left the one of a device uninstalled
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{self, File},
+    io::{self, Read as _},
+    os::unix::fs::OpenOptionsExt as _,
+    path::{Path, PathBuf},
+};
+
+use glob::Pattern;
+use rayon::prelude::*;
+
+use super::{Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    finding::{CheckKind, Finding},
+    metadata::Metadata,
+    privileges, root,
+};
+
+/// Kernel log of the current boot
+const KMSG_PATH: &str = "/dev/kmsg";
+
+/// Modules loaded by the running kernel
+const PROC_MODULES_PATH: &str = "/proc/modules";
+
+/// Directories the kernel loads firmware from, in order
+const FIRMWARE_DIRS: [&str; 2] = ["/usr/lib/firmware/updates", "/usr/lib/firmware"];
+
+/// Extensions of compressed firmware files, which the kernel also loads
+const FIRMWARE_EXTENSIONS: [&str; 3] = ["", ".zst", ".xz"];
+
+/// Maximum size of a kernel log record
+const KMSG_RECORD_MAX_LEN: usize = 8192;
+
+/// Get the messages of the kernel log, without their record header
+fn kernel_messages() -> io::Result<Vec<String>> {
+    let mut kmsg = File::options()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(KMSG_PATH)?;
+    let mut messages = Vec::new();
+    let mut record = vec![0; KMSG_RECORD_MAX_LEN];
+    loop {
+        // Each read returns a single record, until there are no more
+        let len = match kmsg.read(&mut record) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+            // Records overwritten while reading
+            Err(err) if err.raw_os_error() == Some(libc::EPIPE) => continue,
+            Err(err) => return Err(err),
+        };
+        let record = String::from_utf8_lossy(&record[..len]);
+        // Header fields are separated from the message by a semicolon, continuation lines follow it
+        if let Some((_, message)) = record.split_once(';') {
+            messages.push(message.lines().next().unwrap_or_default().to_owned());
+        }
+    }
+    Ok(messages)
+}
+
+/// Get the firmware files of failed loads in kernel messages, with the driver prefixing the message, if any
+fn failed_firmware_loads(messages: &[impl AsRef<str>]) -> BTreeMap<String, Option<String>> {
+    let mut loads = BTreeMap::new();
+    for message in messages.iter().map(AsRef::as_ref) {
+        // Like `iwlwifi 0000:00:14.3: Direct firmware load for iwlwifi-foo.ucode failed with error -2`, or
+        // `amdgpu 0000:03:00.0: firmware: failed to load amdgpu/foo.bin (-2)`
+        let Some((prefix, firmware)) = message
+            .split_once("Direct firmware load for ")
+            .and_then(|(p, m)| Some((p, m.split_once(" failed with error")?.0)))
+            .or_else(|| {
+                message
+                    .split_once("firmware: failed to load ")
+                    .and_then(|(p, m)| Some((p, m.split_once(" (")?.0)))
+            })
+        else {
+            continue;
+        };
+        let driver = prefix
+            .split_whitespace()
+            .next()
+            .filter(|d| !d.ends_with(':'))
+            .map(|d| d.replace('-', "_"));
+        loads
+            .entry(firmware.to_owned())
+            .and_modify(|d: &mut Option<String>| {
+                if d.is_none() {
+                    d.clone_from(&driver);
+                }
+            })
+            .or_insert(driver);
+    }
+    loads
+}
+
+/// Get the names of loaded kernel modules, from the content of `/proc/modules`
+fn loaded_modules(proc_modules: &str) -> HashSet<String> {
+    proc_modules
+        .lines()
+        .filter_map(|l| l.split_whitespace().next())
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+/// Paths a firmware file can be installed at, compressed or not
+fn firmware_paths(firmware: &str) -> impl Iterator<Item = PathBuf> + '_ {
+    FIRMWARE_DIRS.into_iter().flat_map(move |dir| {
+        FIRMWARE_EXTENSIONS
+            .into_iter()
+            .map(move |ext| Path::new(dir).join(format!("{firmware}{ext}")))
+    })
+}
+
+/// Get the firmware patterns of loaded modules, according to modinfo
+fn module_firmware(modules: &HashSet<String>) -> Vec<(String, Vec<Pattern>)> {
+    modules
+        .par_iter()
+        .filter_map(|module| {
+            let output = privileges::command("modinfo")
+                .args(["-F", "firmware", module])
+                .output()
+                .inspect_err(|err| log::debug!("Failed to run modinfo: {err}"))
+                .ok()
+                .filter(|o| o.status.success())?;
+            let patterns: Vec<_> = String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter_map(|f| Pattern::new(f.trim()).ok())
+                .collect();
+            (!patterns.is_empty()).then(|| (module.clone(), patterns))
+        })
+        .collect()
+}
+
+/// Get packages providing firmware files, from the files database
+fn firmware_providers(metadata: &Metadata, firmware: &[&str]) -> HashMap<PathBuf, String> {
+    let paths: Vec<_> = firmware.iter().flat_map(|f| firmware_paths(f)).collect();
+    match metadata.files_db().and_then(|db| {
+        db.as_ref()
+            .as_ref()
+            .map(|db| db.path_providers(&paths))
+            .transpose()
+    }) {
+        Ok(providers) => providers
+            .into_iter()
+            .flatten()
+            .filter_map(|(path, packages)| Some((path, packages.first()?.name().to_owned())))
+            .collect(),
+        Err(err) => {
+            log::warn!("Failed to find packages providing missing firmware: {err:#}");
+            HashMap::new()
+        }
+    }
+}
+
+/// Check for firmware files the running kernel failed to load
+#[derive(Default)]
+pub(super) struct ModuleFirmware {
+    /// Firmware files still missing, with the loaded module that requested it, if known
+    missing: BTreeMap<String, Option<String>>,
+}
+
+impl Check for ModuleFirmware {
+    fn name(&self) -> &str {
+        CheckKind::ModuleFirmware.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Firmware files that loaded kernel modules failed to load during the current boot, and that are still not installed".to_owned(),
+            caveats: vec![
+                "Drivers also try optional firmware files, whose failed loads are reported even though the device works, which is why the check is disabled by default",
+                "Reading the kernel log needs root, the check is skipped otherwise",
+            ],
+            examples: vec![Finding::MissingFirmware {
+                firmware: "intel/ibt-0040-0041.sfi".to_owned(),
+                module: Some("btintel".to_owned()),
+                provider: Some("linux-firmware-intel".to_owned()),
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        // Drivers probe for optional firmware versions, and report a failed load for each missing one
+        false
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        // The running kernel is not the one of another root
+        if root::is_alternate() {
+            return Ok(0);
+        }
+        let messages = match kernel_messages() {
+            Ok(messages) => messages,
+            Err(err) => {
+                log::info!("Unable to read kernel log {KMSG_PATH}, not checking firmware: {err}");
+                return Ok(0);
+            }
+        };
+        // Kernels without module support, and some containers, have no module list
+        let modules = fs::read_to_string(PROC_MODULES_PATH).map_or_else(
+            |err| {
+                log::debug!("Unable to read {PROC_MODULES_PATH}: {err}");
+                HashSet::new()
+            },
+            |m| loaded_modules(&m),
+        );
+        self.missing = failed_firmware_loads(&messages)
+            .into_iter()
+            .filter(|(firmware, _)| !firmware_paths(firmware).any(|p| p.exists()))
+            .map(|(firmware, driver)| (firmware, driver.filter(|d| modules.contains(d))))
+            .collect();
+        // Messages of some drivers are prefixed by their subsystem, like `Bluetooth: hci0:`
+        if self.missing.values().any(Option::is_none) {
+            let patterns = module_firmware(&modules);
+            for (firmware, module) in &mut self.missing {
+                if module.is_none() {
+                    *module = patterns
+                        .iter()
+                        .find(|(_, p)| p.iter().any(|p| p.matches(firmware)))
+                        .map(|(m, _)| m.clone());
+                }
+            }
+        }
+        Ok(self.missing.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        if self.missing.is_empty() {
+            return Vec::new();
+        }
+        let names: Vec<_> = self.missing.keys().map(String::as_str).collect();
+        let providers = firmware_providers(ctx.metadata(), &names);
+        self.missing
+            .iter()
+            .map(|(firmware, module)| {
+                ctx.item_done();
+                Finding::MissingFirmware {
+                    firmware: firmware.clone(),
+                    module: module.clone(),
+                    provider: firmware_paths(firmware)
+                        .find_map(|p| providers.get(&p))
+                        .cloned(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failed_firmware_loads() {
+        let modules = loaded_modules(
+            "iwlwifi 598016 1 iwlmvm, Live 0x0000000000000000
+btusb 77824 0 - Live 0x0000000000000000
+",
+        );
+        assert_eq!(
+            modules,
+            HashSet::from(["iwlwifi".to_owned(), "btusb".to_owned()])
+        );
+        assert_eq!(
+            failed_firmware_loads(&[
+                "Linux version 6.12.1-arch1-1",
+                "iwlwifi 0000:00:14.3: Direct firmware load for iwlwifi-so-a0-gf-a0-89.ucode failed with error -2",
+                "Bluetooth: hci0: Direct firmware load for intel/ibt-0040-0041.sfi failed with error -2",
+                "amdgpu 0000:03:00.0: firmware: failed to load amdgpu/green_sardine_dmcub.bin (-2)",
+                "amdgpu 0000:03:00.0: Direct firmware load for amdgpu/green_sardine_dmcub.bin failed with error -2",
+            ]),
+            BTreeMap::from([
+                (
+                    "amdgpu/green_sardine_dmcub.bin".to_owned(),
+                    Some("amdgpu".to_owned())
+                ),
+                ("intel/ibt-0040-0041.sfi".to_owned(), None),
+                (
+                    "iwlwifi-so-a0-gf-a0-89.ucode".to_owned(),
+                    Some("iwlwifi".to_owned())
+                ),
+            ])
+        );
+        assert_eq!(
+            firmware_paths("intel/ibt-0040-0041.sfi").nth(4).unwrap(),
+            Path::new("/usr/lib/firmware/intel/ibt-0040-0041.sfi.zst")
+        );
+        assert_eq!(
+            Finding::MissingFirmware {
+                firmware: "intel/ibt-0040-0041.sfi".to_owned(),
+                module: Some("btintel".to_owned()),
+                provider: Some("linux-firmware-intel".to_owned()),
+            }
+            .to_string(),
+            r#"Firmware intel/ibt-0040-0041.sfi requested by kernel module "btintel" is not installed, install package "linux-firmware-intel", the device may not work until its driver loads it"#
+        );
+    }
+}
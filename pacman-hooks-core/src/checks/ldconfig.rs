@@ -0,0 +1,217 @@
+//! Soname links of the library search path left pointing to a removed version, until ldconfig updates them
+
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    duplicates::{owner, unique_dirs},
+    Check, CheckDoc, Context, Input,
+};
+use crate::{
+    backend::PackageBackend,
+    cancel, elf,
+    finding::{CheckKind, Finding},
+    root,
+    version::vercmp,
+};
+
+/// Get the missing target of the link `soname` in `dir`, and the library of `dir` that ldconfig would point it to,
+/// or `None` if the link is not stale
+fn stale_link(dir: &Path, soname: &str) -> Option<(PathBuf, PathBuf)> {
+    let link = dir.join(soname);
+    if !fs::symlink_metadata(&link).ok()?.is_symlink() || link.exists() {
+        return None;
+    }
+    let target = root::link_target(&link)?;
+    // Versions of the library are named after the soname, like libfoo.so.1.2.3 for libfoo.so.1
+    let prefix = format!("{soname}.");
+    let (_, library) = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+        .filter_map(|e| {
+            let version = e.file_name().to_str()?.strip_prefix(&prefix)?.to_owned();
+            Some((version, e.path()))
+        })
+        .max_by(|(a, _), (b, _)| match vercmp(a, b) {
+            Ordering::Equal => a.cmp(b),
+            ordering => ordering,
+        })?;
+    Some((target, library))
+}
+
+/// Check soname links of the library search directories for links to removed libraries
+fn check_library_links(ctx: &Context<'_>, dirs: &[PathBuf]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for dir in dirs {
+        if cancel::requested() {
+            return Vec::new();
+        }
+        for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+            // Only soname links are managed by ldconfig, not development links like libfoo.so
+            let Some(soname) = entry
+                .file_name()
+                .to_str()
+                .filter(|n| n.contains(".so."))
+                .map(ToOwned::to_owned)
+            else {
+                continue;
+            };
+            if let Some((target, library)) = stale_link(dir, &soname) {
+                findings.push(Finding::StaleLibraryLink {
+                    link: root::unresolve(&entry.path()),
+                    target: root::unresolve(&target),
+                    package: owner(ctx.backend(), &library),
+                    library: root::unresolve(&library),
+                });
+            }
+        }
+        ctx.item_done();
+    }
+    findings
+}
+
+/// Drop missing dependencies on libraries with a stale soname link among `findings`,
+/// since running ldconfig fixes them
+pub(super) fn drop_stale_linked(findings: Vec<Finding>) -> Vec<Finding> {
+    let stale: HashSet<_> = findings
+        .iter()
+        .filter_map(|f| match f {
+            Finding::StaleLibraryLink { link, .. } => link.file_name().map(ToOwned::to_owned),
+            _ => None,
+        })
+        .collect();
+    if stale.is_empty() {
+        return findings;
+    }
+    findings
+        .into_iter()
+        .filter(|f| {
+            let Finding::MissingDependency {
+                file, dependency, ..
+            } = f
+            else {
+                return true;
+            };
+            let linked = stale.contains(dependency.as_os_str());
+            if linked {
+                log::debug!(
+                    "Not reporting missing dependency {} of file {file:?}, which has a stale link",
+                    dependency.display()
+                );
+            }
+            !linked
+        })
+        .collect()
+}
+
+/// Check for soname links that ldconfig did not update after an upgrade
+#[derive(Default)]
+pub(super) struct LibraryLinks {
+    dirs: Vec<PathBuf>,
+}
+
+impl Check for LibraryLinks {
+    fn name(&self) -> &str {
+        CheckKind::LibraryLinks.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Soname links of the loader search path pointing to a removed version of a library while a newer one is installed, because ldconfig did not run after an upgrade".to_owned(),
+            caveats: vec!["Running ldconfig fixes them, the programs using these libraries are not reported as missing dependencies"],
+            examples: vec![Finding::StaleLibraryLink {
+                link: PathBuf::from("/usr/lib/libfoo.so.1"),
+                target: PathBuf::from("/usr/lib/libfoo.so.1.2.2"),
+                library: PathBuf::from("/usr/lib/libfoo.so.1.2.3"),
+                package: Some("foo".to_owned()),
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn progress_weight(&self) -> u64 {
+        // Each directory has thousands of entries, only soname links are read
+        20
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        self.dirs = unique_dirs(elf::default_search_dirs());
+        Ok(self.dirs.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        check_library_links(ctx, &self.dirs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+    use crate::{backend::MemoryBackend, metadata::Metadata, progress::Progress};
+
+    #[test]
+    fn test_library_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name| dir.path().join(name);
+        for name in ["libfoo.so.1.2.3", "libfoo.so.1.10.0", "libbar.so.2.0"] {
+            fs::write(path(name), "").unwrap();
+        }
+        symlink("libfoo.so.1.2.2", path("libfoo.so.1")).unwrap();
+        symlink("libbar.so.2.0", path("libbar.so.2")).unwrap();
+        // Nothing to point the link to, programs using it are reported as missing the library
+        symlink("libbaz.so.3.0", path("libbaz.so.3")).unwrap();
+        symlink("libfoo.so.1.2.2", path("libfoo.so")).unwrap();
+
+        let foo = path("libfoo.so.1.10.0");
+        let backend =
+            MemoryBackend::default().with_package("foo", "1.10-1", false, &[foo.to_str().unwrap()]);
+        let progress = Progress::new(1, false).unwrap();
+        let findings = check_library_links(
+            &Context::new(&backend, &[], &Metadata::default(), &progress, None),
+            &[dir.path().to_owned()],
+        );
+        assert_eq!(
+            findings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [format!(
+                r#"Library link {} points to removed {}, instead of {} from package "foo", run ldconfig to update it"#,
+                path("libfoo.so.1").display(),
+                path("libfoo.so.1.2.2").display(),
+                foo.display()
+            )]
+        );
+        assert_eq!(stale_link(dir.path(), "libbaz.so.3"), None);
+
+        let missing = |dependency: &str| Finding::MissingDependency {
+            package: std::sync::Arc::new("bar".to_owned()),
+            file: std::sync::Arc::new(PathBuf::from("/usr/bin/bar")),
+            dependency: PathBuf::from(dependency),
+            origin: None,
+        };
+        let findings = drop_stale_linked(
+            [missing("libfoo.so.1"), missing("libbaz.so.3")]
+                .into_iter()
+                .chain(findings)
+                .collect(),
+        );
+        assert!(matches!(
+            findings.as_slice(),
+            [Finding::MissingDependency { dependency, .. }, Finding::StaleLibraryLink { .. }]
+                if dependency == Path::new("libbaz.so.3")
+        ));
+    }
+}
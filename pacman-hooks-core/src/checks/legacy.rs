@@ -0,0 +1,211 @@
+//! Installed packages still needing a legacy runtime, like OpenSSL 1.x or Python 2, counted per runtime to plan the
+//! removal of its compatibility package
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    ffi::OsStr,
+    os::unix::ffi::OsStrExt as _,
+};
+
+use rayon::prelude::*;
+
+use super::{Check, CheckDoc, Context, Input};
+use crate::{
+    backend::{is_executable_file, PackageBackend},
+    cancel, elf,
+    finding::{CheckKind, Finding},
+    logging::LogContext,
+    root,
+};
+
+/// Legacy runtime, provided by a compatibility package
+struct Runtime {
+    /// Compatibility package, that packages depend on
+    package: &'static str,
+    /// Libraries of the runtime, that files link against
+    sonames: &'static [&'static str],
+}
+
+/// Legacy runtimes, whose packages are in the repositories for the programs not ported yet
+const RUNTIMES: [Runtime; 3] = [
+    Runtime {
+        package: "openssl-1.0",
+        sonames: &["libssl.so.1.0.0", "libcrypto.so.1.0.0"],
+    },
+    Runtime {
+        package: "openssl-1.1",
+        sonames: &["libssl.so.1.1", "libcrypto.so.1.1"],
+    },
+    Runtime {
+        package: "python2",
+        sonames: &["libpython2.7.so.1.0"],
+    },
+];
+
+/// Get the legacy runtimes a package declares as dependency, or its executable files link against.
+///
+/// Packages shipping a library of the runtime, like the runtime package itself or applications bundling their own
+/// copy, do not need its package.
+fn needed_runtimes(
+    backend: &dyn PackageBackend,
+    package: &str,
+) -> anyhow::Result<Vec<&'static str>> {
+    let dependencies = backend.dependencies(package)?;
+    let mut names = HashSet::new();
+    let mut executables = Vec::new();
+    backend.for_each_file(package, &mut |file| {
+        if let Some(name) = file.path().file_name() {
+            names.insert(name.to_owned());
+        }
+        // Directories of file lists end with a slash
+        if !file.path().as_os_str().as_bytes().ends_with(b"/") && is_executable_file(file.path()) {
+            executables.push(file.into_path());
+        }
+    })?;
+    let needed: HashSet<String> = executables
+        .iter()
+        .flat_map(|path| {
+            elf::needed_libraries(&root::resolve(path)).unwrap_or_else(|err| {
+                log::debug!("Failed to read dependencies of {path:?}: {err}");
+                Vec::new()
+            })
+        })
+        .collect();
+    Ok(RUNTIMES
+        .iter()
+        .filter(|r| {
+            r.package != package
+                && !r.sonames.iter().any(|s| names.contains(OsStr::new(s)))
+                && (dependencies.iter().any(|d| d == r.package)
+                    || r.sonames.iter().any(|s| needed.contains(*s)))
+        })
+        .map(|r| r.package)
+        .collect())
+}
+
+/// Check for installed packages needing a legacy runtime
+#[derive(Default)]
+pub(super) struct LegacyRuntimes {
+    packages: Vec<String>,
+}
+
+impl Check for LegacyRuntimes {
+    fn name(&self) -> &str {
+        CheckKind::LegacyRuntimes.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Installed packages still needing the OpenSSL 1.0, OpenSSL 1.1 or Python 2 runtimes, counted per runtime to plan the removal of its compatibility package".to_owned(),
+            caveats: vec!["Packages shipping their own copy of the runtime libraries are not counted"],
+            examples: vec![Finding::LegacyRuntime {
+                runtime: "openssl-1.1".to_owned(),
+                packages: vec!["foo".to_owned()],
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        // Compatibility packages still work, the counts only help planning their removal
+        false
+    }
+
+    fn prepare(
+        &mut self,
+        backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        self.packages = backend.installed_packages()?;
+        Ok(self.packages.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        let log_context = LogContext::current();
+        let needed: Vec<(&str, &String)> = self
+            .packages
+            .par_iter()
+            .filter(|_| !cancel::requested())
+            .flat_map_iter(|package| {
+                let _log = log_context.clone().with_package(package).enter();
+                let runtimes = needed_runtimes(ctx.backend(), package).unwrap_or_else(|err| {
+                    log::warn!("Failed to check runtimes of package {package:?}: {err}");
+                    Vec::new()
+                });
+                ctx.item_done();
+                runtimes.into_iter().map(move |r| (r, package))
+            })
+            .collect();
+        let mut dependents: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for (runtime, package) in needed {
+            dependents.entry(runtime).or_default().push(package.clone());
+        }
+        dependents
+            .into_iter()
+            .map(|(runtime, mut packages)| {
+                packages.sort_unstable();
+                Finding::LegacyRuntime {
+                    runtime: runtime.to_owned(),
+                    packages,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::backend::MemoryBackend;
+
+    #[test]
+    fn test_needed_runtimes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_owned();
+        for name in ["libssl.so.1.1", "libpython2.7.so.1.0"] {
+            fs::write(path(name), "").unwrap();
+        }
+        let backend = MemoryBackend::default()
+            .with_package("openssl-1.1", "1.1.1w-1", false, &[&path("libssl.so.1.1")])
+            .with_package("foo", "1.0-1", true, &["/usr/bin/foo"])
+            .with_dependency("foo", "openssl-1.1")
+            .with_dependency("foo", "python2")
+            .with_package("bar", "1.0-1", true, &[&path("libpython2.7.so.1.0")])
+            .with_dependency("bar", "python2")
+            .with_package("baz", "1.0-1", false, &[])
+            .with_dependency("baz", "openssl");
+        assert_eq!(
+            needed_runtimes(&backend, "foo").unwrap(),
+            ["openssl-1.1", "python2"]
+        );
+        // Bundled runtime
+        assert!(needed_runtimes(&backend, "bar").unwrap().is_empty());
+        assert!(needed_runtimes(&backend, "baz").unwrap().is_empty());
+        assert!(needed_runtimes(&backend, "openssl-1.1").unwrap().is_empty());
+
+        for (runtime, packages, message) in [
+            (
+                "openssl-1.1",
+                &["bar", "foo"][..],
+                r#"2 packages still need openssl-1.1: "bar" and "foo", rebuild or replace them before removing openssl-1.1"#,
+            ),
+            (
+                "python2",
+                &["foo"][..],
+                r#"1 package still needs python2: "foo", rebuild or replace it before removing python2"#,
+            ),
+        ] {
+            let finding = Finding::LegacyRuntime {
+                runtime: runtime.to_owned(),
+                packages: packages.iter().map(|p| (*p).to_owned()).collect(),
+            };
+            assert_eq!(finding.to_string(), message);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Convention symlinks choosing a program among several, like `/usr/bin/python` or `/usr/bin/cc`
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    cancel,
+    finding::{CheckKind, Finding},
+    root,
+};
+
+/// Links checked unless configured otherwise
+const DEFAULT_LINKS: [&str; 6] = [
+    "/usr/bin/python",
+    "/usr/bin/pip",
+    "/usr/bin/cc",
+    "/usr/bin/ld",
+    "/usr/bin/java",
+    "/usr/bin/node",
+];
+
+/// Check a convention symlink, from its path in the checked system
+fn check_link(backend: &dyn PackageBackend, link: &Path) -> Option<Finding> {
+    let resolved = root::resolve(link);
+    // Links of parent directories are followed too, like /usr/lib/jvm/default-runtime, which is owned by no package
+    let target = root::canonicalize(&resolved);
+    if !target
+        .as_ref()
+        .is_some_and(|t| fs::metadata(t).is_ok_and(|m| m.is_file()))
+    {
+        return Some(Finding::DanglingConventionLink {
+            link: link.to_owned(),
+            // On a link loop, report the first target
+            target: target.map_or_else(
+                || fs::read_link(&resolved).unwrap_or_default(),
+                |t| root::unresolve(&t),
+            ),
+        });
+    }
+    let target = root::unresolve(&target?);
+    match backend.owners(&target) {
+        Ok(owners) if owners.is_empty() => Some(Finding::UnpackagedConventionLink {
+            link: link.to_owned(),
+            target,
+        }),
+        Ok(_) => None,
+        Err(err) => {
+            log::warn!("Failed to get owner of {target:?}: {err}");
+            None
+        }
+    }
+}
+
+/// Check that convention symlinks point to programs of installed packages
+pub(super) struct ConventionLinks {
+    /// Links to check if they exist
+    links: Vec<PathBuf>,
+    /// Existing links
+    found: Vec<PathBuf>,
+}
+
+impl ConventionLinks {
+    /// Check `links`, instead of the default ones
+    pub(super) fn new(links: Vec<PathBuf>) -> Self {
+        Self {
+            links,
+            found: Vec::new(),
+        }
+    }
+}
+
+impl Default for ConventionLinks {
+    fn default() -> Self {
+        Self::new(DEFAULT_LINKS.map(PathBuf::from).to_vec())
+    }
+}
+
+impl Check for ConventionLinks {
+    fn name(&self) -> &str {
+        CheckKind::ConventionLinks.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Convention symlinks choosing a program among several, like /usr/bin/cc, pointing to a missing file or to a file owned by no package".to_owned(),
+            caveats: vec![
+                "Links to programs installed on purpose outside of packages are also reported",
+                "Links that do not exist are ignored",
+            ],
+            config_keys: vec!["ConventionLinks"],
+            examples: vec![Finding::DanglingConventionLink {
+                link: PathBuf::from("/usr/bin/python"),
+                target: PathBuf::from("/usr/bin/python3.11"),
+            }],
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        // Programs that are not installed have no link, and programs can also be installed without one
+        self.found = self
+            .links
+            .iter()
+            .filter(|l| fs::symlink_metadata(root::resolve(l)).is_ok_and(|m| m.is_symlink()))
+            .cloned()
+            .collect();
+        Ok(self.found.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        self.found
+            .iter()
+            .filter(|_| !cancel::requested())
+            .filter_map(|link| {
+                let finding = check_link(ctx.backend(), link);
+                ctx.item_done();
+                finding
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+    use crate::{backend::MemoryBackend, metadata::Metadata, progress::Progress};
+
+    #[test]
+    fn test_convention_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name| dir.path().join(name);
+        fs::write(path("gcc"), "").unwrap();
+        fs::write(path("node-local"), "").unwrap();
+        symlink("gcc", path("cc")).unwrap();
+        symlink("python3", path("python")).unwrap();
+        symlink(path("node-local"), path("node")).unwrap();
+        // The owned program is below a directory link owned by no package, like the JVM selected by archlinux-java
+        fs::create_dir_all(path("jvm/java-21/bin")).unwrap();
+        fs::write(path("jvm/java-21/bin/java"), "").unwrap();
+        symlink("java-21", path("jvm/default-runtime")).unwrap();
+        symlink("jvm/default-runtime/bin/java", path("java")).unwrap();
+
+        let gcc = path("gcc");
+        let java = path("jvm/java-21/bin/java");
+        let backend = MemoryBackend::default()
+            .with_package("gcc", "14.1-1", false, &[gcc.to_str().unwrap()])
+            .with_package("jre-openjdk", "21.0.3-1", false, &[java.to_str().unwrap()]);
+        let mut check =
+            ConventionLinks::new(["cc", "python", "node", "java", "pip"].map(&path).to_vec());
+        assert_eq!(check.prepare(&backend, &[]).unwrap(), 4);
+        let progress = Progress::new(4, false).unwrap();
+        let findings = check.run(&Context::new(
+            &backend,
+            &[],
+            &Metadata::default(),
+            &progress,
+            None,
+        ));
+        assert!(matches!(
+            findings.as_slice(),
+            [
+                Finding::DanglingConventionLink { link, target },
+                Finding::UnpackagedConventionLink { link: node, .. },
+            ] if *link == path("python") && *target == path("python3") && *node == path("node")
+        ));
+    }
+}
@@ -0,0 +1,301 @@
+//! Units of the boot path to a login prompt, the display manager, default target and gettys, pointing to a unit or
+//! program that is not installed anymore, like after removing the package of the enabled display manager
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use glob::glob;
+
+use super::{duplicates::owner, Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    cancel,
+    finding::{CheckKind, Finding},
+    root,
+};
+
+/// Directories of system units, by decreasing priority
+pub(super) const UNIT_DIRS: [&str; 2] = ["/etc/systemd/system", "/usr/lib/systemd/system"];
+
+/// Alias of the enabled display manager
+const DISPLAY_MANAGER_UNIT: &str = "display-manager.service";
+
+/// Target booted into, set with `systemctl set-default`
+const DEFAULT_TARGET_UNIT: &str = "default.target";
+
+/// Enabled getty instances, like `getty@tty1.service`
+const GETTY_LINKS_PATTERN: &str = "/etc/systemd/system/getty.target.wants/*.service";
+
+/// Directories systemd searches programs of `ExecStart` in, when they are not absolute paths
+const PROGRAM_DIRS: [&str; 4] = ["/usr/local/bin", "/usr/bin", "/usr/local/sbin", "/usr/sbin"];
+
+/// Unit needed to log in after the next boot
+#[derive(Debug, Eq, PartialEq)]
+struct LoginUnit {
+    name: String,
+    /// Resolved path of the unit file, or of the link to it
+    path: PathBuf,
+}
+
+/// Get the template of a unit instance name, like `getty@.service` for `getty@tty1.service`
+pub(super) fn template_name(name: &str) -> Option<String> {
+    let (prefix, rest) = name.split_once('@')?;
+    let (_, suffix) = rest.rsplit_once('.')?;
+    Some(format!("{prefix}@.{suffix}"))
+}
+
+/// Find a unit in unit directories, the path in the first one if none has it
+pub(super) fn find_unit(dirs: &[&str], name: &str) -> PathBuf {
+    let paths: Vec<_> = dirs
+        .iter()
+        .map(|d| root::resolve(&Path::new(d).join(name)))
+        .collect();
+    paths
+        .iter()
+        .find(|p| p.symlink_metadata().is_ok())
+        .unwrap_or(&paths[0])
+        .clone()
+}
+
+/// Get the units needed to log in
+fn login_units() -> Vec<LoginUnit> {
+    // No display manager is enabled without the alias, and systemd is not installed without the default target
+    let mut units: Vec<_> = [DISPLAY_MANAGER_UNIT, DEFAULT_TARGET_UNIT]
+        .into_iter()
+        .map(|name| LoginUnit {
+            name: name.to_owned(),
+            path: find_unit(&UNIT_DIRS, name),
+        })
+        .filter(|u| u.path.symlink_metadata().is_ok())
+        .collect();
+    units.extend(
+        glob(&root::resolve(Path::new(GETTY_LINKS_PATTERN)).to_string_lossy())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|path| {
+                Some(LoginUnit {
+                    name: path.file_name()?.to_str()?.to_owned(),
+                    path,
+                })
+            }),
+    );
+    units
+}
+
+/// Get the drop-in files of a unit in unit directories, sorted by name, the ones of `/etc` overriding the ones of the
+/// same name of `/usr/lib`
+pub(super) fn drop_ins(dirs: &[&str], name: &str) -> Vec<PathBuf> {
+    let mut drop_ins = BTreeMap::new();
+    let names: Vec<_> = template_name(name)
+        .into_iter()
+        .chain([name.to_owned()])
+        .collect();
+    for dir in dirs.iter().rev() {
+        for unit in &names {
+            let pattern = root::resolve(&Path::new(dir).join(format!("{unit}.d/*.conf")));
+            for path in glob(&pattern.to_string_lossy())
+                .into_iter()
+                .flatten()
+                .flatten()
+            {
+                if let Some(file_name) = path.file_name().map(ToOwned::to_owned) {
+                    drop_ins.insert(file_name, path);
+                }
+            }
+        }
+    }
+    drop_ins.into_values().collect()
+}
+
+/// Add the programs of the `ExecStart` settings of a unit file to `programs`, an empty setting resetting them
+pub(super) fn parse_exec_start(content: &str, programs: &mut Vec<String>) {
+    for (key, value) in content.lines().filter_map(|l| l.split_once('=')) {
+        if key.trim() != "ExecStart" {
+            continue;
+        }
+        // Prefixes changing how the command runs, like `-` to ignore its failure
+        let Some(program) = value
+            .split_whitespace()
+            .next()
+            .map(|p| p.trim_start_matches(['@', '-', ':', '+', '!', '|']))
+        else {
+            programs.clear();
+            continue;
+        };
+        // Specifiers are expanded for each instance
+        if !program.is_empty() && !program.contains('%') {
+            programs.push(program.to_owned());
+        }
+    }
+}
+
+/// Whether a program of `ExecStart` exists
+pub(super) fn program_exists(program: &str) -> bool {
+    if program.starts_with('/') {
+        root::resolve(Path::new(program)).is_file()
+    } else {
+        PROGRAM_DIRS
+            .iter()
+            .any(|d| root::resolve(&Path::new(d).join(program)).is_file())
+    }
+}
+
+/// Check that a login unit exists, and that the programs of a service exist
+fn check_unit(ctx: &Context<'_>, unit: &LoginUnit) -> Option<Finding> {
+    let finding = |missing: &Path, program, package| Finding::BrokenLoginUnit {
+        unit: unit.name.clone(),
+        path: root::unresolve(&unit.path),
+        missing: missing.to_string_lossy().into_owned(),
+        program,
+        package,
+    };
+    let Some(file) = root::link_target(&unit.path).filter(|p| p.is_file()) else {
+        let target = root::link_target(&unit.path).unwrap_or_else(|| unit.path.clone());
+        return Some(finding(&root::unresolve(&target), false, None));
+    };
+    if Path::new(&unit.name)
+        .extension()
+        .is_none_or(|e| e != "service")
+    {
+        return None;
+    }
+    let mut programs = Vec::new();
+    for path in [file.clone()]
+        .into_iter()
+        .chain(drop_ins(&UNIT_DIRS, &unit.name))
+    {
+        match fs::read_to_string(&path) {
+            Ok(content) => parse_exec_start(&content, &mut programs),
+            Err(err) => log::warn!("Failed to read {}: {err}", path.display()),
+        }
+    }
+    let missing = programs.iter().find(|p| !program_exists(p))?;
+    Some(finding(
+        Path::new(missing),
+        true,
+        owner(ctx.backend(), &file),
+    ))
+}
+
+/// Check that the units needed to log in after the next boot are installed
+#[derive(Default)]
+pub(super) struct LoginUnits {
+    units: Vec<LoginUnit>,
+}
+
+impl Check for LoginUnits {
+    fn name(&self) -> &str {
+        CheckKind::LoginUnits.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "The display manager alias, default target and enabled gettys pointing to a missing unit or program, which leave no way to log in after the next boot".to_owned(),
+            caveats: vec![
+                "Programs of ExecStart settings using specifiers, like %I, are not checked",
+                "Systems logged into remotely only may not need a login prompt",
+            ],
+            examples: vec![Finding::BrokenLoginUnit {
+                unit: DISPLAY_MANAGER_UNIT.to_owned(),
+                path: PathBuf::from("/etc/systemd/system/display-manager.service"),
+                missing: "/usr/lib/systemd/system/sddm.service".to_owned(),
+                program: false,
+                package: None,
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        self.units = login_units();
+        Ok(self.units.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for unit in self.units.iter().take_while(|_| !cancel::requested()) {
+            findings.extend(check_unit(ctx, unit));
+            ctx.item_done();
+        }
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+    use crate::{backend::MemoryBackend, metadata::Metadata, progress::Progress};
+
+    #[test]
+    fn test_login_units() {
+        assert_eq!(
+            template_name("getty@tty1.service").as_deref(),
+            Some("getty@.service")
+        );
+        assert_eq!(template_name("gdm.service"), None);
+        let mut programs = Vec::new();
+        parse_exec_start(
+            "[Service]\nExecStart=-/sbin/agetty -o '-p -- \\\\u' --noclear - $TERM\n",
+            &mut programs,
+        );
+        assert_eq!(programs, ["/sbin/agetty"]);
+        parse_exec_start(
+            "[Service]\nExecStart=\nExecStart=-/usr/bin/agetty --autologin foo %I\n",
+            &mut programs,
+        );
+        assert_eq!(programs, ["/usr/bin/agetty"]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let service = dir.path().join("missing-dm.service");
+        fs::write(
+            &service,
+            "[Service]\nExecStart=/usr/bin/missing-display-manager\n",
+        )
+        .unwrap();
+        let link = dir.path().join(DISPLAY_MANAGER_UNIT);
+        symlink(&service, &link).unwrap();
+        let backend = MemoryBackend::default().with_package(
+            "missing-dm",
+            "1.0-1",
+            false,
+            &[service.to_str().unwrap()],
+        );
+        let progress = Progress::new(1, false).unwrap();
+        let metadata = Metadata::default();
+        let ctx = Context::new(&backend, &[], &metadata, &progress, None);
+        let unit = LoginUnit {
+            name: DISPLAY_MANAGER_UNIT.to_owned(),
+            path: link.clone(),
+        };
+        assert_eq!(
+            check_unit(&ctx, &unit).map(|f| f.to_string()),
+            Some(
+                r#"Unit display-manager.service of package "missing-dm" needed to log in runs missing program /usr/bin/missing-display-manager, reinstall the package providing it, there will be no login prompt after the next boot"#
+                    .to_owned()
+            )
+        );
+
+        fs::remove_file(&service).unwrap();
+        assert_eq!(
+            check_unit(&ctx, &unit).map(|f| f.to_string()),
+            Some(format!(
+                "Unit display-manager.service needed to log in points to missing {}, enable an installed one, there will be no login prompt after the next boot",
+                service.display()
+            ))
+        );
+    }
+}
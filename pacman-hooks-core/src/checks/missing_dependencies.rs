@@ -0,0 +1,1110 @@
+//! Missing shared library dependencies of package executables
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    ffi::OsStr,
+    fs, io,
+    num::NonZeroUsize,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use rayon::prelude::*;
+
+use super::{duplicates::owner, perl, Check, CheckDoc, Context, Input};
+use crate::{
+    backend::{for_each_package_executable_file, PackageBackend},
+    cancel, elf,
+    filesdb::RepoPackage,
+    finding::{CheckKind, Finding},
+    kb::KnowledgeBase,
+    logging::LogContext,
+    metadata::Metadata,
+    root,
+    timings::{FileOutcome, Phase},
+};
+
+// Exclude executables in commonly used non standard directories,
+// likely to also use non standard library locations
+pub(super) const BLACKLISTED_EXE_DIRS: [&str; 2] = ["/opt/", "/usr/share/"];
+
+/// Directory of packages installed outside of the standard hierarchy, whose libraries programs of other packages
+/// may still use when an ld.so.conf snippet adds them to the search path
+const OPT_DIR: &str = "/opt";
+
+/// Directories only found in Wine prefixes, whose files are for Windows
+const WINE_PREFIX_DIRS: [&str; 2] = ["drive_c", "dosdevices"];
+
+/// Extensions of Windows executables and libraries, that Wine packages install with executable permissions
+const PE_EXTENSIONS: [&str; 6] = ["exe", "dll", "drv", "sys", "ocx", "cpl"];
+
+/// Library directories searched in a directory tree
+const TREE_LIBRARY_DIRS: [&str; 5] = ["usr/lib", "usr/lib32", "usr/lib64", "lib", "lib64"];
+
+/// Maximum number of executable files waiting for analysis, to keep memory usage bounded
+const ANALYSIS_QUEUE_SIZE: usize = 256;
+
+/// Files found in the directory of Electron and CEF applications, which bundle their own libraries
+const ELECTRON_APP_MARKERS: [&str; 3] = ["chrome-sandbox", "libffmpeg.so", "resources/app.asar"];
+
+/// Number of parent directories of an executable searched for an Electron application
+const ELECTRON_APP_MAX_DEPTH: usize = 3;
+
+/// Directories searched by the loader or in `PATH`, whose executables and libraries must be runnable by the system.
+///
+/// Files for other platforms elsewhere, like the sysroots of cross compilers or device firmware, are expected.
+const NATIVE_DIRS: [&str; 11] = [
+    "/usr/bin",
+    "/usr/sbin",
+    "/usr/lib",
+    "/usr/lib32",
+    "/usr/lib64",
+    "/usr/local/bin",
+    "/usr/local/lib",
+    "/bin",
+    "/sbin",
+    "/lib",
+    "/lib64",
+];
+
+/// Files smaller than their recorded size divided by this are reported as truncated
+const TRUNCATION_RATIO: u64 = 2;
+
+/// Get the size of a file much smaller than the size recorded by its package, `None` if it is not truncated
+fn truncated_size(path: &Path, expected: u64) -> Option<u64> {
+    let size = fs::metadata(path).ok()?.len();
+    (size < expected / TRUNCATION_RATIO || (size == 0 && expected > 0)).then_some(size)
+}
+
+/// Whether a file is a Windows executable or library, or in a Wine prefix, and not loaded by the system loader.
+///
+/// ELF modules of Wine itself, like the 32-bit ones of `i386-unix`, are still checked, ldd picks the loader of their class.
+fn is_wine_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| PE_EXTENSIONS.iter().any(|p| e.eq_ignore_ascii_case(p)))
+        || path
+            .components()
+            .any(|c| WINE_PREFIX_DIRS.iter().any(|d| c.as_os_str() == *d))
+}
+
+/// Get the Electron or CEF application directory an installed file is in, if any.
+///
+/// Only directories below standard ones like `/usr/lib` are considered, `resolve` maps them to accessible paths.
+fn electron_app_dir(file: &Path, resolve: impl Fn(&Path) -> PathBuf) -> Option<&Path> {
+    file.ancestors()
+        .skip(1)
+        .take(ELECTRON_APP_MAX_DEPTH)
+        .take_while(|d| d.components().count() > 3)
+        .find(|d| {
+            let dir = resolve(d);
+            ELECTRON_APP_MARKERS.iter().any(|m| dir.join(m).exists())
+        })
+}
+
+/// Whether a missing dependency is a known false positive, and must not be reported
+pub(super) fn is_known(kb: &KnowledgeBase, package: &str, file: &Path, dependency: &Path) -> bool {
+    let known = kb.is_known(package, &dependency.to_string_lossy());
+    if known {
+        log::debug!(
+            "Ignoring known bundled library {} of file {file:?} of package {package:?}",
+            dependency.display()
+        );
+    }
+    known
+}
+
+/// Get missing dependencies of an installed package file, accessible at `path`,
+/// with ldd if native resolution finds some
+fn file_missing_dependencies(
+    resolver: &elf::SonameResolver,
+    package: &str,
+    file: &Path,
+    path: &Path,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let missing = if elf::is_native_only() {
+        resolver.missing_dependencies(path)
+    } else if resolver.may_have_missing_dependencies(path) {
+        elf::get_missing_dependencies(path)?
+    } else {
+        Vec::new()
+    };
+    if !missing.is_empty() {
+        if let Some(dir) = electron_app_dir(file, root::resolve) {
+            log::debug!(
+                "Ignoring missing dependencies of file {file:?} of package {package:?}, in Electron application {dir:?}"
+            );
+            return Ok(Vec::new());
+        }
+    }
+    Ok(missing)
+}
+
+/// Check that the system can run a package file, accessible at `path`, before analyzing its dependencies.
+///
+/// Return the findings to report instead of missing dependencies, which would be misleading, or `None` if analysis can proceed.
+fn platform_findings(pa: &Arc<String>, f: &Path, path: &Path) -> Option<Vec<Finding>> {
+    let target = match elf::platform(path) {
+        Ok(elf::Platform::Native) => return None,
+        Ok(elf::Platform::Musl) => {
+            log::debug!("Ignoring file {f:?} of package {pa:?}, which uses the musl loader");
+            return Some(Vec::new());
+        }
+        Ok(elf::Platform::Foreign(target)) => target,
+        Err(err) => {
+            log::debug!("Failed to read platform of file {f:?} of package {pa:?}: {err}");
+            return None;
+        }
+    };
+    if f.parent()
+        .is_some_and(|d| NATIVE_DIRS.iter().any(|n| d == Path::new(n)))
+    {
+        Some(vec![Finding::ForeignBinary {
+            package: Arc::clone(pa),
+            file: Arc::new(f.to_owned()),
+            platform: target.to_string(),
+        }])
+    } else {
+        log::debug!("Ignoring file {f:?} of package {pa:?}, built for {target}");
+        Some(Vec::new())
+    }
+}
+
+/// Check an executable file of a package, recorded to be `expected` bytes large, for missing dependencies
+fn analyze_file(
+    ctx: &Context<'_>,
+    kb: &KnowledgeBase,
+    resolver: &elf::SonameResolver,
+    pa: Arc<String>,
+    f: PathBuf,
+    expected: Option<u64>,
+) -> Vec<Finding> {
+    let start = Instant::now();
+    let timings = ctx.timings();
+    let path = root::resolve(&f);
+    // ldd fails on truncated files with confusing errors
+    if let Some((size, expected)) = expected.and_then(|e| truncated_size(&path, e).map(|s| (s, e)))
+    {
+        return vec![Finding::TruncatedFile {
+            package: pa,
+            file: Arc::new(f),
+            size,
+            expected,
+        }];
+    }
+    if let Some(findings) = platform_findings(&pa, &f, &path) {
+        return findings;
+    }
+    let missing = match timings {
+        // Static executables have no dependencies, only find them if they are counted
+        Some(timings) if timings.has_package_stats() && elf::is_static(&path) => {
+            timings.record_file_outcome(&pa, FileOutcome::SkippedStatic);
+            Ok(Vec::new())
+        }
+        _ => {
+            if let Some(timings) = timings {
+                timings.record_file_outcome(&pa, FileOutcome::Analyzed);
+            }
+            file_missing_dependencies(resolver, &pa, &f, &path)
+        }
+    };
+    let mut findings = match missing {
+        Ok(ms) => {
+            let fa = Arc::new(f.clone());
+            ms.into_iter()
+                .filter(|m| !is_known(kb, &pa, &f, m) && !perl::is_module_libperl(&f, m))
+                .map(|m| Finding::MissingDependency {
+                    package: Arc::clone(&pa),
+                    file: Arc::clone(&fa),
+                    dependency: m,
+                    origin: None,
+                })
+                .collect()
+        }
+        Err(_) if cancel::requested() => Vec::new(),
+        Err(e) => {
+            log::error!("Failed to get missing dependencies for file {f:?} of package {pa:?}: {e}");
+            Vec::new()
+        }
+    };
+    let opt_libraries = resolver.libraries_below(&path, &root::resolve(Path::new(OPT_DIR)));
+    if !opt_libraries.is_empty() {
+        let fa = Arc::new(f.clone());
+        findings.extend(
+            opt_libraries
+                .into_iter()
+                .map(|l| Finding::OptLibraryDependency {
+                    package: Arc::clone(&pa),
+                    file: Arc::clone(&fa),
+                    library: root::unresolve(&l),
+                    provider: None,
+                }),
+        );
+    }
+    if let Some(timings) = timings {
+        let duration = start.elapsed();
+        timings.record_phase(Phase::ElfAnalysis, duration);
+        timings.record_file(&pa, &f, duration);
+    }
+    findings
+}
+
+/// Check executable files of packages for missing dependencies
+fn check_packages(ctx: &Context<'_>, kb: &KnowledgeBase) -> Vec<Finding> {
+    let backend = ctx.backend();
+    let timings = ctx.timings();
+    let resolver = &elf::SonameResolver::default();
+    let (file_tx, file_rx) =
+        mpsc::sync_channel::<(Arc<String>, PathBuf, Option<u64>)>(ANALYSIS_QUEUE_SIZE);
+    let packages = Mutex::new(ctx.packages().iter());
+    let log_context = LogContext::current();
+    thread::scope(|scope| {
+        // Producers list package files and feed them to the analysis thread pool,
+        // they run on their own threads so that a full queue never blocks analysis
+        let producer_count = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        for _ in 0..producer_count {
+            let file_tx = file_tx.clone();
+            let packages = &packages;
+            let log_context = &log_context;
+            scope.spawn(move || {
+                while let Some(package) = packages
+                    .lock()
+                    .ok()
+                    .filter(|_| !cancel::requested())
+                    .and_then(|mut ps| ps.next())
+                {
+                    let pa = Arc::new(package.to_owned());
+                    let _log = log_context.clone().with_package(&pa).enter();
+                    let start = Instant::now();
+                    let mut blocked = Duration::ZERO;
+                    if let Err(e) = for_each_package_executable_file(backend, &pa, |f| {
+                        if let Some(timings) = timings {
+                            timings.record_file_outcome(&pa, FileOutcome::Listed);
+                        }
+                        if BLACKLISTED_EXE_DIRS.iter().any(|d| f.path().starts_with(d))
+                            || is_wine_file(f.path())
+                        {
+                            if let Some(timings) = timings {
+                                timings.record_file_outcome(&pa, FileOutcome::SkippedBlacklisted);
+                            }
+                        } else {
+                            let send_start = Instant::now();
+                            let size = f.size();
+                            // The receiver outlives all producers
+                            let _ = file_tx.send((Arc::clone(&pa), f.into_path(), size));
+                            blocked += send_start.elapsed();
+                        }
+                    }) {
+                        if !cancel::requested() {
+                            log::error!("Failed to get package executable files for {pa:?}: {e}");
+                        }
+                    }
+                    if let Some(timings) = timings {
+                        let duration = start.elapsed().saturating_sub(blocked);
+                        timings.record_phase(Phase::FileListing, duration);
+                        timings.record_package(&pa, duration);
+                    }
+                    ctx.item_done();
+                }
+            });
+        }
+        drop(file_tx);
+
+        file_rx
+            .into_iter()
+            .par_bridge()
+            .filter(|_| !cancel::requested())
+            .map(|(pa, f, expected)| {
+                let _log = log_context.clone().with_package(&pa).with_file(&f).enter();
+                analyze_file(ctx, kb, resolver, pa, f, expected)
+            })
+            .flatten()
+            .collect()
+    })
+}
+
+/// Add the packages owning the libraries of /opt used by files outside of it, looking up each library once
+fn add_opt_providers(backend: &dyn PackageBackend, findings: Vec<Finding>) -> Vec<Finding> {
+    let mut providers: HashMap<PathBuf, Option<String>> = HashMap::new();
+    findings
+        .into_iter()
+        .map(|mut finding| {
+            if let Finding::OptLibraryDependency {
+                library, provider, ..
+            } = &mut finding
+            {
+                provider.clone_from(
+                    providers
+                        .entry(library.clone())
+                        .or_insert_with(|| owner(backend, &root::resolve(library))),
+                );
+            }
+            finding
+        })
+        .collect()
+}
+
+/// Drop missing dependencies shipped by another foreign package, outside of the library search path,
+/// like split `-libs` packages of the AUR, which their programs load with their own search path.
+///
+/// The library must have the ELF class of the file needing it, so that 32-bit copies are not used for 64-bit programs.
+fn drop_foreign_provided(
+    backend: &dyn PackageBackend,
+    metadata: &Metadata,
+    findings: Vec<Finding>,
+) -> Vec<Finding> {
+    let sonames: HashSet<_> = findings
+        .iter()
+        .filter_map(|f| match f {
+            Finding::MissingDependency { dependency, .. } => Some(dependency.as_os_str()),
+            _ => None,
+        })
+        .collect();
+    if sonames.is_empty() {
+        return findings;
+    }
+    let packages = match metadata.foreign_packages(backend) {
+        Ok(packages) => packages,
+        Err(err) => {
+            log::warn!("Failed to get foreign packages providing missing libraries: {err:#}");
+            return findings;
+        }
+    };
+    let mut providers: HashMap<PathBuf, Vec<(String, PathBuf)>> = HashMap::new();
+    for package in packages.iter() {
+        if let Err(err) = backend.for_each_file(package, &mut |f| {
+            if let Some(name) = f.path().file_name().filter(|n| sonames.contains(n)) {
+                providers
+                    .entry(PathBuf::from(name))
+                    .or_default()
+                    .push((package.clone(), f.path().to_owned()));
+            }
+        }) {
+            log::debug!("Failed to get files of package {package:?}: {err}");
+        }
+    }
+    if providers.is_empty() {
+        return findings;
+    }
+    findings
+        .into_iter()
+        .filter(|f| {
+            let Finding::MissingDependency {
+                package,
+                file,
+                dependency,
+                ..
+            } = f
+            else {
+                return true;
+            };
+            let class = elf::elf_class(&root::resolve(file));
+            let provider = providers.get(dependency).into_iter().flatten().find(|(p, path)| {
+                p != package.as_str() && elf::elf_class(&root::resolve(path)) == class
+            });
+            if let Some((provider, path)) = provider {
+                log::debug!(
+                    "Library {} of file {file:?} of package {package:?} is provided by package {provider:?} in {path:?}",
+                    dependency.display()
+                );
+            }
+            provider.is_none()
+        })
+        .collect()
+}
+
+/// Drop missing dependencies shipped by the package needing them, in any directory, like libraries that
+/// proprietary applications bundle next to their executable and load with their own search path.
+///
+/// The library must have the ELF class of the file needing it, like for libraries of other foreign packages.
+fn drop_self_bundled(backend: &dyn PackageBackend, findings: Vec<Finding>) -> Vec<Finding> {
+    let mut needed: HashMap<&str, HashSet<&OsStr>> = HashMap::new();
+    for finding in &findings {
+        if let Finding::MissingDependency {
+            package,
+            dependency,
+            ..
+        } = finding
+        {
+            needed
+                .entry(package.as_str())
+                .or_default()
+                .insert(dependency.as_os_str());
+        }
+    }
+    let mut bundled: HashMap<(String, PathBuf), Vec<PathBuf>> = HashMap::new();
+    for (package, sonames) in needed {
+        if let Err(err) = backend.for_each_file(package, &mut |f| {
+            if let Some(name) = f.path().file_name().filter(|n| sonames.contains(n)) {
+                bundled
+                    .entry((package.to_owned(), PathBuf::from(name)))
+                    .or_default()
+                    .push(f.path().to_owned());
+            }
+        }) {
+            log::debug!("Failed to get files of package {package:?}: {err}");
+        }
+    }
+    if bundled.is_empty() {
+        return findings;
+    }
+    findings
+        .into_iter()
+        .filter(|f| {
+            let Finding::MissingDependency {
+                package,
+                file,
+                dependency,
+                ..
+            } = f
+            else {
+                return true;
+            };
+            let class = elf::elf_class(&root::resolve(file));
+            let path = bundled
+                .get(&(package.as_str().to_owned(), dependency.clone()))
+                .into_iter()
+                .flatten()
+                .find(|path| elf::elf_class(&root::resolve(path)) == class);
+            if let Some(path) = path {
+                log::debug!(
+                    "Library {} of file {file:?} of package {package:?} is bundled in {path:?}",
+                    dependency.display()
+                );
+            }
+            path.is_none()
+        })
+        .collect()
+}
+
+/// Dependencies of a package that are not installed
+struct UninstalledDependencies {
+    declared: Vec<String>,
+    optional: Vec<String>,
+}
+
+impl UninstalledDependencies {
+    fn is_empty(&self) -> bool {
+        self.declared.is_empty() && self.optional.is_empty()
+    }
+}
+
+/// Get dependencies that are not installed, of packages with missing dependencies
+fn uninstalled_dependencies(
+    backend: &dyn PackageBackend,
+    findings: &[Finding],
+) -> HashMap<Arc<String>, UninstalledDependencies> {
+    let uninstalled = |package: &str, dependencies: anyhow::Result<Vec<String>>| -> Vec<String> {
+        dependencies
+            .unwrap_or_else(|err| {
+                log::debug!("Failed to get dependencies of package {package:?}: {err}");
+                Vec::new()
+            })
+            .into_iter()
+            .filter(|d| backend.version(d).is_ok_and(|v| v.is_none()))
+            .collect()
+    };
+    let mut packages = HashMap::new();
+    for finding in findings {
+        let Finding::MissingDependency { package, .. } = finding else {
+            continue;
+        };
+        if packages.contains_key(package) {
+            continue;
+        }
+        let dependencies = UninstalledDependencies {
+            declared: uninstalled(package, backend.dependencies(package)),
+            optional: uninstalled(package, backend.optional_dependencies(package)),
+        };
+        packages.insert(Arc::clone(package), dependencies);
+    }
+    packages
+}
+
+/// Path a missing library of a 32-bit file of a 64-bit system would be installed at, in the library directory of
+/// `lib32-*` packages, `None` for other files
+fn compat_library_path(file: &Path, dependency: &Path) -> Option<PathBuf> {
+    elf::is_compat(&root::resolve(file))
+        .then(|| Path::new(elf::COMPAT_LIBRARY_DIR).join(dependency))
+}
+
+/// Packages of the files database providing libraries, by soname, and libraries of 32-bit files, by path
+type LibraryProviders = (
+    HashMap<String, Vec<RepoPackage>>,
+    HashMap<PathBuf, Vec<RepoPackage>>,
+);
+
+/// Find packages providing the missing dependencies of packages with uninstalled dependencies, `None` if there are
+/// none or they can not be found
+fn library_providers(
+    metadata: &Metadata,
+    uninstalled: &HashMap<Arc<String>, UninstalledDependencies>,
+    findings: &[Finding],
+) -> Option<LibraryProviders> {
+    // Libraries of 32-bit files are looked up in the library directory of lib32 packages
+    let mut sonames = BTreeSet::new();
+    let mut compat_paths = BTreeSet::new();
+    for finding in findings {
+        if let Finding::MissingDependency {
+            package,
+            file,
+            dependency,
+            ..
+        } = finding
+        {
+            if uninstalled.get(package).is_some_and(|u| !u.is_empty()) {
+                if let Some(path) = compat_library_path(file, dependency) {
+                    compat_paths.insert(path);
+                } else {
+                    sonames.insert(dependency.to_string_lossy().into_owned());
+                }
+            }
+        }
+    }
+    if sonames.is_empty() && compat_paths.is_empty() {
+        return None;
+    }
+    let sonames: Vec<_> = sonames.into_iter().collect();
+    let compat_paths: Vec<_> = compat_paths.into_iter().collect();
+    match metadata.files_db().and_then(|db| {
+        db.as_ref()
+            .as_ref()
+            .map(|db| {
+                anyhow::Ok((
+                    db.soname_providers(&sonames)?,
+                    db.path_providers(&compat_paths)?,
+                ))
+            })
+            .transpose()
+    }) {
+        Ok(Some(providers)) => Some(providers),
+        Ok(None) => {
+            log::debug!("No files database to find dependencies providing missing libraries");
+            None
+        }
+        Err(err) => {
+            log::warn!("Failed to find packages providing missing libraries: {err:#}");
+            None
+        }
+    }
+}
+
+/// Report missing dependencies provided by an uninstalled dependency of their package more precisely:
+/// as a declared dependency to install, or an unavailable optional feature, since the package works without it
+fn reclassify_uninstalled(
+    backend: &dyn PackageBackend,
+    metadata: &Metadata,
+    findings: Vec<Finding>,
+) -> Vec<Finding> {
+    let uninstalled = uninstalled_dependencies(backend, &findings);
+    let Some(providers) = library_providers(metadata, &uninstalled, &findings) else {
+        return findings;
+    };
+    findings
+        .into_iter()
+        .map(|finding| {
+            let Finding::MissingDependency {
+                package,
+                file,
+                dependency,
+                origin,
+            } = finding
+            else {
+                return finding;
+            };
+            let providers: Vec<_> = compat_library_path(&file, &dependency)
+                .map_or_else(
+                    || providers.0.get(dependency.to_string_lossy().as_ref()),
+                    |path| providers.1.get(&path),
+                )
+                .into_iter()
+                .flatten()
+                .map(RepoPackage::name)
+                .collect();
+            let find = |dependencies: &[String]| {
+                dependencies
+                    .iter()
+                    .find(|d| providers.contains(&d.as_str()))
+                    .cloned()
+            };
+            let Some(dependencies) = uninstalled.get(&package) else {
+                return Finding::MissingDependency {
+                    package,
+                    file,
+                    dependency,
+                    origin,
+                };
+            };
+            if let Some(declared) = find(&dependencies.declared) {
+                Finding::DependencyNotInstalled {
+                    package,
+                    file,
+                    dependency,
+                    declared,
+                }
+            } else if let Some(optional) = find(&dependencies.optional) {
+                Finding::OptionalDependencyMissing {
+                    package,
+                    file,
+                    dependency,
+                    optional,
+                }
+            } else {
+                Finding::MissingDependency {
+                    package,
+                    file,
+                    dependency,
+                    origin,
+                }
+            }
+        })
+        .collect()
+}
+
+/// Find which recently replaced or removed package versions provided missing dependencies
+pub(super) fn explain_missing(metadata: &Metadata, findings: Vec<Finding>) -> Vec<Finding> {
+    let sonames: Vec<_> = findings
+        .iter()
+        .filter_map(|f| match f {
+            Finding::MissingDependency { dependency, .. } => {
+                Some(dependency.to_string_lossy().into_owned())
+            }
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    if sonames.is_empty() {
+        return findings;
+    }
+    let origins = match metadata.history() {
+        Ok(history) => history.library_origins(&sonames),
+        Err(err) => {
+            log::debug!("Failed to read package history: {err}");
+            return findings;
+        }
+    };
+    findings
+        .into_iter()
+        .map(|mut finding| {
+            if let Finding::MissingDependency {
+                dependency, origin, ..
+            } = &mut finding
+            {
+                origin.clone_from(&origins.get(dependency.to_string_lossy().as_ref()).cloned());
+            }
+            finding
+        })
+        .collect()
+}
+
+/// Get executable files and shared libraries of a directory tree, recursively
+fn tree_elf_candidates(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            tree_elf_candidates(&entry.path(), files)?;
+        } else if file_type.is_file()
+            && ((entry.metadata()?.permissions().mode() & 0o111) != 0
+                || entry.file_name().to_string_lossy().contains(".so"))
+        {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Check executable files and shared libraries of a directory tree for missing dependencies,
+/// like an extracted package, reporting files at their installed path as owned by `package`.
+///
+/// Libraries are searched in the tree first, then in the checked system.
+/// Dependencies are only resolved natively, because ldd may execute code of the inspected files.
+///
+/// # Errors
+///
+/// Fails if the directory tree can not be walked
+pub fn check_tree(dir: &Path, package: &str) -> anyhow::Result<Vec<Finding>> {
+    let mut files = Vec::new();
+    tree_elf_candidates(dir, &mut files)?;
+    let resolver =
+        elf::SonameResolver::with_library_dirs(TREE_LIBRARY_DIRS.map(|d| dir.join(d)).to_vec());
+    let kb = KnowledgeBase::load();
+    let package = Arc::new(package.to_owned());
+    Ok(files
+        .par_iter()
+        .filter(|_| !cancel::requested())
+        .filter_map(|f| {
+            let installed = Path::new("/").join(f.strip_prefix(dir).ok()?);
+            let excluded = BLACKLISTED_EXE_DIRS
+                .iter()
+                .any(|d| installed.starts_with(d))
+                || is_wine_file(&installed);
+            (!excluded).then_some((f, installed))
+        })
+        .flat_map_iter(|(f, installed)| {
+            let mut missing: Vec<_> = resolver
+                .missing_dependencies(f)
+                .into_iter()
+                .filter(|m| !is_known(&kb, &package, &installed, m))
+                .collect();
+            if !missing.is_empty()
+                && electron_app_dir(&installed, |d| dir.join(d.strip_prefix("/").unwrap_or(d)))
+                    .is_some()
+            {
+                missing.clear();
+            }
+            let file = Arc::new(installed);
+            let package = Arc::clone(&package);
+            missing
+                .into_iter()
+                .map(move |m| Finding::MissingDependency {
+                    package: Arc::clone(&package),
+                    file: Arc::clone(&file),
+                    dependency: m,
+                    origin: None,
+                })
+        })
+        .collect())
+}
+
+/// Check a file given by the user, at `path` below the root, reporting its owner if any
+fn check_file(
+    backend: &dyn PackageBackend,
+    kb: &KnowledgeBase,
+    resolver: &elf::SonameResolver,
+    path: &Path,
+) -> Vec<Finding> {
+    let file = root::unresolve(path);
+    if elf::elf_class(path).is_none() {
+        log::warn!("File {file:?} is not an ELF executable or library");
+        return Vec::new();
+    }
+    match elf::platform(path) {
+        Ok(elf::Platform::Native) => {}
+        Ok(elf::Platform::Musl) => {
+            log::warn!("File {file:?} uses the musl loader, its dependencies are not checked");
+            return Vec::new();
+        }
+        Ok(elf::Platform::Foreign(target)) => {
+            log::warn!("File {file:?} is built for {target}, and can not run on this system");
+            return Vec::new();
+        }
+        Err(err) => {
+            log::warn!("Failed to read platform of file {file:?}: {err}");
+            return Vec::new();
+        }
+    }
+    let mut missing = resolver.missing_dependencies(path);
+    if !missing.is_empty() {
+        if let Some(dir) = electron_app_dir(&file, root::resolve) {
+            log::info!(
+                "Ignoring missing dependencies of file {file:?}, in Electron application {dir:?}"
+            );
+            missing.clear();
+        }
+    }
+    match owner(backend, path) {
+        Some(package) => {
+            missing.retain(|m| !is_known(kb, &package, &file, m));
+            let package = Arc::new(package);
+            let file = Arc::new(file);
+            missing
+                .into_iter()
+                .map(|m| Finding::MissingDependency {
+                    package: Arc::clone(&package),
+                    file: Arc::clone(&file),
+                    dependency: m,
+                    origin: None,
+                })
+                .collect()
+        }
+        None => missing
+            .into_iter()
+            .map(|m| Finding::UnpackagedMissingDependency {
+                file: file.clone(),
+                dependency: m,
+            })
+            .collect(),
+    }
+}
+
+/// Check files below the root for missing dependencies, whether a package installed them or not,
+/// like a single suspicious executable.
+///
+/// Dependencies are only resolved natively, because ldd may execute code of the inspected files.
+pub fn check_files(backend: &dyn PackageBackend, paths: &[PathBuf]) -> Vec<Finding> {
+    let resolver = elf::SonameResolver::default();
+    let kb = KnowledgeBase::load();
+    paths
+        .par_iter()
+        .filter(|_| !cancel::requested())
+        .flat_map_iter(|p| check_file(backend, &kb, &resolver, p))
+        .collect()
+}
+
+/// Check executable files of packages for missing dependencies
+#[derive(Default)]
+pub(super) struct MissingDependencies {
+    kb: KnowledgeBase,
+}
+
+impl Check for MissingDependencies {
+    fn name(&self) -> &str {
+        CheckKind::MissingDependencies.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::Packages]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Executables and libraries of the checked packages needing a shared library that is not installed, usually because an upgrade removed the soname they were built against".to_owned(),
+            caveats: vec![
+                "Programs finding their libraries with a wrapper script or dlopen can be reported, known false positives downloaded with update-kb are skipped",
+                "Executables of /opt and /usr/share, and Windows files of Wine packages, are not analyzed",
+                "Files built for another architecture or operating system are reported as such, instead of with missing dependencies",
+            ],
+            config_keys: vec!["AurHelper", "RebuildCommand", "RebuildScript", "SuggestDowngrade"],
+            examples: vec![
+                Finding::MissingDependency {
+                    package: Arc::new("foo-git".to_owned()),
+                    file: Arc::new(PathBuf::from("/usr/bin/foo")),
+                    dependency: PathBuf::from("libicuuc.so.74"),
+                    origin: None,
+                },
+                Finding::DependencyNotInstalled {
+                    package: Arc::new("bar".to_owned()),
+                    file: Arc::new(PathBuf::from("/usr/bin/bar")),
+                    dependency: PathBuf::from("libQt5Core.so.5"),
+                    declared: "qt5-base".to_owned(),
+                },
+            ],
+        }
+    }
+
+    fn progress_weight(&self) -> u64 {
+        // Each package has many executables, whose libraries are resolved
+        20
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        packages: &[String],
+    ) -> anyhow::Result<u64> {
+        self.kb = KnowledgeBase::load();
+        Ok(packages.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        let backend = ctx.backend();
+        let metadata = ctx.metadata();
+        explain_missing(
+            metadata,
+            reclassify_uninstalled(
+                backend,
+                metadata,
+                drop_foreign_provided(
+                    backend,
+                    metadata,
+                    add_opt_providers(
+                        backend,
+                        drop_self_bundled(backend, check_packages(ctx, &self.kb)),
+                    ),
+                ),
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, mode) in [
+            ("usr/bin/foo", 0o755),
+            ("usr/lib/libfoo.so.1", 0o644),
+            ("usr/share/doc/foo/README", 0o644),
+        ] {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "not an ELF").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        }
+
+        let mut files = Vec::new();
+        tree_elf_candidates(dir.path(), &mut files).unwrap();
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                dir.path().join("usr/bin/foo"),
+                dir.path().join("usr/lib/libfoo.so.1")
+            ]
+        );
+        assert!(check_tree(dir.path(), "foo").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_check_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        let elf = fs::read("/proc/self/exe").unwrap();
+        fs::write(path("foo"), &elf).unwrap();
+        fs::write(path("bar"), &elf).unwrap();
+        fs::write(path("baz.sh"), "#!/bin/sh\n").unwrap();
+        let backend = crate::backend::MemoryBackend::default().with_package(
+            "foo",
+            "1.0-1",
+            true,
+            &[path("foo").to_str().unwrap()],
+        );
+        // Resolvable dependencies, whether the file is owned or not, and a file that is not an ELF
+        assert!(check_files(&backend, &["foo", "bar", "baz.sh"].map(path)).is_empty());
+    }
+
+    #[test]
+    fn test_drop_foreign_provided() {
+        let dir = tempfile::tempdir().unwrap();
+        let elf = fs::read("/proc/self/exe").unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_owned();
+        for name in ["foo", "libfoo.so.1", "libbaz.so.1"] {
+            fs::write(path(name), &elf).unwrap();
+        }
+        fs::write(path("libbar.so.1"), "not an ELF").unwrap();
+        let backend = crate::backend::MemoryBackend::default()
+            .with_package("foo", "1.0-1", true, &[&path("foo"), &path("libbaz.so.1")])
+            .with_package(
+                "foo-libs",
+                "1.0-1",
+                true,
+                &[&path("libfoo.so.1"), &path("libbar.so.1")],
+            );
+        let package = Arc::new("foo".to_owned());
+        let file = Arc::new(PathBuf::from(path("foo")));
+        let findings = ["libfoo.so.1", "libbar.so.1", "libbaz.so.1"]
+            .map(|d| Finding::MissingDependency {
+                package: Arc::clone(&package),
+                file: Arc::clone(&file),
+                dependency: PathBuf::from(d),
+                origin: None,
+            })
+            .into();
+        let findings = drop_foreign_provided(&backend, &Metadata::default(), findings);
+        assert_eq!(
+            findings
+                .iter()
+                .filter_map(|f| match f {
+                    Finding::MissingDependency { dependency, .. } => dependency.to_str(),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            ["libbar.so.1", "libbaz.so.1"]
+        );
+    }
+
+    #[test]
+    fn test_drop_self_bundled() {
+        let dir = tempfile::tempdir().unwrap();
+        let elf = fs::read("/proc/self/exe").unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_owned();
+        fs::create_dir(path("lib")).unwrap();
+        for name in ["foo", "lib/libfoo.so.1", "libbaz.so.1"] {
+            fs::write(path(name), &elf).unwrap();
+        }
+        fs::write(path("libbar.so.1"), "not an ELF").unwrap();
+        let backend = crate::backend::MemoryBackend::default()
+            .with_package(
+                "foo",
+                "1.0-1",
+                false,
+                &[&path("foo"), &path("lib/libfoo.so.1"), &path("libbar.so.1")],
+            )
+            .with_package("baz", "1.0-1", false, &[&path("libbaz.so.1")]);
+        let package = Arc::new("foo".to_owned());
+        let file = Arc::new(PathBuf::from(path("foo")));
+        let findings = ["libfoo.so.1", "libbar.so.1", "libbaz.so.1"]
+            .map(|d| Finding::MissingDependency {
+                package: Arc::clone(&package),
+                file: Arc::clone(&file),
+                dependency: PathBuf::from(d),
+                origin: None,
+            })
+            .into();
+        let findings = drop_self_bundled(&backend, findings);
+        assert_eq!(
+            findings
+                .iter()
+                .filter_map(|f| match f {
+                    Finding::MissingDependency { dependency, .. } => dependency.to_str(),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            ["libbar.so.1", "libbaz.so.1"]
+        );
+    }
+
+    #[test]
+    fn test_is_wine_file() {
+        assert!(is_wine_file(Path::new(
+            "/usr/lib/wine/x86_64-windows/kernel32.dll"
+        )));
+        assert!(is_wine_file(Path::new(
+            "/usr/lib/wine/i386-windows/WINEMINE.EXE"
+        )));
+        assert!(is_wine_file(Path::new(
+            "/usr/lib/game/prefix/drive_c/windows/system32/foo"
+        )));
+        assert!(!is_wine_file(Path::new(
+            "/usr/lib32/wine/i386-unix/ntdll.so"
+        )));
+        assert!(!is_wine_file(Path::new("/usr/bin/wine")));
+    }
+
+    #[test]
+    fn test_electron_app_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = dir.path().join("usr/lib/app");
+        fs::create_dir_all(app.join("resources")).unwrap();
+        fs::write(app.join("resources/app.asar"), "").unwrap();
+        let resolve = |d: &Path| dir.path().join(d.strip_prefix("/").unwrap());
+        assert_eq!(
+            electron_app_dir(Path::new("/usr/lib/app/swiftshader/libEGL.so"), resolve),
+            Some(Path::new("/usr/lib/app"))
+        );
+        assert_eq!(
+            electron_app_dir(Path::new("/usr/lib/app/app"), resolve),
+            Some(Path::new("/usr/lib/app"))
+        );
+        assert_eq!(
+            electron_app_dir(Path::new("/usr/lib/other/other"), resolve),
+            None
+        );
+
+        // Standard directories are never application directories
+        fs::create_dir_all(dir.path().join("usr/bin")).unwrap();
+        fs::write(dir.path().join("usr/bin/chrome-sandbox"), "").unwrap();
+        assert_eq!(electron_app_dir(Path::new("/usr/bin/foo"), resolve), None);
+    }
+
+    #[test]
+    fn test_truncated_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("foo");
+        fs::write(&path, "").unwrap();
+        assert_eq!(truncated_size(&path, 1), Some(0));
+        assert_eq!(truncated_size(&path, 0), None);
+        fs::write(&path, [0; 100]).unwrap();
+        assert_eq!(truncated_size(&path, 16432), Some(100));
+        assert_eq!(truncated_size(&path, 150), None);
+        assert_eq!(truncated_size(&dir.path().join("bar"), 100), None);
+    }
+}
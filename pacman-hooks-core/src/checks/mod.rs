@@ -0,0 +1,900 @@
+//! Checks for broken packages and system state
+
+use std::{
+    any::Any,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+use rayon::prelude::*;
+
+use crate::{
+    backend::{PackageBackend, RetryingBackend},
+    config::Config,
+    error::{Classify as _, ErrorKind},
+    finding::{CheckKind, Finding, Severity, SkipReason},
+    logging::LogContext,
+    metadata::Metadata,
+    network,
+    progress::Progress,
+    timings::{Phase, Timings},
+};
+
+mod aur;
+mod checksums;
+mod cmdline;
+mod conflicts;
+mod duplicates;
+mod external;
+mod firmware;
+mod ldconfig;
+mod legacy;
+mod links;
+mod login;
+mod missing_dependencies;
+mod mounts;
+mod pacnew;
+mod perl;
+mod permissions;
+mod python;
+mod scriptlets;
+mod secureboot;
+mod security;
+mod stale;
+mod symbols;
+mod symlinks;
+mod systemd;
+mod typelibs;
+mod unowned;
+mod upgrade;
+
+pub use missing_dependencies::{check_files, check_tree};
+pub use unowned::snapshot_unowned_dirs;
+pub use upgrade::{impacted_packages, predict_breakage, removal_breakage};
+
+/// Time a check can run before it is skipped, by default
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_mins(10);
+
+/// Time a check can run before it is skipped, by default, when run from a pacman hook, holding up the transaction
+pub const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default check timeout set for the run, if not [`DEFAULT_TIMEOUT`]
+static TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
+/// Progress units of an item of a check, by default
+const DEFAULT_PROGRESS_WEIGHT: u64 = 1;
+
+/// Data a check works on
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Input {
+    /// Packages to analyze, foreign packages unless given explicitly
+    Packages,
+    /// System wide state, not limited to the analyzed packages
+    System,
+}
+
+/// State shared by checks while they run
+pub struct Context<'a> {
+    backend: &'a dyn PackageBackend,
+    packages: &'a [String],
+    metadata: &'a Metadata,
+    progress: &'a Progress,
+    /// Progress units of each item
+    weight: u64,
+    timings: Option<&'a Timings>,
+}
+
+impl<'a> Context<'a> {
+    pub(crate) fn new(
+        backend: &'a dyn PackageBackend,
+        packages: &'a [String],
+        metadata: &'a Metadata,
+        progress: &'a Progress,
+        timings: Option<&'a Timings>,
+    ) -> Self {
+        Self {
+            backend,
+            packages,
+            metadata,
+            progress,
+            weight: DEFAULT_PROGRESS_WEIGHT,
+            timings,
+        }
+    }
+
+    /// Count each item done as `weight` progress units
+    pub(crate) fn with_progress_weight(mut self, weight: u64) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Installed package information
+    #[must_use]
+    pub fn backend(&self) -> &'a dyn PackageBackend {
+        self.backend
+    }
+
+    /// Packages to analyze
+    #[must_use]
+    pub fn packages(&self) -> &'a [String] {
+        self.packages
+    }
+
+    /// Report an item counted by [`Check::prepare`] as checked
+    pub fn item_done(&self) {
+        self.progress.add(self.weight);
+    }
+
+    /// Package metadata, shared with the other checks
+    pub(crate) fn metadata(&self) -> &'a Metadata {
+        self.metadata
+    }
+
+    pub(crate) fn timings(&self) -> Option<&'a Timings> {
+        self.timings
+    }
+}
+
+/// Documentation of a check, for users to understand its findings without reading its code
+#[derive(Default)]
+pub struct CheckDoc {
+    /// What the check detects
+    pub detects: String,
+    /// Known false positives, and findings that may not need action
+    pub caveats: Vec<&'static str>,
+    /// Configuration keys changing what the check does, besides the ones enabling and ordering checks
+    pub config_keys: Vec<&'static str>,
+    /// Findings the check can produce
+    pub examples: Vec<Finding>,
+}
+
+/// A check producing findings
+pub trait Check: Send + Sync {
+    /// Name, used to enable or disable the check
+    fn name(&self) -> &str;
+
+    /// Data the check works on
+    fn inputs(&self) -> &[Input];
+
+    /// Documentation, printed by the describe subcommand
+    fn doc(&self) -> CheckDoc {
+        CheckDoc::default()
+    }
+
+    /// Whether the check runs when not explicitly enabled
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    /// Whether the check needs network access, it is skipped without being prepared when the network is offline
+    fn needs_network(&self) -> bool {
+        false
+    }
+
+    /// Gather what to check, and return the number of items, for progress reporting.
+    ///
+    /// `packages` is only set if the check wants [`Input::Packages`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if what to check can not be gathered, the check is then reported as skipped
+    fn prepare(&mut self, backend: &dyn PackageBackend, packages: &[String])
+        -> anyhow::Result<u64>;
+
+    /// Maximum run time, after which the check is skipped and left running in the background
+    fn timeout(&self) -> Duration {
+        default_timeout()
+    }
+
+    /// Progress units of each item counted by [`Check::prepare`], estimating its cost relative to the items
+    /// of other checks, so that the progress of all checks roughly follows the remaining time
+    fn progress_weight(&self) -> u64 {
+        DEFAULT_PROGRESS_WEIGHT
+    }
+
+    /// Run the check
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding>;
+}
+
+/// Get all built-in checks
+#[must_use]
+pub fn builtin_checks() -> Vec<Box<dyn Check>> {
+    vec![
+        Box::new(missing_dependencies::MissingDependencies::default()),
+        Box::new(python::PythonPackages::default()),
+        Box::new(systemd::ServiceLinks::default()),
+        Box::new(login::LoginUnits::default()),
+        Box::new(duplicates::DuplicateLibraries::default()),
+        Box::new(permissions::LibraryPermissions::default()),
+        Box::new(ldconfig::LibraryLinks::default()),
+        Box::new(typelibs::IntrospectionTypelibs::default()),
+        Box::new(perl::PerlModules::default()),
+        Box::new(symbols::SymbolVersions),
+        Box::new(scriptlets::ScriptletFailures::default()),
+        Box::new(unowned::UnownedFiles::default()),
+        Box::new(pacnew::PacnewFiles::default()),
+        Box::new(checksums::FileChecksums::default()),
+        Box::new(conflicts::FileConflicts::default()),
+        Box::new(links::ConventionLinks::default()),
+        Box::new(symlinks::SymlinkChains),
+        Box::new(mounts::BootMounts::default()),
+        Box::new(cmdline::KernelCmdline::default()),
+        Box::new(secureboot::SecureBootImages::default()),
+        Box::new(firmware::ModuleFirmware::default()),
+        Box::new(aur::AurPackages),
+        Box::new(stale::StaleBuilds::default()),
+        Box::new(legacy::LegacyRuntimes::default()),
+        Box::new(security::SecurityAdvisories::default()),
+    ]
+}
+
+/// Use `timeout` instead of [`DEFAULT_TIMEOUT`] for checks without a timeout of their own, for the rest of the run
+pub fn set_default_timeout(timeout: Duration) {
+    // Checks may already have been timed with the first one otherwise
+    let _ = TIMEOUT.set(timeout);
+}
+
+/// Time a check can run before it is skipped, unless it has a timeout of its own
+fn default_timeout() -> Duration {
+    TIMEOUT.get().copied().unwrap_or(DEFAULT_TIMEOUT)
+}
+
+/// Get built-in checks, and custom checks of the configuration
+///
+/// # Errors
+///
+/// Fails if a custom check of the configuration has the name of a built-in check
+pub fn all_checks(config: &Config) -> anyhow::Result<Vec<Box<dyn Check>>> {
+    let mut checks = builtin_checks();
+    if let Some(links) = config.convention_links() {
+        if let Some(check) = checks
+            .iter_mut()
+            .find(|c| c.name() == CheckKind::ConventionLinks.name())
+        {
+            *check = Box::new(links::ConventionLinks::new(links.to_vec()));
+        }
+    }
+    if let Some(dirs) = config.unowned_files_dirs() {
+        if let Some(check) = checks
+            .iter_mut()
+            .find(|c| c.name() == CheckKind::UnownedFiles.name())
+        {
+            *check = Box::new(unowned::UnownedFiles::new(dirs.to_vec()));
+        }
+    }
+    for custom in config.custom_checks() {
+        if checks.iter().any(|c| c.name() == custom.name()) {
+            return Err(anyhow::anyhow!(
+                "Custom check {:?} has the name of a built-in check",
+                custom.name()
+            ))
+            .classify(ErrorKind::Config);
+        }
+        checks.push(Box::new(external::ExternalCheck::new(custom)));
+    }
+    Ok(checks)
+}
+
+/// Select checks to run, by applying toggles in order, `true` to enable a check by name and `false` to disable it
+///
+/// # Errors
+///
+/// Fails if a toggle names an unknown check
+pub fn select_checks(
+    checks: Vec<Box<dyn Check>>,
+    toggles: &[(String, bool)],
+) -> anyhow::Result<Vec<Box<dyn Check>>> {
+    if let Some((name, _)) = toggles
+        .iter()
+        .find(|(n, _)| !checks.iter().any(|c| c.name() == n))
+    {
+        return Err(anyhow::anyhow!("Unknown check {name:?}")).classify(ErrorKind::Config);
+    }
+    Ok(checks
+        .into_iter()
+        .filter(|c| {
+            toggles
+                .iter()
+                .rev()
+                .find(|(n, _)| n == c.name())
+                .map_or_else(|| c.enabled_by_default(), |(_, enabled)| *enabled)
+        })
+        .collect())
+}
+
+/// Order checks, the ones in `order` first, in that order, and the others after them in their current order
+///
+/// # Errors
+///
+/// Fails if `order` names an unknown check
+pub fn order_checks(
+    mut checks: Vec<Box<dyn Check>>,
+    order: &[String],
+) -> anyhow::Result<Vec<Box<dyn Check>>> {
+    if let Some(name) = order
+        .iter()
+        .find(|n| !checks.iter().any(|c| c.name() == n.as_str()))
+    {
+        return Err(anyhow::anyhow!("Unknown check {name:?}")).classify(ErrorKind::Config);
+    }
+    checks.sort_by_key(|c| {
+        order
+            .iter()
+            .position(|n| n == c.name())
+            .unwrap_or(order.len())
+    });
+    Ok(checks)
+}
+
+/// Whether an error comes from database queries still failing while a pacman transaction writes the database
+fn is_db_busy(err: &anyhow::Error) -> bool {
+    ErrorKind::of(err) == Some(ErrorKind::DbBusy)
+}
+
+/// Enumerate packages if needed with `backend`, and prepare each check with its own backend of `backends`.
+///
+/// Returns the packages to analyze, and the number of items of each check, or the error preparing it.
+/// If packages can not be enumerated because the database is busy, checks needing them fail to be prepared, and the
+/// other checks are still prepared.
+fn prepare_checks(
+    backend: &dyn PackageBackend,
+    backends: &[&dyn PackageBackend],
+    metadata: &Metadata,
+    checks: &mut [Box<dyn Check>],
+    packages: Option<Vec<String>>,
+    timings: Option<&Timings>,
+) -> anyhow::Result<(Vec<String>, Vec<anyhow::Result<u64>>)> {
+    let wants_packages = |c: &dyn Check| c.inputs().contains(&Input::Packages);
+    let any_wants_packages = checks.iter().any(|c| wants_packages(c.as_ref()));
+
+    // Enumerate packages while preparing checks that do not need them
+    let (packages, counts) = rayon::join(
+        || match packages {
+            Some(packages) => Ok(packages),
+            None if !any_wants_packages => Ok(Vec::new()),
+            None => {
+                // Default to "foreign" (AUR) packages
+                let start = Instant::now();
+                let foreign_packages = metadata
+                    .foreign_packages(backend)
+                    .map(|p| p.to_vec())
+                    .context("Unable to get list of AUR packages");
+                if let Some(timings) = timings {
+                    timings.record_phase(Phase::PackageEnumeration, start.elapsed());
+                }
+                foreign_packages
+            }
+        },
+        || {
+            checks
+                .par_iter_mut()
+                .zip(backends)
+                .map(|(c, b)| {
+                    let _log = LogContext::default().with_check(c.name()).enter();
+                    (!wants_packages(c.as_ref())).then(|| c.prepare(*b, &[]))
+                })
+                .collect::<Vec<Option<anyhow::Result<u64>>>>()
+        },
+    );
+    let (packages, enumeration_error) = match packages {
+        Ok(packages) => (packages, None),
+        Err(err) if is_db_busy(&err) => (Vec::new(), Some(err)),
+        Err(err) => return Err(err),
+    };
+    let counts = checks
+        .iter_mut()
+        .zip(backends)
+        .zip(counts)
+        .map(|((c, b), count)| {
+            let _log = LogContext::default().with_check(c.name()).enter();
+            match (count, &enumeration_error) {
+                (Some(count), _) => count,
+                (None, Some(err)) => Err(anyhow::anyhow!("{err:#}")).classify(ErrorKind::DbBusy),
+                (None, None) => c.prepare(*b, &packages),
+            }
+        })
+        .collect();
+    Ok((packages, counts))
+}
+
+/// Get the message of a panic payload
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| (*s).to_owned())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_owned())
+}
+
+/// Run each check in its own thread, and wait for them until their timeout.
+///
+/// Panicking checks and checks still running after their timeout are reported as skipped,
+/// without waiting for them, and findings are returned in check order.
+/// If `exit_early` is set, checks still running when a check finds problems of at least this severity
+/// are also skipped.
+fn run_isolated(
+    checks: Vec<(Box<dyn Check>, RetryingBackend)>,
+    packages: &Arc<[String]>,
+    metadata: &Arc<Metadata>,
+    progress: &Arc<Progress>,
+    timings: Option<&Arc<Timings>>,
+    exit_early: Option<Severity>,
+) -> anyhow::Result<Vec<Finding>> {
+    let start = Instant::now();
+    let (tx, rx) = mpsc::channel();
+    let mut pending = Vec::with_capacity(checks.len());
+    for (index, (check, backend)) in checks.into_iter().enumerate() {
+        let name = check.name().to_owned();
+        let weight = check.progress_weight();
+        pending.push(Some((name.clone(), check.timeout())));
+        let tx = tx.clone();
+        let packages = Arc::clone(packages);
+        let metadata = Arc::clone(metadata);
+        let progress = Arc::clone(progress);
+        let timings = timings.cloned();
+        thread::Builder::new()
+            .name(name.clone())
+            .spawn(move || {
+                let _log = LogContext::default().with_check(&name).enter();
+                let ctx = Context::new(
+                    &backend,
+                    &packages,
+                    &metadata,
+                    &progress,
+                    timings.as_deref(),
+                )
+                .with_progress_weight(weight);
+                let result = panic::catch_unwind(AssertUnwindSafe(|| check.run(&ctx))).map(
+                    |mut findings| {
+                        let skipped = backend.skipped();
+                        if !skipped.is_empty() {
+                            findings.push(Finding::CheckSkipped {
+                                check: name.clone(),
+                                reason: SkipReason::DbBusy(skipped),
+                            });
+                        }
+                        findings
+                    },
+                );
+                // Nobody waits for a timed out check
+                let _ = tx.send((index, result));
+            })
+            .context("Failed to start check thread")?;
+    }
+    drop(tx);
+
+    let mut results: Vec<Vec<Finding>> = pending.iter().map(|_| Vec::new()).collect();
+    while let Some(deadline) = pending.iter().flatten().map(|(_, t)| start + *t).min() {
+        match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok((index, result)) => {
+                let Some((check, _)) = pending[index].take() else {
+                    continue;
+                };
+                let name = check.clone();
+                results[index] = result.unwrap_or_else(|payload| {
+                    let message = panic_message(payload.as_ref());
+                    log::error!("Check {check:?} panicked: {message}");
+                    vec![Finding::CheckSkipped {
+                        check,
+                        reason: SkipReason::Panic(message),
+                    }]
+                });
+                if let Some(severity) = exit_early.filter(|s| {
+                    results[index]
+                        .iter()
+                        .any(|f| !matches!(f, Finding::CheckSkipped { .. }) && f.severity() >= *s)
+                }) {
+                    log::info!(
+                        "Stopping the scan, check {name:?} found problems with severity {severity} or higher"
+                    );
+                    for (slot, skipped) in pending.iter_mut().zip(&mut results) {
+                        if let Some((running, _)) = slot.take() {
+                            *skipped = vec![Finding::CheckSkipped {
+                                check: running,
+                                reason: SkipReason::EarlyExit(severity),
+                            }];
+                        }
+                    }
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                for (slot, result) in pending.iter_mut().zip(&mut results) {
+                    if let Some((check, timeout)) = slot.take_if(|(_, t)| start + *t <= deadline) {
+                        log::error!("Check {check:?} timed out after {}s", timeout.as_secs());
+                        *result = vec![Finding::CheckSkipped {
+                            check,
+                            reason: SkipReason::Timeout(timeout),
+                        }];
+                    }
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    Ok(results.into_iter().flatten().collect())
+}
+
+/// Run checks and return findings.
+///
+/// Checks wanting [`Input::Packages`] analyze the given packages, or all foreign packages if `None`.
+/// Progress is drawn on stderr if `show_progress` is set.
+///
+/// Each check runs in isolation: if it panics or exceeds [`Check::timeout`], it is reported
+/// with a [`Finding::CheckSkipped`] finding, and the other checks are not affected.
+///
+/// With `exit_early`, the scan stops as soon as a check finds problems of at least this severity,
+/// and checks still running are reported as skipped.
+///
+/// # Errors
+///
+/// Fails if the packages to analyze can not be listed
+pub fn run_checks(
+    backend: &Arc<dyn PackageBackend>,
+    checks: Vec<Box<dyn Check>>,
+    packages: Option<Vec<String>>,
+    show_progress: bool,
+    timings: Option<&Arc<Timings>>,
+    exit_early: Option<Severity>,
+) -> anyhow::Result<Vec<Finding>> {
+    let (offline, mut checks): (Vec<_>, Vec<_>) = checks
+        .into_iter()
+        .partition(|c| c.needs_network() && network::is_offline());
+    let mut skipped: Vec<_> = offline
+        .iter()
+        .map(|c| Finding::CheckSkipped {
+            check: c.name().to_owned(),
+            reason: SkipReason::Offline,
+        })
+        .collect();
+    let metadata = Arc::new(Metadata::default());
+    // Each check keeps its backend from its preparation to its run, to report all the packages it skipped
+    let backends: Vec<_> = checks
+        .iter()
+        .map(|_| RetryingBackend::new(Arc::clone(backend)))
+        .collect();
+    let (packages, counts) = prepare_checks(
+        &RetryingBackend::new(Arc::clone(backend)),
+        &backends
+            .iter()
+            .map(|b| b as &dyn PackageBackend)
+            .collect::<Vec<_>>(),
+        &metadata,
+        &mut checks,
+        packages,
+        timings.map(AsRef::as_ref),
+    )?;
+    let mut prepared = Vec::with_capacity(checks.len());
+    let mut units = 0;
+    for ((check, check_backend), count) in checks.into_iter().zip(backends).zip(counts) {
+        match count {
+            Ok(count) => {
+                units += count.saturating_mul(check.progress_weight());
+                prepared.push((check, check_backend));
+            }
+            // Other checks do not depend on the queries that failed
+            Err(err) if is_db_busy(&err) => {
+                log::warn!(
+                    "Skipping check {:?}, the local database is busy: {err:#}",
+                    check.name()
+                );
+                skipped.push(Finding::CheckSkipped {
+                    check: check.name().to_owned(),
+                    reason: SkipReason::DbBusy(Vec::new()),
+                });
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    let progress = Arc::new(Progress::new(units, show_progress)?);
+    let packages = Arc::from(packages);
+
+    // Run checks concurrently
+    let findings = progress.run(|| {
+        run_isolated(
+            prepared, &packages, &metadata, &progress, timings, exit_early,
+        )
+    })?;
+    let mut findings = ldconfig::drop_stale_linked(findings);
+    findings.extend(skipped);
+    Ok(findings)
+}
+
+/// What a run of checks would analyze
+pub struct Plan {
+    /// Check names and their number of items
+    checks: Vec<(String, u64)>,
+    packages: Vec<String>,
+    /// Number of files of the packages, `None` if no check analyzes packages
+    file_count: Option<u64>,
+}
+
+/// Prepare checks like [`run_checks`] does, and describe what they would analyze without running them
+///
+/// # Errors
+///
+/// Fails if the packages to analyze can not be listed
+pub fn plan_checks(
+    backend: &dyn PackageBackend,
+    mut checks: Vec<Box<dyn Check>>,
+    packages: Option<Vec<String>>,
+) -> anyhow::Result<Plan> {
+    let backends = vec![backend; checks.len()];
+    let (packages, counts) = prepare_checks(
+        backend,
+        &backends,
+        &Metadata::default(),
+        &mut checks,
+        packages,
+        None,
+    )?;
+    let counts = counts.into_iter().collect::<anyhow::Result<Vec<_>>>()?;
+    let file_count = if checks.iter().any(|c| c.inputs().contains(&Input::Packages)) {
+        let mut count = 0;
+        for package in &packages {
+            backend.for_each_file(package, &mut |_| count += 1)?;
+        }
+        Some(count)
+    } else {
+        None
+    };
+    Ok(Plan {
+        checks: checks
+            .iter()
+            .map(|c| c.name().to_owned())
+            .zip(counts)
+            .collect(),
+        packages,
+        file_count,
+    })
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Checks (items to check):")?;
+        for (name, count) in &self.checks {
+            writeln!(f, "  {name:<24} {count}")?;
+        }
+        if let Some(file_count) = self.file_count {
+            writeln!(f, "Packages ({}):", self.packages.len())?;
+            for package in &self.packages {
+                writeln!(f, "  {package}")?;
+            }
+            writeln!(f, "Package files: {file_count}")?;
+            writeln!(
+                f,
+                "Excluded directories: {}",
+                missing_dependencies::BLACKLISTED_EXE_DIRS.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(checks: &[Box<dyn Check>]) -> Vec<&str> {
+        checks.iter().map(|c| c.name()).collect()
+    }
+
+    /// Check misbehaving in a given way
+    enum FaultyCheck {
+        Working,
+        Panicking,
+        Hanging,
+        /// Preparing it fails on a busy database
+        Busy,
+    }
+
+    impl Check for FaultyCheck {
+        fn name(&self) -> &str {
+            match self {
+                Self::Working => "working",
+                Self::Panicking => "panicking",
+                Self::Hanging => "hanging",
+                Self::Busy => "busy",
+            }
+        }
+
+        fn inputs(&self) -> &[Input] {
+            &[Input::System]
+        }
+
+        fn timeout(&self) -> Duration {
+            Duration::from_secs(1)
+        }
+
+        fn prepare(
+            &mut self,
+            _backend: &dyn PackageBackend,
+            _packages: &[String],
+        ) -> anyhow::Result<u64> {
+            match self {
+                Self::Busy => Err(anyhow::anyhow!("Database locked")).classify(ErrorKind::DbBusy),
+                _ => Ok(1),
+            }
+        }
+
+        fn run(&self, _ctx: &Context<'_>) -> Vec<Finding> {
+            match self {
+                Self::Busy => unreachable!(),
+                Self::Working => vec![Finding::PacnewFile {
+                    path: "/etc/foo.conf".into(),
+                }],
+                Self::Panicking => panic!("oops"),
+                Self::Hanging => {
+                    thread::sleep(Duration::from_secs(5));
+                    Vec::new()
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_select_checks() {
+        let all = select_checks(builtin_checks(), &[]).unwrap();
+        assert_eq!(
+            names(&all),
+            [
+                "missing-dependencies",
+                "python-packages",
+                "service-links",
+                "login-units",
+                "duplicate-libraries",
+                "library-permissions",
+                "library-links",
+                "introspection-typelibs",
+                "perl-modules",
+                "scriptlet-failures",
+                "unowned-files",
+                "convention-links",
+                "symlink-chains",
+                "boot-mounts",
+                "kernel-cmdline"
+            ]
+        );
+
+        let toggles = [
+            ("python-packages".to_owned(), false),
+            ("service-links".to_owned(), false),
+            ("duplicate-libraries".to_owned(), false),
+            ("python-packages".to_owned(), true),
+        ];
+        let selected = select_checks(builtin_checks(), &toggles).unwrap();
+        assert_eq!(
+            names(&selected),
+            [
+                "missing-dependencies",
+                "python-packages",
+                "login-units",
+                "library-permissions",
+                "library-links",
+                "introspection-typelibs",
+                "perl-modules",
+                "scriptlet-failures",
+                "unowned-files",
+                "convention-links",
+                "symlink-chains",
+                "boot-mounts",
+                "kernel-cmdline"
+            ]
+        );
+
+        assert!(select_checks(builtin_checks(), &[("foo".to_owned(), true)]).is_err());
+    }
+
+    #[test]
+    fn test_run_checks_isolation() {
+        let backend: Arc<dyn PackageBackend> = Arc::new(crate::backend::MemoryBackend::default());
+        let checks: Vec<Box<dyn Check>> = vec![
+            Box::new(FaultyCheck::Hanging),
+            Box::new(FaultyCheck::Panicking),
+            Box::new(FaultyCheck::Busy),
+            Box::new(FaultyCheck::Working),
+        ];
+        let start = Instant::now();
+        let findings = run_checks(&backend, checks, None, false, None, None).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            findings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                r#"Check "hanging" was skipped, it did not finish within 1s"#,
+                r#"Check "panicking" was skipped, it crashed: oops"#,
+                "Configuration file /etc/foo.conf has a pending /etc/foo.conf.pacnew update",
+                r#"Check "busy" was skipped, the local database was busy during a pacman transaction, run it again when the transaction completes"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_checks_exit_early() {
+        let backend: Arc<dyn PackageBackend> = Arc::new(crate::backend::MemoryBackend::default());
+        let checks: Vec<Box<dyn Check>> = vec![
+            Box::new(FaultyCheck::Hanging),
+            Box::new(FaultyCheck::Working),
+        ];
+        let start = Instant::now();
+        let findings =
+            run_checks(&backend, checks, None, false, None, Some(Severity::Info)).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(
+            findings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                r#"Check "hanging" was skipped, the scan stopped early after findings with severity info or higher"#,
+                "Configuration file /etc/foo.conf has a pending /etc/foo.conf.pacnew update",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_order_checks() {
+        let order = [
+            "security-advisories".to_owned(),
+            "python-packages".to_owned(),
+        ];
+        let ordered = order_checks(builtin_checks(), &order).unwrap();
+        assert_eq!(
+            names(&ordered)[..4],
+            [
+                "security-advisories",
+                "python-packages",
+                "missing-dependencies",
+                "service-links"
+            ]
+        );
+        assert!(order_checks(builtin_checks(), &["foo".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn test_plan_checks() {
+        let backend = crate::backend::MemoryBackend::default()
+            .with_package(
+                "foo",
+                "1.0-1",
+                true,
+                &["/usr/bin/foo", "/usr/lib/libfoo.so"],
+            )
+            .with_package("bar", "1.0-1", false, &["/usr/bin/bar"]);
+        let checks = select_checks(
+            builtin_checks(),
+            &[
+                ("python-packages".to_owned(), false),
+                ("service-links".to_owned(), false),
+                ("login-units".to_owned(), false),
+                ("duplicate-libraries".to_owned(), false),
+                ("library-permissions".to_owned(), false),
+                ("library-links".to_owned(), false),
+                ("introspection-typelibs".to_owned(), false),
+                ("perl-modules".to_owned(), false),
+                ("scriptlet-failures".to_owned(), false),
+                ("unowned-files".to_owned(), false),
+                ("convention-links".to_owned(), false),
+                ("symlink-chains".to_owned(), false),
+                ("boot-mounts".to_owned(), false),
+                ("kernel-cmdline".to_owned(), false),
+            ],
+        )
+        .unwrap();
+        let plan = plan_checks(&backend, checks, None).unwrap();
+        assert_eq!(plan.checks, [("missing-dependencies".to_owned(), 1)]);
+        assert_eq!(plan.packages, ["foo"]);
+        assert_eq!(plan.file_count, Some(2));
+    }
+
+    #[test]
+    fn test_check_docs() {
+        for check in builtin_checks() {
+            let doc = check.doc();
+            assert!(!doc.detects.is_empty(), "{}", check.name());
+            assert!(!doc.examples.is_empty(), "{}", check.name());
+            for example in &doc.examples {
+                assert_eq!(example.kind().name(), check.name());
+            }
+        }
+    }
+}
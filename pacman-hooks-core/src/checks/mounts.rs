@@ -0,0 +1,508 @@
+//! Entries of fstab, crypttab and enabled mount units that can make the next boot fail, like filesystems whose
+//! mount helper was removed with its package
+
+use std::{
+    collections::HashSet,
+    ffi::{OsStr, OsString},
+    fs, io,
+    os::unix::ffi::{OsStrExt as _, OsStringExt as _},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use glob::glob;
+
+use super::{Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    cancel,
+    finding::{BootProblem, CheckKind, Finding},
+    root,
+};
+
+/// Filesystems mounted at boot
+const FSTAB_PATH: &str = "/etc/fstab";
+
+/// Encrypted devices unlocked at boot
+const CRYPTTAB_PATH: &str = "/etc/crypttab";
+
+/// Mount units enabled by the administrator
+const MOUNT_UNITS_PATTERN: &str = "/etc/systemd/system/*.target.*/*.mount";
+
+/// Directories of fsck tools and mount helpers, `/usr/sbin` being a link to `/usr/bin` on Arch Linux
+const TOOL_DIRS: [&str; 2] = ["/usr/bin", "/usr/sbin"];
+
+/// Filesystem types only mounted through a helper program, other ones only need the kernel
+const HELPER_MOUNTED_TYPES: [&str; 6] = ["nfs", "nfs4", "cifs", "smb3", "ntfs-3g", "glusterfs"];
+
+/// Options of entries whose failure does not stop the boot
+const NON_BLOCKING_OPTIONS: [&str; 2] = ["noauto", "nofail"];
+
+/// Device tags of fstab and crypttab, and the directory of their device links
+const DEVICE_TAGS: [(&str, &str); 4] = [
+    ("UUID", "/dev/disk/by-uuid"),
+    ("LABEL", "/dev/disk/by-label"),
+    ("PARTUUID", "/dev/disk/by-partuuid"),
+    ("PARTLABEL", "/dev/disk/by-partlabel"),
+];
+
+/// Filesystem mount of fstab or of a mount unit
+#[derive(Debug, Eq, PartialEq)]
+struct Mount {
+    /// File defining the mount
+    config: PathBuf,
+    /// Mount point, or `none` for swap
+    target: String,
+    /// Device, tag like `UUID=…`, or remote source
+    what: String,
+    fs_type: String,
+    options: Vec<String>,
+    /// Whether the filesystem is checked at boot
+    fsck: bool,
+}
+
+/// Encrypted device of crypttab
+#[derive(Debug, Eq, PartialEq)]
+struct CryptDevice {
+    /// Name of the unlocked device in `/dev/mapper`
+    name: String,
+    /// Device, or tag like `UUID=…`
+    device: String,
+    /// Key file, `None` if a passphrase is asked
+    key_file: Option<PathBuf>,
+    options: Vec<String>,
+}
+
+/// Decode the octal escapes of fstab fields, like `\040` for spaces.
+///
+/// Escapes are bytes, several of them encoding a non ASCII character, which may not even be valid UTF-8 in a path.
+fn unescape(field: &str) -> OsString {
+    let mut decoded = Vec::with_capacity(field.len());
+    let mut rest = field;
+    while let Some((before, after)) = rest.split_once('\\') {
+        decoded.extend_from_slice(before.as_bytes());
+        if let Some(byte) = after.get(..3).and_then(|o| u8::from_str_radix(o, 8).ok()) {
+            decoded.push(byte);
+            rest = after.get(3..).unwrap_or_default();
+        } else {
+            decoded.push(b'\\');
+            rest = after;
+        }
+    }
+    decoded.extend_from_slice(rest.as_bytes());
+    OsString::from_vec(decoded)
+}
+
+/// Get a decoded field as text, for fields that are not paths
+fn text(field: &OsStr) -> String {
+    field.to_string_lossy().into_owned()
+}
+
+/// Split mount options
+fn split_options(options: &str) -> Vec<String> {
+    options.split(',').map(ToOwned::to_owned).collect()
+}
+
+/// Get the fields of the non comment lines of fstab or crypttab
+fn table_lines(content: &str) -> impl Iterator<Item = Vec<OsString>> + '_ {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| l.split_whitespace().map(unescape).collect())
+}
+
+/// Parse fstab entries
+fn parse_fstab(config: &Path, content: &str) -> Vec<Mount> {
+    table_lines(content)
+        .filter_map(|fields| match fields.as_slice() {
+            [what, target, fs_type, rest @ ..] => Some(Mount {
+                config: config.to_owned(),
+                target: text(target),
+                what: text(what),
+                fs_type: text(fs_type),
+                options: rest
+                    .first()
+                    .map(|o| split_options(&text(o)))
+                    .unwrap_or_default(),
+                // Missing pass number fields are 0
+                fsck: rest.get(2).is_some_and(|p| p != "0"),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parse the `[Mount]` section of a mount unit
+fn parse_mount_unit(config: &Path, content: &str) -> Option<Mount> {
+    let mut in_mount = false;
+    let (mut what, mut target, mut fs_type, mut options) = (None, None, None, Vec::new());
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_mount = line == "[Mount]";
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=').filter(|_| in_mount) else {
+            continue;
+        };
+        let value = value.trim().to_owned();
+        match key.trim() {
+            "What" => what = Some(value),
+            "Where" => target = Some(value),
+            "Type" => fs_type = Some(value),
+            "Options" => options = split_options(&value),
+            _ => {}
+        }
+    }
+    Some(Mount {
+        config: config.to_owned(),
+        target: target?,
+        what: what?,
+        fs_type: fs_type.unwrap_or_else(|| "auto".to_owned()),
+        options,
+        // Only fstab entries get an fsck unit
+        fsck: false,
+    })
+}
+
+/// Parse crypttab entries
+fn parse_crypttab(content: &str) -> Vec<CryptDevice> {
+    table_lines(content)
+        .filter_map(|fields| match fields.as_slice() {
+            [name, device, rest @ ..] => Some(CryptDevice {
+                name: text(name),
+                device: text(device),
+                key_file: rest
+                    .first()
+                    .filter(|k| !matches!(k.as_bytes(), b"none" | b"-" | b""))
+                    .map(PathBuf::from),
+                options: rest
+                    .get(1)
+                    .map(|o| split_options(&text(o)))
+                    .unwrap_or_default(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Get the device node or link of a device of fstab or crypttab, `None` for sources that are not local devices,
+/// like remote shares or pseudo filesystems
+fn device_path(what: &str) -> Option<PathBuf> {
+    if what.starts_with("/dev/") {
+        return Some(PathBuf::from(what));
+    }
+    let (tag, value) = what.split_once('=')?;
+    let (_, dir) = DEVICE_TAGS.iter().find(|(t, _)| *t == tag)?;
+    Some(Path::new(dir).join(value.trim_matches('"')))
+}
+
+/// Get the fsck tool and mount helper a filesystem type needs
+fn needed_tools(mount: &Mount) -> Vec<String> {
+    let mut tools = Vec::new();
+    if mount.fsck && !matches!(mount.fs_type.as_str(), "auto" | "none" | "swap") {
+        tools.push(format!("fsck.{}", mount.fs_type));
+    }
+    if let Some(program) = mount.fs_type.strip_prefix("fuse.") {
+        // Started by mount.fuse, with the device as its first argument
+        tools.push(program.to_owned());
+    } else if HELPER_MOUNTED_TYPES.contains(&mount.fs_type.as_str()) {
+        tools.push(format!("mount.{}", mount.fs_type));
+    }
+    tools
+}
+
+/// Whether a device is missing, devices of other roots being those of the running system
+fn is_missing_device(device: &Path) -> bool {
+    !root::is_alternate() && fs::metadata(device).is_err()
+}
+
+/// Whether a tool is in one of the tool directories
+fn has_tool(tool_dirs: &[PathBuf], tool: &str) -> bool {
+    tool_dirs.iter().any(|d| d.join(tool).is_file())
+}
+
+/// Check that the device and tools of a mount exist, ignoring devices of crypttab that appear once unlocked
+fn check_mount(mount: &Mount, unlocked: &HashSet<PathBuf>, tool_dirs: &[PathBuf]) -> Vec<Finding> {
+    if mount
+        .options
+        .iter()
+        .any(|o| NON_BLOCKING_OPTIONS.contains(&o.as_str()))
+    {
+        return Vec::new();
+    }
+    let finding = |missing: String, problem| Finding::BrokenBootEntry {
+        path: mount.config.clone(),
+        entry: mount.target.clone(),
+        missing,
+        problem,
+    };
+    let mut findings = Vec::new();
+    if device_path(&mount.what).is_some_and(|d| !unlocked.contains(&d) && is_missing_device(&d)) {
+        findings.push(finding(mount.what.clone(), BootProblem::Device));
+    }
+    for tool in needed_tools(mount) {
+        if !has_tool(tool_dirs, &tool) {
+            let problem = if tool.starts_with("fsck.") {
+                BootProblem::Fsck
+            } else {
+                BootProblem::MountHelper
+            };
+            let path = Path::new(TOOL_DIRS[0]).join(tool);
+            findings.push(finding(path.to_string_lossy().into_owned(), problem));
+        }
+    }
+    findings
+}
+
+/// Check that the device and key file of an encrypted device exist
+fn check_crypt_device(device: &CryptDevice) -> Vec<Finding> {
+    if device
+        .options
+        .iter()
+        .any(|o| NON_BLOCKING_OPTIONS.contains(&o.as_str()))
+    {
+        return Vec::new();
+    }
+    let finding = |missing: String, problem| Finding::BrokenBootEntry {
+        path: PathBuf::from(CRYPTTAB_PATH),
+        entry: device.name.clone(),
+        missing,
+        problem,
+    };
+    let mut findings = Vec::new();
+    if device_path(&device.device).is_some_and(|d| is_missing_device(&d)) {
+        findings.push(finding(device.device.clone(), BootProblem::Device));
+    }
+    // Key files on another device, like `/key:UUID=…`, are only available at boot
+    if let Some(path) = device
+        .key_file
+        .as_ref()
+        .filter(|k| k.is_absolute() && !k.as_os_str().as_bytes().contains(&b':'))
+    {
+        let missing = if path.starts_with("/dev") {
+            is_missing_device(path)
+        } else {
+            fs::metadata(root::resolve(path)).is_err()
+        };
+        if missing {
+            findings.push(finding(
+                path.to_string_lossy().into_owned(),
+                BootProblem::KeyFile,
+            ));
+        }
+    }
+    findings
+}
+
+/// Read a configuration file of the checked system, empty if it does not exist
+fn read_config(path: &Path) -> anyhow::Result<String> {
+    match fs::read_to_string(root::resolve(path)) {
+        Ok(content) => Ok(content),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(err) => Err(err).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Get the mounts of enabled mount units, by their unit file
+fn enabled_mount_units() -> anyhow::Result<Vec<Mount>> {
+    let pattern = root::resolve(Path::new(MOUNT_UNITS_PATTERN));
+    let mut mounts = Vec::new();
+    let mut seen = HashSet::new();
+    for link in glob(&pattern.to_string_lossy())? {
+        // Broken enablement links are reported by the service links check
+        let Some(unit) = root::link_target(&link?) else {
+            continue;
+        };
+        if !seen.insert(unit.clone()) {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(&unit) else {
+            continue;
+        };
+        mounts.extend(parse_mount_unit(&root::unresolve(&unit), &content));
+    }
+    Ok(mounts)
+}
+
+/// Get the UUIDs of the filesystems that fstab mounts on some mount points, from their tag or from the device links of
+/// the running system
+pub(super) fn filesystem_uuids(targets: &[&str]) -> Vec<String> {
+    let fstab = Path::new(FSTAB_PATH);
+    let content = read_config(fstab)
+        .inspect_err(|err| log::debug!("{err:#}"))
+        .unwrap_or_default();
+    let mut uuids = Vec::new();
+    let mut devices = Vec::new();
+    for mount in parse_fstab(fstab, &content)
+        .into_iter()
+        .filter(|m| targets.contains(&m.target.as_str()))
+    {
+        match mount.what.strip_prefix("UUID=") {
+            Some(uuid) => uuids.push(uuid.trim_matches('"').to_owned()),
+            None => devices.extend(device_path(&mount.what).and_then(|p| fs::canonicalize(p).ok())),
+        }
+    }
+    if !devices.is_empty() {
+        let (_, uuid_dir) = DEVICE_TAGS[0];
+        uuids.extend(
+            fs::read_dir(uuid_dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter(|e| fs::canonicalize(e.path()).is_ok_and(|d| devices.contains(&d)))
+                .map(|e| e.file_name().to_string_lossy().into_owned()),
+        );
+    }
+    uuids
+}
+
+/// Check that filesystems and encrypted devices set up at boot have their devices, tools and key files
+#[derive(Default)]
+pub(super) struct BootMounts {
+    mounts: Vec<Mount>,
+    crypt_devices: Vec<CryptDevice>,
+    tool_dirs: Vec<PathBuf>,
+}
+
+impl BootMounts {
+    fn check(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        let unlocked: HashSet<_> = self
+            .crypt_devices
+            .iter()
+            .map(|d| Path::new("/dev/mapper").join(&d.name))
+            .collect();
+        let mut findings = Vec::new();
+        for mount in self.mounts.iter().take_while(|_| !cancel::requested()) {
+            findings.extend(check_mount(mount, &unlocked, &self.tool_dirs));
+            ctx.item_done();
+        }
+        for device in self
+            .crypt_devices
+            .iter()
+            .take_while(|_| !cancel::requested())
+        {
+            findings.extend(check_crypt_device(device));
+            ctx.item_done();
+        }
+        findings
+    }
+}
+
+impl Check for BootMounts {
+    fn name(&self) -> &str {
+        CheckKind::BootMounts.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Entries of fstab, crypttab and enabled mount units whose device, mount helper, fsck tool or key file is missing, which can make the next boot fail".to_owned(),
+            caveats: vec![
+                "Entries with the noauto or nofail options are skipped",
+                "Devices only connected at boot, like some external disks, are reported missing",
+                "Devices are not checked for another root directory",
+            ],
+            examples: vec![Finding::BrokenBootEntry {
+                path: PathBuf::from("/etc/fstab"),
+                entry: "nas:/data".to_owned(),
+                missing: "/usr/bin/mount.nfs".to_owned(),
+                problem: BootProblem::MountHelper,
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        let fstab = Path::new(FSTAB_PATH);
+        self.mounts = parse_fstab(fstab, &read_config(fstab)?);
+        self.mounts
+            .extend(enabled_mount_units().context("Failed to list enabled mount units")?);
+        self.crypt_devices = parse_crypttab(&read_config(Path::new(CRYPTTAB_PATH))?);
+        self.tool_dirs = TOOL_DIRS.map(|d| root::resolve(Path::new(d))).to_vec();
+        Ok((self.mounts.len() + self.crypt_devices.len()) as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        self.check(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::MemoryBackend, metadata::Metadata, progress::Progress};
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape(r"My\040Data"), "My Data");
+        // UTF-8 bytes of a non ASCII character, not one character each
+        assert_eq!(unescape(r"/mnt/donn\303\251es"), "/mnt/données");
+        assert_eq!(unescape(r"/keys/\377.key").as_bytes(), b"/keys/\xff.key");
+        assert_eq!(unescape(r"a\b\04"), r"a\b\04");
+    }
+
+    #[test]
+    fn test_boot_mounts() {
+        let tools = tempfile::tempdir().unwrap();
+        fs::write(tools.path().join("fsck.ext4"), "").unwrap();
+        let fstab = Path::new(FSTAB_PATH);
+        let mut mounts = parse_fstab(
+            fstab,
+            r"# <file system> <dir> <type> <options> <dump> <pass>
+/dev/null / ext4 rw,relatime 0 1
+UUID=0000-missing /boot vfat rw 0 2
+LABEL=My\040Data /data xfs rw,nofail 0 2
+/dev/mapper/cryptdata /srv ext4 rw 0 2
+server:/export /mnt/nfs nfs rw 0 0
+tmpfs /tmp tmpfs rw
+",
+        );
+        assert_eq!(mounts[2].what, "LABEL=My Data");
+        assert_eq!(mounts[5].options, ["rw"]);
+        assert!(!mounts[5].fsck);
+        mounts.extend(parse_mount_unit(
+            Path::new("/etc/systemd/system/media-user.mount"),
+            "[Unit]\nDescription=Remote files\n\n[Mount]\nWhat=user@host:\nWhere=/media/user\nType=fuse.sshfs\n",
+        ));
+        let crypt_devices = parse_crypttab(
+            "cryptdata UUID=1111-missing /etc/keys/data.key luks
+cryptswap /dev/null none swap,nofail
+",
+        );
+        assert_eq!(crypt_devices[1].key_file, None);
+
+        let check = BootMounts {
+            mounts,
+            crypt_devices,
+            tool_dirs: vec![tools.path().to_owned()],
+        };
+        let backend = MemoryBackend::default();
+        let progress = Progress::new(9, false).unwrap();
+        let findings = check.check(&Context::new(
+            &backend,
+            &[],
+            &Metadata::default(),
+            &progress,
+            None,
+        ));
+        assert_eq!(
+            findings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                r#"Entry "/boot" of /etc/fstab uses missing device UUID=0000-missing, fix the entry or add the nofail option, the next boot may fail"#,
+                r#"Entry "/boot" of /etc/fstab needs missing /usr/bin/fsck.vfat, install the package providing it, the filesystem is not checked at boot anymore"#,
+                r#"Entry "/mnt/nfs" of /etc/fstab needs missing /usr/bin/mount.nfs, install the package providing it, the next boot may fail"#,
+                r#"Entry "/media/user" of /etc/systemd/system/media-user.mount needs missing /usr/bin/sshfs, install the package providing it, the next boot may fail"#,
+                r#"Entry "cryptdata" of /etc/crypttab uses missing device UUID=1111-missing, fix the entry or add the nofail option, the next boot may fail"#,
+                r#"Entry "cryptdata" of /etc/crypttab uses missing key file /etc/keys/data.key, the next boot may ask for its passphrase or fail"#,
+            ]
+        );
+    }
+}
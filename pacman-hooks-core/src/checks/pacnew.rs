@@ -0,0 +1,116 @@
+//! Pending pacnew configuration updates
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use super::{Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    cancel,
+    finding::{CheckKind, Finding},
+    root,
+};
+
+/// Path of the new version of a configuration file, installed by pacman when the file was modified
+fn pacnew_path(path: &Path) -> PathBuf {
+    let mut pacnew = OsString::from(path);
+    pacnew.push(".pacnew");
+    PathBuf::from(pacnew)
+}
+
+/// Check configuration files for pacnew files
+fn check_pacnew_files(ctx: &Context<'_>, files: &[PathBuf]) -> Vec<Finding> {
+    files
+        .iter()
+        .filter(|_| !cancel::requested())
+        .filter(|f| {
+            let exists = pacnew_path(&root::resolve(f)).exists();
+            ctx.item_done();
+            exists
+        })
+        .map(|f| Finding::PacnewFile { path: f.to_owned() })
+        .collect()
+}
+
+/// Check for configuration files with a pending pacnew update
+#[derive(Default)]
+pub(super) struct PacnewFiles {
+    files: Vec<PathBuf>,
+}
+
+impl Check for PacnewFiles {
+    fn name(&self) -> &str {
+        CheckKind::PacnewFiles.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Configuration files with a pending .pacnew update".to_owned(),
+            caveats: vec!["The pacdiff hook already lists them, which is why the check is disabled by default"],
+            examples: vec![Finding::PacnewFile {
+                path: PathBuf::from("/etc/pacman.conf"),
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        // The pacdiff hook already reviews them after upgrades
+        false
+    }
+
+    fn prepare(
+        &mut self,
+        backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        self.files = backend.backup_files()?;
+        Ok(self.files.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        check_pacnew_files(ctx, &self.files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{backend::MemoryBackend, metadata::Metadata, progress::Progress};
+
+    #[test]
+    fn test_pacnew_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let updated = dir.path().join("foo.conf");
+        let current = dir.path().join("bar.conf");
+        fs::write(&updated, "").unwrap();
+        fs::write(pacnew_path(&updated), "").unwrap();
+        fs::write(&current, "").unwrap();
+
+        let backend = MemoryBackend::default()
+            .with_backup_file(&updated)
+            .with_backup_file(&current);
+        let mut check = PacnewFiles::default();
+        assert_eq!(check.prepare(&backend, &[]).unwrap(), 2);
+        let progress = Progress::new(2, false).unwrap();
+        let findings = check.run(&Context::new(
+            &backend,
+            &[],
+            &Metadata::default(),
+            &progress,
+            None,
+        ));
+        assert!(matches!(
+            findings.as_slice(),
+            [Finding::PacnewFile { path }] if *path == updated
+        ));
+    }
+}
@@ -0,0 +1,157 @@
+//! Perl XS modules linked against the libperl of a Perl version that is not installed anymore, left by modules
+//! of the AUR or installed with cpan that were not rebuilt after a Perl upgrade
+
+use std::path::{Path, PathBuf};
+
+use glob::glob;
+
+use super::{duplicates::owner, Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    cancel,
+    elf::SonameResolver,
+    finding::{CheckKind, Finding},
+    root,
+};
+
+/// Directory of Perl modules, with a subdirectory per Perl version
+const PERL_DIR: &str = "/usr/lib/perl5";
+
+/// Shared objects of XS modules, in the vendor and site directories of each Perl version, and in the unversioned
+/// directories of older Perl packages
+const MODULE_PATTERNS: [&str; 4] = [
+    "/usr/lib/perl5/*/vendor_perl/auto/**/*.so",
+    "/usr/lib/perl5/*/site_perl/auto/**/*.so",
+    "/usr/lib/perl5/vendor_perl/auto/**/*.so",
+    "/usr/lib/perl5/site_perl/auto/**/*.so",
+];
+
+/// File name prefix of the Perl interpreter library
+const LIBPERL_PREFIX: &str = "libperl.so";
+
+/// Whether a missing dependency is the libperl of a Perl module, reported by this check rather than as a generic
+/// missing dependency
+pub(super) fn is_module_libperl(file: &Path, dependency: &Path) -> bool {
+    file.starts_with(PERL_DIR)
+        && dependency
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().starts_with(LIBPERL_PREFIX))
+}
+
+/// Check that the libperl a module is linked against, with its own search path, resolves
+fn check_module(ctx: &Context<'_>, resolver: &SonameResolver, path: &Path) -> Vec<Finding> {
+    let file = root::unresolve(path);
+    let missing: Vec<_> = resolver
+        .missing_dependencies(path)
+        .into_iter()
+        .filter(|d| is_module_libperl(&file, d))
+        .collect();
+    if missing.is_empty() {
+        return Vec::new();
+    }
+    let package = owner(ctx.backend(), path);
+    missing
+        .into_iter()
+        .map(|dependency| Finding::BrokenPerlModule {
+            path: file.clone(),
+            dependency,
+            package: package.clone(),
+        })
+        .collect()
+}
+
+/// Check for Perl XS modules linked against a removed libperl
+#[derive(Default)]
+pub(super) struct PerlModules {
+    modules: Vec<PathBuf>,
+}
+
+impl Check for PerlModules {
+    fn name(&self) -> &str {
+        CheckKind::PerlModules.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Perl XS modules linked against the libperl of a Perl version that is not installed anymore, which were not rebuilt after a Perl upgrade".to_owned(),
+            caveats: vec!["Modules installed with cpan are owned by no package, and need to be reinstalled with cpan"],
+            examples: vec![Finding::BrokenPerlModule {
+                path: PathBuf::from("/usr/lib/perl5/5.38/vendor_perl/auto/Foo/Foo.so"),
+                dependency: PathBuf::from("libperl.so"),
+                package: Some("perl-foo".to_owned()),
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        self.modules = MODULE_PATTERNS
+            .iter()
+            .filter_map(|p| glob(&root::resolve(p.as_ref()).to_string_lossy()).ok())
+            .flatten()
+            .flatten()
+            .collect();
+        Ok(self.modules.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        let resolver = SonameResolver::default();
+        let mut findings = Vec::new();
+        for module in self.modules.iter().take_while(|_| !cancel::requested()) {
+            findings.extend(check_module(ctx, &resolver, module));
+            ctx.item_done();
+        }
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{backend::MemoryBackend, metadata::Metadata, progress::Progress};
+
+    #[test]
+    fn test_perl_modules() {
+        let module = Path::new("/usr/lib/perl5/5.38/site_perl/auto/Foo/Foo.so");
+        assert!(is_module_libperl(module, Path::new("libperl.so")));
+        assert!(is_module_libperl(
+            module,
+            Path::new("/usr/lib/perl5/5.38/core_perl/CORE/libperl.so")
+        ));
+        assert!(!is_module_libperl(module, Path::new("libfoo.so.1")));
+        assert!(!is_module_libperl(
+            Path::new("/usr/bin/foo"),
+            Path::new("libperl.so")
+        ));
+
+        // Files that are not ELF have no dependencies
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Foo.so");
+        fs::write(&path, "").unwrap();
+        let backend = MemoryBackend::default();
+        let progress = Progress::new(1, false).unwrap();
+        let metadata = Metadata::default();
+        let ctx = Context::new(&backend, &[], &metadata, &progress, None);
+        assert!(check_module(&ctx, &SonameResolver::default(), &path).is_empty());
+
+        let finding = Finding::BrokenPerlModule {
+            path: module.to_owned(),
+            dependency: PathBuf::from("libperl.so"),
+            package: None,
+        };
+        assert_eq!(
+            finding.to_string(),
+            "Perl module /usr/lib/perl5/5.38/site_perl/auto/Foo/Foo.so, owned by no package, is linked against removed libperl.so, reinstall it with cpan"
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! Libraries of the search path and dynamic loaders that users other than root can not load
+
+use std::{collections::HashSet, fs, os::unix::fs::MetadataExt as _, path::PathBuf};
+
+use super::{
+    duplicates::{is_library_name, owner, unique_dirs},
+    Check, CheckDoc, Context, Input,
+};
+use crate::{
+    backend::PackageBackend,
+    cancel, elf,
+    finding::{CheckKind, Finding},
+    root,
+};
+
+/// File name prefixes of the glibc and musl dynamic loaders
+const LOADER_PREFIXES: [&str; 2] = ["ld-linux", "ld-musl-"];
+
+/// Permission bits of other users needed to map a library
+const LIBRARY_MODE: u32 = 0o004;
+
+/// Permission bits of other users needed for the kernel to start the loader of a program
+const LOADER_MODE: u32 = 0o005;
+
+/// Check libraries of the search directories for permissions preventing users other than root to load them
+fn check_library_permissions(ctx: &Context<'_>, dirs: &[PathBuf]) -> Vec<Finding> {
+    let mut seen = HashSet::new();
+    let mut findings = Vec::new();
+    for dir in dirs {
+        if cancel::requested() {
+            return Vec::new();
+        }
+        for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+            let Some(name) = entry.file_name().to_str().map(ToOwned::to_owned) else {
+                continue;
+            };
+            let loader = LOADER_PREFIXES.iter().any(|p| name.starts_with(p));
+            if !loader && !is_library_name(&name) {
+                continue;
+            }
+            // Several links to the same library are reported once, with the path of the library itself
+            let Some(path) = root::link_target(&entry.path()).filter(|p| seen.insert(p.clone()))
+            else {
+                continue;
+            };
+            let Some(metadata) = fs::metadata(&path).ok().filter(fs::Metadata::is_file) else {
+                continue;
+            };
+            let required = if loader { LOADER_MODE } else { LIBRARY_MODE };
+            let mode = metadata.mode() & 0o7777;
+            if mode & required != required {
+                findings.push(Finding::LibraryPermissions {
+                    path: root::unresolve(&path),
+                    mode,
+                    loader,
+                    package: owner(ctx.backend(), &path),
+                });
+            }
+        }
+        ctx.item_done();
+    }
+    findings
+}
+
+/// Check for libraries and loaders with permissions preventing programs of users other than root to start
+#[derive(Default)]
+pub(super) struct LibraryPermissions {
+    dirs: Vec<PathBuf>,
+}
+
+impl Check for LibraryPermissions {
+    fn name(&self) -> &str {
+        CheckKind::LibraryPermissions.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Libraries of the loader search path that users other than root can not read, and dynamic loaders they can not execute, like after a botched chmod".to_owned(),
+            caveats: vec!["Programs only run by root still work, even though the library is reported"],
+            examples: vec![Finding::LibraryPermissions {
+                path: PathBuf::from("/usr/lib/libfoo.so.1"),
+                mode: 0o600,
+                loader: false,
+                package: Some("foo".to_owned()),
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn progress_weight(&self) -> u64 {
+        // Each directory has thousands of libraries, whose metadata is read
+        100
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        self.dirs = unique_dirs(elf::default_search_dirs());
+        Ok(self.dirs.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        check_library_permissions(ctx, &self.dirs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::{symlink, PermissionsExt as _};
+
+    use super::*;
+    use crate::{backend::MemoryBackend, metadata::Metadata, progress::Progress};
+
+    #[test]
+    fn test_library_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name| dir.path().join(name);
+        for (name, mode) in [
+            ("libfoo.so.1.2", 0o600),
+            ("libbar.so.1", 0o644),
+            ("ld-linux-x86-64.so.2", 0o644),
+            ("foo.conf", 0o600),
+        ] {
+            fs::write(path(name), "").unwrap();
+            fs::set_permissions(path(name), fs::Permissions::from_mode(mode)).unwrap();
+        }
+        symlink("libfoo.so.1.2", path("libfoo.so.1")).unwrap();
+
+        let foo = path("libfoo.so.1.2");
+        let backend =
+            MemoryBackend::default().with_package("foo", "1.0-1", false, &[foo.to_str().unwrap()]);
+        let progress = Progress::new(1, false).unwrap();
+        let mut findings = check_library_permissions(
+            &Context::new(&backend, &[], &Metadata::default(), &progress, None),
+            &[dir.path().to_owned()],
+        );
+        findings.sort_by_key(ToString::to_string);
+        assert_eq!(
+            findings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                format!(
+                    "Dynamic loader {}, not installed by a package, has mode 0644, so programs of users other than root can not start",
+                    path("ld-linux-x86-64.so.2").display()
+                ),
+                format!(
+                    r#"Library {} from package "foo" has mode 0600, so programs of users other than root can not load it"#,
+                    foo.display()
+                ),
+            ]
+        );
+    }
+}
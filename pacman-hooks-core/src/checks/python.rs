@@ -0,0 +1,585 @@
+//! Python packages installed for an older interpreter, and scripts pinning a removed one
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt,
+    fs::{self, File},
+    io::{self, Read as _},
+    os::unix::ffi::OsStrExt as _,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Instant,
+};
+
+use glob::glob;
+use rayon::prelude::*;
+
+use super::{duplicates::owner, Check, CheckDoc, Context, Input};
+use crate::{
+    backend::{reverse_dependencies, PackageBackend},
+    cancel,
+    finding::{CheckKind, Finding, PythonAction},
+    logging::LogContext,
+    root,
+    timings::Phase,
+};
+
+/// Directory of console scripts, whose shebangs are checked for pinned interpreter versions
+const SCRIPT_DIR: &str = "/usr/bin";
+
+/// Directories searched for interpreters run with `env`, as in the default `PATH`
+const PATH_DIRS: [&str; 2] = ["/usr/local/bin", "/usr/bin"];
+
+/// Number of bytes read at the start of scripts, longer shebangs are not parsed
+const SHEBANG_MAX_LEN: u64 = 256;
+
+/// Installed Python interpreter package
+struct PythonInterpreter {
+    package: String,
+    version: String,
+    major: u32,
+    minor: u32,
+}
+
+impl PythonInterpreter {
+    /// Get the interpreter of a package version, its version being like `3.12.4-1`.
+    ///
+    /// Only the major and minor parts need to be numeric, the release and package parts may have suffixes.
+    fn parse(package: &str, version: &str) -> anyhow::Result<Self> {
+        // Skip epoch
+        let upstream = version.rsplit_once(':').map_or(version, |(_, v)| v);
+        let mut parts = upstream.split(['.', '-']);
+        let mut next_part = |name| {
+            parts
+                .next()
+                .and_then(|p| u32::from_str(p).ok())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Unable to parse Python version {name} part of {version:?}")
+                })
+        };
+        Ok(Self {
+            package: package.to_owned(),
+            version: version.to_owned(),
+            major: next_part("major")?,
+            minor: next_part("minor")?,
+        })
+    }
+
+    /// Library directory, relative to the root
+    fn lib_dir(&self) -> String {
+        format!("/usr/lib/python{}.{}", self.major, self.minor)
+    }
+}
+
+impl fmt::Display for PythonInterpreter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.package, self.version)
+    }
+}
+
+/// Whether a package is a Python interpreter, like `python`, or legacy versions like `python310`
+fn is_interpreter_package(package: &str) -> bool {
+    package
+        .strip_prefix("python")
+        .is_some_and(|v| v.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Get installed Python interpreters, packages with a version we can not parse are skipped
+fn get_python_interpreters(backend: &dyn PackageBackend) -> anyhow::Result<Vec<PythonInterpreter>> {
+    let mut interpreters = Vec::new();
+    for package in backend
+        .installed_packages()?
+        .into_iter()
+        .filter(|p| is_interpreter_package(p))
+    {
+        let Some(version) = backend.version(&package)? else {
+            continue;
+        };
+        match PythonInterpreter::parse(&package, &version) {
+            Ok(interpreter) => interpreters.push(interpreter),
+            Err(err) => log::warn!("Ignoring Python package {package:?}: {err}"),
+        }
+    }
+    if interpreters.is_empty() {
+        anyhow::bail!("No Python package is installed");
+    }
+    Ok(interpreters)
+}
+
+/// Get Python library directories that are not used by any installed interpreter,
+/// for the major versions of the interpreters
+fn get_stale_python_dirs(interpreters: &[PythonInterpreter]) -> anyhow::Result<Vec<String>> {
+    let used_dirs: HashSet<_> = interpreters
+        .iter()
+        .map(PythonInterpreter::lib_dir)
+        .collect();
+    let majors: BTreeSet<_> = interpreters.iter().map(|i| i.major).collect();
+
+    let mut dirs = Vec::new();
+    for major in majors {
+        let pattern = root::resolve(Path::new(&format!("/usr/lib/python{major}.*")));
+        let pattern = pattern
+            .to_str()
+            .ok_or_else(|| anyhow::anyhow!("Failed to convert OS string to native string"))?;
+        for python_dir_entry in glob(pattern)? {
+            let python_dir = root::unresolve(&python_dir_entry?)
+                .into_os_string()
+                .into_string()
+                .map_err(|_| anyhow::anyhow!("Failed to convert OS string to native string"))?;
+
+            if !used_dirs.contains(&python_dir) {
+                dirs.push(python_dir);
+            }
+        }
+    }
+
+    Ok(dirs)
+}
+
+/// Get the total size of files in a directory, recursively, without following symlinks
+fn dir_size(dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
+/// Files of a package in stale Python directories
+struct StaleFiles {
+    count: u64,
+    /// Total size in bytes
+    size: u64,
+    /// Whether the package also has files in other directories
+    elsewhere: bool,
+}
+
+/// Count files of a package in stale Python directories, and their size on disk
+fn stale_files(
+    backend: &dyn PackageBackend,
+    package: &str,
+    dirs: &[String],
+) -> anyhow::Result<StaleFiles> {
+    let mut stale = StaleFiles {
+        count: 0,
+        size: 0,
+        elsewhere: false,
+    };
+    backend.for_each_file(package, &mut |file| {
+        let path = file.path();
+        let metadata = fs::symlink_metadata(root::resolve(path)).ok();
+        // Directories end with a slash in file lists
+        if path.as_os_str().as_bytes().ends_with(b"/")
+            || metadata.as_ref().is_some_and(fs::Metadata::is_dir)
+        {
+            return;
+        }
+        if dirs.iter().any(|d| path.starts_with(d)) {
+            stale.count += 1;
+            // Missing files are counted with their recorded size
+            stale.size += metadata.map_or(file.size(), |m| Some(m.len())).unwrap_or(0);
+        } else {
+            stale.elsewhere = true;
+        }
+    })?;
+    Ok(stale)
+}
+
+/// Suggest how to fix a package with files in stale Python directories
+fn python_action(ctx: &Context<'_>, package: &str, stale: &StaleFiles) -> PythonAction {
+    let backend = ctx.backend();
+    match ctx.metadata().foreign_packages(backend) {
+        Ok(foreign) if !foreign.iter().any(|p| p == package) => return PythonAction::Reinstall,
+        Ok(_) => {}
+        Err(err) => log::warn!("Failed to get foreign packages: {err:#}"),
+    }
+    if stale.elsewhere {
+        return PythonAction::Rebuild;
+    }
+    match reverse_dependencies(backend, &[package.to_owned()], &[]) {
+        Ok(dependents) if dependents.is_empty() => PythonAction::Remove,
+        Ok(_) => PythonAction::Rebuild,
+        Err(err) => {
+            log::warn!("Failed to get packages depending on {package:?}: {err:#}");
+            PythonAction::Rebuild
+        }
+    }
+}
+
+/// Check Python directories not used by any installed interpreter for packages still installing files there,
+/// reported once per package
+fn check_python_dirs(ctx: &Context<'_>, stale_dirs: &[String]) -> Vec<Finding> {
+    let log_context = LogContext::current();
+    let owners: Vec<_> = stale_dirs
+        .par_iter()
+        .filter(|_| !cancel::requested())
+        .map(|dir| {
+            let _log = log_context.clone().enter();
+            let start = Instant::now();
+            let packages = ctx.backend().owners(Path::new(dir)).unwrap_or_else(|err| {
+                log::error!("Failed to list packages owning {dir:?}: {err}");
+                Vec::new()
+            });
+            if let Some(timings) = ctx.timings() {
+                timings.record_phase(Phase::Python, start.elapsed());
+            }
+            ctx.item_done();
+            (dir, packages)
+        })
+        .collect();
+
+    let mut findings = Vec::new();
+    let mut package_dirs: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (dir, packages) in owners {
+        if !packages.is_empty() {
+            for package in packages {
+                package_dirs.entry(package).or_default().push(dir.clone());
+            }
+            continue;
+        }
+        match dir_size(&root::resolve(Path::new(dir))) {
+            Ok(size) => findings.push(Finding::UnownedPythonDir {
+                dir: dir.clone(),
+                size,
+            }),
+            Err(err) => log::error!("Failed to get size of {dir:?}: {err}"),
+        }
+    }
+    findings.par_extend(
+        package_dirs
+            .into_par_iter()
+            .filter_map(|(package, mut dirs)| {
+                let _log = log_context.clone().with_package(&package).enter();
+                dirs.sort_unstable();
+                let stale = match stale_files(ctx.backend(), &package, &dirs) {
+                    Ok(stale) => stale,
+                    Err(err) => {
+                        log::error!("Failed to list files of package {package:?}: {err}");
+                        return None;
+                    }
+                };
+                let action = python_action(ctx, &package, &stale);
+                Some(Finding::BrokenPythonPackage {
+                    package,
+                    dirs,
+                    files: stale.count,
+                    size: stale.size,
+                    action,
+                })
+            }),
+    );
+    findings
+}
+
+/// Get the interpreter of a shebang line pinning a Python version, like `/usr/bin/python3.11`
+/// or `/usr/bin/env python3.11`, `None` for other interpreters, including the generic `python3`
+fn pinned_interpreter(line: &str) -> Option<&str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut interpreter = words.next()?;
+    if interpreter.rsplit('/').next() == Some("env") {
+        // Skip options, like -S
+        interpreter = words.find(|w| !w.starts_with('-'))?;
+    }
+    let (major, minor) = interpreter
+        .rsplit('/')
+        .next()?
+        .strip_prefix("python")?
+        .split_once('.')?;
+    let numeric = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    (numeric(major) && numeric(minor)).then_some(interpreter)
+}
+
+/// Whether the interpreter of a shebang is installed on the checked system
+fn interpreter_exists(interpreter: &str) -> bool {
+    if interpreter.starts_with('/') {
+        root::resolve(Path::new(interpreter)).exists()
+    } else {
+        PATH_DIRS
+            .iter()
+            .any(|d| root::resolve(&Path::new(d).join(interpreter)).exists())
+    }
+}
+
+/// Get scripts of a directory whose shebang pins a Python interpreter that is not installed, with that interpreter
+fn pinned_scripts(dir: &Path) -> Vec<(PathBuf, String)> {
+    let mut scripts = Vec::new();
+    for entry in fs::read_dir(root::resolve(dir))
+        .into_iter()
+        .flatten()
+        .flatten()
+    {
+        // Links are skipped, only the file with the shebang is reported
+        if !entry.file_type().is_ok_and(|t| t.is_file()) {
+            continue;
+        }
+        let mut start = Vec::new();
+        if File::open(entry.path())
+            .and_then(|f| f.take(SHEBANG_MAX_LEN).read_to_end(&mut start))
+            .is_err()
+        {
+            continue;
+        }
+        let start = String::from_utf8_lossy(&start);
+        if let Some(interpreter) = start
+            .lines()
+            .next()
+            .and_then(pinned_interpreter)
+            .filter(|i| !interpreter_exists(i))
+        {
+            scripts.push((root::unresolve(&entry.path()), interpreter.to_owned()));
+        }
+    }
+    scripts.sort_unstable();
+    scripts
+}
+
+/// Report scripts pinning a removed Python interpreter, with their package
+fn check_pinned_scripts(ctx: &Context<'_>, scripts: &[(PathBuf, String)]) -> Vec<Finding> {
+    scripts
+        .iter()
+        .take_while(|_| !cancel::requested())
+        .map(|(path, interpreter)| {
+            let package = owner(ctx.backend(), &root::resolve(path));
+            ctx.item_done();
+            Finding::PinnedPythonScript {
+                path: path.clone(),
+                interpreter: interpreter.clone(),
+                package,
+            }
+        })
+        .collect()
+}
+
+/// Check packages with files in Python directories not used by any installed interpreter,
+/// and scripts pinning a Python interpreter that is not installed
+#[derive(Default)]
+pub(super) struct PythonPackages {
+    stale_dirs: Vec<String>,
+    pinned_scripts: Vec<(PathBuf, String)>,
+}
+
+impl Check for PythonPackages {
+    fn name(&self) -> &str {
+        CheckKind::PythonPackages.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Packages with files in the directory of a Python version that no installed interpreter uses, such directories owned by no package, and scripts whose shebang pins a Python version that is not installed".to_owned(),
+            caveats: vec![
+                "Directories of legacy interpreter packages, like python310, are in use and not reported",
+                "Directories owned by no package are usually left by pip, and only waste space",
+            ],
+            examples: vec![
+                Finding::BrokenPythonPackage {
+                    package: "python-foo".to_owned(),
+                    dirs: vec!["/usr/lib/python3.12/site-packages".to_owned()],
+                    files: 42,
+                    size: 1_048_576,
+                    action: PythonAction::Rebuild,
+                },
+                Finding::PinnedPythonScript {
+                    path: PathBuf::from("/usr/bin/foo"),
+                    interpreter: "python3.11".to_owned(),
+                    package: Some("foo".to_owned()),
+                },
+            ],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn progress_weight(&self) -> u64 {
+        // Owners of each directory or script are searched in the file lists of all packages
+        20
+    }
+
+    fn prepare(
+        &mut self,
+        backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        self.stale_dirs = match get_python_interpreters(backend) {
+            Ok(interpreters) => {
+                for interpreter in &interpreters {
+                    log::debug!("Python interpreter: {interpreter}");
+                }
+                get_stale_python_dirs(&interpreters).unwrap_or_else(|err| {
+                    log::error!("Failed to list Python directories: {err}");
+                    Vec::new()
+                })
+            }
+            Err(err) => {
+                log::error!("Failed to get Python interpreters: {err}");
+                Vec::new()
+            }
+        };
+        self.pinned_scripts = pinned_scripts(Path::new(SCRIPT_DIR));
+        Ok((self.stale_dirs.len() + self.pinned_scripts.len()) as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        let mut findings = check_python_dirs(ctx, &self.stale_dirs);
+        findings.extend(check_pinned_scripts(ctx, &self.pinned_scripts));
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{backend::MemoryBackend, metadata::Metadata, progress::Progress};
+
+    use super::*;
+
+    #[test]
+    fn test_python_checks() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let stale_dir = tmp_dir.path().join("python3.11");
+        let site_packages = stale_dir.join("site-packages");
+        fs::create_dir_all(&site_packages).unwrap();
+        let file = |name: &str| {
+            let path = site_packages.join(name);
+            fs::write(&path, vec![0; 1 << 19]).unwrap();
+            path.to_str().unwrap().to_owned()
+        };
+        let (foo, bar, baz) = (file("foo.py"), file("bar.py"), file("baz.py"));
+        let foo_bin = tmp_dir.path().join("foo");
+        fs::write(&foo_bin, "").unwrap();
+        let stale_dir = stale_dir.to_str().unwrap().to_owned();
+        let backend = MemoryBackend::default()
+            .with_package("python", "3.12.4-1", false, &["/usr/lib/python3.12/"])
+            .with_package(
+                "python-foo",
+                "1.0-1",
+                true,
+                &[&stale_dir, &foo, foo_bin.to_str().unwrap()],
+            )
+            .with_package("python-bar", "1.0-1", false, &[&stale_dir, &bar])
+            .with_package("python-baz", "1.0-1", true, &[&stale_dir, &baz]);
+
+        let interpreters = get_python_interpreters(&backend).unwrap();
+        assert_eq!(
+            interpreters
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["python 3.12.4-1"]
+        );
+
+        let progress = Progress::new(2, false).unwrap();
+        let metadata = Metadata::default();
+        let ctx = Context::new(&backend, &[], &metadata, &progress, None);
+        let unowned_dir = tempfile::tempdir().unwrap();
+        fs::create_dir(unowned_dir.path().join("site-packages")).unwrap();
+        fs::write(
+            unowned_dir.path().join("site-packages/foo.py"),
+            vec![0; 3 << 19],
+        )
+        .unwrap();
+        let unowned_dir = unowned_dir.path().to_str().unwrap().to_owned();
+        let findings = check_python_dirs(&ctx, &[stale_dir.clone(), unowned_dir.clone()]);
+        let ignored = |package: &str| {
+            format!("Package {package:?} has 1 file(s) (0.5 MiB) ignored by the current Python interpreter in {stale_dir:?}")
+        };
+        assert_eq!(
+            findings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                format!("Directory {unowned_dir:?} is ignored by installed Python interpreters and owned by no package, remove its 1.5 MiB of leftover files"),
+                format!("{}, reinstall it from the repositories", ignored("python-bar")),
+                format!("{}, remove it, it has no other files and no package depends on it", ignored("python-baz")),
+                format!("{}, rebuild it", ignored("python-foo")),
+            ]
+        );
+
+        assert!(get_python_interpreters(&MemoryBackend::default()).is_err());
+    }
+
+    #[test]
+    fn test_pinned_scripts() {
+        assert_eq!(
+            pinned_interpreter("#!/usr/bin/python3.11 -s"),
+            Some("/usr/bin/python3.11")
+        );
+        assert_eq!(
+            pinned_interpreter("#!/usr/bin/env -S python3.11"),
+            Some("python3.11")
+        );
+        assert_eq!(pinned_interpreter("#!/usr/bin/python3"), None);
+        assert_eq!(pinned_interpreter("#!/usr/bin/python3.11-config"), None);
+        assert_eq!(pinned_interpreter("#!/bin/sh"), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in [
+            ("foo", "#!/usr/bin/python3.0\nimport foo\n"),
+            ("bar", "#!/usr/bin/python3\n"),
+            ("baz", "#!/usr/bin/env python3.0\n"),
+            ("qux", "#!/bin/sh\n"),
+        ] {
+            fs::write(dir.path().join(name), content).unwrap();
+        }
+        std::os::unix::fs::symlink("foo", dir.path().join("foo-link")).unwrap();
+        let scripts = pinned_scripts(dir.path());
+        let foo = dir.path().join("foo");
+        let backend = MemoryBackend::default().with_package(
+            "python-foo",
+            "1.0-1",
+            true,
+            &[foo.to_str().unwrap()],
+        );
+        let progress = Progress::new(2, false).unwrap();
+        let findings = check_pinned_scripts(
+            &Context::new(&backend, &[], &Metadata::default(), &progress, None),
+            &scripts,
+        );
+        assert_eq!(
+            findings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                format!(
+                    "Script {}, owned by no package, runs removed interpreter python3.0, change its shebang to python3",
+                    dir.path().join("baz").display()
+                ),
+                format!(
+                    r#"Script {} from package "python-foo" runs removed interpreter /usr/bin/python3.0, rebuild the package"#,
+                    foo.display()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_python_interpreters() {
+        let backend = MemoryBackend::default()
+            .with_package("python", "3.13.1-1.1", false, &[])
+            .with_package("python310", "3.10.14-2", true, &[])
+            .with_package("python39", "3.9.19rc1-1", true, &[])
+            .with_package("python-foo", "1.0-1", false, &[]);
+        let interpreters = get_python_interpreters(&backend).unwrap();
+        let mut dirs: Vec<_> = interpreters
+            .iter()
+            .map(PythonInterpreter::lib_dir)
+            .collect();
+        dirs.sort_unstable();
+        assert_eq!(
+            dirs,
+            [
+                "/usr/lib/python3.10",
+                "/usr/lib/python3.13",
+                "/usr/lib/python3.9"
+            ]
+        );
+
+        assert!(PythonInterpreter::parse("python", "1:3.12.4-1").is_ok_and(|i| i.minor == 12));
+        assert!(PythonInterpreter::parse("python", "3").is_err());
+        assert!(!is_interpreter_package("python-foo"));
+        assert!(is_interpreter_package("python"));
+    }
+}
@@ -0,0 +1,245 @@
+//! Packages whose install scriptlets failed in recent transactions, leaving what they set up missing
+
+use std::collections::HashMap;
+
+use super::{Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    cancel,
+    finding::{CheckKind, Finding},
+    history,
+};
+
+/// Number of most recent transactions searched for failed scriptlets
+const RECENT_TRANSACTIONS: usize = 20;
+
+/// Error logged by pacman when a scriptlet exits with an error
+const SCRIPTLET_FAILED: &str = "error: command failed to execute correctly";
+
+/// Scriptlet failure of a package version, from the pacman log
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct ScriptletFailure {
+    package: String,
+    version: String,
+    /// Day of the transaction, like `2024-03-05`
+    date: String,
+    /// First error printed by the scriptlet, or the one logged by pacman
+    error: String,
+}
+
+/// Parse pacman log lines of the recent transactions into the scriptlet failures of installed package versions.
+///
+/// Scriptlet output follows the log line of the package it was run for, until the next package, hook or transaction
+/// boundary, so errors of pre-install scriptlets are not attributed.
+fn parse_failures(log: &str) -> Vec<ScriptletFailure> {
+    let lines: Vec<_> = log.lines().collect();
+    let starts: Vec<_> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| l.ends_with("] [ALPM] transaction started"))
+        .map(|(i, _)| i)
+        .collect();
+    let start = starts
+        .get(starts.len().saturating_sub(RECENT_TRANSACTIONS))
+        .copied()
+        .unwrap_or_default();
+    let mut current: Option<ScriptletFailure> = None;
+    let mut failures: HashMap<String, ScriptletFailure> = HashMap::new();
+    for line in &lines[start..] {
+        let Some((timestamp, rest)) = line.strip_prefix('[').and_then(|l| l.split_once("] "))
+        else {
+            continue;
+        };
+        let error = if let Some(output) = rest.strip_prefix("[ALPM-SCRIPTLET] ") {
+            Some(output.trim()).filter(|o| o.to_lowercase().starts_with("error"))
+        } else if let Some(message) = rest.strip_prefix("[ALPM] ") {
+            if message != SCRIPTLET_FAILED {
+                current = parse_action(timestamp, message, &mut failures);
+                continue;
+            }
+            Some(message)
+        } else {
+            None
+        };
+        // The first error is the most telling, pacman logs its own after the ones of the scriptlet
+        if let (Some(error), Some(failure)) = (error, &current) {
+            failures
+                .entry(failure.package.clone())
+                .or_insert_with(|| ScriptletFailure {
+                    error: error.to_owned(),
+                    ..failure.clone()
+                });
+        }
+    }
+    let mut failures: Vec<_> = failures.into_values().collect();
+    failures.sort_by(|a, b| a.package.cmp(&b.package));
+    failures
+}
+
+/// Parse a pacman log message like `upgraded icu (74.2-1 -> 75.1-1)` into the installed package version,
+/// forgetting earlier failures of the package since its scriptlets ran again or it was removed.
+///
+/// Other messages, like hooks or transaction boundaries, end the output of the previous package, so `None` is returned.
+fn parse_action(
+    timestamp: &str,
+    message: &str,
+    failures: &mut HashMap<String, ScriptletFailure>,
+) -> Option<ScriptletFailure> {
+    let (verb, rest) = message.split_once(' ')?;
+    if ![
+        "installed",
+        "upgraded",
+        "downgraded",
+        "reinstalled",
+        "removed",
+    ]
+    .contains(&verb)
+    {
+        return None;
+    }
+    let (package, versions) = rest.split_once(" (")?;
+    failures.remove(package);
+    if verb == "removed" {
+        return None;
+    }
+    let version = versions.strip_suffix(')')?.rsplit(" -> ").next()?;
+    Some(ScriptletFailure {
+        package: package.to_owned(),
+        version: version.to_owned(),
+        // Old logs use "2019-01-02 10:00"
+        date: timestamp
+            .split(['T', ' '])
+            .next()
+            .unwrap_or_default()
+            .to_owned(),
+        error: String::new(),
+    })
+}
+
+/// Check for packages whose scriptlets failed, with the failed version still installed
+fn check_scriptlet_failures(ctx: &Context<'_>, failures: &[ScriptletFailure]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for failure in failures.iter().take_while(|_| !cancel::requested()) {
+        let version = ctx
+            .backend()
+            .version(&failure.package)
+            .unwrap_or_else(|err| {
+                log::error!(
+                    "Failed to get version of package {:?}: {err}",
+                    failure.package
+                );
+                None
+            });
+        if version.as_deref() == Some(failure.version.as_str()) {
+            findings.push(Finding::FailedScriptlet {
+                package: failure.package.clone(),
+                version: failure.version.clone(),
+                date: failure.date.clone(),
+                error: failure.error.clone(),
+            });
+        }
+        ctx.item_done();
+    }
+    findings
+}
+
+/// Check for packages whose install or upgrade scriptlets failed in recent transactions
+#[derive(Default)]
+pub(super) struct ScriptletFailures {
+    failures: Vec<ScriptletFailure>,
+}
+
+impl Check for ScriptletFailures {
+    fn name(&self) -> &str {
+        CheckKind::ScriptletFailures.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Packages whose install or upgrade scriptlet failed in one of the last transactions of the pacman log, with the first error it printed".to_owned(),
+            caveats: vec![
+                "Scriptlets printing errors they recover from are also reported",
+                "Packages upgraded, reinstalled or removed since the failure are not reported",
+            ],
+            examples: vec![Finding::FailedScriptlet {
+                package: "foo".to_owned(),
+                version: "1.0-1".to_owned(),
+                date: "2024-03-05T10:12:34+0100".to_owned(),
+                error: "useradd: group 'foo' does not exist".to_owned(),
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        self.failures = parse_failures(&history::read_log()?);
+        Ok(self.failures.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        check_scriptlet_failures(ctx, &self.failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::MemoryBackend, metadata::Metadata, progress::Progress};
+
+    #[test]
+    fn test_scriptlet_failures() {
+        let failures = parse_failures(
+            "[2024-03-05T18:12:01+0100] [ALPM] transaction started
+[2024-03-05T18:12:02+0100] [ALPM] installed foo (1.0-1)
+[2024-03-05T18:12:02+0100] [ALPM-SCRIPTLET] ==> Creating user foo
+[2024-03-05T18:12:02+0100] [ALPM-SCRIPTLET] error: useradd failed
+[2024-03-05T18:12:02+0100] [ALPM] error: command failed to execute correctly
+[2024-03-05T18:12:03+0100] [ALPM] upgraded bar (1.0-1 -> 2.0-1)
+[2024-03-05T18:12:03+0100] [ALPM] error: command failed to execute correctly
+[2024-03-05T18:12:03+0100] [ALPM] upgraded baz (1.0-1 -> 2.0-1)
+[2024-03-05T18:12:03+0100] [ALPM-SCRIPTLET] error: cache update failed
+[2024-03-05T18:12:04+0100] [ALPM] transaction completed
+[2024-03-05T18:12:04+0100] [ALPM] running '30-systemd-update.hook'...
+[2024-03-05T18:12:04+0100] [ALPM] error: command failed to execute correctly
+[2024-03-06T09:00:00+0100] [ALPM] transaction started
+[2024-03-06T09:00:01+0100] [ALPM] reinstalled baz (2.0-1)
+[2024-03-06T09:00:02+0100] [ALPM] transaction completed
+",
+        );
+        assert_eq!(
+            failures
+                .iter()
+                .map(|f| (f.package.as_str(), f.version.as_str(), f.error.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                ("bar", "2.0-1", SCRIPTLET_FAILED),
+                ("foo", "1.0-1", "error: useradd failed"),
+            ]
+        );
+        assert_eq!(failures[0].date, "2024-03-05");
+
+        // The failed version of bar was since replaced
+        let backend = MemoryBackend::default()
+            .with_package("foo", "1.0-1", false, &[])
+            .with_package("bar", "2.0-2", false, &[]);
+        let progress = Progress::new(2, false).unwrap();
+        let findings = check_scriptlet_failures(
+            &Context::new(&backend, &[], &Metadata::default(), &progress, None),
+            &failures,
+        );
+        assert_eq!(
+            findings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                r#"Scriptlet of package "foo" 1.0-1 failed on 2024-03-05 with "error: useradd failed", reinstall the package to run it again"#
+            ]
+        );
+    }
+}
@@ -0,0 +1,311 @@
+//! Kernels and unified kernel images of the boot partitions that are not signed with a key enrolled for Secure Boot,
+//! like images rebuilt by an upgrade without being signed again, which the firmware refuses to boot
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use glob::glob;
+
+use super::{cmdline, Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    cancel,
+    finding::{CheckKind, Finding},
+    root,
+};
+
+/// Kernels, only checked when a boot configuration loads them, since they stay unsigned when unified kernel images
+/// built from them are booted instead
+const KERNEL_PATTERN: &str = "/boot/vmlinuz-*";
+
+/// Unified kernel images, booted by the firmware or a boot loader without a configuration
+const UKI_PATTERNS: [&str; 3] = [
+    "/boot/EFI/Linux/*.efi",
+    "/efi/EFI/Linux/*.efi",
+    "/boot/efi/EFI/Linux/*.efi",
+];
+
+/// EFI variables with signature lists of the keys images are checked with, and the size of their attributes prefix:
+/// the `db` of the firmware, and the machine owner keys of shim
+const KEY_VARIABLES: [(&str, usize); 3] = [
+    (
+        "/sys/firmware/efi/efivars/db-d719b2cb-3d3a-4596-a3bc-dad00e67656f",
+        4,
+    ),
+    (
+        "/sys/firmware/efi/efivars/MokListRT-605dab50-e046-4300-abb6-3dd810dd8b23",
+        4,
+    ),
+    ("/sys/firmware/efi/mok-variables/MokListRT", 0),
+];
+
+/// Type of signature lists of X.509 certificates, `EFI_CERT_X509_GUID` in its binary form
+const X509_GUID: [u8; 16] = [
+    0xa1, 0x59, 0xc0, 0xa5, 0xe4, 0x94, 0xa7, 0x4a, 0x87, 0xb5, 0xab, 0x15, 0x5c, 0x2b, 0xf0, 0x72,
+];
+
+/// Size of the header of a signature list: type, list size, header size and signature size
+const SIGNATURE_LIST_HEADER_SIZE: usize = 28;
+
+/// Size of the owner of each signature of a signature list
+const SIGNATURE_OWNER_SIZE: usize = 16;
+
+/// Read a little endian `u16` at `offset`
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset.checked_add(2)?)?.try_into().ok()?,
+    ))
+}
+
+/// Read a little endian `u32` at `offset`, as a size or offset
+fn u32_at(data: &[u8], offset: usize) -> Option<usize> {
+    usize::try_from(u32::from_le_bytes(
+        data.get(offset..offset.checked_add(4)?)?.try_into().ok()?,
+    ))
+    .ok()
+}
+
+/// Get the Authenticode signatures of a PE image, empty if it is not signed, or `None` if it is not a PE image
+fn pe_signatures(data: &[u8]) -> Option<&[u8]> {
+    if data.get(..2)? != b"MZ" {
+        return None;
+    }
+    let pe_offset = u32_at(data, 0x3c)?;
+    if data.get(pe_offset..pe_offset.checked_add(4)?)? != b"PE\0\0" {
+        return None;
+    }
+    // After the signature and the COFF header
+    let optional_header = pe_offset + 24;
+    // Number of data directories, and their offset, for PE32 and PE32+
+    let (count_offset, directories) = match u16_at(data, optional_header)? {
+        0x10b => (92, 96),
+        0x20b => (108, 112),
+        _ => return None,
+    };
+    // The certificate table is the fifth data directory, its address is a file offset
+    if u32_at(data, optional_header + count_offset)? < 5 {
+        return Some(&[]);
+    }
+    let entry = optional_header + directories + 4 * 8;
+    let (offset, size) = (u32_at(data, entry)?, u32_at(data, entry + 4)?);
+    Some(
+        data.get(offset..offset.checked_add(size)?)
+            .unwrap_or_default(),
+    )
+}
+
+/// Get the X.509 certificates of EFI signature lists
+fn x509_certificates(mut lists: &[u8]) -> Vec<&[u8]> {
+    let mut certificates = Vec::new();
+    while let (Some(list_size), Some(header_size), Some(signature_size)) =
+        (u32_at(lists, 16), u32_at(lists, 20), u32_at(lists, 24))
+    {
+        let Some(list) = lists
+            .get(..list_size)
+            .filter(|_| list_size >= SIGNATURE_LIST_HEADER_SIZE)
+        else {
+            break;
+        };
+        if list.get(..16) == Some(&X509_GUID[..]) && signature_size > SIGNATURE_OWNER_SIZE {
+            certificates.extend(
+                list.get(SIGNATURE_LIST_HEADER_SIZE + header_size..)
+                    .unwrap_or_default()
+                    .chunks_exact(signature_size)
+                    .filter_map(|s| s.get(SIGNATURE_OWNER_SIZE..)),
+            );
+        }
+        lists = lists.get(list_size..).unwrap_or_default();
+    }
+    certificates
+}
+
+/// Check that an image is signed, with one of `certificates` if there are any.
+///
+/// Signers embed their certificate in the signature, so finding an enrolled certificate in it tells which key signed
+/// the image, the firmware verifying the signature itself at boot.
+fn check_image(path: &Path, data: &[u8], certificates: &[Vec<u8>]) -> Option<Finding> {
+    let Some(signatures) = pe_signatures(data) else {
+        log::debug!("Not checking {}, which is not a PE image", path.display());
+        return None;
+    };
+    let signed = !signatures.is_empty();
+    let enrolled = certificates.is_empty()
+        || certificates
+            .iter()
+            .any(|c| signatures.windows(c.len()).any(|w| w == c.as_slice()));
+    (!signed || !enrolled).then(|| Finding::UnsignedBootImage {
+        path: root::unresolve(path),
+        signed,
+    })
+}
+
+/// Get the certificates enrolled in the firmware and shim of the running system
+fn enrolled_certificates() -> Vec<Vec<u8>> {
+    KEY_VARIABLES
+        .iter()
+        .filter_map(|(path, prefix)| {
+            fs::read(path)
+                .inspect_err(|err| log::debug!("Failed to read {path}: {err}"))
+                .ok()
+                .map(|data| {
+                    x509_certificates(data.get(*prefix..).unwrap_or_default())
+                        .into_iter()
+                        .map(<[u8]>::to_vec)
+                        .collect::<Vec<_>>()
+                })
+        })
+        .flatten()
+        .collect()
+}
+
+/// Get the resolved paths of the images matching a pattern of the checked system
+fn glob_images(pattern: &str) -> impl Iterator<Item = PathBuf> {
+    glob(&root::resolve(Path::new(pattern)).to_string_lossy())
+        .into_iter()
+        .flatten()
+        .flatten()
+}
+
+/// Check that boot images are signed for Secure Boot
+#[derive(Default)]
+pub(super) struct SecureBootImages {
+    images: Vec<PathBuf>,
+    /// Enrolled certificates, empty to only check that images are signed
+    certificates: Vec<Vec<u8>>,
+}
+
+impl Check for SecureBootImages {
+    fn name(&self) -> &str {
+        CheckKind::SecureBoot.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Kernels and unified kernel images that are not signed with a certificate enrolled for Secure Boot, like after an upgrade rebuilt an image without signing it again".to_owned(),
+            caveats: vec![
+                "Images are signed by other hooks running after pacman-hooks, so the check is only meaningful in the hook generated for it",
+                "Without readable EFI variables, like for another root directory, images only need to be signed",
+                "Kernels are only checked if a boot entry loads them, so kernels booted from a configuration only root can read are skipped when not running as root",
+            ],
+            examples: vec![Finding::UnsignedBootImage {
+                path: PathBuf::from("/boot/vmlinuz-linux"),
+                signed: false,
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn progress_weight(&self) -> u64 {
+        // Images are read entirely, since signatures are at their end
+        50
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        // Only useful with Secure Boot enabled
+        false
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        let referenced = cmdline::referenced_images();
+        self.images = glob_images(KERNEL_PATTERN)
+            .filter(|k| {
+                let booted = referenced.contains(k);
+                if !booted {
+                    log::debug!("Not checking {}, which no boot entry loads", k.display());
+                }
+                booted
+            })
+            .chain(UKI_PATTERNS.iter().flat_map(|p| glob_images(p)))
+            .collect();
+        // The firmware of the running system does not boot other roots
+        self.certificates = if root::is_alternate() {
+            Vec::new()
+        } else {
+            enrolled_certificates()
+        };
+        if self.certificates.is_empty() {
+            log::debug!("No enrolled certificates, only checking that images are signed");
+        }
+        Ok(self.images.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for image in self.images.iter().take_while(|_| !cancel::requested()) {
+            match fs::read(image) {
+                Ok(data) => findings.extend(check_image(image, &data, &self.certificates)),
+                Err(err) => log::warn!("Failed to read {}: {err}", image.display()),
+            }
+            ctx.item_done();
+        }
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a PE32+ image with a certificate table
+    fn pe_image(signatures: &[u8]) -> Vec<u8> {
+        let mut data = vec![0; 0x200];
+        data[..2].copy_from_slice(b"MZ");
+        data[0x3c] = 0x80;
+        data[0x80..0x84].copy_from_slice(b"PE\0\0");
+        let optional_header = 0x80 + 24;
+        data[optional_header..optional_header + 2].copy_from_slice(&0x20b_u16.to_le_bytes());
+        data[optional_header + 108] = 16;
+        let entry = optional_header + 112 + 32;
+        data[entry..entry + 4].copy_from_slice(&0x200_u32.to_le_bytes());
+        data[entry + 4..entry + 8]
+            .copy_from_slice(&u32::try_from(signatures.len()).unwrap().to_le_bytes());
+        data.extend_from_slice(signatures);
+        data
+    }
+
+    #[test]
+    fn test_secure_boot_images() {
+        let certificate = b"0\x82certificate".to_vec();
+        let mut lists = X509_GUID.to_vec();
+        let signature_size = SIGNATURE_OWNER_SIZE + certificate.len();
+        for size in [
+            SIGNATURE_LIST_HEADER_SIZE + signature_size,
+            0,
+            signature_size,
+        ] {
+            lists.extend_from_slice(&u32::try_from(size).unwrap().to_le_bytes());
+        }
+        lists.extend_from_slice(&[0; SIGNATURE_OWNER_SIZE]);
+        lists.extend_from_slice(&certificate);
+        assert_eq!(x509_certificates(&lists), [certificate.as_slice()]);
+
+        let path = PathBuf::from("/boot/EFI/Linux/arch-linux.efi");
+        let certificates = [certificate];
+        let signed = pe_image(b"\0\0\0\0\x02\x02\0\0pkcs7 0\x82certificate");
+        assert_eq!(pe_signatures(b"\x7fELF"), None);
+        assert!(check_image(&path, &signed, &certificates).is_none());
+        assert!(check_image(&path, &signed, &[]).is_none());
+        assert_eq!(
+            check_image(&path, &pe_image(b""), &[])
+                .map(|f| f.to_string())
+                .as_deref(),
+            Some("Boot image /boot/EFI/Linux/arch-linux.efi is not signed, the firmware refuses to boot it with Secure Boot enabled, sign it with sbctl or sbsign")
+        );
+        assert_eq!(
+            check_image(&path, &pe_image(b"pkcs7 0\x82other"), &certificates)
+                .map(|f| f.to_string())
+                .as_deref(),
+            Some("Boot image /boot/EFI/Linux/arch-linux.efi is not signed with a key enrolled in the firmware or shim, the firmware refuses to boot it with Secure Boot enabled, sign it with an enrolled key")
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! Installed packages affected by security advisories
+
+use std::collections::HashMap;
+
+use super::{Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    cancel,
+    finding::{CheckKind, Finding},
+    security::{self, Advisory},
+};
+
+/// Check installed packages against the security tracker advisories
+#[derive(Default)]
+pub(super) struct SecurityAdvisories {
+    advisories: Vec<Advisory>,
+}
+
+impl Check for SecurityAdvisories {
+    fn name(&self) -> &str {
+        CheckKind::SecurityAdvisories.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Installed packages affected by advisories of the Arch Linux security tracker".to_owned(),
+            caveats: vec![
+                "Advisories without a fixed version yet are also reported, with nothing to upgrade to",
+                "An outdated copy of the advisory feed is used when it can not be downloaded",
+            ],
+            config_keys: vec!["Offline", "NetworkProbeTimeout"],
+            examples: vec![Finding::VulnerablePackage {
+                package: "foo".to_owned(),
+                version: "1.0-1".to_owned(),
+                advisory: "AVG-1234".to_owned(),
+                risk: "High".to_owned(),
+                issue_type: "arbitrary code execution".to_owned(),
+                fixed: Some("1.0.1-1".to_owned()),
+            }],
+        }
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        // Needs network access, at least for the first run
+        false
+    }
+
+    fn needs_network(&self) -> bool {
+        // An outdated cached feed is used when offline
+        !security::has_cached_feed()
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        self.advisories = security::load_advisories().unwrap_or_else(|err| {
+            log::error!("Failed to load security advisories: {err:#}");
+            Vec::new()
+        });
+        Ok(self.advisories.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        // Packages are often affected by several advisories
+        let mut versions: HashMap<&str, Option<String>> = HashMap::new();
+        let mut findings = Vec::new();
+        for advisory in self.advisories.iter().take_while(|_| !cancel::requested()) {
+            for package in advisory.packages() {
+                let version = versions.entry(package).or_insert_with(|| {
+                    ctx.backend().version(package).unwrap_or_else(|err| {
+                        log::error!("Failed to get version of package {package:?}: {err}");
+                        None
+                    })
+                });
+                if let Some(version) = version.as_deref().filter(|v| advisory.affects(v)) {
+                    findings.push(Finding::VulnerablePackage {
+                        package: package.to_owned(),
+                        version: version.to_owned(),
+                        advisory: advisory.name().to_owned(),
+                        risk: advisory.severity().to_owned(),
+                        issue_type: advisory.kind().to_owned(),
+                        fixed: advisory.fixed().map(ToOwned::to_owned),
+                    });
+                }
+            }
+            ctx.item_done();
+        }
+        findings
+    }
+}
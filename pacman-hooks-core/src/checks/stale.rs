@@ -0,0 +1,149 @@
+//! Foreign packages built long before the installed versions of their repository dependencies, like AUR packages
+//! built against a library that had a major upgrade since, which may misbehave before anything is visibly broken
+
+use std::collections::HashSet;
+
+use super::{Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    cancel,
+    finding::{CheckKind, Finding},
+};
+
+/// Seconds a dependency must have been installed after the build of a package for the package to be reported,
+/// so that it is not reported for every minor upgrade since
+const MIN_BUILD_AGE: u64 = 180 * 86_400;
+
+/// Check that a package was not built long before its repository dependencies were installed.
+///
+/// Dependencies not installed as a package of that name, like the ones satisfied by a provider, are skipped.
+fn check_package(
+    backend: &dyn PackageBackend,
+    foreign: &HashSet<String>,
+    package: &str,
+) -> anyhow::Result<Option<Finding>> {
+    let Some(built) = backend.build_date(package)? else {
+        return Ok(None);
+    };
+    let mut dependencies = Vec::new();
+    for dependency in backend.dependencies(package)? {
+        if foreign.contains(&dependency) {
+            continue;
+        }
+        if backend
+            .install_date(&dependency)?
+            .is_some_and(|installed| installed > built.saturating_add(MIN_BUILD_AGE))
+        {
+            dependencies.push(dependency);
+        }
+    }
+    if dependencies.is_empty() {
+        return Ok(None);
+    }
+    dependencies.sort_unstable();
+    dependencies.dedup();
+    Ok(Some(Finding::StaleBuild {
+        package: package.to_owned(),
+        built,
+        dependencies,
+    }))
+}
+
+/// Check for foreign packages built long before their repository dependencies were upgraded
+#[derive(Default)]
+pub(super) struct StaleBuilds {
+    foreign: HashSet<String>,
+}
+
+impl Check for StaleBuilds {
+    fn name(&self) -> &str {
+        CheckKind::StaleBuilds.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::Packages]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Foreign packages built long before their repository dependencies were last upgraded, as candidates for a rebuild".to_owned(),
+            caveats: vec![
+                "Most old builds keep working, the dates are only a hint",
+                "Dependencies satisfied by a package of another name, and foreign dependencies, are skipped",
+            ],
+            examples: vec![Finding::StaleBuild {
+                package: "foo".to_owned(),
+                built: 1_700_000_000,
+                dependencies: vec!["icu".to_owned()],
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        // Most old builds keep working, the rebuild candidates are only hints
+        false
+    }
+
+    fn prepare(
+        &mut self,
+        backend: &dyn PackageBackend,
+        packages: &[String],
+    ) -> anyhow::Result<u64> {
+        self.foreign = backend.foreign_packages()?.into_iter().collect();
+        Ok(packages.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for package in ctx.packages().iter().take_while(|_| !cancel::requested()) {
+            match check_package(ctx.backend(), &self.foreign, package) {
+                Ok(finding) => findings.extend(finding),
+                Err(err) => log::warn!("Failed to check build date of package {package:?}: {err}"),
+            }
+            ctx.item_done();
+        }
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+
+    #[test]
+    fn test_stale_builds() {
+        let built = 1_700_000_000;
+        let backend = MemoryBackend::default()
+            .with_package("foo", "1.0-1", true, &[])
+            .with_dates("foo", built, built)
+            .with_dependency("foo", "glibc")
+            .with_dependency("foo", "icu")
+            .with_dependency("foo", "libbar-git")
+            .with_dependency("foo", "sh")
+            .with_dependency("foo", "zlib")
+            .with_package("glibc", "2.40-1", false, &[])
+            .with_dates("glibc", built, built + MIN_BUILD_AGE + 1)
+            .with_package("icu", "76.1-1", false, &[])
+            .with_dates("icu", built, built + MIN_BUILD_AGE + 1)
+            .with_package("libbar-git", "2.0-1", true, &[])
+            .with_dates("libbar-git", built, built + MIN_BUILD_AGE + 1)
+            .with_package("zlib", "1.3-1", false, &[])
+            .with_dates("zlib", built, built + MIN_BUILD_AGE)
+            .with_package("bar", "1.0-1", true, &[])
+            .with_dependency("bar", "glibc");
+        let foreign = backend.foreign_packages().unwrap().into_iter().collect();
+        assert_eq!(
+            check_package(&backend, &foreign, "foo")
+                .unwrap()
+                .map(|f| f.to_string()),
+            Some(
+                r#"Package "foo" was built on 2023-11-14 22:13:20 UTC, long before its dependencies "glibc" and "icu" were upgraded, rebuild it if it misbehaves"#
+                    .to_owned()
+            )
+        );
+        // Unknown build date
+        assert!(check_package(&backend, &foreign, "bar").unwrap().is_none());
+    }
+}
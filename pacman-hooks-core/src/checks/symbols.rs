@@ -0,0 +1,169 @@
+//! Executables of packages requiring symbol versions that their libraries do not define, like `GLIBC_2.38` after a
+//! downgrade of glibc, which the loader refuses to run even though all their libraries are found
+
+use std::path::PathBuf;
+
+use rayon::prelude::*;
+
+use super::{
+    duplicates::owner, missing_dependencies::BLACKLISTED_EXE_DIRS, Check, CheckDoc, Context, Input,
+};
+use crate::{
+    backend::{for_each_package_executable_file, PackageBackend},
+    cancel,
+    elf::{MissingVersions, SonameResolver},
+    finding::{CheckKind, Finding},
+    logging::LogContext,
+    root,
+};
+
+/// Check the symbol versions required by the executables of a package
+fn check_package(
+    backend: &dyn PackageBackend,
+    resolver: &SonameResolver,
+    package: &str,
+) -> anyhow::Result<Vec<Finding>> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for_each_package_executable_file(backend, package, |f| {
+        if !BLACKLISTED_EXE_DIRS.iter().any(|d| f.path().starts_with(d)) {
+            files.push(f.into_path());
+        }
+    })?;
+    Ok(files
+        .into_iter()
+        .flat_map(|file| {
+            resolver
+                .missing_versions(&root::resolve(&file))
+                .into_iter()
+                .map(move |m| (file.clone(), m))
+        })
+        .map(
+            |(
+                file,
+                MissingVersions {
+                    soname,
+                    library,
+                    versions,
+                },
+            )| Finding::MissingSymbolVersions {
+                package: package.to_owned(),
+                file,
+                soname,
+                provider: owner(backend, &library),
+                library: root::unresolve(&library),
+                versions,
+            },
+        )
+        .collect())
+}
+
+/// Check for package executables requiring symbol versions their libraries do not define
+pub(super) struct SymbolVersions;
+
+impl Check for SymbolVersions {
+    fn name(&self) -> &str {
+        CheckKind::SymbolVersions.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::Packages]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Executables of the checked packages requiring symbol versions, like GLIBC_2.38, that the library found for one of their dependencies does not define, after a downgrade or a partial upgrade".to_owned(),
+            caveats: vec![
+                "Only the direct dependencies of each executable are read, libraries are checked as the executables of their own package",
+                "Libraries defining no versions at all are skipped, since the loader only warns about them",
+            ],
+            examples: vec![Finding::MissingSymbolVersions {
+                package: "foo".to_owned(),
+                file: PathBuf::from("/usr/bin/foo"),
+                soname: "libc.so.6".to_owned(),
+                library: PathBuf::from("/usr/lib/libc.so.6"),
+                versions: vec!["GLIBC_2.38".to_owned()],
+                provider: Some("glibc".to_owned()),
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        // Versions only go missing after a downgrade or a partial upgrade, reading them doubles the analysis
+        false
+    }
+
+    fn progress_weight(&self) -> u64 {
+        // Each package has many executables, whose version sections and libraries are read
+        20
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        packages: &[String],
+    ) -> anyhow::Result<u64> {
+        Ok(packages.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        let resolver = SonameResolver::default();
+        let log_context = LogContext::current();
+        ctx.packages()
+            .par_iter()
+            .filter(|_| !cancel::requested())
+            .flat_map_iter(|package| {
+                let _log = log_context.clone().with_package(package).enter();
+                let findings =
+                    check_package(ctx.backend(), &resolver, package).unwrap_or_else(|err| {
+                        log::warn!("Failed to check symbol versions of package {package:?}: {err}");
+                        Vec::new()
+                    });
+                ctx.item_done();
+                findings
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_symbol_versions() {
+        for (versions, provider, message) in [
+            (
+                &["GLIBC_2.38"][..],
+                Some("glibc"),
+                r#"File /usr/bin/foo from package "foo" needs version GLIBC_2.38 of libc.so.6, not defined by /usr/lib/libc.so.6 from package "glibc", it fails to start until "glibc" is upgraded or "foo" rebuilt"#,
+            ),
+            (
+                &["GLIBC_2.38", "GLIBC_2.39"][..],
+                None,
+                r#"File /usr/bin/foo from package "foo" needs versions GLIBC_2.38 and GLIBC_2.39 of libc.so.6, not defined by /usr/lib/libc.so.6, owned by no package, it fails to start until the library is removed or "foo" rebuilt"#,
+            ),
+        ] {
+            let finding = Finding::MissingSymbolVersions {
+                package: "foo".to_owned(),
+                file: PathBuf::from("/usr/bin/foo"),
+                soname: "libc.so.6".to_owned(),
+                library: PathBuf::from("/usr/lib/libc.so.6"),
+                versions: versions.iter().map(|v| (*v).to_owned()).collect(),
+                provider: provider.map(ToOwned::to_owned),
+            };
+            assert_eq!(finding.to_string(), message);
+        }
+
+        // Libraries of the running system define the versions its programs need
+        let backend = crate::backend::MemoryBackend::default().with_package(
+            "foo",
+            "1.0-1",
+            true,
+            &["/proc/self/exe"],
+        );
+        assert!(check_package(&backend, &SonameResolver::default(), "foo")
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,192 @@
+//! Symlinks of packages whose chain of links is a packaging error: a loop, too many links, or a link into `/home` or
+//! `/tmp`, which programs following links without a limit may hang on
+
+use std::{
+    collections::HashSet,
+    fs,
+    os::unix::ffi::OsStrExt as _,
+    path::{Component, Path, PathBuf},
+};
+
+use super::{Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    cancel,
+    finding::{ChainProblem, CheckKind, Finding},
+    root,
+};
+
+/// Maximum number of links a packaged symlink goes through, much less than the limit of the kernel
+const MAX_CHAIN_DEPTH: usize = 8;
+
+/// Directories packaged links must not point into, from outside of them
+const USER_DIRS: [&str; 2] = ["/home", "/tmp"];
+
+/// Normalize `.` and `..` components of an absolute path, without following links
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(name) => normalized.push(name),
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+    normalized
+}
+
+/// Follow the chain of a symlink, from its path in the checked system, and find what is wrong with it
+fn chain_problem(link: &Path) -> Option<(ChainProblem, PathBuf)> {
+    let mut visited = HashSet::from([link.to_owned()]);
+    let mut path = link.to_owned();
+    for _ in 0..MAX_CHAIN_DEPTH {
+        // The chain ends at a file that is not a link, or does not exist
+        let target = fs::read_link(root::resolve(&path)).ok()?;
+        let next = normalize(&path.parent().unwrap_or(&path).join(target));
+        if USER_DIRS
+            .iter()
+            .any(|d| next.starts_with(d) && !link.starts_with(d))
+        {
+            return Some((ChainProblem::UserDirectory, next));
+        }
+        if !visited.insert(next.clone()) {
+            return Some((ChainProblem::Circular, next));
+        }
+        path = next;
+    }
+    fs::symlink_metadata(root::resolve(&path))
+        .is_ok_and(|m| m.file_type().is_symlink())
+        .then_some((ChainProblem::TooDeep, path))
+}
+
+/// Check the symlinks of a package
+fn check_package(backend: &dyn PackageBackend, package: &str) -> Vec<Finding> {
+    let mut links = Vec::new();
+    let result = backend.for_each_file(package, &mut |file| {
+        // Directories of file lists end with a slash
+        if !file.path().as_os_str().as_bytes().ends_with(b"/")
+            && fs::symlink_metadata(root::resolve(file.path()))
+                .is_ok_and(|m| m.file_type().is_symlink())
+        {
+            links.push(file.into_path());
+        }
+    });
+    if let Err(err) = result {
+        log::warn!("Failed to list files of package {package:?}: {err}");
+    }
+    links
+        .into_iter()
+        .filter_map(|link| {
+            let (problem, target) = chain_problem(&link)?;
+            Some(Finding::BadSymlinkChain {
+                package: package.to_owned(),
+                link,
+                target,
+                problem,
+            })
+        })
+        .collect()
+}
+
+/// Check for package symlinks in a loop, with a long chain, or pointing into user directories
+pub(super) struct SymlinkChains;
+
+impl Check for SymlinkChains {
+    fn name(&self) -> &str {
+        CheckKind::SymlinkChains.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::Packages]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Symlinks of the checked packages in a loop, going through too many links, or pointing into /home or /tmp, which programs following them fail or hang on".to_owned(),
+            caveats: vec!["Chains ending at a missing file are not reported"],
+            examples: vec![Finding::BadSymlinkChain {
+                package: "foo".to_owned(),
+                link: PathBuf::from("/usr/lib/foo/data"),
+                target: PathBuf::from("/home/builder/foo/data"),
+                problem: ChainProblem::UserDirectory,
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        packages: &[String],
+    ) -> anyhow::Result<u64> {
+        Ok(packages.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for package in ctx.packages().iter().take_while(|_| !cancel::requested()) {
+            findings.extend(check_package(ctx.backend(), package));
+            ctx.item_done();
+        }
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+    use crate::backend::MemoryBackend;
+
+    #[test]
+    fn test_symlink_chains() {
+        assert_eq!(
+            normalize(Path::new("/usr/lib/../bin/./foo")),
+            Path::new("/usr/bin/foo")
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        fs::write(path("file"), "").unwrap();
+        symlink("file", path("ok")).unwrap();
+        symlink("loop-b", path("loop-a")).unwrap();
+        symlink("./loop-a", path("loop-b")).unwrap();
+        symlink("/home/foo/.local/bin/foo", path("home")).unwrap();
+        symlink("missing", path("dangling")).unwrap();
+        for i in 0..MAX_CHAIN_DEPTH {
+            symlink(format!("deep-{}", i + 1), path(&format!("deep-{i}"))).unwrap();
+        }
+        symlink("file", path(&format!("deep-{MAX_CHAIN_DEPTH}"))).unwrap();
+        let names = [
+            "file", "ok", "loop-a", "home", "dangling", "deep-0", "deep-1",
+        ];
+        let files: Vec<_> = names.iter().map(|n| path(n)).collect();
+        let files: Vec<_> = files.iter().map(|f| f.to_str().unwrap()).collect();
+        let backend = MemoryBackend::default().with_package("foo", "1.0-1", true, &files);
+        assert_eq!(
+            check_package(&backend, "foo")
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                format!(
+                    r#"Link {} from package "foo" is part of a symlink loop through {}, opening it fails"#,
+                    path("loop-a").display(),
+                    path("loop-a").display()
+                ),
+                format!(
+                    r#"Link {} from package "foo" points into user directory /home/foo/.local/bin/foo, whose content differs between systems"#,
+                    path("home").display()
+                ),
+                format!(
+                    r#"Link {} from package "foo" goes through too many symlinks, the last one followed being {}"#,
+                    path("deep-0").display(),
+                    path(&format!("deep-{MAX_CHAIN_DEPTH}")).display()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Broken systemd enablement links, and enabled socket and path units activating a missing or broken service
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use anyhow::Context as _;
+use glob::glob;
+use rayon::prelude::*;
+
+use super::{
+    duplicates::owner,
+    login::{drop_ins, find_unit, parse_exec_start, program_exists, template_name, UNIT_DIRS},
+    Check, CheckDoc, Context, Input,
+};
+use crate::{
+    backend::PackageBackend,
+    cancel,
+    finding::{CheckKind, Finding},
+    root,
+    timings::Phase,
+};
+
+/// Directories of user units, by decreasing priority
+const USER_UNIT_DIRS: [&str; 2] = ["/etc/systemd/user", "/usr/lib/systemd/user"];
+
+/// Values systemd parses as true for boolean settings
+const TRUE_VALUES: [&str; 4] = ["yes", "true", "on", "1"];
+
+fn get_sd_enabled_service_links() -> anyhow::Result<Vec<PathBuf>> {
+    let dirs_content = [
+        "/etc/systemd/system/*.target.*",
+        "/etc/systemd/user/*.target.*",
+    ]
+    .map(|p| root::resolve(Path::new(p)).to_str().map(glob));
+
+    let service_links: Vec<PathBuf> = dirs_content
+        .into_iter()
+        .flatten()
+        .flatten()
+        .flatten()
+        .collect::<Result<Vec<PathBuf>, _>>()?
+        .into_iter()
+        .filter_map(|p| fs::read_dir(p.as_path()).ok())
+        .flatten()
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|f| f.is_symlink()))
+        .map(|f| f.path())
+        .collect();
+
+    Ok(service_links)
+}
+
+fn is_valid_link(link: &Path) -> bool {
+    root::link_target(link).is_some_and(|t| fs::metadata(t).is_ok_and(|m| m.is_file()))
+}
+
+/// Get the package owning an enablement link, installed to enable its service by default
+fn link_owner(backend: &dyn PackageBackend, link: &Path) -> Option<String> {
+    backend
+        .owners(&root::unresolve(link))
+        .unwrap_or_else(|err| {
+            log::debug!("Failed to get owner of {link:?}: {err}");
+            Vec::new()
+        })
+        .into_iter()
+        .next()
+}
+
+/// Get the service a socket or path unit activates, from the content of its unit file and drop-ins, in order.
+///
+/// It is the one of the `Service=` setting of sockets and `Unit=` of paths, or the service of the same name, its
+/// template for sockets spawning an instance per connection with `Accept=yes`.
+fn activated_service(name: &str, contents: &[impl AsRef<str>]) -> Option<String> {
+    let (stem, kind) = name.rsplit_once('.')?;
+    let key = match kind {
+        "socket" => "Service",
+        "path" => "Unit",
+        _ => return None,
+    };
+    let mut service = None;
+    let mut accept = false;
+    for (setting, value) in contents
+        .iter()
+        .flat_map(|c| c.as_ref().lines())
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| (k.trim(), v.trim()))
+    {
+        if setting == key {
+            service = (!value.is_empty()).then(|| value.to_owned());
+        } else if setting == "Accept" && kind == "socket" {
+            accept = TRUE_VALUES.contains(&value.to_lowercase().as_str());
+        }
+    }
+    Some(service.unwrap_or_else(|| {
+        if accept {
+            format!("{stem}@.service")
+        } else {
+            format!("{stem}.service")
+        }
+    }))
+}
+
+/// Check that the service activated by an enabled socket or path unit exists, and that its programs exist
+fn check_activated_service(ctx: &Context<'_>, link: &Path) -> Option<Finding> {
+    let unit = link.file_name()?.to_str()?;
+    let dirs: &[&str] = if root::unresolve(link).starts_with(USER_UNIT_DIRS[0]) {
+        &USER_UNIT_DIRS
+    } else {
+        &UNIT_DIRS
+    };
+    let unit_files: Vec<_> = root::link_target(link)
+        .into_iter()
+        .chain(drop_ins(dirs, unit))
+        .collect();
+    let contents: Vec<_> = unit_files
+        .iter()
+        .filter_map(|p| {
+            fs::read_to_string(p)
+                .inspect_err(|err| log::warn!("Failed to read {}: {err}", p.display()))
+                .ok()
+        })
+        .collect();
+    let service = activated_service(unit, &contents)?;
+    let finding = |missing: &Path, program, package| Finding::BrokenActivatedService {
+        unit: unit.to_owned(),
+        link: link.to_owned(),
+        service: service.clone(),
+        missing: missing.to_string_lossy().into_owned(),
+        program,
+        package,
+    };
+    // Instances without their own unit file are instantiated from the template
+    let service_path = [Some(service.clone()), template_name(&service)]
+        .into_iter()
+        .flatten()
+        .map(|s| find_unit(dirs, &s))
+        .find(|p| p.symlink_metadata().is_ok())
+        .unwrap_or_else(|| find_unit(dirs, &service));
+    let Some(file) = root::link_target(&service_path).filter(|p| p.is_file()) else {
+        let target = root::link_target(&service_path).unwrap_or(service_path);
+        return Some(finding(&root::unresolve(&target), false, None));
+    };
+    let mut programs = Vec::new();
+    for path in [file.clone()].into_iter().chain(drop_ins(dirs, &service)) {
+        match fs::read_to_string(&path) {
+            Ok(content) => parse_exec_start(&content, &mut programs),
+            Err(err) => log::warn!("Failed to read {}: {err}", path.display()),
+        }
+    }
+    let missing = programs.iter().find(|p| !program_exists(p))?;
+    Some(finding(
+        Path::new(missing),
+        true,
+        owner(ctx.backend(), &file),
+    ))
+}
+
+/// Check systemd enablement links, and the services activated by the enabled socket and path units
+fn check_service_links(ctx: &Context<'_>, links: &[PathBuf]) -> Vec<Finding> {
+    links
+        .par_iter()
+        .filter(|_| !cancel::requested())
+        .filter_map(|link| {
+            let start = Instant::now();
+            let finding = if is_valid_link(link) {
+                check_activated_service(ctx, link)
+            } else {
+                Some(Finding::BrokenServiceLink {
+                    link: link.to_owned(),
+                    package: link_owner(ctx.backend(), link),
+                })
+            };
+            if let Some(timings) = ctx.timings() {
+                timings.record_phase(Phase::Systemd, start.elapsed());
+            }
+            ctx.item_done();
+            finding
+        })
+        .collect()
+}
+
+/// Check enablement links of systemd services
+#[derive(Default)]
+pub(super) struct ServiceLinks {
+    links: Vec<PathBuf>,
+}
+
+impl Check for ServiceLinks {
+    fn name(&self) -> &str {
+        CheckKind::ServiceLinks.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Enablement links of systemd units pointing to a missing unit, and enabled socket and path units whose activated service is missing or runs a missing program".to_owned(),
+            caveats: vec![
+                "Links created by systemctl enable are owned by no package, disabling the unit removes them",
+                "Programs of ExecStart settings using specifiers, like %i, are not checked",
+            ],
+            examples: vec![
+                Finding::BrokenServiceLink {
+                    link: PathBuf::from("/etc/systemd/system/multi-user.target.wants/foo.service"),
+                    package: None,
+                },
+                Finding::BrokenActivatedService {
+                    unit: "cups.socket".to_owned(),
+                    link: PathBuf::from("/etc/systemd/system/sockets.target.wants/cups.socket"),
+                    service: "cups.service".to_owned(),
+                    missing: "/usr/bin/cupsd".to_owned(),
+                    program: true,
+                    package: Some("cups".to_owned()),
+                },
+            ],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        self.links =
+            get_sd_enabled_service_links().context("Unable to Systemd enabled services")?;
+        Ok(self.links.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        check_service_links(ctx, &self.links)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+    use crate::{backend::MemoryBackend, metadata::Metadata, progress::Progress};
+
+    #[test]
+    fn test_activated_service() {
+        for (name, contents, service) in [
+            (
+                "cups.socket",
+                &["[Socket]\nListenStream=631\n"][..],
+                Some("cups.service"),
+            ),
+            (
+                "sshd.socket",
+                &["[Socket]\nAccept=yes\n", "[Socket]\nAccept=no\n"][..],
+                Some("sshd.service"),
+            ),
+            (
+                "sshd.socket",
+                &["[Socket]\nAccept=Yes\n"][..],
+                Some("sshd@.service"),
+            ),
+            (
+                "foo.path",
+                &["[Path]\nPathChanged=/etc/foo\nUnit=bar.service\n"][..],
+                Some("bar.service"),
+            ),
+            (
+                "foo.path",
+                &["[Path]\nUnit=bar.service\n", "[Path]\nUnit=\n"][..],
+                Some("foo.service"),
+            ),
+            ("foo.service", &[][..], None),
+        ] {
+            assert_eq!(activated_service(name, contents).as_deref(), service);
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("pacman-hooks-test.socket");
+        fs::write(&socket, "[Socket]\nService=pacman-hooks-missing.service\n").unwrap();
+        fs::create_dir(dir.path().join("sockets.target.wants")).unwrap();
+        let link = dir
+            .path()
+            .join("sockets.target.wants/pacman-hooks-test.socket");
+        symlink(&socket, &link).unwrap();
+        let backend = MemoryBackend::default();
+        let progress = Progress::new(1, false).unwrap();
+        let metadata = Metadata::default();
+        let ctx = Context::new(&backend, &[], &metadata, &progress, None);
+        assert_eq!(
+            check_activated_service(&ctx, &link).map(|f| f.to_string()),
+            Some(
+                "Enabled unit pacman-hooks-test.socket activates missing /etc/systemd/system/pacman-hooks-missing.service, install the package providing pacman-hooks-missing.service or disable pacman-hooks-test.socket, its first activation will fail"
+                    .to_owned()
+            )
+        );
+        assert_eq!(
+            Finding::BrokenActivatedService {
+                unit: "cups.socket".to_owned(),
+                link,
+                service: "cups.service".to_owned(),
+                missing: "/usr/bin/cupsd".to_owned(),
+                program: true,
+                package: Some("cups".to_owned()),
+            }
+            .to_string(),
+            r#"Enabled unit cups.socket activates cups.service of package "cups", which runs missing program /usr/bin/cupsd, reinstall the package providing it, its first activation will fail"#
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! Introspection typelibs whose shared library is missing or can not be loaded, which break GNOME Shell extensions
+//! and Python programs using `PyGObject` when they import their namespace
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use glob::glob;
+
+use super::{duplicates::owner, Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    cancel,
+    elf::SonameResolver,
+    finding::{CheckKind, Finding},
+    root,
+};
+
+/// Typelibs of the system, searched by libgirepository
+const TYPELIB_PATTERN: &str = "/usr/lib/girepository-1.0/*.typelib";
+
+/// Start of the header of typelibs
+const TYPELIB_MAGIC: &[u8; 16] = b"GOBJ\nMETADATA\r\n\x1a";
+
+/// Offset of the header field with the offset of the comma separated list of shared libraries
+const SHARED_LIBRARY_FIELD: usize = 52;
+
+/// Get the shared libraries a typelib loads its symbols from, `None` if it is not a typelib.
+///
+/// Libraries are usually sonames, searched in the default search path, but can also be absolute paths.
+fn shared_libraries(data: &[u8]) -> Option<Vec<String>> {
+    if !data.starts_with(TYPELIB_MAGIC) {
+        return None;
+    }
+    let offset = usize::try_from(u32::from_le_bytes(
+        data.get(SHARED_LIBRARY_FIELD..SHARED_LIBRARY_FIELD + 4)?
+            .try_into()
+            .ok()?,
+    ))
+    .ok()?;
+    // Typelibs of namespaces without code, like the ones of D-Bus interfaces, have no library
+    if offset == 0 {
+        return Some(Vec::new());
+    }
+    let libraries = data.get(offset..)?.split(|b| *b == 0).next()?;
+    Some(
+        String::from_utf8_lossy(libraries)
+            .split(',')
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(ToOwned::to_owned)
+            .collect(),
+    )
+}
+
+/// Check that the shared libraries of a typelib, and their dependencies, resolve
+fn check_typelib(
+    ctx: &Context<'_>,
+    resolver: &SonameResolver,
+    path: &Path,
+    data: &[u8],
+) -> Vec<Finding> {
+    let Some(libraries) = shared_libraries(data) else {
+        log::debug!("Not checking {}, which is not a typelib", path.display());
+        return Vec::new();
+    };
+    let missing = resolver.missing_libraries(&libraries);
+    if missing.is_empty() {
+        return Vec::new();
+    }
+    let package = owner(ctx.backend(), path);
+    missing
+        .into_iter()
+        .map(|dependency| Finding::BrokenTypelib {
+            path: root::unresolve(path),
+            dependency,
+            package: package.clone(),
+        })
+        .collect()
+}
+
+/// Check for introspection typelibs with a missing shared library
+#[derive(Default)]
+pub(super) struct IntrospectionTypelibs {
+    typelibs: Vec<PathBuf>,
+}
+
+impl Check for IntrospectionTypelibs {
+    fn name(&self) -> &str {
+        CheckKind::IntrospectionTypelibs.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "GObject introspection typelibs whose shared library, or one of its dependencies, is missing, which programs importing their namespace fail on at runtime".to_owned(),
+            caveats: vec!["Libraries only found in a directory that the program loading the typelib adds to the search path are reported"],
+            examples: vec![Finding::BrokenTypelib {
+                path: PathBuf::from("/usr/lib/girepository-1.0/Foo-1.0.typelib"),
+                dependency: PathBuf::from("libfoo-1.so.0"),
+                package: Some("libfoo".to_owned()),
+            }],
+            ..CheckDoc::default()
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        self.typelibs = glob(&root::resolve(Path::new(TYPELIB_PATTERN)).to_string_lossy())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect();
+        Ok(self.typelibs.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        // Typelibs of a library stack share most libraries, which are resolved once
+        let resolver = SonameResolver::default();
+        let mut findings = Vec::new();
+        for typelib in self.typelibs.iter().take_while(|_| !cancel::requested()) {
+            match fs::read(typelib) {
+                Ok(data) => findings.extend(check_typelib(ctx, &resolver, typelib, &data)),
+                Err(err) => log::warn!("Failed to read {}: {err}", typelib.display()),
+            }
+            ctx.item_done();
+        }
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::MemoryBackend, metadata::Metadata, progress::Progress};
+
+    /// Build a typelib header, with its shared libraries
+    fn typelib(libraries: &str) -> Vec<u8> {
+        let mut data = TYPELIB_MAGIC.to_vec();
+        data.resize(0x70, 0);
+        data[SHARED_LIBRARY_FIELD..SHARED_LIBRARY_FIELD + 4]
+            .copy_from_slice(&0x70_u32.to_le_bytes());
+        data.extend_from_slice(libraries.as_bytes());
+        data.push(0);
+        data
+    }
+
+    #[test]
+    fn test_introspection_typelibs() {
+        assert_eq!(shared_libraries(b"\x7fELF"), None);
+        assert_eq!(
+            shared_libraries(&typelib("libgobject-2.0.so.0,libglib-2.0.so.0")),
+            Some(vec![
+                "libgobject-2.0.so.0".to_owned(),
+                "libglib-2.0.so.0".to_owned()
+            ])
+        );
+        let mut no_library = typelib("");
+        no_library[SHARED_LIBRARY_FIELD..SHARED_LIBRARY_FIELD + 4].fill(0);
+        assert_eq!(shared_libraries(&no_library), Some(Vec::new()));
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("libfoo-1.0.so.0"), "").unwrap();
+        let resolver = SonameResolver::with_library_dirs(vec![dir.path().to_owned()]);
+        let path = PathBuf::from("/usr/lib/girepository-1.0/Foo-1.0.typelib");
+        let backend = MemoryBackend::default().with_package(
+            "foo",
+            "1.0-1",
+            false,
+            &["/usr/lib/girepository-1.0/Foo-1.0.typelib"],
+        );
+        let progress = Progress::new(1, false).unwrap();
+        let metadata = Metadata::default();
+        let ctx = Context::new(&backend, &[], &metadata, &progress, None);
+        assert!(check_typelib(&ctx, &resolver, &path, &typelib("libfoo-1.0.so.0")).is_empty());
+        assert_eq!(
+            check_typelib(
+                &ctx,
+                &resolver,
+                &path,
+                &typelib("libfoo-1.0.so.0,libmissing-foo-1.0.so.0")
+            )
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+            [
+                r#"Introspection typelib /usr/lib/girepository-1.0/Foo-1.0.typelib from package "foo" needs missing library libmissing-foo-1.0.so.0, programs importing its namespace fail"#
+            ]
+        );
+    }
+}
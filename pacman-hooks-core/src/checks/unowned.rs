@@ -0,0 +1,365 @@
+//! Files owned by no package that appeared during a transaction, usually created by scriptlets or installers,
+//! from snapshots of the watched directories taken before the transaction
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs,
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context as _;
+
+use super::{Check, CheckDoc, Context, Input};
+use crate::{
+    backend::PackageBackend,
+    baseline::STATE_DIR,
+    cancel,
+    config::Config,
+    finding::{CheckKind, Finding},
+    root,
+};
+
+/// Directories watched unless configured otherwise
+const DEFAULT_DIRS: [&str; 3] = ["/etc", "/usr/bin", "/opt"];
+
+/// File name in the state directory of the files of the watched directories, before the running transaction
+const SNAPSHOT_FILENAME: &str = "unowned-files.snapshot";
+
+/// File name in the state directory of the unowned files that appeared during transactions,
+/// with the Unix time they were found at
+const RECORD_FILENAME: &str = "unowned-files";
+
+/// Get files and links of directory trees of the checked system, without following links.
+///
+/// Paths that are not valid UTF-8 or contain a newline are skipped, since state files store a path per line.
+fn list_files(dirs: &[PathBuf]) -> BTreeSet<PathBuf> {
+    let mut files = BTreeSet::new();
+    let mut pending: Vec<_> = dirs.iter().map(|d| root::resolve(d)).collect();
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => pending.push(entry.path()),
+                Ok(_) => {
+                    let path = root::unresolve(&entry.path());
+                    if path.to_str().is_some_and(|p| !p.contains('\n')) {
+                        files.insert(path);
+                    }
+                }
+                Err(err) => log::debug!("Failed to get type of {:?}: {err}", entry.path()),
+            }
+        }
+    }
+    files
+}
+
+/// Write paths, one per line, replacing the file at `path`
+fn write_paths(path: &Path, lines: impl Iterator<Item = String>) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    let mut file = io::BufWriter::new(fs::File::create(&tmp_path)?);
+    for line in lines {
+        writeln!(file, "{line}")?;
+    }
+    file.into_inner().map_err(io::IntoInnerError::into_error)?;
+    fs::rename(tmp_path, path)
+}
+
+/// Read the lines of a state file, `None` if it does not exist
+fn read_lines(path: &Path) -> io::Result<Option<Vec<String>>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content.lines().map(ToOwned::to_owned).collect())),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Get the directories to watch for unowned files, from the configuration
+fn watched_dirs(config: &Config) -> Vec<PathBuf> {
+    config
+        .unowned_files_dirs()
+        .map_or_else(|| DEFAULT_DIRS.map(PathBuf::from).to_vec(), <[_]>::to_vec)
+}
+
+/// Save the files of the watched directories before a transaction, and get their number.
+///
+/// A snapshot no check compared with yet, like after a transaction only installing packages, where system wide checks
+/// do not run, is kept so that the files appearing during that transaction are still found, and `None` is returned.
+fn save_snapshot(state_dir: &Path, dirs: &[PathBuf]) -> anyhow::Result<Option<usize>> {
+    let path = state_dir.join(SNAPSHOT_FILENAME);
+    if path.exists() {
+        return Ok(None);
+    }
+    let files = list_files(dirs);
+    write_paths(
+        &path,
+        files.iter().map(|f| f.to_string_lossy().into_owned()),
+    )
+    .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(Some(files.len()))
+}
+
+/// Save the files of the directories watched for unowned files before a transaction, and get their number,
+/// `None` if a previous snapshot is kept
+///
+/// # Errors
+///
+/// Fails if the snapshot can not be written
+pub fn snapshot_unowned_dirs(config: &Config) -> anyhow::Result<Option<usize>> {
+    save_snapshot(Path::new(STATE_DIR), &watched_dirs(config))
+}
+
+/// Parse the record of unowned files, by path
+fn parse_record(lines: &[String]) -> BTreeMap<PathBuf, u64> {
+    lines
+        .iter()
+        .filter_map(|l| {
+            let (time, path) = l.split_once('\t')?;
+            Some((PathBuf::from(path), time.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Get the files of all installed packages.
+///
+/// Packages whose file list fails to be read are skipped, their files then look unowned until a later run.
+fn owned_files(backend: &dyn PackageBackend) -> anyhow::Result<HashSet<PathBuf>> {
+    let mut owned = HashSet::new();
+    for package in backend.installed_packages()? {
+        if let Err(err) = backend.for_each_file(&package, &mut |f| {
+            owned.insert(f.into_path());
+        }) {
+            log::warn!("Failed to list files of package {package:?}: {err:#}");
+        }
+    }
+    Ok(owned)
+}
+
+/// Check that files of the watched directories that appeared during transactions are owned by a package
+pub(super) struct UnownedFiles {
+    dirs: Vec<PathBuf>,
+    state_dir: PathBuf,
+    /// Recorded files that still exist, and files that appeared since the snapshot, with the Unix time they appeared
+    /// at, whether owned by a package or not
+    candidates: BTreeMap<PathBuf, u64>,
+    /// Whether the candidates include files compared with a snapshot, which is removed once they are recorded
+    compared: bool,
+}
+
+impl UnownedFiles {
+    /// Watch `dirs`, instead of the default ones
+    pub(super) fn new(dirs: Vec<PathBuf>) -> Self {
+        Self {
+            dirs,
+            state_dir: PathBuf::from(STATE_DIR),
+            candidates: BTreeMap::new(),
+            compared: false,
+        }
+    }
+
+    /// Read the recorded files that still exist, and add the files that appeared since the snapshot, if there is one,
+    /// without modifying the state files
+    fn load_candidates(&mut self, now: u64) -> anyhow::Result<()> {
+        let record_path = self.state_dir.join(RECORD_FILENAME);
+        let mut candidates = parse_record(&read_lines(&record_path)?.unwrap_or_default());
+        // Removed files may be created again later, by another package
+        candidates.retain(|f, _| fs::symlink_metadata(root::resolve(f)).is_ok());
+        let snapshot_path = self.state_dir.join(SNAPSHOT_FILENAME);
+        // Not after a transaction, or without the pre transaction hook, if there is no snapshot
+        self.compared = match read_lines(&snapshot_path)? {
+            Some(snapshot) => {
+                let before: HashSet<_> = snapshot.iter().map(PathBuf::from).collect();
+                for file in list_files(&self.dirs) {
+                    if !before.contains(&file) {
+                        candidates.entry(file).or_insert(now);
+                    }
+                }
+                true
+            }
+            None => false,
+        };
+        self.candidates = candidates;
+        Ok(())
+    }
+
+    /// Save the record of unowned files, and remove the snapshot they were compared with, if any
+    fn save_record(&self, unowned: &BTreeMap<&PathBuf, u64>) {
+        let record_path = self.state_dir.join(RECORD_FILENAME);
+        // Each transaction is compared to its own snapshot, the record is kept for later runs
+        let saved = write_paths(
+            &record_path,
+            unowned
+                .iter()
+                .map(|(f, t)| format!("{t}\t{}", f.to_string_lossy())),
+        )
+        .and_then(|()| {
+            if self.compared {
+                fs::remove_file(self.state_dir.join(SNAPSHOT_FILENAME))
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(err) = saved {
+            log::warn!("Failed to update {}: {err}", record_path.display());
+        }
+    }
+}
+
+impl Default for UnownedFiles {
+    fn default() -> Self {
+        Self::new(DEFAULT_DIRS.map(PathBuf::from).to_vec())
+    }
+}
+
+impl Check for UnownedFiles {
+    fn name(&self) -> &str {
+        CheckKind::UnownedFiles.name()
+    }
+
+    fn inputs(&self) -> &[Input] {
+        &[Input::System]
+    }
+
+    fn doc(&self) -> CheckDoc {
+        CheckDoc {
+            detects: "Files owned by no package that appeared during a transaction in the watched directories, usually created by scriptlets or misbehaving installers".to_owned(),
+            caveats: vec![
+                "Scriptlets also create files on purpose, like keys and caches",
+                "Files are only found after transactions that ran the snapshot hook before them",
+            ],
+            config_keys: vec!["UnownedFilesDirs"],
+            examples: vec![Finding::UnownedFile {
+                path: PathBuf::from("/usr/bin/foo-helper"),
+                appeared: 1_700_000_000,
+            }],
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        _backend: &dyn PackageBackend,
+        _packages: &[String],
+    ) -> anyhow::Result<u64> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        // State files are only updated by the run, dry runs leave them untouched
+        self.load_candidates(now)?;
+        Ok(self.candidates.len() as u64)
+    }
+
+    fn run(&self, ctx: &Context<'_>) -> Vec<Finding> {
+        if self.candidates.is_empty() && !self.compared {
+            return Vec::new();
+        }
+        // Packages installed or upgraded since a file was recorded may own it now
+        let owned = if self.candidates.is_empty() {
+            HashSet::new()
+        } else {
+            match owned_files(ctx.backend()) {
+                Ok(owned) => owned,
+                Err(err) => {
+                    log::error!("Failed to list files of installed packages: {err:#}");
+                    return Vec::new();
+                }
+            }
+        };
+        let unowned: BTreeMap<_, _> = self
+            .candidates
+            .iter()
+            .take_while(|_| !cancel::requested())
+            .filter(|(path, _)| {
+                ctx.item_done();
+                !owned.contains(*path)
+            })
+            .map(|(path, appeared)| (path, *appeared))
+            .collect();
+        if cancel::requested() {
+            return Vec::new();
+        }
+        self.save_record(&unowned);
+        unowned
+            .into_iter()
+            .map(|(path, appeared)| Finding::UnownedFile {
+                path: path.to_owned(),
+                appeared,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::MemoryBackend, metadata::Metadata, progress::Progress};
+
+    #[test]
+    fn test_unowned_files() {
+        let state_dir = tempfile::tempdir().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name| dir.path().join(name);
+        fs::create_dir(path("foo")).unwrap();
+        fs::write(path("old.conf"), "").unwrap();
+        let dirs = vec![dir.path().to_owned()];
+        assert_eq!(save_snapshot(state_dir.path(), &dirs).unwrap(), Some(1));
+        // Not compared yet
+        assert_eq!(save_snapshot(state_dir.path(), &dirs).unwrap(), None);
+
+        for name in ["foo/owned.conf", "foo/generated.key"] {
+            fs::write(path(name), "").unwrap();
+        }
+        let owned = path("foo/owned.conf");
+        let backend = MemoryBackend::default().with_package(
+            "foo",
+            "1.0-1",
+            false,
+            &[owned.to_str().unwrap()],
+        );
+        let mut check = UnownedFiles {
+            dirs: dirs.clone(),
+            state_dir: state_dir.path().to_owned(),
+            candidates: BTreeMap::new(),
+            compared: false,
+        };
+        let progress = Progress::new(1, false).unwrap();
+        let run = |unowned: &UnownedFiles, packages: &MemoryBackend| {
+            unowned
+                .run(&Context::new(
+                    packages,
+                    &[],
+                    &Metadata::default(),
+                    &progress,
+                    None,
+                ))
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+        check.load_candidates(1_709_662_321).unwrap();
+        assert_eq!(check.candidates.len(), 2);
+        // Only runs update the state
+        assert_eq!(check.prepare(&backend, &[]).unwrap(), 2);
+        assert!(state_dir.path().join(SNAPSHOT_FILENAME).exists());
+        assert!(!state_dir.path().join(RECORD_FILENAME).exists());
+        let message = format!(
+            "File {}, owned by no package, appeared during the transaction of 2024-03-05 18:12:01 UTC",
+            path("foo/generated.key").display()
+        );
+        check.load_candidates(1_709_662_321).unwrap();
+        assert_eq!(run(&check, &backend), std::slice::from_ref(&message));
+        assert!(!state_dir.path().join(SNAPSHOT_FILENAME).exists());
+
+        // Later runs report recorded files while they exist, with the time they appeared at
+        assert_eq!(check.prepare(&backend, &[]).unwrap(), 1);
+        assert_eq!(run(&check, &backend), [message]);
+        // Until a package owns them
+        let key = path("foo/generated.key");
+        let backend = backend.with_package("bar", "1.0-1", false, &[key.to_str().unwrap()]);
+        check.prepare(&backend, &[]).unwrap();
+        assert!(run(&check, &backend).is_empty());
+        assert_eq!(check.prepare(&backend, &[]).unwrap(), 0);
+    }
+}
@@ -0,0 +1,376 @@
+//! Packages broken by libraries going away: foreign packages an upgrade will break, predicted before it happens,
+//! installed packages broken by a package removal, and the packages a change of some packages may impact
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use rayon::prelude::*;
+
+use super::missing_dependencies::{explain_missing, is_known, BLACKLISTED_EXE_DIRS};
+use crate::{
+    backend::{for_each_package_executable_file, PackageBackend},
+    elf,
+    filesdb::FilesDb,
+    finding::Finding,
+    kb::KnowledgeBase,
+    metadata::Metadata,
+    root,
+};
+
+/// Directory of libraries looked up by soname, relative to the root
+const LIBRARY_DIR: &str = "usr/lib/";
+
+/// Get the name of a shared library directly in the library directory, from a path relative to the root
+fn library_name(path: &str) -> Option<&str> {
+    path.strip_prefix(LIBRARY_DIR).filter(|n| {
+        !n.contains('/')
+            && (n.contains(".so.") || Path::new(n).extension().is_some_and(|e| e == "so"))
+    })
+}
+
+/// Get libraries removed or renamed by a soname bump, and the upgraded package that provided them,
+/// from installed and new file lists of the upgraded packages.
+///
+/// Packages without a new file list are assumed to keep their libraries.
+fn removed_libraries(
+    installed: &HashMap<String, Vec<String>>,
+    upgraded: &HashMap<String, Vec<String>>,
+) -> HashMap<String, String> {
+    // Libraries may move to another upgraded package
+    let kept: HashSet<_> = upgraded
+        .values()
+        .flatten()
+        .filter_map(|f| library_name(f))
+        .collect();
+    installed
+        .iter()
+        .filter(|(package, _)| upgraded.contains_key(*package))
+        .flat_map(|(package, files)| {
+            files
+                .iter()
+                .filter_map(|f| library_name(f))
+                .filter(|l| !kept.contains(l))
+                .map(|l| (l.to_owned(), package.to_owned()))
+        })
+        .collect()
+}
+
+/// Whether an executable of a package, outside of the directories excluded from checks, directly depends on one of
+/// `libraries`
+fn uses_libraries(backend: &dyn PackageBackend, package: &str, libraries: &HashSet<&str>) -> bool {
+    let mut files = Vec::new();
+    if let Err(err) = for_each_package_executable_file(backend, package, |f| {
+        if !BLACKLISTED_EXE_DIRS.iter().any(|d| f.path().starts_with(d)) {
+            files.push(f.into_path());
+        }
+    }) {
+        log::error!("Failed to get package executable files for {package:?}: {err}");
+        return false;
+    }
+    files.iter().any(|f| {
+        elf::needed_libraries(&root::resolve(f))
+            .is_ok_and(|needed| needed.iter().any(|n| libraries.contains(n.as_str())))
+    })
+}
+
+/// Get files of installed packages, with paths relative to the root, skipping packages not installed
+fn installed_files(
+    backend: &dyn PackageBackend,
+    packages: &[String],
+) -> anyhow::Result<HashMap<String, Vec<String>>> {
+    let mut installed = HashMap::new();
+    for package in packages {
+        if backend.version(package)?.is_none() {
+            continue;
+        }
+        let mut files = Vec::new();
+        backend.for_each_file(package, &mut |f| {
+            if let Some(path) = f.path().strip_prefix("/").ok().and_then(Path::to_str) {
+                files.push(path.to_owned());
+            }
+        })?;
+        installed.insert(package.to_owned(), files);
+    }
+    Ok(installed)
+}
+
+/// Find executables of a package that directly depend on removed libraries, reported by `finding`
+/// from the package, file, removed library and the package that provided it
+fn package_breakage<F>(
+    backend: &dyn PackageBackend,
+    kb: &KnowledgeBase,
+    removed: &HashMap<String, String>,
+    package: &str,
+    finding: F,
+) -> Vec<Finding>
+where
+    F: Fn(&Arc<String>, &Arc<PathBuf>, String, &str) -> Finding,
+{
+    let mut files = Vec::new();
+    let mut bundled = HashSet::new();
+    if let Err(err) = backend.for_each_file(package, &mut |f| {
+        if let Some(name) = f.path().file_name().and_then(|n| n.to_str()) {
+            bundled.insert(name.to_owned());
+        }
+    }) {
+        log::error!("Failed to get package files for {package:?}: {err}");
+        return Vec::new();
+    }
+    if let Err(err) = for_each_package_executable_file(backend, package, |f| {
+        if !BLACKLISTED_EXE_DIRS.iter().any(|d| f.path().starts_with(d)) {
+            files.push(f.into_path());
+        }
+    }) {
+        log::error!("Failed to get package executable files for {package:?}: {err}");
+        return Vec::new();
+    }
+
+    let package = Arc::new(package.to_owned());
+    let mut findings = Vec::new();
+    for file in files {
+        let needed = elf::needed_libraries(&root::resolve(&file)).unwrap_or_else(|err| {
+            log::debug!("Failed to analyze {file:?}: {err}");
+            Vec::new()
+        });
+        let file = Arc::new(file);
+        for soname in needed {
+            let Some(provider) = removed.get(&soname) else {
+                continue;
+            };
+            // Packages shipping a library of the same name likely load their own copy
+            if bundled.contains(&soname) || is_known(kb, &package, &file, Path::new(&soname)) {
+                continue;
+            }
+            findings.push(finding(&package, &file, soname, provider));
+        }
+    }
+    findings
+}
+
+/// Predict which executables of foreign packages will miss a library after upgrading packages,
+/// by comparing installed file lists of the upgrade targets with the ones of the files database.
+///
+/// Only direct dependencies are considered, and foreign upgrade targets are assumed to be rebuilt.
+///
+/// # Errors
+///
+/// Fails if the installed file lists or the files database can not be read
+pub fn predict_breakage(
+    backend: &dyn PackageBackend,
+    files_db: &FilesDb,
+    targets: &[String],
+) -> anyhow::Result<Vec<Finding>> {
+    let installed = installed_files(backend, targets)?;
+    let upgraded = files_db.package_files(targets)?;
+    let removed = removed_libraries(&installed, &upgraded);
+    if removed.is_empty() {
+        return Ok(Vec::new());
+    }
+    log::debug!(
+        "Libraries removed by the upgrade: {}",
+        removed.keys().cloned().collect::<Vec<_>>().join(", ")
+    );
+
+    let kb = KnowledgeBase::load();
+    let packages: Vec<_> = backend
+        .foreign_packages()?
+        .into_iter()
+        .filter(|p| !targets.contains(p))
+        .collect();
+    Ok(packages
+        .par_iter()
+        .flat_map_iter(|p| {
+            package_breakage(
+                backend,
+                &kb,
+                &removed,
+                p,
+                |package, file, soname, upgrade| Finding::UpgradeBreakage {
+                    package: Arc::clone(package),
+                    file: Arc::clone(file),
+                    dependency: soname,
+                    upgrade: upgrade.to_owned(),
+                },
+            )
+        })
+        .collect())
+}
+
+/// Get the installed packages whose executables directly depend on a library of `changed` packages, like upgraded
+/// or removed ones, and the installed changed packages themselves, sorted.
+///
+/// Libraries of changed packages that are not installed are looked up in the files database, if any.
+///
+/// # Errors
+///
+/// Fails if the installed file lists or the files database can not be read
+pub fn impacted_packages(
+    backend: &dyn PackageBackend,
+    files_db: Option<&FilesDb>,
+    changed: &[String],
+) -> anyhow::Result<Vec<String>> {
+    let mut files = installed_files(backend, changed)?;
+    let missing: Vec<_> = changed
+        .iter()
+        .filter(|p| !files.contains_key(*p))
+        .cloned()
+        .collect();
+    if !missing.is_empty() {
+        if let Some(files_db) = files_db {
+            files.extend(files_db.package_files(&missing)?);
+        } else {
+            log::warn!(
+                "No files database to find libraries of packages not installed: {}",
+                missing.join(", ")
+            );
+        }
+    }
+    let libraries: HashSet<_> = files
+        .values()
+        .flatten()
+        .filter_map(|f| library_name(f))
+        .collect();
+    log::debug!(
+        "Libraries of the changed packages: {}",
+        libraries.iter().copied().collect::<Vec<_>>().join(", ")
+    );
+    let mut impacted: Vec<_> = backend
+        .installed_packages()?
+        .into_par_iter()
+        .filter(|p| {
+            changed.contains(p) || (!libraries.is_empty() && uses_libraries(backend, p, &libraries))
+        })
+        .collect();
+    impacted.sort_unstable();
+    Ok(impacted)
+}
+
+/// Find executables of installed packages, foreign or not, that directly depend on a library
+/// of packages that were just removed, from the file lists of the files database.
+///
+/// Libraries still installed, by another package, are not considered removed.
+///
+/// # Errors
+///
+/// Fails if the files database or the package database can not be read
+pub fn removal_breakage(
+    backend: &dyn PackageBackend,
+    files_db: &FilesDb,
+    removed: &[String],
+) -> anyhow::Result<Vec<Finding>> {
+    let libraries: HashMap<_, _> = files_db
+        .package_files(removed)?
+        .into_iter()
+        .flat_map(|(package, files)| {
+            files
+                .iter()
+                .filter_map(|f| library_name(f).map(ToOwned::to_owned))
+                .filter(|l| !root::resolve(&Path::new("/").join(LIBRARY_DIR).join(l)).exists())
+                .map(|l| (l, package.clone()))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    if libraries.is_empty() {
+        return Ok(Vec::new());
+    }
+    log::debug!(
+        "Libraries removed with their packages: {}",
+        libraries.keys().cloned().collect::<Vec<_>>().join(", ")
+    );
+
+    let kb = KnowledgeBase::load();
+    let packages: Vec<_> = backend
+        .installed_packages()?
+        .into_iter()
+        .filter(|p| !removed.contains(p))
+        .collect();
+    let findings = packages
+        .par_iter()
+        .flat_map_iter(|p| {
+            package_breakage(backend, &kb, &libraries, p, |package, file, soname, _| {
+                Finding::MissingDependency {
+                    package: Arc::clone(package),
+                    file: Arc::clone(file),
+                    dependency: PathBuf::from(soname),
+                    origin: None,
+                }
+            })
+        })
+        .collect();
+    Ok(explain_missing(&Metadata::default(), findings))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt as _};
+
+    use super::*;
+    use crate::backend::MemoryBackend;
+
+    fn files(list: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        list.iter()
+            .map(|(p, f)| ((*p).to_owned(), f.iter().map(|s| (*s).to_owned()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_removed_libraries() {
+        let installed = files(&[
+            (
+                "icu",
+                &[
+                    "usr/lib/libicuuc.so",
+                    "usr/lib/libicuuc.so.74",
+                    "usr/bin/uconv",
+                ],
+            ),
+            ("libfoo", &["usr/lib/libfoo.so.1", "usr/lib/foo/plugin.so"]),
+            ("notinrepo", &["usr/lib/libbar.so.1"]),
+        ]);
+        let upgraded = files(&[
+            (
+                "icu",
+                &[
+                    "usr/lib/libicuuc.so",
+                    "usr/lib/libicuuc.so.75",
+                    "usr/bin/uconv",
+                ],
+            ),
+            // Library moved to a new split package
+            ("libfoo-split", &["usr/lib/libfoo.so.1"]),
+            ("libfoo", &["usr/lib/foo/plugin.so"]),
+        ]);
+        assert_eq!(
+            removed_libraries(&installed, &upgraded),
+            HashMap::from([("libicuuc.so.74".to_owned(), "icu".to_owned())])
+        );
+    }
+
+    #[test]
+    fn test_impacted_packages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_owned();
+        // Test executables depend on libc.so.6
+        fs::write(path("foo"), fs::read("/proc/self/exe").unwrap()).unwrap();
+        fs::write(path("bar"), "#!/bin/sh\n").unwrap();
+        for name in ["foo", "bar"] {
+            fs::set_permissions(path(name), fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let backend = MemoryBackend::default()
+            .with_package("glibc", "2.40-1", false, &["/usr/lib/libc.so.6"])
+            .with_package("foo", "1.0-1", true, &[&path("foo")])
+            .with_package("bar", "1.0-1", true, &[&path("bar")])
+            .with_package("icu", "75.1-1", false, &["/usr/lib/libicuuc.so.75"]);
+        assert_eq!(
+            impacted_packages(&backend, None, &["glibc".to_owned(), "removed".to_owned()]).unwrap(),
+            ["foo", "glibc"]
+        );
+        assert_eq!(
+            impacted_packages(&backend, None, &["icu".to_owned()]).unwrap(),
+            ["icu"]
+        );
+    }
+}
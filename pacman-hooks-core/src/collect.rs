@@ -0,0 +1,138 @@
+//! Reports of full scans sent to a central collection service, to track broken packages across machines
+
+use std::{fs, path::Path, thread, time::Duration};
+
+use anyhow::Context as _;
+
+use crate::{
+    cancel, http,
+    json::{ToJson, Value},
+    reports::Report,
+    root,
+    schema::SCHEMA_VERSION,
+};
+
+/// File with the unique id of the checked system, set by systemd
+const MACHINE_ID_PATH: &str = "/etc/machine-id";
+
+/// Maximum number of upload attempts of a report, when retrying
+const MAX_ATTEMPTS: u32 = 4;
+
+/// Maximum time of an upload attempt, when retrying
+const UPLOAD_TIMEOUT: Duration = Duration::from_mins(1);
+
+/// Maximum time of the single upload attempt of a run that must not be held up, like the pacman hook
+const QUICK_UPLOAD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Delay before retrying a failed upload, doubled after each attempt
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Get the unique id of the checked system, stable across reboots and hostname changes
+fn host_id() -> anyhow::Result<String> {
+    let path = root::resolve(Path::new(MACHINE_ID_PATH));
+    let id = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .trim()
+        .to_owned();
+    if id.is_empty() {
+        anyhow::bail!("Empty machine id in {}", path.display());
+    }
+    Ok(id)
+}
+
+/// Build the uploaded document: the report, with the host it comes from and the schema version of its findings
+fn payload(host_id: &str, report: &Report) -> Value {
+    let mut fields = vec![
+        ("host_id".to_owned(), host_id.into()),
+        ("schema_version".to_owned(), SCHEMA_VERSION.into()),
+    ];
+    if let Value::Object(report_fields) = report.to_json() {
+        fields.extend(report_fields);
+    }
+    Value::Object(fields)
+}
+
+/// Whether an upload that got HTTP status `status` may succeed later
+fn is_transient(status: u16) -> bool {
+    status == 408 || status == 429 || status >= 500
+}
+
+/// Delay before upload attempt number `attempt`, counting from 1
+fn retry_delay(attempt: u32) -> Duration {
+    FIRST_RETRY_DELAY * 2_u32.pow(attempt.saturating_sub(2))
+}
+
+/// Send `report` to the collection service at `url`, authenticated with `token` if set.
+///
+/// With `retry`, failed connections, server errors and rate limiting are retried with an exponential backoff, other
+/// HTTP errors, like an invalid token, are not. Without it, a single short attempt is made.
+///
+/// # Errors
+///
+/// Fails if the host identifier can not be read, or the report can not be sent
+pub fn send(url: &str, token: Option<&str>, report: &Report, retry: bool) -> anyhow::Result<()> {
+    let body = payload(&host_id()?, report).to_string();
+    let (max_attempts, timeout) = if retry {
+        (MAX_ATTEMPTS, UPLOAD_TIMEOUT)
+    } else {
+        (1, QUICK_UPLOAD_TIMEOUT)
+    };
+    let mut attempt = 1;
+    loop {
+        let error = match http::post_json(url, token, &body, timeout) {
+            Ok(status) if (200..300).contains(&status) => return Ok(()),
+            Ok(status) if is_transient(status) => anyhow::anyhow!("HTTP status {status}"),
+            Ok(status) => anyhow::bail!("Report rejected with HTTP status {status}"),
+            Err(err) => err,
+        };
+        if attempt == max_attempts || cancel::requested() {
+            return Err(error.context(format!("Failed to send report after {attempt} attempt(s)")));
+        }
+        attempt += 1;
+        let delay = retry_delay(attempt);
+        log::warn!(
+            "Failed to send report: {error:#}, retrying in {}s",
+            delay.as_secs()
+        );
+        thread::sleep(delay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::finding::Finding;
+
+    #[test]
+    fn test_payload() {
+        let report = Report::new(
+            &[Finding::PacnewFile {
+                path: PathBuf::from("/etc/foo.conf"),
+            }],
+            vec!["pacnew-files".to_owned()],
+            "pacman-hooks check all".to_owned(),
+        );
+        let payload = payload("0123456789abcdef", &report);
+        assert_eq!(
+            payload.get("host_id").and_then(Value::as_str),
+            Some("0123456789abcdef")
+        );
+        assert!(payload.get("schema_version").is_some());
+        assert_eq!(
+            payload
+                .get("findings")
+                .and_then(Value::as_array)
+                .map(<[_]>::len),
+            Some(1)
+        );
+
+        assert!(is_transient(503));
+        assert!(!is_transient(401));
+        assert_eq!(
+            (2..=MAX_ATTEMPTS).map(retry_delay).collect::<Vec<_>>(),
+            [2, 4, 8].map(Duration::from_secs)
+        );
+    }
+}
@@ -0,0 +1,477 @@
+//! Configuration file, in the same format as pacman.conf
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    error::{Classify as _, ErrorKind},
+    finding::Severity,
+    overrides::SeverityOverride,
+    rebuild,
+};
+
+/// Configuration file path
+pub(crate) const CONFIG_PATH: &str = "/etc/pacman-hooks.conf";
+
+/// Section of the configuration file for this hook
+const SECTION: &str = "check-broken-packages";
+
+/// Section name prefix for custom checks
+const CUSTOM_CHECK_SECTION_PREFIX: &str = "check:";
+
+/// Default user to run analysis child processes as, when running as root
+pub const DEFAULT_USER: &str = "nobody";
+
+/// Hook configuration
+pub struct Config {
+    /// User to run analysis child processes as when running as root, `None` to keep root privileges
+    user: Option<String>,
+    /// Checks to enable (`true`) or disable, in order
+    check_toggles: Vec<(String, bool)>,
+    /// Checks to run and report first, in order
+    check_order: Vec<String>,
+    custom_checks: Vec<CustomCheck>,
+    /// Command template to rebuild broken packages, with a `{packages}` placeholder
+    rebuild_command: Option<String>,
+    /// Script file to write the rebuild command to
+    rebuild_script: Option<PathBuf>,
+    /// Suggest downgrading upgraded libraries from the package cache
+    suggest_downgrade: bool,
+    /// Never access the network
+    offline: bool,
+    /// Timeout of the reachability probe run before accessing the network, `None` to not probe
+    network_probe_timeout: Option<Duration>,
+    /// Root directories of containers checked with `--containers`, besides systemd-nspawn machines
+    container_roots: Vec<PathBuf>,
+    /// Convention symlinks to check, instead of the default ones
+    convention_links: Option<Vec<PathBuf>>,
+    /// Directories watched for unowned files appearing during transactions, instead of the default ones
+    unowned_files_dirs: Option<Vec<PathBuf>>,
+    /// Severity overrides, the last matching one applies
+    severity_overrides: Vec<SeverityOverride>,
+    /// URL of the service full scan reports are sent to
+    collect_url: Option<String>,
+    /// File with the token authenticating to the collection service
+    collect_token_file: Option<PathBuf>,
+}
+
+/// Output format of a custom check command
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// One finding message per line
+    Lines,
+    /// JSON array of objects with `message` and optional `severity` fields
+    Json,
+}
+
+/// Custom check running an external command
+pub struct CustomCheck {
+    name: String,
+    /// Program and arguments
+    command: Vec<String>,
+    output: OutputFormat,
+    /// Severity of findings that do not set one
+    severity: Severity,
+}
+
+impl CustomCheck {
+    fn parse_option(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        match (key, value) {
+            ("Command", command) => {
+                self.command = command.split_whitespace().map(ToOwned::to_owned).collect();
+            }
+            ("Output", "lines") => self.output = OutputFormat::Lines,
+            ("Output", "json") => self.output = OutputFormat::Json,
+            ("Output", output) => anyhow::bail!("Invalid output format {output:?}"),
+            ("Severity", severity) => self.severity = severity.parse()?,
+            (key, _) => anyhow::bail!("Unknown custom check option {key:?}"),
+        }
+        Ok(())
+    }
+
+    /// Check name
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Program and arguments
+    #[must_use]
+    pub fn command(&self) -> &[String] {
+        &self.command
+    }
+
+    /// Output format of the command
+    #[must_use]
+    pub fn output(&self) -> OutputFormat {
+        self.output
+    }
+
+    /// Severity of findings that do not set one
+    #[must_use]
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+}
+
+/// Section of the configuration file being parsed
+enum Section {
+    /// Options of this hook
+    Hook,
+    /// Custom check definition, index in custom checks
+    CustomCheck(usize),
+    /// Section for something else
+    Other,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            user: Some(DEFAULT_USER.to_owned()),
+            check_toggles: Vec::new(),
+            check_order: Vec::new(),
+            custom_checks: Vec::new(),
+            rebuild_command: None,
+            rebuild_script: None,
+            suggest_downgrade: false,
+            offline: false,
+            network_probe_timeout: None,
+            container_roots: Vec::new(),
+            convention_links: None,
+            unowned_files_dirs: None,
+            severity_overrides: Vec::new(),
+            collect_url: None,
+            collect_token_file: None,
+        }
+    }
+}
+
+impl Config {
+    /// Load configuration, which is the default one if the file does not exist
+    ///
+    /// # Errors
+    ///
+    /// Fails if the configuration file can not be read or is invalid
+    pub fn load() -> anyhow::Result<Self> {
+        Self::load_from(Path::new(CONFIG_PATH))
+    }
+
+    /// Load configuration from `path`, which is the default one if the file does not exist
+    pub(crate) fn load_from(path: &Path) -> anyhow::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => Self::parse(&content).classify(ErrorKind::Config),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn parse(content: &str) -> anyhow::Result<Self> {
+        let mut config = Self::default();
+        let mut section = Section::Other;
+        for (i, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = if name == SECTION {
+                    Section::Hook
+                } else if let Some(check) = name.strip_prefix(CUSTOM_CHECK_SECTION_PREFIX) {
+                    if check.is_empty() || config.custom_checks.iter().any(|c| c.name == check) {
+                        anyhow::bail!("Line {}: invalid or duplicate check name {check:?}", i + 1);
+                    }
+                    config.custom_checks.push(CustomCheck {
+                        name: check.to_owned(),
+                        command: Vec::new(),
+                        output: OutputFormat::Lines,
+                        severity: Severity::Warning,
+                    });
+                    Section::CustomCheck(config.custom_checks.len() - 1)
+                } else {
+                    // Other sections are for other hooks
+                    Section::Other
+                };
+                continue;
+            }
+            let (key, value) = line.split_once('=').map_or((line, ""), |(k, v)| (k, v));
+            let (key, value) = (key.trim(), value.trim());
+            match section {
+                Section::Hook => config.parse_hook_option(key, value),
+                Section::CustomCheck(index) => config.custom_checks[index].parse_option(key, value),
+                Section::Other => Ok(()),
+            }
+            .map_err(|e| anyhow::anyhow!("Line {}: {e}", i + 1))?;
+        }
+        if let Some(check) = config.custom_checks.iter().find(|c| c.command.is_empty()) {
+            anyhow::bail!("Missing command for custom check {:?}", check.name);
+        }
+        Ok(config)
+    }
+
+    fn parse_hook_option(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        match (key, value) {
+            ("User", "") => anyhow::bail!("Missing value for User"),
+            ("User", "root") => self.user = None,
+            ("User", user) => self.user = Some(user.to_owned()),
+            ("EnableChecks", checks) => self
+                .check_toggles
+                .extend(checks.split_whitespace().map(|c| (c.to_owned(), true))),
+            ("DisableChecks", checks) => self
+                .check_toggles
+                .extend(checks.split_whitespace().map(|c| (c.to_owned(), false))),
+            ("CheckOrder", checks) => self
+                .check_order
+                .extend(checks.split_whitespace().map(ToOwned::to_owned)),
+            ("AurHelper", helper) => {
+                let template = rebuild::helper_template(helper)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown AUR helper {helper:?}"))?;
+                self.rebuild_command = Some(template.to_owned());
+            }
+            ("RebuildCommand", "") => anyhow::bail!("Missing value for RebuildCommand"),
+            ("RebuildCommand", template) => self.rebuild_command = Some(template.to_owned()),
+            ("RebuildScript", "") => anyhow::bail!("Missing value for RebuildScript"),
+            ("RebuildScript", path) => self.rebuild_script = Some(PathBuf::from(path)),
+            ("SuggestDowngrade", "") => self.suggest_downgrade = true,
+            ("SuggestDowngrade", _) => anyhow::bail!("SuggestDowngrade takes no value"),
+            ("Offline", "") => self.offline = true,
+            ("Offline", _) => anyhow::bail!("Offline takes no value"),
+            ("NetworkProbeTimeout", timeout) => {
+                let timeout = timeout
+                    .parse()
+                    .ok()
+                    .and_then(|s| Duration::try_from_secs_f64(s).ok())
+                    .filter(|t| !t.is_zero())
+                    .ok_or_else(|| anyhow::anyhow!("Invalid network probe timeout {timeout:?}"))?;
+                self.network_probe_timeout = Some(timeout);
+            }
+            ("ConventionLinks", "") => anyhow::bail!("Missing value for ConventionLinks"),
+            ("ConventionLinks", links) => self
+                .convention_links
+                .get_or_insert_with(Vec::new)
+                .extend(links.split_whitespace().map(PathBuf::from)),
+            ("UnownedFilesDirs", "") => anyhow::bail!("Missing value for UnownedFilesDirs"),
+            ("UnownedFilesDirs", dirs) => self
+                .unowned_files_dirs
+                .get_or_insert_with(Vec::new)
+                .extend(dirs.split_whitespace().map(PathBuf::from)),
+            ("ContainerRoots", roots) => self
+                .container_roots
+                .extend(roots.split_whitespace().map(PathBuf::from)),
+            ("SeverityOverride", severity_override) => {
+                self.severity_overrides.push(severity_override.parse()?);
+            }
+            ("CollectUrl", "") => anyhow::bail!("Missing value for CollectUrl"),
+            ("CollectUrl", url) => self.collect_url = Some(url.to_owned()),
+            ("CollectTokenFile", "") => anyhow::bail!("Missing value for CollectTokenFile"),
+            ("CollectTokenFile", path) => self.collect_token_file = Some(PathBuf::from(path)),
+            (key, _) => anyhow::bail!("Unknown option {key:?}"),
+        }
+        Ok(())
+    }
+
+    /// User to run analysis child processes as when running as root
+    #[must_use]
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    /// Checks to enable (`true`) or disable, to apply in order
+    #[must_use]
+    pub fn check_toggles(&self) -> &[(String, bool)] {
+        &self.check_toggles
+    }
+
+    /// Checks to run and report first, in order
+    #[must_use]
+    pub fn check_order(&self) -> &[String] {
+        &self.check_order
+    }
+
+    /// Custom checks
+    #[must_use]
+    pub fn custom_checks(&self) -> &[CustomCheck] {
+        &self.custom_checks
+    }
+
+    /// Command template to rebuild broken packages, with a `{packages}` placeholder, if configured
+    #[must_use]
+    pub fn rebuild_command(&self) -> Option<&str> {
+        self.rebuild_command.as_deref()
+    }
+
+    /// Script file to write the rebuild command to, if configured
+    #[must_use]
+    pub fn rebuild_script(&self) -> Option<&Path> {
+        self.rebuild_script.as_deref()
+    }
+
+    /// Whether to suggest downgrading upgraded libraries from the package cache
+    #[must_use]
+    pub fn suggest_downgrade(&self) -> bool {
+        self.suggest_downgrade
+    }
+
+    /// Whether to disable features needing network access
+    #[must_use]
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Timeout of the reachability probe run before accessing the network, if enabled
+    #[must_use]
+    pub fn network_probe_timeout(&self) -> Option<Duration> {
+        self.network_probe_timeout
+    }
+
+    /// Root directories of configured containers
+    #[must_use]
+    pub fn container_roots(&self) -> &[PathBuf] {
+        &self.container_roots
+    }
+
+    /// Convention symlinks to check, if configured
+    #[must_use]
+    pub fn convention_links(&self) -> Option<&[PathBuf]> {
+        self.convention_links.as_deref()
+    }
+
+    /// Directories watched for unowned files, if configured
+    #[must_use]
+    pub fn unowned_files_dirs(&self) -> Option<&[PathBuf]> {
+        self.unowned_files_dirs.as_deref()
+    }
+
+    /// Severity overrides, in order
+    #[must_use]
+    pub fn severity_overrides(&self) -> &[SeverityOverride] {
+        &self.severity_overrides
+    }
+
+    /// URL of the service full scan reports are sent to, if configured
+    #[must_use]
+    pub fn collect_url(&self) -> Option<&str> {
+        self.collect_url.as_deref()
+    }
+
+    /// File with the token authenticating to the collection service, if configured
+    #[must_use]
+    pub fn collect_token_file(&self) -> Option<&Path> {
+        self.collect_token_file.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let custom = Config::parse(
+            "# comment
+[options]
+User = root
+
+[check-broken-packages]
+User = checker # inline comment
+DisableChecks = python-packages service-links
+EnableChecks = service-links
+CheckOrder = security-advisories missing-dependencies
+AurHelper = paru
+RebuildScript = /root/rebuild.sh
+SuggestDowngrade
+Offline
+NetworkProbeTimeout = 1.5
+ContainerRoots = /srv/arch /srv/builder
+ConventionLinks = /usr/bin/cc /usr/bin/c++
+UnownedFilesDirs = /etc /srv
+SeverityOverride = info /usr/lib/erlang libfoo.so.1
+SeverityOverride = error openssl
+CollectUrl = https://reports.example.com/api/reports
+CollectTokenFile = /etc/pacman-hooks.token
+
+[check:foo]
+Command = /usr/local/bin/check-foo --all
+Output = json
+",
+        )
+        .unwrap();
+        assert_eq!(custom.user(), Some("checker"));
+        assert_eq!(
+            custom.check_toggles(),
+            [
+                ("python-packages".to_owned(), false),
+                ("service-links".to_owned(), false),
+                ("service-links".to_owned(), true),
+            ]
+        );
+        assert_eq!(
+            custom.check_order(),
+            ["security-advisories", "missing-dependencies"]
+        );
+        assert_eq!(
+            custom.rebuild_command(),
+            Some("paru -S --rebuild {packages}")
+        );
+        assert_eq!(custom.rebuild_script(), Some(Path::new("/root/rebuild.sh")));
+        assert!(custom.suggest_downgrade());
+        assert!(custom.offline());
+        assert_eq!(
+            custom.network_probe_timeout(),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            custom.convention_links(),
+            Some(&[PathBuf::from("/usr/bin/cc"), PathBuf::from("/usr/bin/c++")][..])
+        );
+        assert_eq!(
+            custom.unowned_files_dirs(),
+            Some(&[PathBuf::from("/etc"), PathBuf::from("/srv")][..])
+        );
+        assert_eq!(
+            custom.container_roots(),
+            [PathBuf::from("/srv/arch"), PathBuf::from("/srv/builder")]
+        );
+        assert_eq!(
+            custom
+                .severity_overrides()
+                .iter()
+                .map(SeverityOverride::severity)
+                .collect::<Vec<_>>(),
+            [Severity::Info, Severity::Error]
+        );
+        assert_eq!(
+            custom.collect_url(),
+            Some("https://reports.example.com/api/reports")
+        );
+        assert_eq!(
+            custom.collect_token_file(),
+            Some(Path::new("/etc/pacman-hooks.token"))
+        );
+        let [foo] = custom.custom_checks() else {
+            panic!();
+        };
+        assert_eq!(foo.name(), "foo");
+        assert_eq!(foo.command(), ["/usr/local/bin/check-foo", "--all"]);
+        assert_eq!(foo.output(), OutputFormat::Json);
+        assert_eq!(foo.severity(), Severity::Warning);
+
+        let root = Config::parse("[check-broken-packages]\nUser=root\n").unwrap();
+        assert_eq!(root.user(), None);
+
+        let default = Config::parse("").unwrap();
+        assert_eq!(default.user(), Some(DEFAULT_USER));
+        assert!(!default.suggest_downgrade());
+        assert!(!default.offline());
+        assert_eq!(default.network_probe_timeout(), None);
+        assert_eq!(default.convention_links(), None);
+        assert_eq!(default.unowned_files_dirs(), None);
+        assert_eq!(default.collect_url(), None);
+        assert!(Config::parse("[check-broken-packages]\nFoo = bar\n").is_err());
+        assert!(Config::parse("[check-broken-packages]\nAurHelper = pacaur\n").is_err());
+        assert!(Config::parse("[check-broken-packages]\nSeverityOverride = info\n").is_err());
+        assert!(Config::parse("[check:foo]\nOutput = json\n").is_err());
+    }
+}
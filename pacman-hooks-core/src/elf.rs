@@ -0,0 +1,1248 @@
+//! Native ELF dynamic dependency resolution
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fmt,
+    fs::{self, File},
+    io::{self, BufRead, Read},
+    os::unix::ffi::OsStrExt as _,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock, RwLock,
+    },
+};
+
+use glob::glob;
+use object::{
+    elf,
+    read::elf::{Dyn as _, FileHeader, ProgramHeader as _},
+    Endianness, ReadCache,
+};
+
+use crate::{
+    error::{Classify as _, ErrorKind},
+    privileges, root,
+};
+
+/// Whether missing dependencies are only resolved natively, without confirming them with ldd
+static NATIVE_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Resolve missing dependencies natively only for the rest of the run, ldd running the loader of the inspected files
+pub fn resolve_natively() {
+    NATIVE_ONLY.store(true, Ordering::Relaxed);
+}
+
+/// Whether missing dependencies are only resolved natively, because it was requested or ldd would use the libraries
+/// of the running system instead of the ones of the checked root
+pub fn is_native_only() -> bool {
+    NATIVE_ONLY.load(Ordering::Relaxed) || root::is_alternate()
+}
+
+/// Default library directories of the dynamic loader, for files of the ELF class of the checked system
+const LIBRARY_DIRS: [&str; 4] = ["/usr/lib", "/usr/lib64", "/lib", "/lib64"];
+
+/// Library directory of 32-bit files of 64-bit systems, like the ones of `multilib` packages
+pub const COMPAT_LIBRARY_DIR: &str = "/usr/lib32";
+
+/// Default library directories of the dynamic loader, for 32-bit files of 64-bit systems
+const COMPAT_LIBRARY_DIRS: [&str; 2] = [COMPAT_LIBRARY_DIR, "/lib32"];
+
+/// Dynamic linking information read from an ELF file
+struct ElfDynamic {
+    /// ELF class (`ELFCLASS32` or `ELFCLASS64`)
+    class: u8,
+    needed: Vec<String>,
+    rpath: Vec<String>,
+    runpath: Vec<String>,
+}
+
+impl ElfDynamic {
+    /// Directories searched before the default ones, with `$ORIGIN` and `$LIB` expanded
+    fn search_dirs(&self, origin: &Path) -> Vec<PathBuf> {
+        // DT_RPATH is ignored if DT_RUNPATH is present
+        let dirs = if self.runpath.is_empty() {
+            &self.rpath
+        } else {
+            &self.runpath
+        };
+        // The loader of 32-bit files of 64-bit systems has its libraries in lib32 directories
+        let lib = if self.class == native_class() {
+            "lib"
+        } else {
+            "lib32"
+        };
+        dirs.iter()
+            .filter_map(|d| expand_origin(d, origin, lib))
+            .collect()
+    }
+
+    /// Directories searched for the dependencies of the file, before the default ones: its own, then the `DT_RPATH`
+    /// directories `inherited` from the files loading it, unless it has a `DT_RUNPATH`
+    fn dependency_dirs(&self, origin: &Path, inherited: &[PathBuf]) -> Vec<PathBuf> {
+        let mut dirs = self.search_dirs(origin);
+        if self.runpath.is_empty() {
+            dirs.extend_from_slice(inherited);
+        }
+        dirs
+    }
+
+    /// `DT_RPATH` directories that the loader also searches for the transitive dependencies of the file, its own ones
+    /// unless it has a `DT_RUNPATH`, then the ones `inherited` from the files loading it
+    fn inherited_dirs(&self, origin: &Path, inherited: &[PathBuf]) -> Vec<PathBuf> {
+        let mut dirs = if self.runpath.is_empty() {
+            self.search_dirs(origin)
+        } else {
+            Vec::new()
+        };
+        dirs.extend_from_slice(inherited);
+        dirs
+    }
+}
+
+/// Expand `$ORIGIN`, and `$LIB` to `lib`, in a `DT_RPATH`/`DT_RUNPATH` entry, returning `None` for entries we can
+/// not interpret.
+///
+/// Other entries are absolute paths of the checked system, and are resolved below its root.
+fn expand_origin(dir: &str, origin: &Path, lib: &str) -> Option<PathBuf> {
+    let dir = dir.replace("${LIB}", lib).replace("$LIB", lib);
+    if !dir.contains('$') {
+        return Some(root::resolve(Path::new(&dir)));
+    }
+    let origin = origin.to_str()?;
+    let expanded = dir.replace("${ORIGIN}", origin).replace("$ORIGIN", origin);
+    if expanded.contains('$') {
+        // $PLATFORM, etc.
+        return None;
+    }
+    Some(PathBuf::from(expanded))
+}
+
+/// Result of ELF analysis
+enum ElfKind {
+    /// Not an ELF file
+    NotElf,
+    /// Statically linked executable
+    Static,
+    /// Dynamically linked ELF
+    Dynamic(ElfDynamic),
+    /// ELF we do not resolve natively (stripped section headers, unknown class...)
+    Unsupported,
+}
+
+/// Open an ELF file, and get its class, `None` if it is not an ELF file
+fn open_elf(path: &Path) -> io::Result<Option<(ReadCache<File>, u8)>> {
+    let mut file = File::open(path)?;
+    let mut ident = [0; 5];
+    if file.read_exact(&mut ident).is_err() || ident[..4] != elf::ELFMAG {
+        return Ok(None);
+    }
+    Ok(Some((ReadCache::new(file), ident[4])))
+}
+
+fn read_elf(path: &Path) -> anyhow::Result<ElfKind> {
+    match open_elf(path)? {
+        None => Ok(ElfKind::NotElf),
+        Some((data, elf::ELFCLASS64)) => {
+            parse_elf::<elf::FileHeader64<Endianness>>(&data, elf::ELFCLASS64)
+        }
+        Some((data, elf::ELFCLASS32)) => {
+            parse_elf::<elf::FileHeader32<Endianness>>(&data, elf::ELFCLASS32)
+        }
+        Some(_) => Ok(ElfKind::Unsupported),
+    }
+}
+
+fn parse_elf<Elf: FileHeader<Endian = Endianness>>(
+    data: &ReadCache<File>,
+    class: u8,
+) -> anyhow::Result<ElfKind> {
+    let Ok(header) = Elf::parse(data) else {
+        return Ok(ElfKind::Unsupported);
+    };
+    let endian = header.endian().classify(ErrorKind::ElfParse)?;
+    let sections = header
+        .sections(endian, data)
+        .classify(ErrorKind::ElfParse)?;
+    if sections.is_empty() {
+        return Ok(ElfKind::Unsupported);
+    }
+    let Some((entries, strings_index)) = sections
+        .dynamic(endian, data)
+        .classify(ErrorKind::ElfParse)?
+    else {
+        return Ok(ElfKind::Static);
+    };
+    let strings = sections
+        .strings(endian, data, strings_index)
+        .classify(ErrorKind::ElfParse)?;
+
+    let mut dynamic = ElfDynamic {
+        class,
+        needed: Vec::new(),
+        rpath: Vec::new(),
+        runpath: Vec::new(),
+    };
+    for entry in entries {
+        let tag = entry.tag32(endian);
+        if tag == Some(elf::DT_NULL) {
+            break;
+        }
+        if !matches!(tag, Some(elf::DT_NEEDED | elf::DT_RPATH | elf::DT_RUNPATH)) {
+            continue;
+        }
+        let offset = u32::try_from(entry.d_val(endian).into()).classify(ErrorKind::ElfParse)?;
+        let value = String::from_utf8(
+            strings
+                .get(offset)
+                .map_err(|()| anyhow::anyhow!("Invalid dynamic string offset"))
+                .classify(ErrorKind::ElfParse)?
+                .to_vec(),
+        )
+        .classify(ErrorKind::ElfParse)?;
+        match tag {
+            Some(elf::DT_NEEDED) => dynamic.needed.push(value),
+            Some(elf::DT_RPATH) => dynamic.rpath.extend(split_path_list(&value)),
+            _ => dynamic.runpath.extend(split_path_list(&value)),
+        }
+    }
+    Ok(ElfKind::Dynamic(dynamic))
+}
+
+/// Symbol versions read from an ELF file
+#[derive(Default)]
+struct SymbolVersions {
+    /// Versions required from each library, by soname, without the weak ones the loader does not enforce
+    needed: Vec<(String, Vec<String>)>,
+    /// Versions defined by the file, if it is a library
+    defined: HashSet<String>,
+}
+
+/// Read the symbol versions of an ELF file, empty if it is not an ELF file
+fn read_symbol_versions(path: &Path) -> anyhow::Result<SymbolVersions> {
+    match open_elf(path)? {
+        Some((data, elf::ELFCLASS64)) => {
+            parse_symbol_versions::<elf::FileHeader64<Endianness>>(&data)
+        }
+        Some((data, elf::ELFCLASS32)) => {
+            parse_symbol_versions::<elf::FileHeader32<Endianness>>(&data)
+        }
+        _ => Ok(SymbolVersions::default()),
+    }
+}
+
+fn parse_symbol_versions<Elf: FileHeader<Endian = Endianness>>(
+    data: &ReadCache<File>,
+) -> anyhow::Result<SymbolVersions> {
+    let mut versions = SymbolVersions::default();
+    let Ok(header) = Elf::parse(data) else {
+        return Ok(versions);
+    };
+    let endian = header.endian().classify(ErrorKind::ElfParse)?;
+    let sections = header
+        .sections(endian, data)
+        .classify(ErrorKind::ElfParse)?;
+    let name = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+
+    if let Some((mut needs, strings_index)) = sections
+        .gnu_verneed(endian, data)
+        .classify(ErrorKind::ElfParse)?
+    {
+        let strings = sections
+            .strings(endian, data, strings_index)
+            .classify(ErrorKind::ElfParse)?;
+        while let Some((need, mut auxiliaries)) = needs.next().classify(ErrorKind::ElfParse)? {
+            let soname = name(need.file(endian, strings).classify(ErrorKind::ElfParse)?);
+            let mut required = Vec::new();
+            while let Some(auxiliary) = auxiliaries.next().classify(ErrorKind::ElfParse)? {
+                if auxiliary.vna_flags.get(endian) & elf::VER_FLG_WEAK == 0 {
+                    required.push(name(
+                        auxiliary
+                            .name(endian, strings)
+                            .classify(ErrorKind::ElfParse)?,
+                    ));
+                }
+            }
+            versions.needed.push((soname, required));
+        }
+    }
+
+    if let Some((mut defs, strings_index)) = sections
+        .gnu_verdef(endian, data)
+        .classify(ErrorKind::ElfParse)?
+    {
+        let strings = sections
+            .strings(endian, data, strings_index)
+            .classify(ErrorKind::ElfParse)?;
+        while let Some((_, mut auxiliaries)) = defs.next().classify(ErrorKind::ElfParse)? {
+            // The first auxiliary entry names the version, the next ones the versions it inherits from
+            if let Some(auxiliary) = auxiliaries.next().classify(ErrorKind::ElfParse)? {
+                versions.defined.insert(name(
+                    auxiliary
+                        .name(endian, strings)
+                        .classify(ErrorKind::ElfParse)?,
+                ));
+            }
+        }
+    }
+    Ok(versions)
+}
+
+fn split_path_list(paths: &str) -> impl Iterator<Item = String> + '_ {
+    paths
+        .split(':')
+        .filter(|d| !d.is_empty())
+        .map(ToOwned::to_owned)
+}
+
+/// Get library directories from ld.so.conf, following include directives
+fn read_ld_so_conf(path: &Path, dirs: &mut Vec<PathBuf>) {
+    let Ok(content) = fs::read_to_string(path) else {
+        return;
+    };
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if let Some(pattern) = line.strip_prefix("include") {
+            let pattern = root::resolve(Path::new(pattern.trim()));
+            for include in pattern
+                .to_str()
+                .and_then(|p| glob(p).ok())
+                .into_iter()
+                .flatten()
+                .flatten()
+            {
+                read_ld_so_conf(&include, dirs);
+            }
+        } else if !line.is_empty() {
+            dirs.push(root::resolve(Path::new(line)));
+        }
+    }
+}
+
+/// Get the library search path of the dynamic loader, in search order, from ld.so.conf and its default directories.
+///
+/// ld.so.cache is built from these directories by ldconfig, which may not have run yet after an upgrade.
+fn search_dirs(library_dirs: &[&str]) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    read_ld_so_conf(&root::resolve(Path::new("/etc/ld.so.conf")), &mut dirs);
+    dirs.extend(library_dirs.iter().map(|d| root::resolve(Path::new(d))));
+    dirs
+}
+
+/// Get the default library search path of the dynamic loader, in search order
+#[must_use]
+pub fn default_search_dirs() -> Vec<PathBuf> {
+    search_dirs(&LIBRARY_DIRS)
+}
+
+/// Get the ELF class of a file (`ELFCLASS32` or `ELFCLASS64`), `None` if it is not an ELF file
+#[must_use]
+pub fn elf_class(path: &Path) -> Option<u8> {
+    let mut ident = [0; 5];
+    File::open(path).ok()?.read_exact(&mut ident).ok()?;
+    (ident[..4] == elf::ELFMAG).then_some(ident[4])
+}
+
+/// Get the ELF class of the binaries of the checked system
+fn native_class() -> u8 {
+    static CLASS: OnceLock<u8> = OnceLock::new();
+    *CLASS.get_or_init(|| {
+        elf_class(&root::resolve(Path::new(REFERENCE_BINARY))).unwrap_or(
+            if cfg!(target_pointer_width = "64") {
+                elf::ELFCLASS64
+            } else {
+                elf::ELFCLASS32
+            },
+        )
+    })
+}
+
+/// Whether a file is an ELF file of the other class the checked system runs, like a 32-bit executable of a 64-bit
+/// system, whose libraries are in lib32 directories
+#[must_use]
+pub fn is_compat(path: &Path) -> bool {
+    elf_class(path).is_some_and(|c| c != native_class())
+}
+
+/// Whether the loader can load a library file for an ELF file of `class`, files that are not ELF files being assumed
+/// to be loadable
+fn is_loadable(path: &Path, class: u8) -> bool {
+    path.is_file() && elf_class(path).is_none_or(|c| c == class)
+}
+
+/// Whether a file is a statically linked ELF executable
+#[must_use]
+pub fn is_static(path: &Path) -> bool {
+    matches!(read_elf(path), Ok(ElfKind::Static))
+}
+
+/// Binary of the checked system its architecture is read from
+const REFERENCE_BINARY: &str = "/usr/bin/pacman";
+
+/// Architecture of the running system, assumed for the checked system if its reference binary can not be read
+const HOST_MACHINE: u16 = if cfg!(target_arch = "aarch64") {
+    elf::EM_AARCH64
+} else if cfg!(target_arch = "riscv64") {
+    elf::EM_RISCV
+} else {
+    elf::EM_X86_64
+};
+
+/// OS ABIs of binaries built for Linux, the GNU one is set by binaries using its extensions like IFUNC
+const LINUX_OS_ABIS: [u8; 2] = [elf::ELFOSABI_NONE, elf::ELFOSABI_GNU];
+
+/// File name prefix of the musl dynamic loader
+const MUSL_LOADER_PREFIX: &str = "ld-musl-";
+
+/// Platform an ELF file is built for
+struct ElfTarget {
+    machine: u16,
+    os_abi: u8,
+    /// Dynamic loader, for dynamically linked executables
+    interpreter: Option<PathBuf>,
+}
+
+fn parse_target<Elf: FileHeader<Endian = Endianness>>(
+    data: &ReadCache<File>,
+) -> anyhow::Result<ElfTarget> {
+    let header = Elf::parse(data).classify(ErrorKind::ElfParse)?;
+    let endian = header.endian().classify(ErrorKind::ElfParse)?;
+    let interpreter = header
+        .program_headers(endian, data)
+        .classify(ErrorKind::ElfParse)?
+        .iter()
+        .find_map(|h| h.interpreter(endian, data).transpose())
+        .transpose()
+        .classify(ErrorKind::ElfParse)?
+        .map(|i| PathBuf::from(OsStr::from_bytes(i)));
+    Ok(ElfTarget {
+        machine: header.e_machine(endian),
+        os_abi: header.e_ident().os_abi,
+        interpreter,
+    })
+}
+
+/// Read the platform of an ELF file of any class, `None` if it is not an ELF file
+fn read_target(path: &Path) -> anyhow::Result<Option<ElfTarget>> {
+    let parse = match elf_class(path) {
+        Some(elf::ELFCLASS64) => parse_target::<elf::FileHeader64<Endianness>>,
+        Some(elf::ELFCLASS32) => parse_target::<elf::FileHeader32<Endianness>>,
+        _ => return Ok(None),
+    };
+    parse(&ReadCache::new(File::open(path)?)).map(Some)
+}
+
+/// Get the dynamic loader of the checked system, the one of its reference binary, `None` if it is not a dynamically
+/// linked ELF file
+///
+/// # Errors
+///
+/// Fails if the reference binary can not be read
+pub fn system_loader() -> anyhow::Result<Option<PathBuf>> {
+    let reference = root::resolve(Path::new(REFERENCE_BINARY));
+    // Files that can not be read are not ELF files for `read_target`
+    fs::metadata(&reference)
+        .map_err(|err| anyhow::anyhow!("Unable to read {}: {err}", reference.display()))?;
+    Ok(read_target(&reference)?.and_then(|t| t.interpreter))
+}
+
+/// Get the architectures the checked system runs binaries of: its own, and its 32-bit counterpart
+fn native_machines() -> &'static [u16] {
+    static MACHINES: OnceLock<Vec<u16>> = OnceLock::new();
+    MACHINES.get_or_init(|| {
+        let reference = root::resolve(Path::new(REFERENCE_BINARY));
+        let machine = if let Ok(Some(target)) = read_target(&reference) {
+            target.machine
+        } else {
+            log::debug!(
+                "Unable to read architecture of {reference:?}, assuming it is the one of the running system"
+            );
+            HOST_MACHINE
+        };
+        match machine {
+            elf::EM_X86_64 => vec![machine, elf::EM_386],
+            elf::EM_AARCH64 => vec![machine, elf::EM_ARM],
+            _ => vec![machine],
+        }
+    })
+}
+
+/// Platform of an ELF file that the checked system can not run
+#[derive(Debug, Eq, PartialEq)]
+pub enum ForeignTarget {
+    /// Other processor architecture
+    Machine(u16),
+    /// Other operating system
+    OsAbi(u8),
+    /// Linked against musl libc, whose loader is not installed
+    Musl(PathBuf),
+}
+
+impl fmt::Display for ForeignTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Machine(machine) => {
+                let name = match *machine {
+                    elf::EM_386 => "x86",
+                    elf::EM_X86_64 => "x86-64",
+                    elf::EM_ARM => "ARM",
+                    elf::EM_AARCH64 => "AArch64",
+                    elf::EM_RISCV => "RISC-V",
+                    elf::EM_PPC | elf::EM_PPC64 => "PowerPC",
+                    elf::EM_MIPS => "MIPS",
+                    elf::EM_S390 => "S/390",
+                    elf::EM_LOONGARCH => "LoongArch",
+                    elf::EM_AVR => "AVR",
+                    _ => return write!(f, "ELF machine {machine}"),
+                };
+                write!(f, "the {name} architecture")
+            }
+            Self::OsAbi(os_abi) => {
+                let name = match *os_abi {
+                    elf::ELFOSABI_FREEBSD => "FreeBSD",
+                    elf::ELFOSABI_NETBSD => "NetBSD",
+                    elf::ELFOSABI_OPENBSD => "OpenBSD",
+                    elf::ELFOSABI_SOLARIS => "Solaris",
+                    elf::ELFOSABI_HURD => "GNU Hurd",
+                    elf::ELFOSABI_STANDALONE => "bare metal",
+                    _ => return write!(f, "ELF OS ABI {os_abi}"),
+                };
+                write!(f, "{name}")
+            }
+            Self::Musl(loader) => write!(
+                f,
+                "musl libc, whose loader {} is not installed",
+                loader.display()
+            ),
+        }
+    }
+}
+
+/// How the checked system runs an ELF file
+#[derive(Debug, Eq, PartialEq)]
+pub enum Platform {
+    /// With the glibc loader, or not an ELF file
+    Native,
+    /// With the musl loader, which does not search libraries like the glibc one
+    Musl,
+    /// It does not
+    Foreign(ForeignTarget),
+}
+
+/// Find out if the checked system can run an ELF file, and with which loader
+///
+/// # Errors
+///
+/// Fails if the file can not be read
+pub fn platform(path: &Path) -> anyhow::Result<Platform> {
+    let Some(target) = read_target(path)? else {
+        return Ok(Platform::Native);
+    };
+    if !native_machines().contains(&target.machine) {
+        return Ok(Platform::Foreign(ForeignTarget::Machine(target.machine)));
+    }
+    if !LINUX_OS_ABIS.contains(&target.os_abi) {
+        return Ok(Platform::Foreign(ForeignTarget::OsAbi(target.os_abi)));
+    }
+    Ok(match target.interpreter {
+        Some(loader)
+            if loader
+                .file_name()
+                .is_some_and(|n| n.as_bytes().starts_with(MUSL_LOADER_PREFIX.as_bytes())) =>
+        {
+            if root::resolve(&loader).exists() {
+                Platform::Musl
+            } else {
+                Platform::Foreign(ForeignTarget::Musl(loader))
+            }
+        }
+        _ => Platform::Native,
+    })
+}
+
+/// Symbol versions an executable file requires from one of its libraries, that the library found for it does not
+/// define
+#[derive(Debug, Eq, PartialEq)]
+pub struct MissingVersions {
+    /// Soname of the library
+    pub soname: String,
+    /// Library found for the soname
+    pub library: PathBuf,
+    /// Versions the library does not define, like `GLIBC_2.38`
+    pub versions: Vec<String>,
+}
+
+/// Resolves sonames against the library search path, memoizing results for the duration of the run
+pub struct SonameResolver {
+    search_dirs: Vec<PathBuf>,
+    /// Search path of 32-bit files of 64-bit systems
+    compat_search_dirs: Vec<PathBuf>,
+    /// ELF class and soname to path in the default search path, or `None` if missing
+    sonames: RwLock<HashMap<(u8, String), Option<PathBuf>>>,
+    /// Library path and `DT_RPATH` directories inherited from the files loading it, to whether all its transitive
+    /// dependencies resolve
+    closures: RwLock<HashMap<(PathBuf, Vec<PathBuf>), bool>>,
+    /// Library path to the symbol versions it defines
+    definitions: RwLock<HashMap<PathBuf, HashSet<String>>>,
+}
+
+impl Default for SonameResolver {
+    fn default() -> Self {
+        Self::with_library_dirs(Vec::new())
+    }
+}
+
+impl SonameResolver {
+    /// Create a resolver searching libraries in `dirs` before the default search path
+    #[must_use]
+    pub fn with_library_dirs(dirs: Vec<PathBuf>) -> Self {
+        let mut search_dirs = dirs.clone();
+        search_dirs.extend(default_search_dirs());
+        let mut compat_search_dirs = dirs;
+        compat_search_dirs.extend(self::search_dirs(&COMPAT_LIBRARY_DIRS));
+        Self {
+            search_dirs,
+            compat_search_dirs,
+            sonames: RwLock::new(HashMap::new()),
+            closures: RwLock::new(HashMap::new()),
+            definitions: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Search path of files of an ELF class
+    fn class_search_dirs(&self, class: u8) -> &[PathBuf] {
+        if class == native_class() {
+            &self.search_dirs
+        } else {
+            &self.compat_search_dirs
+        }
+    }
+
+    /// Cheaply check if an executable may have unresolvable dependencies.
+    ///
+    /// This does not miss missing dependencies that ldd would report, but may return false positives
+    /// (or `true` for files it can not analyze), so a positive result needs to be confirmed with ldd.
+    pub fn may_have_missing_dependencies(&self, exec_path: &Path) -> bool {
+        let Some(exec_dir) = exec_path.parent() else {
+            return true;
+        };
+        match read_elf(exec_path) {
+            Ok(ElfKind::NotElf | ElfKind::Static) => false,
+            Ok(ElfKind::Dynamic(dynamic)) => {
+                // ldd is run with LD_LIBRARY_PATH set to the executable directory
+                let mut dirs = dynamic.search_dirs(exec_dir);
+                dirs.push(exec_dir.to_owned());
+                !self.resolve_all(
+                    &dynamic.needed,
+                    dynamic.class,
+                    &dirs,
+                    &dynamic.inherited_dirs(exec_dir, &[]),
+                    &mut Vec::new(),
+                )
+            }
+            Ok(ElfKind::Unsupported) => true,
+            Err(err) => {
+                log::debug!("Failed to analyze {exec_path:?}: {err}");
+                true
+            }
+        }
+    }
+
+    /// Resolve sonames of a file of an ELF class and their transitive dependencies, with the `DT_RPATH` directories
+    /// `inherited` by its dependencies, return true if all resolve
+    fn resolve_all(
+        &self,
+        sonames: &[String],
+        class: u8,
+        extra_dirs: &[PathBuf],
+        inherited: &[PathBuf],
+        stack: &mut Vec<PathBuf>,
+    ) -> bool {
+        sonames.iter().all(|soname| {
+            self.find(soname, class, extra_dirs)
+                .is_some_and(|p| self.is_complete(&p, inherited, stack))
+        })
+    }
+
+    /// Find a library for a file of an ELF class, in extra directories or the default search path
+    fn find(&self, soname: &str, class: u8, extra_dirs: &[PathBuf]) -> Option<PathBuf> {
+        if soname.contains('/') {
+            Some(root::resolve(Path::new(soname))).filter(|p| is_loadable(p, class))
+        } else {
+            extra_dirs
+                .iter()
+                .map(|d| d.join(soname))
+                .find(|p| is_loadable(p, class))
+                .or_else(|| self.lookup(soname, class))
+        }
+    }
+
+    /// Get missing dependencies of an executable file, including transitive ones, like `ldd` does.
+    ///
+    /// This is used when ldd can not be run, because the checked system is not the running one, or is not wanted.
+    pub fn missing_dependencies(&self, exec_path: &Path) -> Vec<PathBuf> {
+        let Some(exec_dir) = exec_path.parent() else {
+            return Vec::new();
+        };
+        let dynamic = match read_elf(exec_path) {
+            Ok(ElfKind::Dynamic(dynamic)) => dynamic,
+            Ok(ElfKind::NotElf | ElfKind::Static) => return Vec::new(),
+            Ok(ElfKind::Unsupported) => {
+                log::debug!("Unable to analyze {exec_path:?} without ldd");
+                return Vec::new();
+            }
+            Err(err) => {
+                log::debug!("Failed to analyze {exec_path:?}: {err}");
+                return Vec::new();
+            }
+        };
+        let mut missing = Vec::new();
+        let mut seen = HashSet::new();
+        let mut dirs = dynamic.search_dirs(exec_dir);
+        dirs.push(exec_dir.to_owned());
+        self.collect_missing(
+            &dynamic.needed,
+            dynamic.class,
+            &dirs,
+            &dynamic.inherited_dirs(exec_dir, &[]),
+            &mut seen,
+            &mut missing,
+        );
+        missing
+    }
+
+    /// Get missing libraries among `sonames` loaded with `dlopen`, like the ones of introspection typelibs,
+    /// and their transitive dependencies
+    pub fn missing_libraries(&self, sonames: &[String]) -> Vec<PathBuf> {
+        let mut missing = Vec::new();
+        self.collect_missing(
+            sonames,
+            native_class(),
+            &[],
+            &[],
+            &mut HashSet::new(),
+            &mut missing,
+        );
+        missing
+    }
+
+    /// Get direct dependencies of an executable file that the default search path finds below `dir`,
+    /// like the libraries of a package of /opt added by an ld.so.conf snippet.
+    ///
+    /// Libraries found in the own search path of the file, with `$ORIGIN` for example, are not returned.
+    pub fn libraries_below(&self, exec_path: &Path, dir: &Path) -> Vec<PathBuf> {
+        let (Some(exec_dir), Ok(ElfKind::Dynamic(dynamic))) =
+            (exec_path.parent(), read_elf(exec_path))
+        else {
+            return Vec::new();
+        };
+        if !self
+            .class_search_dirs(dynamic.class)
+            .iter()
+            .any(|d| d.starts_with(dir))
+        {
+            return Vec::new();
+        }
+        let own_dirs = dynamic.search_dirs(exec_dir);
+        dynamic
+            .needed
+            .iter()
+            .filter(|s| {
+                !s.contains('/')
+                    && !own_dirs
+                        .iter()
+                        .any(|d| is_loadable(&d.join(s), dynamic.class))
+            })
+            .filter_map(|s| self.lookup(s, dynamic.class))
+            .filter(|p| p.starts_with(dir))
+            .collect()
+    }
+
+    /// Get the symbol versions an executable file requires from its direct dependencies that the libraries found for
+    /// them do not define, which the loader refuses to run it with, like `ldd -r` reports.
+    ///
+    /// Missing libraries are skipped, and so are libraries defining no versions at all, that the loader only warns
+    /// about.
+    pub fn missing_versions(&self, exec_path: &Path) -> Vec<MissingVersions> {
+        let (Some(exec_dir), Ok(ElfKind::Dynamic(dynamic))) =
+            (exec_path.parent(), read_elf(exec_path))
+        else {
+            return Vec::new();
+        };
+        let needed = match read_symbol_versions(exec_path) {
+            Ok(versions) => versions.needed,
+            Err(err) => {
+                log::debug!("Failed to read symbol versions of {exec_path:?}: {err}");
+                return Vec::new();
+            }
+        };
+        let mut dirs = dynamic.search_dirs(exec_dir);
+        dirs.push(exec_dir.to_owned());
+        needed
+            .into_iter()
+            .filter(|(_, versions)| !versions.is_empty())
+            .filter_map(|(soname, versions)| {
+                let library = self.find(&soname, dynamic.class, &dirs)?;
+                let versions = self.undefined_versions(&library, &versions);
+                (!versions.is_empty()).then_some(MissingVersions {
+                    soname,
+                    library,
+                    versions,
+                })
+            })
+            .collect()
+    }
+
+    /// Get the versions among `versions` that a library does not define, none if it defines no versions
+    fn undefined_versions(&self, library: &Path, versions: &[String]) -> Vec<String> {
+        let undefined = |defined: &HashSet<String>| -> Vec<String> {
+            if defined.is_empty() {
+                return Vec::new();
+            }
+            versions
+                .iter()
+                .filter(|v| !defined.contains(*v))
+                .cloned()
+                .collect()
+        };
+        if let Some(found) = self
+            .definitions
+            .read()
+            .ok()
+            .and_then(|c| c.get(library).map(undefined))
+        {
+            return found;
+        }
+        let defined = read_symbol_versions(library).map_or_else(
+            |err| {
+                log::debug!("Failed to read symbol versions of {library:?}: {err}");
+                HashSet::new()
+            },
+            |v| v.defined,
+        );
+        let found = undefined(&defined);
+        if let Ok(mut cache) = self.definitions.write() {
+            cache.insert(library.to_owned(), defined);
+        }
+        found
+    }
+
+    /// Collect missing sonames of a file of an ELF class, and of their transitive dependencies, which also search the
+    /// `DT_RPATH` directories `inherited` from the files loading them, like the loader does
+    fn collect_missing(
+        &self,
+        sonames: &[String],
+        class: u8,
+        extra_dirs: &[PathBuf],
+        inherited: &[PathBuf],
+        seen: &mut HashSet<PathBuf>,
+        missing: &mut Vec<PathBuf>,
+    ) {
+        for soname in sonames {
+            let Some(path) = self.find(soname, class, extra_dirs) else {
+                let soname = PathBuf::from(soname);
+                if !missing.contains(&soname) {
+                    missing.push(soname);
+                }
+                continue;
+            };
+            if !seen.insert(path.clone()) {
+                continue;
+            }
+            if let (Ok(ElfKind::Dynamic(dynamic)), Some(dir)) = (read_elf(&path), path.parent()) {
+                self.collect_missing(
+                    &dynamic.needed,
+                    dynamic.class,
+                    &dynamic.dependency_dirs(dir, inherited),
+                    &dynamic.inherited_dirs(dir, inherited),
+                    seen,
+                    missing,
+                );
+            }
+        }
+    }
+
+    /// Find soname in the default search path of files of an ELF class, skipping libraries of the other class like
+    /// the loader does
+    fn lookup(&self, soname: &str, class: u8) -> Option<PathBuf> {
+        let key = (class, soname.to_owned());
+        if let Some(path) = self.sonames.read().ok().and_then(|c| c.get(&key).cloned()) {
+            return path;
+        }
+        let path = self
+            .class_search_dirs(class)
+            .iter()
+            .map(|d| d.join(soname))
+            .find(|p| is_loadable(p, class));
+        if let Ok(mut cache) = self.sonames.write() {
+            cache.insert(key, path.clone());
+        }
+        path
+    }
+
+    /// Check if all transitive dependencies of a library loaded with the `DT_RPATH` directories `inherited` from the
+    /// files loading it resolve
+    fn is_complete(&self, path: &Path, inherited: &[PathBuf], stack: &mut Vec<PathBuf>) -> bool {
+        let key = (path.to_owned(), inherited.to_vec());
+        if let Some(complete) = self.closures.read().ok().and_then(|c| c.get(&key).copied()) {
+            return complete;
+        }
+        if stack.iter().any(|p| p == path) {
+            // Dependency cycle, be conservative and let ldd confirm
+            return false;
+        }
+
+        stack.push(path.to_owned());
+        let complete = match read_elf(path) {
+            Ok(ElfKind::Dynamic(dynamic)) => {
+                let (dirs, inherited) = path.parent().map_or_else(
+                    || (Vec::new(), inherited.to_vec()),
+                    |d| {
+                        (
+                            dynamic.dependency_dirs(d, inherited),
+                            dynamic.inherited_dirs(d, inherited),
+                        )
+                    },
+                );
+                self.resolve_all(&dynamic.needed, dynamic.class, &dirs, &inherited, stack)
+            }
+            Ok(ElfKind::NotElf | ElfKind::Static) => true,
+            Ok(ElfKind::Unsupported) | Err(_) => false,
+        };
+        stack.pop();
+
+        if let Ok(mut cache) = self.closures.write() {
+            cache.insert(key, complete);
+        }
+        complete
+    }
+}
+
+/// Get sonames an ELF file directly depends on, empty if it is not a dynamically linked ELF of the class of the
+/// checked system, whose libraries are in the default library directory
+///
+/// # Errors
+///
+/// Fails if the file can not be read
+pub fn needed_libraries(path: &Path) -> anyhow::Result<Vec<String>> {
+    Ok(match read_elf(path)? {
+        ElfKind::Dynamic(dynamic) if dynamic.class == native_class() => dynamic.needed,
+        ElfKind::Dynamic(_) | ElfKind::NotElf | ElfKind::Static | ElfKind::Unsupported => {
+            Vec::new()
+        }
+    })
+}
+
+/// Get missing dependencies of an executable file according to `ldd`
+///
+/// # Errors
+///
+/// Fails if `ldd` can not be run
+pub fn get_missing_dependencies(exec_path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let exec_dir = exec_path
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("Unable to get parent dir for path {exec_path:?}"))?;
+    let output = privileges::command("ldd")
+        .arg(exec_path)
+        .env("LANG", "C")
+        .env("LD_LIBRARY_PATH", exec_dir)
+        .output()?;
+
+    let missing_deps = if output.status.success() {
+        output
+            .stdout
+            .lines()
+            .collect::<Result<Vec<String>, _>>()?
+            .into_iter()
+            .filter(|l| l.ends_with("=> not found"))
+            .filter_map(|l| l.split(' ').next().map(|s| PathBuf::from(s.trim_start())))
+            .collect()
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // Statically linked executables have no dependencies
+        if stderr.contains("not a dynamic executable") {
+            log::debug!("{exec_path:?} is not a dynamic executable");
+        } else {
+            log::warn!(
+                "ldd failed for {exec_path:?} with {}: {}",
+                output.status,
+                stderr.trim()
+            );
+        }
+        Vec::new()
+    };
+
+    Ok(missing_deps)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, ffi::OsString, fs::Permissions, io::Write, os::unix::fs::PermissionsExt};
+
+    use super::*;
+
+    fn update_path(dir: &str) -> OsString {
+        let path_orig = env::var_os("PATH").unwrap();
+
+        let mut paths_vec = env::split_paths(&path_orig).collect::<Vec<_>>();
+        paths_vec.insert(0, PathBuf::from(dir));
+
+        let paths = env::join_paths(paths_vec).unwrap();
+        env::set_var("PATH", paths);
+
+        path_orig
+    }
+
+    #[test]
+    fn test_get_missing_dependencies() {
+        let ldd_output = "	linux-vdso.so.1 (0x00007ffea89a7000)
+	libavdevice.so.57 => not found
+	libavfilter.so.6 => not found
+	libavformat.so.57 => not found
+	libavcodec.so.57 => not found
+	libavresample.so.3 => not found
+	libpostproc.so.54 => not found
+	libswresample.so.2 => not found
+	libswscale.so.4 => not found
+	libavutil.so.55 => not found
+	libm.so.6 => /usr/lib/libm.so.6 (0x00007f4bd9cc3000)
+	libpthread.so.0 => /usr/lib/libpthread.so.0 (0x00007f4bd9ca2000)
+	libc.so.6 => /usr/lib/libc.so.6 (0x00007f4bd9add000)
+	/lib64/ld-linux-x86-64.so.2 => /usr/lib64/ld-linux-x86-64.so.2 (0x00007f4bda08d000)
+";
+
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+
+        let output_filepath = tmp_dir.path().join("output.txt");
+        let mut output_file = File::create(&output_filepath).unwrap();
+        output_file.write_all(ldd_output.as_bytes()).unwrap();
+        drop(output_file);
+
+        let fake_ldd_filepath = tmp_dir.path().join("ldd");
+        let mut fake_ldd_file = File::create(fake_ldd_filepath).unwrap();
+        write!(
+            &mut fake_ldd_file,
+            "#!/bin/sh\ncat {}",
+            output_filepath.into_os_string().into_string().unwrap()
+        )
+        .unwrap();
+        fake_ldd_file
+            .set_permissions(Permissions::from_mode(0o700))
+            .unwrap();
+        drop(fake_ldd_file);
+
+        let path_orig = update_path(tmp_dir.path().to_str().unwrap());
+
+        let missing_deps = get_missing_dependencies(Path::new("dummy"));
+        assert!(missing_deps.is_ok());
+        assert_eq!(
+            missing_deps.unwrap(),
+            [
+                Path::new("libavdevice.so.57"),
+                Path::new("libavfilter.so.6"),
+                Path::new("libavformat.so.57"),
+                Path::new("libavcodec.so.57"),
+                Path::new("libavresample.so.3"),
+                Path::new("libpostproc.so.54"),
+                Path::new("libswresample.so.2"),
+                Path::new("libswscale.so.4"),
+                Path::new("libavutil.so.55"),
+            ]
+        );
+
+        env::set_var("PATH", path_orig);
+    }
+
+    #[test]
+    fn test_libraries_below() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().join("opt/foo/lib");
+        fs::create_dir_all(&lib_dir).unwrap();
+        fs::write(lib_dir.join("libc.so.6"), "").unwrap();
+        let resolver = SonameResolver::with_library_dirs(vec![lib_dir.clone()]);
+        let exe = Path::new("/proc/self/exe");
+        assert_eq!(
+            resolver.libraries_below(exe, &dir.path().join("opt")),
+            [lib_dir.join("libc.so.6")]
+        );
+        assert!(resolver
+            .libraries_below(exe, &dir.path().join("usr"))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_missing_versions() {
+        let exe = Path::new("/proc/self/exe");
+        let needed = read_symbol_versions(exe).unwrap().needed;
+        let (_, libc_versions) = needed.iter().find(|(s, _)| s == "libc.so.6").unwrap();
+        assert!(libc_versions.iter().all(|v| v.starts_with("GLIBC_")));
+
+        let dir = tempfile::tempdir().unwrap();
+        let libc = dir.path().join("libc.so.6");
+        fs::write(&libc, "").unwrap();
+        let resolver = SonameResolver::with_library_dirs(vec![dir.path().to_owned()]);
+        // Library without version definitions
+        assert!(resolver.missing_versions(exe).is_empty());
+        resolver
+            .definitions
+            .write()
+            .unwrap()
+            .insert(libc.clone(), HashSet::from(["LIBC_OTHER".to_owned()]));
+        assert_eq!(
+            resolver.missing_versions(exe),
+            [MissingVersions {
+                soname: "libc.so.6".to_owned(),
+                library: libc,
+                versions: libc_versions.clone(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_expand_origin() {
+        let origin = Path::new("/opt/foo/bin");
+        assert_eq!(
+            expand_origin("$ORIGIN/../lib", origin, "lib"),
+            Some(PathBuf::from("/opt/foo/bin/../lib"))
+        );
+        assert_eq!(
+            expand_origin("${ORIGIN}/lib", origin, "lib"),
+            Some(PathBuf::from("/opt/foo/bin/lib"))
+        );
+        assert_eq!(
+            expand_origin("/usr/$LIB/foo", origin, "lib32"),
+            Some(PathBuf::from("/usr/lib32/foo"))
+        );
+        assert_eq!(
+            expand_origin("$ORIGIN/../${LIB}", origin, "lib"),
+            Some(PathBuf::from("/opt/foo/bin/../lib"))
+        );
+        assert_eq!(expand_origin("/usr/lib/$PLATFORM", origin, "lib"), None);
+        assert_eq!(
+            expand_origin("/usr/lib/foo", origin, "lib"),
+            Some(PathBuf::from("/usr/lib/foo"))
+        );
+    }
+
+    #[test]
+    fn test_compat_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib_dir = dir.path().join("lib");
+        let lib32_dir = dir.path().join("lib32");
+        let mut library = elf_file(elf::EM_X86_64, elf::ELFOSABI_NONE, None);
+        for (class_dir, class) in [(&lib_dir, elf::ELFCLASS64), (&lib32_dir, elf::ELFCLASS32)] {
+            fs::create_dir_all(class_dir).unwrap();
+            library[4] = class;
+            fs::write(class_dir.join("libfoo.so.1"), &library).unwrap();
+        }
+        fs::write(lib32_dir.join("libbar.so.1"), &library).unwrap();
+        let resolver = SonameResolver::with_library_dirs(vec![lib_dir.clone(), lib32_dir.clone()]);
+        assert_eq!(
+            resolver.lookup("libfoo.so.1", elf::ELFCLASS64),
+            Some(lib_dir.join("libfoo.so.1"))
+        );
+        assert_eq!(
+            resolver.lookup("libfoo.so.1", elf::ELFCLASS32),
+            Some(lib32_dir.join("libfoo.so.1"))
+        );
+        // The loader skips libraries of the other class
+        assert_eq!(resolver.lookup("libbar.so.1", elf::ELFCLASS64), None);
+        assert_eq!(
+            resolver.find("libbar.so.1", elf::ELFCLASS32, &[lib_dir]),
+            Some(lib32_dir.join("libbar.so.1"))
+        );
+        // Needed entries with a path are loaded from it, and must exist
+        let bar_path = lib32_dir.join("libbar.so.1");
+        assert_eq!(
+            resolver.find(&bar_path.to_string_lossy(), elf::ELFCLASS32, &[]),
+            Some(bar_path.clone())
+        );
+        assert_eq!(
+            resolver.find(&bar_path.to_string_lossy(), elf::ELFCLASS64, &[]),
+            None
+        );
+        assert_eq!(
+            resolver.find(
+                &lib32_dir.join("libmissing.so.1").to_string_lossy(),
+                elf::ELFCLASS32,
+                &[]
+            ),
+            None
+        );
+        assert!(is_compat(&lib32_dir.join("libbar.so.1")));
+        assert!(!is_compat(Path::new("/proc/self/exe")));
+    }
+
+    /// Build a little endian 64-bit ELF executable header, with a `PT_INTERP` segment if `interpreter` is set
+    fn elf_file(machine: u16, os_abi: u8, interpreter: Option<&Path>) -> Vec<u8> {
+        let mut data = vec![0; 64];
+        data[..4].copy_from_slice(&elf::ELFMAG);
+        data[4..8].copy_from_slice(&[elf::ELFCLASS64, elf::ELFDATA2LSB, elf::EV_CURRENT, os_abi]);
+        data[16..18].copy_from_slice(&elf::ET_EXEC.to_le_bytes());
+        data[18..20].copy_from_slice(&machine.to_le_bytes());
+        data[20..24].copy_from_slice(&u32::from(elf::EV_CURRENT).to_le_bytes());
+        data[52..54].copy_from_slice(&64_u16.to_le_bytes());
+        data[54..56].copy_from_slice(&56_u16.to_le_bytes());
+        if let Some(interpreter) = interpreter {
+            data[32..40].copy_from_slice(&64_u64.to_le_bytes());
+            data[56..58].copy_from_slice(&1_u16.to_le_bytes());
+            let path = interpreter.as_os_str().as_bytes();
+            let size = (path.len() as u64 + 1).to_le_bytes();
+            data.extend(elf::PT_INTERP.to_le_bytes());
+            data.extend([0; 4]);
+            data.extend(120_u64.to_le_bytes());
+            data.extend([0; 16]);
+            data.extend(size);
+            data.extend(size);
+            data.extend([0; 8]);
+            data.extend(path);
+            data.push(0);
+        }
+        data
+    }
+
+    #[test]
+    fn test_platform() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let write = |name: &str, data: &[u8]| {
+            let path = tmp_dir.path().join(name);
+            fs::write(&path, data).unwrap();
+            path
+        };
+        let machine = native_machines()[0];
+        let loader = write("ld-musl-x86_64.so.1", b"");
+
+        let glibc = write("glibc", &elf_file(machine, elf::ELFOSABI_GNU, None));
+        assert_eq!(platform(&glibc).unwrap(), Platform::Native);
+        let script = write("script", b"#!/bin/sh\n");
+        assert_eq!(platform(&script).unwrap(), Platform::Native);
+        let musl = write(
+            "musl",
+            &elf_file(machine, elf::ELFOSABI_NONE, Some(&loader)),
+        );
+        assert_eq!(platform(&musl).unwrap(), Platform::Musl);
+
+        let s390 = write("s390", &elf_file(elf::EM_S390, elf::ELFOSABI_NONE, None));
+        assert_eq!(
+            platform(&s390).unwrap(),
+            Platform::Foreign(ForeignTarget::Machine(elf::EM_S390))
+        );
+        let freebsd = write("freebsd", &elf_file(machine, elf::ELFOSABI_FREEBSD, None));
+        assert_eq!(
+            platform(&freebsd).unwrap(),
+            Platform::Foreign(ForeignTarget::OsAbi(elf::ELFOSABI_FREEBSD))
+        );
+        let missing_loader = Path::new("/nonexistent/ld-musl-x86_64.so.1");
+        let loaderless = write(
+            "loaderless",
+            &elf_file(machine, elf::ELFOSABI_NONE, Some(missing_loader)),
+        );
+        let target = ForeignTarget::Musl(missing_loader.to_owned());
+        assert_eq!(
+            target.to_string(),
+            "musl libc, whose loader /nonexistent/ld-musl-x86_64.so.1 is not installed"
+        );
+        assert_eq!(platform(&loaderless).unwrap(), Platform::Foreign(target));
+    }
+}
@@ -0,0 +1,129 @@
+//! Error classification by originating subsystem, so failures can be told apart by scripts
+
+use std::{fmt, io};
+
+/// Subsystem an error comes from
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorKind {
+    /// Running pacman or reading its databases, or parsing their content
+    PacmanQuery,
+    /// Parsing an ELF file
+    ElfParse,
+    /// Other input/output error, like a missing file or permission denied
+    Io,
+    /// Invalid configuration
+    Config,
+    /// Local database locked or being written by a pacman transaction
+    DbBusy,
+}
+
+impl ErrorKind {
+    /// Stable name, used in machine readable output
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PacmanQuery => "pacman-query",
+            Self::ElfParse => "elf-parse",
+            Self::Io => "io",
+            Self::Config => "config",
+            Self::DbBusy => "db-busy",
+        }
+    }
+
+    /// Get the kind of an error, from the innermost classified error of its chain,
+    /// or [`ErrorKind::Io`] if it was caused by an I/O error
+    #[must_use]
+    pub fn of(err: &anyhow::Error) -> Option<Self> {
+        err.chain()
+            .filter_map(|e| e.downcast_ref::<Error>())
+            .last()
+            .map(|e| e.kind)
+            .or_else(|| {
+                err.chain()
+                    .any(<dyn std::error::Error>::is::<io::Error>)
+                    .then_some(Self::Io)
+            })
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Error tagged with the subsystem it comes from
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    source: anyhow::Error,
+}
+
+impl Error {
+    /// Subsystem the error comes from
+    #[must_use]
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The source chain is flattened, so the kind shows in logs even without alternate formatting
+        write!(f, "[{}] {:#}", self.kind, self.source)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Tag errors of results with a subsystem
+pub trait Classify<T> {
+    /// Tag the error with `kind`
+    ///
+    /// # Errors
+    ///
+    /// Fails with the classified error if `self` is an error
+    fn classify(self, kind: ErrorKind) -> anyhow::Result<T>;
+}
+
+impl<T, E> Classify<T> for Result<T, E>
+where
+    E: Into<anyhow::Error>,
+{
+    fn classify(self, kind: ErrorKind) -> anyhow::Result<T> {
+        self.map_err(|e| {
+            Error {
+                kind,
+                source: e.into(),
+            }
+            .into()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Context as _;
+
+    use super::*;
+
+    #[test]
+    fn test_error_kind() {
+        let err = Err::<(), _>(anyhow::anyhow!("Unexpected line"))
+            .classify(ErrorKind::PacmanQuery)
+            .context("Failed to list packages")
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&err), Some(ErrorKind::PacmanQuery));
+        assert_eq!(
+            format!("{err:#}"),
+            "Failed to list packages: [pacman-query] Unexpected line"
+        );
+
+        let io_err = Err::<(), _>(io::Error::from(io::ErrorKind::PermissionDenied))
+            .context("Failed to read file")
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&io_err), Some(ErrorKind::Io));
+
+        assert_eq!(ErrorKind::of(&anyhow::anyhow!("Other")), None);
+    }
+}
@@ -0,0 +1,613 @@
+//! Pacman sync files databases access, to find repository packages providing files, like `pacman -F`,
+//! or the pkgfile database if they were never downloaded.
+//!
+//! Lookups are cached until the databases change, since scanning them is slow, and commands of a
+//! transaction often look up the same files.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use anyhow::Context as _;
+
+use crate::{
+    baseline::STATE_DIR,
+    cache::LookupCache,
+    error::{Classify as _, ErrorKind},
+    json::Value,
+    localdb, privileges, root, syncdb,
+};
+
+/// Directory of libraries looked up by soname, relative to the root
+const LIBRARY_DIR: &str = "usr/lib/";
+
+/// Default pkgfile database directory
+const PKGFILE_CACHE_DIR: &str = "/var/cache/pkgfile";
+
+/// Maximum time lookups are cached, even if the databases did not change
+const LOOKUP_CACHE_TTL: Duration = Duration::from_hours(24);
+
+/// Cache of lookups, shared by files databases opened in the process
+static LOOKUP_CACHE: OnceLock<Arc<LookupCache>> = OnceLock::new();
+
+/// Package of a sync repository
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct RepoPackage {
+    repo: String,
+    name: String,
+}
+
+impl RepoPackage {
+    /// Repository name
+    #[must_use]
+    pub fn repo(&self) -> &str {
+        &self.repo
+    }
+
+    /// Package name
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl fmt::Display for RepoPackage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.repo, self.name)
+    }
+}
+
+/// Where repository file lists come from
+enum Source {
+    /// Pacman files databases, repository names and database paths
+    Pacman(Vec<(String, PathBuf)>),
+    /// pkgfile database directory, queried with the pkgfile command
+    Pkgfile(PathBuf),
+}
+
+/// Value of a lookup that can be cached
+trait CachedLookup: Sized {
+    fn to_value(&self) -> Value;
+
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl CachedLookup for Vec<RepoPackage> {
+    fn to_value(&self) -> Value {
+        Value::Array(self.iter().map(|p| p.to_string().into()).collect())
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value
+            .as_array()?
+            .iter()
+            .map(|p| {
+                let (repo, name) = p.as_str()?.split_once('/')?;
+                Some(RepoPackage {
+                    repo: repo.to_owned(),
+                    name: name.to_owned(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl CachedLookup for Vec<String> {
+    fn to_value(&self) -> Value {
+        Value::Array(self.iter().map(|f| f.as_str().into()).collect())
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value
+            .as_array()?
+            .iter()
+            .map(|f| f.as_str().map(ToOwned::to_owned))
+            .collect()
+    }
+}
+
+/// Identify the state of database files by their paths and modification times
+fn db_stamp(files: impl IntoIterator<Item = PathBuf>) -> String {
+    files
+        .into_iter()
+        .map(|path| {
+            let modified = fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_nanos());
+            format!("{}:{modified}", path.display())
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// File lists of the configured repositories, from pacman files databases or the pkgfile database.
+///
+/// Databases are read again for each query, to avoid keeping all repository files in memory,
+/// so queries should be batched.
+pub struct FilesDb {
+    source: Source,
+    cache: Arc<LookupCache>,
+    /// State of the databases, cached lookups of another state are ignored
+    stamp: String,
+}
+
+impl FilesDb {
+    /// Find pacman files databases, falling back to the pkgfile database,
+    /// return `None` if neither was downloaded (with `pacman -Fy` or `pkgfile -u`)
+    ///
+    /// # Errors
+    ///
+    /// Fails if the pacman files databases can not be listed
+    pub fn open() -> anyhow::Result<Option<Self>> {
+        let source = if let Some(dbs) = Self::pacman_dbs()? {
+            Source::Pacman(dbs)
+        } else {
+            let cache_dir = root::resolve(Path::new(PKGFILE_CACHE_DIR));
+            let has_pkgfile_db = cache_dir
+                .read_dir()
+                .is_ok_and(|mut entries| entries.next().is_some());
+            if !has_pkgfile_db {
+                return Ok(None);
+            }
+            log::debug!("Using pkgfile database in {}", cache_dir.display());
+            Source::Pkgfile(cache_dir)
+        };
+        let stamp = match &source {
+            Source::Pacman(dbs) => db_stamp(dbs.iter().map(|(_, p)| p.to_owned())),
+            Source::Pkgfile(cache_dir) => {
+                let mut files: Vec<_> = cache_dir
+                    .read_dir()?
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .collect();
+                files.sort_unstable();
+                db_stamp(files)
+            }
+        };
+        let cache = LOOKUP_CACHE
+            .get_or_init(|| Arc::new(LookupCache::new(PathBuf::from(STATE_DIR).join("files"))));
+        Ok(Some(Self {
+            source,
+            cache: Arc::clone(cache),
+            stamp,
+        }))
+    }
+
+    /// Get cached lookups of `keys`, and look up the others with `lookup`, caching what they found or not
+    fn cached_lookup<T: CachedLookup>(
+        &self,
+        kind: &str,
+        keys: &[String],
+        lookup: impl FnOnce(&[String]) -> anyhow::Result<HashMap<String, T>>,
+    ) -> anyhow::Result<HashMap<String, T>> {
+        let mut results = HashMap::new();
+        let mut uncached = Vec::new();
+        for key in keys {
+            let cached = self
+                .cache
+                .get(kind, key, LOOKUP_CACHE_TTL)
+                .filter(|v| v.get("stamp").and_then(Value::as_str) == Some(self.stamp.as_str()));
+            match cached.as_ref().and_then(|v| v.get("value")) {
+                // Nothing found
+                Some(Value::Null) => {}
+                Some(value) => {
+                    if let Some(result) = T::from_value(value) {
+                        results.insert(key.to_owned(), result);
+                    } else {
+                        uncached.push(key.to_owned());
+                    }
+                }
+                None => uncached.push(key.to_owned()),
+            }
+        }
+        if uncached.is_empty() {
+            return Ok(results);
+        }
+        let found = lookup(&uncached)?;
+        for key in &uncached {
+            let value = found.get(key).map_or(Value::Null, CachedLookup::to_value);
+            self.cache.set(
+                kind,
+                key,
+                Value::Object(vec![
+                    ("stamp".to_owned(), self.stamp.as_str().into()),
+                    ("value".to_owned(), value),
+                ]),
+            );
+        }
+        results.extend(found);
+        Ok(results)
+    }
+
+    /// Find pacman files databases of the configured repositories, if any was downloaded
+    fn pacman_dbs() -> anyhow::Result<Option<Vec<(String, PathBuf)>>> {
+        let sync_dir = syncdb::sync_dir();
+        let dbs: Vec<_> = syncdb::repositories()
+            .classify(ErrorKind::PacmanQuery)?
+            .into_iter()
+            .map(|r| {
+                let path = sync_dir.join(format!("{r}.files"));
+                (r, path)
+            })
+            .filter(|(r, p)| {
+                let exists = p.is_file();
+                if !exists {
+                    log::debug!("No files database for repository {r:?}");
+                }
+                exists
+            })
+            .collect();
+        Ok((!dbs.is_empty()).then_some(dbs))
+    }
+
+    /// Call `callback` for each file of each package of pacman files databases,
+    /// with its path relative to the root
+    fn for_each_file<F>(dbs: &[(String, PathBuf)], mut callback: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&RepoPackage, &str),
+    {
+        for (repo, path) in dbs {
+            let tar = syncdb::read_archive(path)?;
+            scan_files(repo, &tar, &mut callback).classify(ErrorKind::PacmanQuery)?;
+        }
+        Ok(())
+    }
+
+    /// Find packages providing files, by absolute path
+    ///
+    /// # Errors
+    ///
+    /// Fails if the files database can not be read
+    pub fn path_providers(
+        &self,
+        paths: &[PathBuf],
+    ) -> anyhow::Result<HashMap<PathBuf, Vec<RepoPackage>>> {
+        let keys: Vec<_> = paths
+            .iter()
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect();
+        let providers = self.cached_lookup("paths", &keys, |uncached| {
+            let uncached: Vec<_> = uncached.iter().map(PathBuf::from).collect();
+            Ok(self
+                .find_path_providers(&uncached)?
+                .into_iter()
+                .map(|(p, packages)| (p.to_string_lossy().into_owned(), packages))
+                .collect())
+        })?;
+        Ok(providers
+            .into_iter()
+            .map(|(p, packages)| (PathBuf::from(p), packages))
+            .collect())
+    }
+
+    /// Find packages providing files, by absolute path, without the cache
+    fn find_path_providers(
+        &self,
+        paths: &[PathBuf],
+    ) -> anyhow::Result<HashMap<PathBuf, Vec<RepoPackage>>> {
+        let dbs = match &self.source {
+            Source::Pacman(dbs) => dbs,
+            Source::Pkgfile(cache_dir) => {
+                let mut providers = HashMap::new();
+                for path in paths {
+                    let packages = pkgfile_query(cache_dir, path)?;
+                    if !packages.is_empty() {
+                        providers.insert(path.to_owned(), packages);
+                    }
+                }
+                return Ok(providers);
+            }
+        };
+        let wanted: HashSet<_> = paths
+            .iter()
+            .filter_map(|p| p.strip_prefix("/").ok()?.to_str())
+            .collect();
+        let mut providers: HashMap<PathBuf, Vec<RepoPackage>> = HashMap::new();
+        Self::for_each_file(dbs, |package, file| {
+            if wanted.contains(file) {
+                providers
+                    .entry(Path::new("/").join(file))
+                    .or_default()
+                    .push(package.to_owned());
+            }
+        })?;
+        Ok(providers)
+    }
+
+    /// Find packages providing libraries, by soname
+    ///
+    /// # Errors
+    ///
+    /// Fails if the files database can not be read
+    pub fn soname_providers(
+        &self,
+        sonames: &[String],
+    ) -> anyhow::Result<HashMap<String, Vec<RepoPackage>>> {
+        self.cached_lookup("sonames", sonames, |uncached| {
+            self.find_soname_providers(uncached)
+        })
+    }
+
+    /// Find packages providing libraries, by soname, without the cache
+    fn find_soname_providers(
+        &self,
+        sonames: &[String],
+    ) -> anyhow::Result<HashMap<String, Vec<RepoPackage>>> {
+        let mut providers: HashMap<String, Vec<RepoPackage>> = HashMap::new();
+        let dbs = match &self.source {
+            Source::Pacman(dbs) => dbs,
+            Source::Pkgfile(_) => {
+                let paths: Vec<_> = sonames
+                    .iter()
+                    .map(|s| Path::new("/").join(LIBRARY_DIR).join(s))
+                    .collect();
+                for (path, packages) in self.find_path_providers(&paths)? {
+                    if let Some(soname) = path.file_name().and_then(|n| n.to_str()) {
+                        providers.insert(soname.to_owned(), packages);
+                    }
+                }
+                return Ok(providers);
+            }
+        };
+        let wanted: HashSet<_> = sonames.iter().map(String::as_str).collect();
+        Self::for_each_file(dbs, |package, file| {
+            if let Some(soname) = file
+                .strip_prefix(LIBRARY_DIR)
+                .filter(|n| !n.contains('/') && wanted.contains(n))
+            {
+                providers
+                    .entry(soname.to_owned())
+                    .or_default()
+                    .push(package.to_owned());
+            }
+        })?;
+        Ok(providers)
+    }
+
+    /// Get file lists of packages by name, with paths relative to the root.
+    ///
+    /// Like pacman, the first repository with a package is used. Packages missing from the result
+    /// are not in any repository.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the files database can not be read
+    pub fn package_files(&self, names: &[String]) -> anyhow::Result<HashMap<String, Vec<String>>> {
+        self.cached_lookup("packages", names, |uncached| {
+            self.find_package_files(uncached)
+        })
+    }
+
+    /// Get file lists of packages by name, without the cache
+    fn find_package_files(&self, names: &[String]) -> anyhow::Result<HashMap<String, Vec<String>>> {
+        let dbs = match &self.source {
+            Source::Pacman(dbs) => dbs,
+            Source::Pkgfile(cache_dir) => {
+                let mut files = HashMap::new();
+                for name in names {
+                    if let Some(list) = pkgfile_list(cache_dir, name)? {
+                        files.insert(name.to_owned(), list);
+                    }
+                }
+                return Ok(files);
+            }
+        };
+        let wanted: HashSet<_> = names.iter().map(String::as_str).collect();
+        let mut files: HashMap<String, (String, Vec<String>)> = HashMap::new();
+        Self::for_each_file(dbs, |package, file| {
+            if !wanted.contains(package.name()) {
+                return;
+            }
+            let (repo, list) = files
+                .entry(package.name.clone())
+                .or_insert_with(|| (package.repo.clone(), Vec::new()));
+            if *repo == package.repo {
+                list.push(file.to_owned());
+            }
+        })?;
+        Ok(files
+            .into_iter()
+            .map(|(name, (_, list))| (name, list))
+            .collect())
+    }
+}
+
+/// Call `callback` for each file listed in a files database tar archive
+fn scan_files<F>(repo: &str, tar: &[u8], callback: &mut F) -> anyhow::Result<()>
+where
+    F: FnMut(&RepoPackage, &str),
+{
+    for (path, data) in syncdb::tar_entries(tar)? {
+        // Entries are <name>-<pkgver>-<pkgrel>/files
+        let Some(name) = path.strip_suffix("/files").and_then(localdb::package_name) else {
+            continue;
+        };
+        let package = RepoPackage {
+            repo: repo.to_owned(),
+            name: name.to_owned(),
+        };
+        let files = String::from_utf8_lossy(data);
+        for file in localdb::parse_sections(&files)
+            .remove("FILES")
+            .unwrap_or_default()
+        {
+            callback(&package, file);
+        }
+    }
+    Ok(())
+}
+
+/// Run pkgfile on its database, and get its output, `None` if nothing matched
+fn run_pkgfile(cache_dir: &Path, args: &[&OsStr]) -> anyhow::Result<Option<String>> {
+    let output = privileges::command("pkgfile")
+        .arg("--cachedir")
+        .arg(cache_dir)
+        .args(args)
+        .output()
+        .context("Failed to run pkgfile")?;
+    match output.status.code() {
+        Some(0) => Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned())),
+        // No match
+        Some(1) => Ok(None),
+        _ => Err(anyhow::anyhow!(
+            "pkgfile failed for {}: {}",
+            args.iter()
+                .map(|a| a.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .classify(ErrorKind::PacmanQuery),
+    }
+}
+
+/// Find packages providing a file by absolute path, with the pkgfile command
+fn pkgfile_query(cache_dir: &Path, path: &Path) -> anyhow::Result<Vec<RepoPackage>> {
+    run_pkgfile(cache_dir, &[path.as_os_str()])?
+        .map_or_else(|| Ok(Vec::new()), |o| parse_pkgfile_output(&o))
+}
+
+/// Get the file list of a package with the pkgfile command, `None` if it is not in any repository
+fn pkgfile_list(cache_dir: &Path, package: &str) -> anyhow::Result<Option<Vec<String>>> {
+    run_pkgfile(cache_dir, &[OsStr::new("--list"), OsStr::new(package)])?
+        .map(|o| parse_pkgfile_list(&o))
+        .transpose()
+}
+
+/// Parse pkgfile output, one `<repo>/<package>` per line
+fn parse_pkgfile_output(output: &str) -> anyhow::Result<Vec<RepoPackage>> {
+    output
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(|l| {
+            let (repo, name) = l
+                .split_once('/')
+                .ok_or_else(|| anyhow::anyhow!("Unexpected pkgfile output line {l:?}"))?;
+            Ok(RepoPackage {
+                repo: repo.to_owned(),
+                name: name.to_owned(),
+            })
+        })
+        .collect::<anyhow::Result<_>>()
+        .classify(ErrorKind::PacmanQuery)
+}
+
+/// Parse pkgfile file list output, `<repo>/<package>` and an absolute path per line,
+/// keeping files of the first repository
+fn parse_pkgfile_list(output: &str) -> anyhow::Result<Vec<String>> {
+    let mut first_repo = None;
+    let mut files = Vec::new();
+    for line in output.lines().filter(|l| !l.is_empty()) {
+        let (package, path) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| anyhow::anyhow!("Unexpected pkgfile output line {line:?}"))
+            .classify(ErrorKind::PacmanQuery)?;
+        if *first_repo.get_or_insert(package) == package {
+            let path = path.trim_start();
+            files.push(path.strip_prefix('/').unwrap_or(path).to_owned());
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tar_entry(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut entry = vec![0; 512];
+        entry[..name.len()].copy_from_slice(name.as_bytes());
+        let size = format!("{:011o}", data.len());
+        entry[124..124 + size.len()].copy_from_slice(size.as_bytes());
+        entry[156] = b'0';
+        entry[257..262].copy_from_slice(b"ustar");
+        entry.extend_from_slice(data);
+        entry.resize(512 + data.len().div_ceil(512) * 512, 0);
+        entry
+    }
+
+    #[test]
+    fn test_scan_files() {
+        let mut tar = tar_entry("zlib-1:1.3.1-2/desc", b"%NAME%\nzlib\n");
+        tar.extend(tar_entry(
+            "zlib-1:1.3.1-2/files",
+            b"%FILES%\nusr/\nusr/lib/\nusr/lib/libz.so.1\nusr/share/man/man3/zlib.3.gz\n",
+        ));
+        tar.extend(vec![0; 1024]);
+
+        let mut files = Vec::new();
+        scan_files("core", &tar, &mut |p: &RepoPackage, f: &str| {
+            files.push((p.to_string(), f.to_owned()));
+        })
+        .unwrap();
+        assert_eq!(
+            files,
+            [
+                ("core/zlib".to_owned(), "usr/".to_owned()),
+                ("core/zlib".to_owned(), "usr/lib/".to_owned()),
+                ("core/zlib".to_owned(), "usr/lib/libz.so.1".to_owned()),
+                (
+                    "core/zlib".to_owned(),
+                    "usr/share/man/man3/zlib.3.gz".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cached_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = FilesDb {
+            source: Source::Pacman(Vec::new()),
+            cache: Arc::new(LookupCache::new(dir.path().to_owned())),
+            stamp: "core.files:1".to_owned(),
+        };
+        let lookups = std::cell::RefCell::new(Vec::new());
+        let lookup = |files_db: &FilesDb, names: &[String]| {
+            files_db
+                .cached_lookup("packages", names, |uncached| {
+                    lookups.borrow_mut().push(uncached.to_vec());
+                    Ok(HashMap::from([(
+                        "zlib".to_owned(),
+                        vec!["usr/lib/libz.so.1".to_owned()],
+                    )]))
+                })
+                .unwrap()
+        };
+        let names = ["zlib".to_owned(), "foo".to_owned()];
+        for _ in 0..2 {
+            let files = lookup(&db, &names);
+            assert_eq!(files.len(), 1);
+            assert_eq!(files["zlib"], ["usr/lib/libz.so.1"]);
+        }
+        // Results of an updated database are looked up again
+        db.stamp = "core.files:2".to_owned();
+        lookup(&db, &names[..1]);
+        assert_eq!(
+            lookups.into_inner(),
+            [names.to_vec(), vec!["zlib".to_owned()]]
+        );
+    }
+
+    #[test]
+    fn test_parse_pkgfile_output() {
+        let packages = parse_pkgfile_output("core/zlib\nextra/lib32-zlib\n").unwrap();
+        assert_eq!(
+            packages.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["core/zlib", "extra/lib32-zlib"]
+        );
+        assert!(parse_pkgfile_output("error\n").is_err());
+
+        let files = parse_pkgfile_list(
+            "core/zlib\t/usr/\ncore/zlib\t/usr/lib/libz.so.1\ntesting/zlib\t/usr/lib/libz.so.2\n",
+        )
+        .unwrap();
+        assert_eq!(files, ["usr/", "usr/lib/libz.so.1"]);
+    }
+}
@@ -0,0 +1,2140 @@
+//! Problems found by checks
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    hints,
+    history::LibraryOrigin,
+    json::{ToJson, Value},
+    origins::{self, PackageOrigin},
+    overrides, reports,
+    schema::SCHEMA_VERSION,
+};
+
+/// Check producing findings
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CheckKind {
+    /// Missing shared library dependencies of package executables
+    MissingDependencies,
+    /// Python packages installed for an older interpreter, and scripts pinning a removed one
+    PythonPackages,
+    /// Broken systemd enablement links, and enabled socket and path units activating a broken service
+    ServiceLinks,
+    /// Display manager, default target and getty units pointing to a missing unit or program
+    LoginUnits,
+    /// Libraries of a package shadowing the ones of another package in the search path
+    DuplicateLibraries,
+    /// Libraries and loaders of the search path that users other than root can not load
+    LibraryPermissions,
+    /// Soname links of the search path pointing to a removed library version
+    LibraryLinks,
+    /// Introspection typelibs with a missing shared library
+    IntrospectionTypelibs,
+    /// Perl XS modules linked against a removed libperl
+    PerlModules,
+    /// Executables requiring symbol versions their libraries do not define
+    SymbolVersions,
+    /// Packages whose install scriptlets failed in recent transactions
+    ScriptletFailures,
+    /// Files owned by no package that appeared during a transaction
+    UnownedFiles,
+    /// Configuration files with a pending pacnew update
+    PacnewFiles,
+    /// Installed files not matching the checksums recorded by their package
+    FileChecksums,
+    /// Files owned by several installed packages
+    FileConflicts,
+    /// Broken convention symlinks choosing a program among several, like `/usr/bin/cc`
+    ConventionLinks,
+    /// Package symlinks in a loop, too deep a chain, or pointing into `/home` or `/tmp`
+    SymlinkChains,
+    /// Entries of fstab, crypttab and mount units with a missing device, tool or key file
+    BootMounts,
+    /// Kernel command lines and boot loader entries referencing files that are not installed
+    KernelCmdline,
+    /// Boot images not signed with a key enrolled for Secure Boot
+    SecureBoot,
+    /// Firmware files the running kernel failed to load that are not installed
+    ModuleFirmware,
+    /// Foreign packages deleted, orphaned or out of date in the AUR
+    AurPackages,
+    /// Foreign packages built long before an upgrade of their repository dependencies
+    StaleBuilds,
+    /// Installed packages still needing the OpenSSL 1.x or Python 2 runtimes
+    LegacyRuntimes,
+    /// Installed packages affected by security advisories
+    SecurityAdvisories,
+    /// Custom check, running an external command
+    External,
+}
+
+impl CheckKind {
+    /// All check kinds
+    pub const ALL: [Self; 26] = [
+        Self::MissingDependencies,
+        Self::PythonPackages,
+        Self::ServiceLinks,
+        Self::LoginUnits,
+        Self::DuplicateLibraries,
+        Self::LibraryPermissions,
+        Self::LibraryLinks,
+        Self::IntrospectionTypelibs,
+        Self::PerlModules,
+        Self::SymbolVersions,
+        Self::ScriptletFailures,
+        Self::UnownedFiles,
+        Self::PacnewFiles,
+        Self::FileChecksums,
+        Self::FileConflicts,
+        Self::ConventionLinks,
+        Self::SymlinkChains,
+        Self::BootMounts,
+        Self::KernelCmdline,
+        Self::SecureBoot,
+        Self::ModuleFirmware,
+        Self::AurPackages,
+        Self::StaleBuilds,
+        Self::LegacyRuntimes,
+        Self::SecurityAdvisories,
+        Self::External,
+    ];
+
+    /// Stable name, used in machine readable output
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::MissingDependencies => "missing-dependencies",
+            Self::PythonPackages => "python-packages",
+            Self::ServiceLinks => "service-links",
+            Self::LoginUnits => "login-units",
+            Self::DuplicateLibraries => "duplicate-libraries",
+            Self::LibraryPermissions => "library-permissions",
+            Self::LibraryLinks => "library-links",
+            Self::IntrospectionTypelibs => "introspection-typelibs",
+            Self::PerlModules => "perl-modules",
+            Self::SymbolVersions => "symbol-versions",
+            Self::ScriptletFailures => "scriptlet-failures",
+            Self::UnownedFiles => "unowned-files",
+            Self::PacnewFiles => "pacnew-files",
+            Self::FileChecksums => "file-checksums",
+            Self::FileConflicts => "file-conflicts",
+            Self::ConventionLinks => "convention-links",
+            Self::SymlinkChains => "symlink-chains",
+            Self::BootMounts => "boot-mounts",
+            Self::KernelCmdline => "kernel-cmdline",
+            Self::SecureBoot => "secure-boot",
+            Self::ModuleFirmware => "module-firmware",
+            Self::AurPackages => "aur-packages",
+            Self::StaleBuilds => "stale-builds",
+            Self::LegacyRuntimes => "legacy-runtimes",
+            Self::SecurityAdvisories => "security-advisories",
+            Self::External => "external",
+        }
+    }
+}
+
+impl fmt::Display for CheckKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Problem of a foreign package in the AUR
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AurStatus {
+    /// Not in the AUR, it was deleted or merged into another package
+    NotFound,
+    /// Without maintainer
+    Orphaned,
+    /// Flagged out of date
+    OutOfDate,
+}
+
+impl AurStatus {
+    /// Stable name, used in machine readable output
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::NotFound => "not-found",
+            Self::Orphaned => "orphaned",
+            Self::OutOfDate => "out-of-date",
+        }
+    }
+}
+
+/// What an entry set up at boot is missing
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum BootProblem {
+    /// Device, or device tag like `UUID=…`, matching no device
+    Device,
+    /// fsck tool of the filesystem type
+    Fsck,
+    /// Program mounting the filesystem type, like `mount.nfs`
+    MountHelper,
+    /// Key file of the encrypted device
+    KeyFile,
+}
+
+impl BootProblem {
+    /// Stable name, used in machine readable output
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Device => "device",
+            Self::Fsck => "fsck",
+            Self::MountHelper => "mount-helper",
+            Self::KeyFile => "key-file",
+        }
+    }
+}
+
+/// What is wrong with the chain of symlinks starting at a package symlink
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ChainProblem {
+    /// A link of the chain points back to an earlier one
+    Circular,
+    /// More links than a packaged file is expected to go through
+    TooDeep,
+    /// A link points below `/home` or `/tmp`, whose content differs between systems and users
+    UserDirectory,
+}
+
+impl ChainProblem {
+    /// Stable name, used in machine readable output
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Circular => "circular",
+            Self::TooDeep => "too-deep",
+            Self::UserDirectory => "user-directory",
+        }
+    }
+}
+
+/// File a boot configuration refers to
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum BootReference {
+    /// Kernel, initramfs or microcode image loaded by the boot loader
+    Image,
+    /// Program started as init, with the `init` kernel parameter
+    Init,
+    /// Theme of the `plymouth.theme` kernel parameter
+    PlymouthTheme,
+}
+
+impl BootReference {
+    /// Stable name, used in machine readable output
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Image => "image",
+            Self::Init => "init",
+            Self::PlymouthTheme => "plymouth-theme",
+        }
+    }
+}
+
+/// How to fix a package with files in Python directories ignored by installed interpreters
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PythonAction {
+    /// Foreign package, to build again for the current interpreter
+    Rebuild,
+    /// Package of the repositories, which have a version for the current interpreter
+    Reinstall,
+    /// Foreign package with no other files, that no package depends on
+    Remove,
+}
+
+impl PythonAction {
+    /// Stable name, used in machine readable output
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rebuild => "rebuild",
+            Self::Reinstall => "reinstall",
+            Self::Remove => "remove",
+        }
+    }
+}
+
+/// Reason a check did not complete
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SkipReason {
+    /// Still running after its timeout
+    Timeout(Duration),
+    /// Panicked, with the panic message
+    Panic(String),
+    /// Still running when another check found problems of at least this severity, and the scan stopped early
+    EarlyExit(Severity),
+    /// Needs network access, and the network is offline
+    Offline,
+    /// Queries of these packages kept failing while a pacman transaction was writing the local database, or queries
+    /// preparing the check if there are none
+    DbBusy(Vec<String>),
+}
+
+impl SkipReason {
+    /// Stable name, used in machine readable output
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Timeout(_) => "timeout",
+            Self::Panic(_) => "panic",
+            Self::EarlyExit(_) => "early-exit",
+            Self::Offline => "offline",
+            Self::DbBusy(_) => "db-busy",
+        }
+    }
+}
+
+/// How serious a finding is
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    /// Worth knowing, nothing is broken
+    Info,
+    /// Something may not work as expected
+    Warning,
+    /// Something is broken
+    Error,
+}
+
+impl Severity {
+    /// Stable name, used in machine readable output
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "info" => Ok(Self::Info),
+            "warning" => Ok(Self::Warning),
+            "error" => Ok(Self::Error),
+            _ => anyhow::bail!("Invalid severity {s:?}"),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A problem found on the system
+pub enum Finding {
+    /// Executable file with a dependency not found by the dynamic loader
+    MissingDependency {
+        /// Package owning the file
+        package: Arc<String>,
+        /// Executable file
+        file: Arc<PathBuf>,
+        /// Missing library soname
+        dependency: PathBuf,
+        /// Package version that provided the library, if found
+        origin: Option<LibraryOrigin>,
+    },
+    /// Executable file with a missing dependency provided by a dependency of its package that is not installed
+    DependencyNotInstalled {
+        /// Package owning the file
+        package: Arc<String>,
+        /// Executable file
+        file: Arc<PathBuf>,
+        /// Missing library soname
+        dependency: PathBuf,
+        /// Declared dependency providing the library
+        declared: String,
+    },
+    /// Executable file with a missing dependency provided by an uninstalled optional dependency of its package
+    OptionalDependencyMissing {
+        /// Package owning the file
+        package: Arc<String>,
+        /// Executable file
+        file: Arc<PathBuf>,
+        /// Missing library soname
+        dependency: PathBuf,
+        /// Optional dependency providing the library
+        optional: String,
+    },
+    /// Executable file installed by no package with a dependency not found by the dynamic loader, checked on demand
+    UnpackagedMissingDependency {
+        /// Executable file
+        file: PathBuf,
+        /// Missing library soname
+        dependency: PathBuf,
+    },
+    /// Executable file much smaller than recorded by its package, corrupted by a full disk or an interrupted upgrade
+    TruncatedFile {
+        /// Package owning the file
+        package: Arc<String>,
+        /// Executable file
+        file: Arc<PathBuf>,
+        /// Size on disk in bytes
+        size: u64,
+        /// Size recorded by the package in bytes
+        expected: u64,
+    },
+    /// Executable file in a standard directory built for another architecture, operating system or libc
+    ForeignBinary {
+        /// Package owning the file
+        package: Arc<String>,
+        /// Executable file
+        file: Arc<PathBuf>,
+        /// Platform the file is built for
+        platform: String,
+    },
+    /// Executable file outside of /opt using a library of /opt, found through the ld.so.conf search path
+    OptLibraryDependency {
+        /// Package owning the file
+        package: Arc<String>,
+        /// Executable file
+        file: Arc<PathBuf>,
+        /// Library of /opt loaded for the file
+        library: PathBuf,
+        /// Package owning the library, if any
+        provider: Option<String>,
+    },
+    /// Executable file with a dependency that an upgrade will remove
+    UpgradeBreakage {
+        /// Package owning the file
+        package: Arc<String>,
+        /// Executable file
+        file: Arc<PathBuf>,
+        /// Library soname the upgrade removes
+        dependency: String,
+        /// Upgraded package currently providing the library
+        upgrade: String,
+    },
+    /// Executable file requiring symbol versions that the library found for one of its dependencies does not define
+    MissingSymbolVersions {
+        /// Package owning the file
+        package: String,
+        /// Executable file
+        file: PathBuf,
+        /// Library soname
+        soname: String,
+        /// Library found for the soname
+        library: PathBuf,
+        /// Required versions the library does not define, like `GLIBC_2.38`
+        versions: Vec<String>,
+        /// Package owning the library, if any
+        provider: Option<String>,
+    },
+    /// Package with files in a directory not used by the current Python interpreter
+    BrokenPythonPackage {
+        /// Package owning files in the directories
+        package: String,
+        /// Stale Python library directories, sorted
+        dirs: Vec<String>,
+        /// Number of files of the package in the directories
+        files: u64,
+        /// Total size of these files in bytes
+        size: u64,
+        /// Suggested fix
+        action: PythonAction,
+    },
+    /// Directory of a Python interpreter that is not installed anymore, owned by no package, like leftovers of pip
+    UnownedPythonDir {
+        /// Stale Python library directory
+        dir: String,
+        /// Total size of its files in bytes
+        size: u64,
+    },
+    /// Script whose shebang pins a Python interpreter version that is not installed, instead of the generic `python3`
+    PinnedPythonScript {
+        /// Script path
+        path: PathBuf,
+        /// Missing interpreter, as written in the shebang
+        interpreter: String,
+        /// Package owning the script, if any
+        package: Option<String>,
+    },
+    /// Systemd enablement symlink pointing to a missing unit
+    BrokenServiceLink {
+        /// Enablement symlink path
+        link: PathBuf,
+        /// Package owning the symlink, if the service is enabled by the package rather than with systemctl
+        package: Option<String>,
+    },
+    /// Enabled socket or path unit activating a service that is missing, or runs a missing program
+    BrokenActivatedService {
+        /// Enabled socket or path unit, like `cups.socket`
+        unit: String,
+        /// Enablement symlink path
+        link: PathBuf,
+        /// Service the unit activates, like `cups.service`
+        service: String,
+        /// Missing unit file or program of the service
+        missing: String,
+        /// Whether the missing file is a program of the service, rather than the service itself
+        program: bool,
+        /// Package owning the service, if a program is missing
+        package: Option<String>,
+    },
+    /// Unit needed to log in after the next boot, missing or running a missing program
+    BrokenLoginUnit {
+        /// Unit name, like `display-manager.service`
+        unit: String,
+        /// Unit file, or link to it
+        path: PathBuf,
+        /// Missing unit file or program
+        missing: String,
+        /// Whether the missing file is a program of the unit, rather than the unit itself
+        program: bool,
+        /// Package owning the unit, if a program is missing
+        package: Option<String>,
+    },
+    /// Library found first in the search path, hiding a library of the same soname from another package
+    ShadowedLibrary {
+        /// Library soname
+        soname: String,
+        /// Package owning the library loaded
+        package: String,
+        /// Path of the library loaded
+        path: PathBuf,
+        /// Package owning the hidden library
+        shadowed_package: String,
+        /// Path of the hidden library
+        shadowed_path: PathBuf,
+    },
+    /// Library not installed by a package, found first in the search path and hiding a packaged library
+    UnpackagedLibrary {
+        /// Library soname
+        soname: String,
+        /// Path of the library loaded
+        path: PathBuf,
+        /// Whether users other than root can modify the library
+        writable: bool,
+        /// Package owning the hidden library
+        shadowed_package: String,
+        /// Path of the hidden library
+        shadowed_path: PathBuf,
+    },
+    /// Library or dynamic loader of the search path, with permissions preventing users other than root to load it
+    LibraryPermissions {
+        /// Library file, links to it are not reported
+        path: PathBuf,
+        /// Permission bits
+        mode: u32,
+        /// Whether the library is a dynamic loader, which must also be executable
+        loader: bool,
+        /// Package owning the library, if any
+        package: Option<String>,
+    },
+    /// Soname link of the search path pointing to a removed version of the library, that ldconfig did not update
+    StaleLibraryLink {
+        /// Soname link path
+        link: PathBuf,
+        /// Missing target of the link
+        target: PathBuf,
+        /// Installed version of the library, that ldconfig would point the link to
+        library: PathBuf,
+        /// Package owning the installed library, if any
+        package: Option<String>,
+    },
+    /// Introspection typelib whose shared library, or one of its dependencies, is not found
+    BrokenTypelib {
+        /// Typelib path
+        path: PathBuf,
+        /// Missing library soname
+        dependency: PathBuf,
+        /// Package owning the typelib, if any
+        package: Option<String>,
+    },
+    /// Perl XS module linked against the libperl of a Perl version that is not installed
+    BrokenPerlModule {
+        /// Shared object of the module
+        path: PathBuf,
+        /// Missing libperl
+        dependency: PathBuf,
+        /// Package owning the module, if any
+        package: Option<String>,
+    },
+    /// Package version installed by a recent transaction, whose install or upgrade scriptlet failed
+    FailedScriptlet {
+        /// Package name
+        package: String,
+        /// Installed version, that the scriptlet was run for
+        version: String,
+        /// Day of the transaction, like `2024-03-05`
+        date: String,
+        /// First error printed by the scriptlet
+        error: String,
+    },
+    /// File of a watched directory owned by no package, that appeared during a transaction
+    UnownedFile {
+        /// File path
+        path: PathBuf,
+        /// Unix time of the end of the transaction
+        appeared: u64,
+    },
+    /// Modified configuration file, with a new version from the package installed beside it
+    PacnewFile {
+        /// Configuration file path
+        path: PathBuf,
+    },
+    /// Installed file with content different from the one recorded by its package
+    ChecksumMismatch {
+        /// Package owning the file
+        package: String,
+        /// File path
+        path: PathBuf,
+    },
+    /// File owned by several installed packages, installed with `--overwrite`
+    FileConflict {
+        /// File path
+        path: PathBuf,
+        /// Owning packages, sorted
+        packages: Vec<String>,
+    },
+    /// Convention symlink pointing to a missing file, like `/usr/bin/cc` after removing the compiler it chose
+    DanglingConventionLink {
+        /// Symlink path
+        link: PathBuf,
+        /// Final target of the symlink
+        target: PathBuf,
+    },
+    /// Convention symlink pointing to a file owned by no package, like a manually installed program
+    UnpackagedConventionLink {
+        /// Symlink path
+        link: PathBuf,
+        /// Final target of the symlink
+        target: PathBuf,
+    },
+    /// Symlink of a package whose chain of links is a packaging error
+    BadSymlinkChain {
+        /// Package owning the symlink
+        package: String,
+        /// Symlink path
+        link: PathBuf,
+        /// Link of the chain where the problem is: the one pointing back, the last one followed, or the one
+        /// pointing into a user directory
+        target: PathBuf,
+        /// What is wrong with the chain
+        problem: ChainProblem,
+    },
+    /// Entry of fstab, crypttab or a mount unit that can make the next boot fail
+    BrokenBootEntry {
+        /// File of the entry
+        path: PathBuf,
+        /// Mount point, or name of the encrypted device
+        entry: String,
+        /// Missing device, tool path or key file
+        missing: String,
+        /// What is missing
+        problem: BootProblem,
+    },
+    /// Kernel command line or boot loader entry referring to a file that is not installed
+    BrokenBootReference {
+        /// Boot configuration file
+        path: PathBuf,
+        /// Path of the image or init, or name of the theme
+        missing: String,
+        /// What the missing file is
+        reference: BootReference,
+    },
+    /// Kernel or unified kernel image that the firmware refuses to boot with Secure Boot enabled
+    UnsignedBootImage {
+        /// Image path
+        path: PathBuf,
+        /// Whether the image is signed, with a key that is not enrolled
+        signed: bool,
+    },
+    /// Firmware file that the running kernel failed to load, and that is still not installed
+    MissingFirmware {
+        /// Firmware file, relative to the firmware directory
+        firmware: String,
+        /// Loaded kernel module that requested it, if known
+        module: Option<String>,
+        /// Repository package providing it, if known
+        provider: Option<String>,
+    },
+    /// Foreign package with a problem in the AUR
+    AurPackage {
+        /// Package name
+        package: String,
+        /// Problem
+        status: AurStatus,
+    },
+    /// Foreign package built long before the installed versions of some of its repository dependencies, that may
+    /// need a rebuild against them
+    StaleBuild {
+        /// Package name
+        package: String,
+        /// Unix time the package was built at
+        built: u64,
+        /// Repository dependencies installed long after the build, sorted
+        dependencies: Vec<String>,
+    },
+    /// Legacy runtime that installed packages still need
+    LegacyRuntime {
+        /// Compatibility package of the runtime, like `openssl-1.1`
+        runtime: String,
+        /// Packages needing it, sorted
+        packages: Vec<String>,
+    },
+    /// Installed package version affected by a security advisory
+    VulnerablePackage {
+        /// Package name
+        package: String,
+        /// Installed version
+        version: String,
+        /// Security tracker vulnerability group, like `AVG-1234`
+        advisory: String,
+        /// Security tracker severity, like `High`
+        risk: String,
+        /// Vulnerability type, like `arbitrary code execution`
+        issue_type: String,
+        /// First version with the vulnerabilities fixed, if any
+        fixed: Option<String>,
+    },
+    /// Problem reported by a custom check
+    External {
+        /// Custom check name
+        check: String,
+        /// Severity reported by the check
+        severity: Severity,
+        /// Description of the problem
+        message: String,
+    },
+    /// Check that did not complete, so its problems are unknown
+    CheckSkipped {
+        /// Name of the skipped check
+        check: String,
+        /// Why it did not complete
+        reason: SkipReason,
+    },
+}
+
+impl fmt::Display for Finding {
+    #[expect(clippy::too_many_lines, reason = "one arm per finding variant")]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingDependency {
+                package,
+                file,
+                dependency,
+                origin,
+            } => {
+                write!(
+                    f,
+                    "File {} from package {package:?} is missing dependency {}",
+                    file.display(),
+                    dependency.display()
+                )?;
+                match origin {
+                    Some(origin) => write!(f, ", was provided by {origin}"),
+                    None => Ok(()),
+                }
+            }
+            Self::DependencyNotInstalled {
+                package,
+                file,
+                dependency,
+                declared,
+            } => write!(
+                f,
+                "File {} from package {package:?} is missing dependency {}, declared dependency {declared:?} not installed",
+                file.display(),
+                dependency.display()
+            ),
+            Self::OptionalDependencyMissing {
+                package,
+                file,
+                dependency,
+                optional,
+            } => write!(
+                f,
+                "File {} from package {package:?} is missing dependency {}, optional feature unavailable: install {optional}",
+                file.display(),
+                dependency.display()
+            ),
+            Self::UnpackagedMissingDependency { file, dependency } => write!(
+                f,
+                "File {}, not installed by a package, is missing dependency {}",
+                file.display(),
+                dependency.display()
+            ),
+            Self::TruncatedFile {
+                package,
+                file,
+                size,
+                expected,
+            } => write!(
+                f,
+                "File {} from package {package:?} is truncated, its size is {size} bytes instead of {expected}",
+                file.display()
+            ),
+            Self::ForeignBinary {
+                package,
+                file,
+                platform,
+            } => write!(
+                f,
+                "File {} from package {package:?} is built for {platform}, and can not run on this system",
+                file.display()
+            ),
+            Self::OptLibraryDependency {
+                package,
+                file,
+                library,
+                provider,
+            } => {
+                write!(
+                    f,
+                    "File {} from package {package:?} uses library {}",
+                    file.display(),
+                    library.display()
+                )?;
+                match provider {
+                    Some(provider) => write!(
+                        f,
+                        " of package {provider:?}, found through ld.so.conf, removing {provider:?} breaks it"
+                    ),
+                    None => write!(f, ", owned by no package, found through ld.so.conf"),
+                }
+            }
+            Self::MissingSymbolVersions {
+                package,
+                file,
+                soname,
+                library,
+                versions,
+                provider,
+            } => {
+                let (last, others) = versions.split_last().unzip();
+                let others = others.unwrap_or_default();
+                write!(
+                    f,
+                    "File {} from package {package:?} needs {} {}{}{} of {soname}, not defined by {}",
+                    file.display(),
+                    if others.is_empty() { "version" } else { "versions" },
+                    others.join(", "),
+                    if others.is_empty() { "" } else { " and " },
+                    last.map_or("", String::as_str),
+                    library.display()
+                )?;
+                match provider {
+                    Some(provider) => write!(
+                        f,
+                        " from package {provider:?}, it fails to start until {provider:?} is upgraded or {package:?} rebuilt"
+                    ),
+                    None => write!(
+                        f,
+                        ", owned by no package, it fails to start until the library is removed or {package:?} rebuilt"
+                    ),
+                }
+            }
+            Self::UpgradeBreakage {
+                package,
+                file,
+                dependency,
+                upgrade,
+            } => write!(
+                f,
+                "File {} from package {package:?} will miss dependency {dependency}, removed by the upgrade of {upgrade:?}",
+                file.display()
+            ),
+            #[expect(clippy::cast_precision_loss, reason = "approximate size for display")]
+            Self::BrokenPythonPackage {
+                package,
+                dirs,
+                files,
+                size,
+                action,
+            } => {
+                let dirs = dirs
+                    .iter()
+                    .map(|d| format!("{d:?}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(
+                    f,
+                    "Package {package:?} has {files} file(s) ({:.1} MiB) ignored by the current Python interpreter in {dirs}, ",
+                    *size as f64 / f64::from(1 << 20)
+                )?;
+                match action {
+                    PythonAction::Rebuild => write!(f, "rebuild it"),
+                    PythonAction::Reinstall => write!(f, "reinstall it from the repositories"),
+                    PythonAction::Remove => {
+                        write!(f, "remove it, it has no other files and no package depends on it")
+                    }
+                }
+            }
+            #[expect(clippy::cast_precision_loss, reason = "approximate size for display")]
+            Self::UnownedPythonDir { dir, size } => write!(
+                f,
+                "Directory {dir:?} is ignored by installed Python interpreters and owned by no package, remove its {:.1} MiB of leftover files",
+                *size as f64 / f64::from(1 << 20)
+            ),
+            Self::PinnedPythonScript {
+                path,
+                interpreter,
+                package,
+            } => match package {
+                Some(package) => write!(
+                    f,
+                    "Script {} from package {package:?} runs removed interpreter {interpreter}, rebuild the package",
+                    path.display()
+                ),
+                None => write!(
+                    f,
+                    "Script {}, owned by no package, runs removed interpreter {interpreter}, change its shebang to python3",
+                    path.display()
+                ),
+            },
+            Self::BrokenServiceLink { link, package } => {
+                write!(
+                    f,
+                    "Systemd enabled service has broken link in {}",
+                    link.display()
+                )?;
+                match package {
+                    Some(package) => write!(f, ", owned by package {package:?}, reinstall it"),
+                    None => write!(
+                        f,
+                        ", created by systemctl enable, remove it with systemctl disable"
+                    ),
+                }
+            }
+            Self::BrokenActivatedService {
+                unit,
+                service,
+                missing,
+                program,
+                package,
+                ..
+            } => {
+                write!(f, "Enabled unit {unit} activates")?;
+                if *program {
+                    write!(f, " {service}")?;
+                    if let Some(package) = package {
+                        write!(f, " of package {package:?}")?;
+                    }
+                    let verb = if package.is_some() { "reinstall" } else { "install" };
+                    write!(
+                        f,
+                        ", which runs missing program {missing}, {verb} the package providing it"
+                    )?;
+                } else {
+                    write!(
+                        f,
+                        " missing {missing}, install the package providing {service} or disable {unit}"
+                    )?;
+                }
+                write!(f, ", its first activation will fail")
+            }
+            Self::BrokenLoginUnit {
+                unit,
+                missing,
+                program,
+                package,
+                ..
+            } => {
+                write!(f, "Unit {unit}")?;
+                if let Some(package) = package {
+                    write!(f, " of package {package:?}")?;
+                }
+                if *program {
+                    let verb = if package.is_some() { "reinstall" } else { "install" };
+                    write!(
+                        f,
+                        " needed to log in runs missing program {missing}, {verb} the package providing it"
+                    )?;
+                } else {
+                    write!(
+                        f,
+                        " needed to log in points to missing {missing}, enable an installed one"
+                    )?;
+                }
+                write!(f, ", there will be no login prompt after the next boot")
+            }
+            Self::ShadowedLibrary {
+                soname,
+                package,
+                path,
+                shadowed_package,
+                shadowed_path,
+            } => write!(
+                f,
+                "Library {soname} from package {package:?} in {} shadows the one from package {shadowed_package:?} in {}",
+                path.parent().unwrap_or(path).display(),
+                shadowed_path.parent().unwrap_or(shadowed_path).display()
+            ),
+            Self::UnpackagedLibrary {
+                soname,
+                path,
+                writable,
+                shadowed_package,
+                shadowed_path,
+            } => {
+                write!(
+                    f,
+                    "Library {soname} in {}, not installed by a package, shadows the one from package {shadowed_package:?} in {}",
+                    path.parent().unwrap_or(path).display(),
+                    shadowed_path.parent().unwrap_or(shadowed_path).display()
+                )?;
+                if *writable {
+                    write!(f, ", and is writable by other users than root")?;
+                }
+                Ok(())
+            }
+            Self::LibraryPermissions {
+                path,
+                mode,
+                loader,
+                package,
+            } => {
+                let (kind, effect) = if *loader {
+                    ("Dynamic loader", "start")
+                } else {
+                    ("Library", "load it")
+                };
+                match package {
+                    Some(package) => write!(
+                        f,
+                        "{kind} {} from package {package:?} has mode",
+                        path.display()
+                    ),
+                    None => write!(f, "{kind} {}, not installed by a package, has mode", path.display()),
+                }?;
+                write!(f, " {mode:04o}, so programs of users other than root can not {effect}")
+            }
+            Self::StaleLibraryLink {
+                link,
+                target,
+                library,
+                package,
+            } => {
+                write!(
+                    f,
+                    "Library link {} points to removed {}, instead of {}",
+                    link.display(),
+                    target.display(),
+                    library.display()
+                )?;
+                if let Some(package) = package {
+                    write!(f, " from package {package:?}")?;
+                }
+                write!(f, ", run ldconfig to update it")
+            }
+            Self::BrokenTypelib {
+                path,
+                dependency,
+                package,
+            } => {
+                write!(f, "Introspection typelib {}", path.display())?;
+                match package {
+                    Some(package) => write!(f, " from package {package:?}"),
+                    None => write!(f, ", owned by no package,"),
+                }?;
+                write!(
+                    f,
+                    " needs missing library {}, programs importing its namespace fail",
+                    dependency.display()
+                )
+            }
+            Self::BrokenPerlModule {
+                path,
+                dependency,
+                package,
+            } => {
+                write!(f, "Perl module {}", path.display())?;
+                match package {
+                    Some(package) => write!(
+                        f,
+                        " from package {package:?} is linked against removed {}, rebuild the package from the AUR or its PKGBUILD",
+                        dependency.display()
+                    ),
+                    None => write!(
+                        f,
+                        ", owned by no package, is linked against removed {}, reinstall it with cpan",
+                        dependency.display()
+                    ),
+                }
+            }
+            Self::FailedScriptlet {
+                package,
+                version,
+                date,
+                error,
+            } => write!(
+                f,
+                "Scriptlet of package {package:?} {version} failed on {date} with {error:?}, reinstall the package to run it again"
+            ),
+            Self::UnownedFile { path, appeared } => write!(
+                f,
+                "File {}, owned by no package, appeared during the transaction of {}",
+                path.display(),
+                reports::format_time(*appeared)
+            ),
+            Self::PacnewFile { path } => write!(
+                f,
+                "Configuration file {} has a pending {}.pacnew update",
+                path.display(),
+                path.display()
+            ),
+            Self::ChecksumMismatch { package, path } => write!(
+                f,
+                "File {} from package {package:?} does not match the checksum recorded by the package",
+                path.display()
+            ),
+            Self::FileConflict { path, packages } => {
+                let names: Vec<_> = packages.iter().map(|p| format!("{p:?}")).collect();
+                let (last, others) = names.split_last().unzip();
+                write!(
+                    f,
+                    "File {} is owned by packages {} and {}, removing one of them deletes it for the {}",
+                    path.display(),
+                    others.unwrap_or_default().join(", "),
+                    last.map_or("", String::as_str),
+                    if packages.len() > 2 { "others" } else { "other" }
+                )
+            }
+            Self::DanglingConventionLink { link, target } => write!(
+                f,
+                "Link {} points to missing file {}, install the package providing it, or retarget the link",
+                link.display(),
+                target.display()
+            ),
+            Self::UnpackagedConventionLink { link, target } => write!(
+                f,
+                "Link {} points to {}, which is owned by no package",
+                link.display(),
+                target.display()
+            ),
+            Self::BadSymlinkChain {
+                package,
+                link,
+                target,
+                problem,
+            } => match problem {
+                ChainProblem::Circular => write!(
+                    f,
+                    "Link {} from package {package:?} is part of a symlink loop through {}, opening it fails",
+                    link.display(),
+                    target.display()
+                ),
+                ChainProblem::TooDeep => write!(
+                    f,
+                    "Link {} from package {package:?} goes through too many symlinks, the last one followed being {}",
+                    link.display(),
+                    target.display()
+                ),
+                ChainProblem::UserDirectory => write!(
+                    f,
+                    "Link {} from package {package:?} points into user directory {}, whose content differs between systems",
+                    link.display(),
+                    target.display()
+                ),
+            },
+            Self::BrokenBootEntry {
+                path,
+                entry,
+                missing,
+                problem,
+            } => {
+                write!(f, "Entry {entry:?} of {} ", path.display())?;
+                match problem {
+                    BootProblem::Device => write!(
+                        f,
+                        "uses missing device {missing}, fix the entry or add the nofail option, the next boot may fail"
+                    ),
+                    BootProblem::Fsck => write!(
+                        f,
+                        "needs missing {missing}, install the package providing it, the filesystem is not checked at boot anymore"
+                    ),
+                    BootProblem::MountHelper => write!(
+                        f,
+                        "needs missing {missing}, install the package providing it, the next boot may fail"
+                    ),
+                    BootProblem::KeyFile => write!(
+                        f,
+                        "uses missing key file {missing}, the next boot may ask for its passphrase or fail"
+                    ),
+                }
+            }
+            Self::BrokenBootReference {
+                path,
+                missing,
+                reference,
+            } => {
+                write!(f, "Boot configuration {} ", path.display())?;
+                match reference {
+                    BootReference::Image => write!(f, "loads missing image {missing}")?,
+                    BootReference::Init => write!(f, "sets missing init {missing}")?,
+                    BootReference::PlymouthTheme => {
+                        write!(f, "selects missing Plymouth theme {missing:?}")?;
+                    }
+                }
+                write!(
+                    f,
+                    ", install the package providing it or update the configuration"
+                )?;
+                if *reference == BootReference::PlymouthTheme {
+                    Ok(())
+                } else {
+                    write!(f, ", the next boot may fail")
+                }
+            }
+            Self::UnsignedBootImage { path, signed } => {
+                if *signed {
+                    write!(
+                        f,
+                        "Boot image {} is not signed with a key enrolled in the firmware or shim, the firmware refuses to boot it with Secure Boot enabled, sign it with an enrolled key",
+                        path.display()
+                    )
+                } else {
+                    write!(
+                        f,
+                        "Boot image {} is not signed, the firmware refuses to boot it with Secure Boot enabled, sign it with sbctl or sbsign",
+                        path.display()
+                    )
+                }
+            }
+            Self::MissingFirmware {
+                firmware,
+                module,
+                provider,
+            } => {
+                write!(f, "Firmware {firmware} requested by ")?;
+                match module {
+                    Some(module) => write!(f, "kernel module {module:?}")?,
+                    None => write!(f, "the kernel")?,
+                }
+                write!(f, " is not installed, install ")?;
+                match provider {
+                    Some(provider) => write!(f, "package {provider:?}")?,
+                    None => write!(f, "the package providing it")?,
+                }
+                write!(f, ", the device may not work until its driver loads it")
+            }
+            Self::AurPackage { package, status } => match status {
+                AurStatus::NotFound => write!(f, "Package {package:?} is not in the AUR"),
+                AurStatus::Orphaned => write!(f, "Package {package:?} is orphaned in the AUR"),
+                AurStatus::OutOfDate => {
+                    write!(f, "Package {package:?} is flagged out of date in the AUR")
+                }
+            },
+            Self::StaleBuild {
+                package,
+                built,
+                dependencies,
+            } => {
+                let names: Vec<_> = dependencies.iter().map(|d| format!("{d:?}")).collect();
+                let (last, others) = names.split_last().unzip();
+                let others = others.unwrap_or_default();
+                write!(
+                    f,
+                    "Package {package:?} was built on {}, long before its {} {}{}{} {} upgraded, rebuild it if it misbehaves",
+                    reports::format_time(*built),
+                    if others.is_empty() { "dependency" } else { "dependencies" },
+                    others.join(", "),
+                    if others.is_empty() { "" } else { " and " },
+                    last.map_or("", String::as_str),
+                    if others.is_empty() { "was" } else { "were" }
+                )
+            }
+            Self::LegacyRuntime { runtime, packages } => {
+                let names: Vec<_> = packages.iter().map(|p| format!("{p:?}")).collect();
+                let (last, others) = names.split_last().unzip();
+                let others = others.unwrap_or_default();
+                let single = others.is_empty();
+                write!(
+                    f,
+                    "{} {} still {} {runtime}: {}{}{}, rebuild or replace {} before removing {runtime}",
+                    packages.len(),
+                    if single { "package" } else { "packages" },
+                    if single { "needs" } else { "need" },
+                    others.join(", "),
+                    if single { "" } else { " and " },
+                    last.map_or("", String::as_str),
+                    if single { "it" } else { "them" }
+                )
+            }
+            Self::VulnerablePackage {
+                package,
+                version,
+                advisory,
+                risk,
+                issue_type,
+                fixed,
+            } => {
+                write!(
+                    f,
+                    "Package {package:?} {version} is affected by {advisory} ({} risk {issue_type}), ",
+                    risk.to_lowercase()
+                )?;
+                match fixed {
+                    Some(fixed) => write!(f, "fixed in {fixed}"),
+                    None => write!(f, "with no fix available yet"),
+                }
+            }
+            Self::External { check, message, .. } => write!(f, "{check}: {message}"),
+            Self::CheckSkipped { check, reason } => match reason {
+                SkipReason::Timeout(timeout) => write!(
+                    f,
+                    "Check {check:?} was skipped, it did not finish within {}s",
+                    timeout.as_secs()
+                ),
+                SkipReason::Panic(message) => {
+                    write!(f, "Check {check:?} was skipped, it crashed: {message}")
+                }
+                SkipReason::EarlyExit(severity) => write!(
+                    f,
+                    "Check {check:?} was skipped, the scan stopped early after findings with severity {severity} or higher"
+                ),
+                SkipReason::Offline => {
+                    write!(f, "Check {check:?} was skipped, the network is offline")
+                }
+                SkipReason::DbBusy(packages) if packages.is_empty() => write!(
+                    f,
+                    "Check {check:?} was skipped, the local database was busy during a pacman transaction, \
+                     run it again when the transaction completes"
+                ),
+                SkipReason::DbBusy(packages) => {
+                    let names: Vec<_> = packages.iter().map(|p| format!("{p:?}")).collect();
+                    let (last, others) = names.split_last().unzip();
+                    let others = others.unwrap_or_default();
+                    write!(
+                        f,
+                        "Check {check:?} skipped {} {}{}{}, the local database was busy during a pacman transaction, \
+                         run it again when the transaction completes",
+                        if others.is_empty() { "package" } else { "packages" },
+                        others.join(", "),
+                        if others.is_empty() { "" } else { " and " },
+                        last.map_or("", String::as_str),
+                    )
+                }
+            },
+        }
+    }
+}
+
+impl Finding {
+    /// Check that produced this finding
+    #[must_use]
+    pub fn kind(&self) -> CheckKind {
+        match self {
+            Self::MissingDependency { .. }
+            | Self::DependencyNotInstalled { .. }
+            | Self::OptionalDependencyMissing { .. }
+            | Self::UnpackagedMissingDependency { .. }
+            | Self::TruncatedFile { .. }
+            | Self::ForeignBinary { .. }
+            | Self::OptLibraryDependency { .. }
+            | Self::UpgradeBreakage { .. } => CheckKind::MissingDependencies,
+            Self::BrokenPythonPackage { .. }
+            | Self::UnownedPythonDir { .. }
+            | Self::PinnedPythonScript { .. } => CheckKind::PythonPackages,
+            Self::BrokenServiceLink { .. } | Self::BrokenActivatedService { .. } => {
+                CheckKind::ServiceLinks
+            }
+            Self::BrokenLoginUnit { .. } => CheckKind::LoginUnits,
+            Self::ShadowedLibrary { .. } | Self::UnpackagedLibrary { .. } => {
+                CheckKind::DuplicateLibraries
+            }
+            Self::LibraryPermissions { .. } => CheckKind::LibraryPermissions,
+            Self::StaleLibraryLink { .. } => CheckKind::LibraryLinks,
+            Self::BrokenTypelib { .. } => CheckKind::IntrospectionTypelibs,
+            Self::BrokenPerlModule { .. } => CheckKind::PerlModules,
+            Self::MissingSymbolVersions { .. } => CheckKind::SymbolVersions,
+            Self::FailedScriptlet { .. } => CheckKind::ScriptletFailures,
+            Self::UnownedFile { .. } => CheckKind::UnownedFiles,
+            Self::PacnewFile { .. } => CheckKind::PacnewFiles,
+            Self::ChecksumMismatch { .. } => CheckKind::FileChecksums,
+            Self::FileConflict { .. } => CheckKind::FileConflicts,
+            Self::DanglingConventionLink { .. } | Self::UnpackagedConventionLink { .. } => {
+                CheckKind::ConventionLinks
+            }
+            Self::BadSymlinkChain { .. } => CheckKind::SymlinkChains,
+            Self::BrokenBootEntry { .. } => CheckKind::BootMounts,
+            Self::BrokenBootReference { .. } => CheckKind::KernelCmdline,
+            Self::UnsignedBootImage { .. } => CheckKind::SecureBoot,
+            Self::MissingFirmware { .. } => CheckKind::ModuleFirmware,
+            Self::AurPackage { .. } => CheckKind::AurPackages,
+            Self::StaleBuild { .. } => CheckKind::StaleBuilds,
+            Self::LegacyRuntime { .. } => CheckKind::LegacyRuntimes,
+            Self::VulnerablePackage { .. } => CheckKind::SecurityAdvisories,
+            Self::External { .. } => CheckKind::External,
+            Self::CheckSkipped { check, .. } => CheckKind::ALL
+                .into_iter()
+                .find(|k| k.name() == check)
+                .unwrap_or(CheckKind::External),
+        }
+    }
+
+    /// Name of the check that produced this finding
+    #[must_use]
+    pub fn check_name(&self) -> &str {
+        match self {
+            Self::External { check, .. } | Self::CheckSkipped { check, .. } => check,
+            _ => self.kind().name(),
+        }
+    }
+
+    /// Whether this finding reports a check skipped because the scan stopped early, or packages a check skipped
+    /// because the local database was busy, so that other findings are partial
+    #[must_use]
+    pub fn is_partial(&self) -> bool {
+        matches!(
+            self,
+            Self::CheckSkipped {
+                reason: SkipReason::EarlyExit(_) | SkipReason::DbBusy(_),
+                ..
+            }
+        )
+    }
+
+    /// What to do about a missing library of a well known family, if any
+    #[must_use]
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::MissingDependency { dependency, .. }
+            | Self::UnpackagedMissingDependency { dependency, .. }
+            | Self::BrokenTypelib { dependency, .. } => {
+                hints::remediation_hint(&dependency.to_string_lossy())
+            }
+            Self::UpgradeBreakage { dependency, .. } => hints::remediation_hint(dependency),
+            _ => None,
+        }
+    }
+
+    /// Packages this finding is about
+    pub(crate) fn packages(&self) -> Vec<&str> {
+        match self {
+            Self::MissingDependency { package, .. }
+            | Self::DependencyNotInstalled { package, .. }
+            | Self::OptionalDependencyMissing { package, .. }
+            | Self::TruncatedFile { package, .. }
+            | Self::ForeignBinary { package, .. }
+            | Self::UpgradeBreakage { package, .. } => vec![package.as_str()],
+            Self::BrokenPythonPackage { package, .. }
+            | Self::ChecksumMismatch { package, .. }
+            | Self::FailedScriptlet { package, .. }
+            | Self::AurPackage { package, .. }
+            | Self::StaleBuild { package, .. }
+            | Self::BadSymlinkChain { package, .. }
+            | Self::VulnerablePackage { package, .. } => vec![package],
+            Self::BrokenServiceLink { package, .. }
+            | Self::PinnedPythonScript { package, .. }
+            | Self::LibraryPermissions { package, .. }
+            | Self::StaleLibraryLink { package, .. }
+            | Self::BrokenTypelib { package, .. }
+            | Self::BrokenPerlModule { package, .. }
+            | Self::BrokenLoginUnit { package, .. }
+            | Self::BrokenActivatedService { package, .. }
+            | Self::MissingFirmware {
+                provider: package, ..
+            } => package.iter().map(String::as_str).collect(),
+            Self::OptLibraryDependency {
+                package, provider, ..
+            } => [package.as_str()]
+                .into_iter()
+                .chain(provider.as_deref())
+                .collect(),
+            Self::MissingSymbolVersions {
+                package, provider, ..
+            } => [package.as_str()]
+                .into_iter()
+                .chain(provider.as_deref())
+                .collect(),
+            Self::ShadowedLibrary {
+                package,
+                shadowed_package,
+                ..
+            } => vec![package, shadowed_package],
+            Self::UnpackagedLibrary {
+                shadowed_package, ..
+            } => vec![shadowed_package],
+            Self::FileConflict { packages, .. } | Self::LegacyRuntime { packages, .. } => {
+                packages.iter().map(String::as_str).collect()
+            }
+            Self::UnpackagedMissingDependency { .. }
+            | Self::UnownedPythonDir { .. }
+            | Self::UnownedFile { .. }
+            | Self::PacnewFile { .. }
+            | Self::DanglingConventionLink { .. }
+            | Self::UnpackagedConventionLink { .. }
+            | Self::BrokenBootEntry { .. }
+            | Self::BrokenBootReference { .. }
+            | Self::UnsignedBootImage { .. }
+            | Self::External { .. }
+            | Self::CheckSkipped { .. } => Vec::new(),
+        }
+    }
+
+    /// Where the package this finding is about comes from, the first one with a known origin if there are several
+    pub fn package_origin(&self) -> Option<PackageOrigin> {
+        self.packages().into_iter().find_map(origins::get)
+    }
+
+    /// Files and directories this finding is about
+    pub(crate) fn paths(&self) -> Vec<&Path> {
+        match self {
+            Self::MissingDependency { file, .. }
+            | Self::DependencyNotInstalled { file, .. }
+            | Self::OptionalDependencyMissing { file, .. }
+            | Self::TruncatedFile { file, .. }
+            | Self::ForeignBinary { file, .. }
+            | Self::UpgradeBreakage { file, .. } => vec![file.as_path()],
+            Self::UnpackagedMissingDependency { file, .. } => vec![file],
+            Self::OptLibraryDependency { file, library, .. } => vec![file.as_path(), library],
+            Self::MissingSymbolVersions { file, library, .. } => vec![file, library],
+            Self::BrokenPythonPackage { dirs, .. } => dirs.iter().map(Path::new).collect(),
+            Self::UnownedPythonDir { dir, .. } => vec![Path::new(dir)],
+            Self::BrokenServiceLink { link: path, .. }
+            | Self::BrokenActivatedService { link: path, .. }
+            | Self::LibraryPermissions { path, .. }
+            | Self::PinnedPythonScript { path, .. }
+            | Self::UnownedFile { path, .. }
+            | Self::PacnewFile { path }
+            | Self::ChecksumMismatch { path, .. }
+            | Self::FileConflict { path, .. }
+            | Self::BrokenTypelib { path, .. }
+            | Self::BrokenPerlModule { path, .. }
+            | Self::BrokenLoginUnit { path, .. }
+            | Self::BrokenBootEntry { path, .. }
+            | Self::BrokenBootReference { path, .. }
+            | Self::UnsignedBootImage { path, .. } => vec![path],
+            Self::ShadowedLibrary {
+                path,
+                shadowed_path,
+                ..
+            }
+            | Self::UnpackagedLibrary {
+                path,
+                shadowed_path,
+                ..
+            } => vec![path, shadowed_path],
+            Self::DanglingConventionLink { link, target }
+            | Self::UnpackagedConventionLink { link, target }
+            | Self::BadSymlinkChain { link, target, .. } => vec![link, target],
+            Self::StaleLibraryLink {
+                link,
+                target,
+                library,
+                ..
+            } => vec![link, target, library],
+            Self::FailedScriptlet { .. }
+            | Self::AurPackage { .. }
+            | Self::MissingFirmware { .. }
+            | Self::StaleBuild { .. }
+            | Self::LegacyRuntime { .. }
+            | Self::VulnerablePackage { .. }
+            | Self::External { .. }
+            | Self::CheckSkipped { .. } => Vec::new(),
+        }
+    }
+
+    /// Library soname this finding is about, if any
+    pub(crate) fn soname(&self) -> Option<&str> {
+        match self {
+            Self::MissingDependency { dependency, .. }
+            | Self::DependencyNotInstalled { dependency, .. }
+            | Self::OptionalDependencyMissing { dependency, .. }
+            | Self::UnpackagedMissingDependency { dependency, .. }
+            | Self::BrokenTypelib { dependency, .. }
+            | Self::BrokenPerlModule { dependency, .. } => dependency.to_str(),
+            Self::UpgradeBreakage { dependency, .. } => Some(dependency),
+            Self::MissingSymbolVersions { soname, .. } => Some(soname),
+            Self::ShadowedLibrary { soname, .. } | Self::UnpackagedLibrary { soname, .. } => {
+                Some(soname)
+            }
+            Self::StaleLibraryLink { link, .. } => link.file_name()?.to_str(),
+            Self::OptLibraryDependency { library, .. } => library.file_name()?.to_str(),
+            _ => None,
+        }
+    }
+
+    /// How serious this finding is, after the severity overrides of the configuration
+    #[must_use]
+    pub fn severity(&self) -> Severity {
+        overrides::severity(self).unwrap_or_else(|| self.default_severity())
+    }
+
+    /// How serious this finding is, as reported by its check
+    #[must_use]
+    pub fn default_severity(&self) -> Severity {
+        match self {
+            // The executable will fail to start
+            Self::MissingDependency { .. }
+            | Self::MissingSymbolVersions { .. }
+            | Self::DependencyNotInstalled { .. }
+            | Self::UnpackagedMissingDependency { .. }
+            | Self::TruncatedFile { .. }
+            | Self::ForeignBinary { .. }
+            | Self::PinnedPythonScript { .. }
+            // Only root can still run the programs using the library
+            | Self::LibraryPermissions { .. }
+            // Programs using the library fail to start, until ldconfig runs
+            | Self::StaleLibraryLink { .. }
+            // Programs importing the namespace fail, often at runtime
+            | Self::BrokenTypelib { .. }
+            // Perl fails to load the module, and scripts using it stop
+            | Self::BrokenPerlModule { .. }
+            // The system boots without a way to log in
+            | Self::BrokenLoginUnit { .. }
+            // Users, caches or other state the package needs may be missing
+            | Self::FailedScriptlet { .. }
+            // Booting it fails, the check being only enabled with Secure Boot
+            | Self::UnsignedBootImage { .. } => Severity::Error,
+            // The upgrade can still be postponed, or the package rebuilt
+            Self::UpgradeBreakage { .. }
+            | Self::BrokenPythonPackage { .. }
+            | Self::BrokenServiceLink { .. }
+            // The unit is started, and only fails when something connects or the watched path changes
+            | Self::BrokenActivatedService { .. }
+            // Programs linked against the hidden library may not work with the loaded one
+            | Self::ShadowedLibrary { .. }
+            // The file may also have been modified on purpose
+            | Self::ChecksumMismatch { .. }
+            // The file only disappears when one of the packages is removed
+            | Self::FileConflict { .. }
+            // Builds and scripts using the program fail, with confusing errors
+            | Self::DanglingConventionLink { .. }
+            // Programs following the link fail, or hang when they do not limit how many links they follow
+            | Self::BadSymlinkChain { .. }
+            // The system still boots, without the device
+            | Self::MissingFirmware { .. }
+            // Other checks still ran
+            | Self::CheckSkipped {
+                reason: SkipReason::Timeout(_) | SkipReason::Panic(_),
+                ..
+            }
+            // Problems of the skipped packages go unreported
+            | Self::CheckSkipped {
+                reason: SkipReason::DbBusy(_),
+                ..
+            } => Severity::Warning,
+            // Any user allowed to modify it can run code in processes of root
+            Self::UnpackagedLibrary { writable, .. } => {
+                if *writable {
+                    Severity::Error
+                } else {
+                    Severity::Warning
+                }
+            }
+            Self::BrokenBootEntry { problem, .. } => match problem {
+                // Systemd waits for the device or the mount, then starts the emergency shell
+                BootProblem::Device | BootProblem::MountHelper => Severity::Error,
+                // The system still boots, without checking the filesystem or with a passphrase prompt
+                BootProblem::Fsck | BootProblem::KeyFile => Severity::Warning,
+            },
+            Self::BrokenBootReference { reference, .. } => match reference {
+                // The boot loader or the kernel stops
+                BootReference::Image | BootReference::Init => Severity::Error,
+                // Plymouth falls back to another theme
+                BootReference::PlymouthTheme => Severity::Warning,
+            },
+            Self::AurPackage { status, .. } => match status {
+                // Updates will not come from the AUR anymore
+                AurStatus::NotFound => Severity::Warning,
+                AurStatus::Orphaned | AurStatus::OutOfDate => Severity::Info,
+            },
+            Self::VulnerablePackage { risk, .. } => match risk.as_str() {
+                "Critical" | "High" => Severity::Error,
+                "Medium" => Severity::Warning,
+                _ => Severity::Info,
+            },
+            // The package works, without the feature
+            Self::OptionalDependencyMissing { .. } | Self::PacnewFile { .. }
+            // The program works, as long as the package of /opt stays installed
+            | Self::OptLibraryDependency { .. }
+            // Nothing loads the files, they only waste space
+            | Self::UnownedPythonDir { .. }
+            // Scriptlets also generate files on purpose, like keys and caches
+            | Self::UnownedFile { .. }
+            // The program works, but is not updated with the system
+            | Self::UnpackagedConventionLink { .. }
+            // The package may still work, the date is only a hint
+            | Self::StaleBuild { .. }
+            // Compatibility packages keep the programs working
+            | Self::LegacyRuntime { .. }
+            // The stop was requested
+            | Self::CheckSkipped {
+                reason: SkipReason::EarlyExit(_),
+                ..
+            }
+            // The next run with network access checks again
+            | Self::CheckSkipped {
+                reason: SkipReason::Offline,
+                ..
+            } => Severity::Info,
+            Self::External { severity, .. } => *severity,
+        }
+    }
+}
+
+/// Get the JSON value of a path
+fn path_value(path: &Path) -> Value {
+    path.to_string_lossy().into_owned().into()
+}
+
+impl ToJson for Finding {
+    #[expect(clippy::too_many_lines, reason = "one arm per finding variant")]
+    fn to_json(&self) -> Value {
+        let mut fields = vec![
+            ("schema_version".to_owned(), SCHEMA_VERSION.into()),
+            ("check".to_owned(), self.check_name().into()),
+            ("severity".to_owned(), self.severity().name().into()),
+            (
+                "message".to_owned(),
+                match self {
+                    Self::External { message, .. } => message.clone(),
+                    _ => self.to_string(),
+                }
+                .into(),
+            ),
+        ];
+        match self {
+            Self::MissingDependency {
+                package,
+                file,
+                dependency,
+                origin,
+            } => {
+                fields.extend([
+                    ("package".to_owned(), package.as_str().into()),
+                    ("file".to_owned(), path_value(file)),
+                    ("dependency".to_owned(), path_value(dependency)),
+                ]);
+                if let Some(origin) = origin {
+                    fields.push(("origin".to_owned(), origin.to_json()));
+                }
+            }
+            Self::DependencyNotInstalled {
+                package,
+                file,
+                dependency,
+                declared,
+            } => fields.extend([
+                ("package".to_owned(), package.as_str().into()),
+                ("file".to_owned(), path_value(file)),
+                ("dependency".to_owned(), path_value(dependency)),
+                ("declared".to_owned(), declared.as_str().into()),
+            ]),
+            Self::OptionalDependencyMissing {
+                package,
+                file,
+                dependency,
+                optional,
+            } => fields.extend([
+                ("package".to_owned(), package.as_str().into()),
+                ("file".to_owned(), path_value(file)),
+                ("dependency".to_owned(), path_value(dependency)),
+                ("optional".to_owned(), optional.as_str().into()),
+            ]),
+            Self::UnpackagedMissingDependency { file, dependency } => fields.extend([
+                ("file".to_owned(), path_value(file)),
+                ("dependency".to_owned(), path_value(dependency)),
+            ]),
+            Self::TruncatedFile {
+                package,
+                file,
+                size,
+                expected,
+            } => fields.extend([
+                ("package".to_owned(), package.as_str().into()),
+                ("file".to_owned(), path_value(file)),
+                ("size".to_owned(), (*size).into()),
+                ("expected".to_owned(), (*expected).into()),
+            ]),
+            Self::ForeignBinary {
+                package,
+                file,
+                platform,
+            } => fields.extend([
+                ("package".to_owned(), package.as_str().into()),
+                ("file".to_owned(), path_value(file)),
+                ("platform".to_owned(), platform.as_str().into()),
+            ]),
+            Self::OptLibraryDependency {
+                package,
+                file,
+                library,
+                provider,
+            } => {
+                fields.extend([
+                    ("package".to_owned(), package.as_str().into()),
+                    ("file".to_owned(), path_value(file)),
+                    ("library".to_owned(), path_value(library)),
+                ]);
+                if let Some(provider) = provider {
+                    fields.push(("provider".to_owned(), provider.as_str().into()));
+                }
+            }
+            Self::MissingSymbolVersions {
+                package,
+                file,
+                soname,
+                library,
+                versions,
+                provider,
+            } => {
+                fields.extend([
+                    ("package".to_owned(), package.as_str().into()),
+                    ("file".to_owned(), path_value(file)),
+                    ("soname".to_owned(), soname.as_str().into()),
+                    ("library".to_owned(), path_value(library)),
+                    (
+                        "versions".to_owned(),
+                        Value::Array(versions.iter().map(|v| v.as_str().into()).collect()),
+                    ),
+                ]);
+                if let Some(provider) = provider {
+                    fields.push(("provider".to_owned(), provider.as_str().into()));
+                }
+            }
+            Self::UpgradeBreakage {
+                package,
+                file,
+                dependency,
+                upgrade,
+            } => fields.extend([
+                ("package".to_owned(), package.as_str().into()),
+                ("file".to_owned(), path_value(file)),
+                ("dependency".to_owned(), dependency.as_str().into()),
+                ("upgrade".to_owned(), upgrade.as_str().into()),
+            ]),
+            Self::BrokenPythonPackage {
+                package,
+                dirs,
+                files,
+                size,
+                action,
+            } => {
+                fields.push(("package".to_owned(), package.as_str().into()));
+                // Kept from when there was a finding per directory
+                if let Some(dir) = dirs.first() {
+                    fields.push(("dir".to_owned(), dir.as_str().into()));
+                }
+                fields.extend([
+                    (
+                        "dirs".to_owned(),
+                        Value::Array(dirs.iter().map(|d| d.as_str().into()).collect()),
+                    ),
+                    ("files".to_owned(), (*files).into()),
+                    ("size".to_owned(), (*size).into()),
+                    ("action".to_owned(), action.name().into()),
+                ]);
+            }
+            Self::UnownedPythonDir { dir, size } => fields.extend([
+                ("dir".to_owned(), dir.as_str().into()),
+                ("size".to_owned(), (*size).into()),
+            ]),
+            Self::PinnedPythonScript {
+                path,
+                interpreter,
+                package,
+            } => {
+                fields.extend([
+                    ("path".to_owned(), path_value(path)),
+                    ("interpreter".to_owned(), interpreter.as_str().into()),
+                ]);
+                if let Some(package) = package {
+                    fields.push(("package".to_owned(), package.as_str().into()));
+                }
+            }
+            Self::BrokenServiceLink { link, package } => {
+                fields.push(("link".to_owned(), path_value(link)));
+                if let Some(package) = package {
+                    fields.push(("package".to_owned(), package.as_str().into()));
+                }
+            }
+            Self::BrokenActivatedService {
+                unit,
+                link,
+                service,
+                missing,
+                program,
+                package,
+            } => {
+                fields.extend([
+                    ("unit".to_owned(), unit.as_str().into()),
+                    ("link".to_owned(), path_value(link)),
+                    ("service".to_owned(), service.as_str().into()),
+                    ("missing".to_owned(), missing.as_str().into()),
+                    ("program".to_owned(), (*program).into()),
+                ]);
+                if let Some(package) = package {
+                    fields.push(("package".to_owned(), package.as_str().into()));
+                }
+            }
+            Self::BrokenLoginUnit {
+                unit,
+                path,
+                missing,
+                program,
+                package,
+            } => {
+                fields.extend([
+                    ("unit".to_owned(), unit.as_str().into()),
+                    ("path".to_owned(), path_value(path)),
+                    ("missing".to_owned(), missing.as_str().into()),
+                    ("program".to_owned(), (*program).into()),
+                ]);
+                if let Some(package) = package {
+                    fields.push(("package".to_owned(), package.as_str().into()));
+                }
+            }
+            Self::ShadowedLibrary {
+                soname,
+                package,
+                path,
+                shadowed_package,
+                shadowed_path,
+            } => fields.extend([
+                ("soname".to_owned(), soname.as_str().into()),
+                ("package".to_owned(), package.as_str().into()),
+                ("path".to_owned(), path_value(path)),
+                (
+                    "shadowed_package".to_owned(),
+                    shadowed_package.as_str().into(),
+                ),
+                ("shadowed_path".to_owned(), path_value(shadowed_path)),
+            ]),
+            Self::UnpackagedLibrary {
+                soname,
+                path,
+                writable,
+                shadowed_package,
+                shadowed_path,
+            } => fields.extend([
+                ("soname".to_owned(), soname.as_str().into()),
+                ("path".to_owned(), path_value(path)),
+                ("writable".to_owned(), (*writable).into()),
+                (
+                    "shadowed_package".to_owned(),
+                    shadowed_package.as_str().into(),
+                ),
+                ("shadowed_path".to_owned(), path_value(shadowed_path)),
+            ]),
+            Self::LibraryPermissions {
+                path,
+                mode,
+                loader,
+                package,
+            } => {
+                fields.extend([
+                    ("path".to_owned(), path_value(path)),
+                    ("mode".to_owned(), format!("{mode:04o}").as_str().into()),
+                    ("loader".to_owned(), (*loader).into()),
+                ]);
+                if let Some(package) = package {
+                    fields.push(("package".to_owned(), package.as_str().into()));
+                }
+            }
+            Self::StaleLibraryLink {
+                link,
+                target,
+                library,
+                package,
+            } => {
+                fields.extend([
+                    ("link".to_owned(), path_value(link)),
+                    ("target".to_owned(), path_value(target)),
+                    ("library".to_owned(), path_value(library)),
+                ]);
+                if let Some(package) = package {
+                    fields.push(("package".to_owned(), package.as_str().into()));
+                }
+            }
+            Self::BrokenTypelib {
+                path,
+                dependency,
+                package,
+            }
+            | Self::BrokenPerlModule {
+                path,
+                dependency,
+                package,
+            } => {
+                fields.extend([
+                    ("path".to_owned(), path_value(path)),
+                    ("dependency".to_owned(), path_value(dependency)),
+                ]);
+                if let Some(package) = package {
+                    fields.push(("package".to_owned(), package.as_str().into()));
+                }
+            }
+            Self::FailedScriptlet {
+                package,
+                version,
+                date,
+                error,
+            } => fields.extend([
+                ("package".to_owned(), package.as_str().into()),
+                ("version".to_owned(), version.as_str().into()),
+                ("date".to_owned(), date.as_str().into()),
+                ("error".to_owned(), error.as_str().into()),
+            ]),
+            Self::UnownedFile { path, appeared } => fields.extend([
+                ("path".to_owned(), path_value(path)),
+                ("appeared".to_owned(), (*appeared).into()),
+            ]),
+            Self::PacnewFile { path } => fields.push(("path".to_owned(), path_value(path))),
+            Self::ChecksumMismatch { package, path } => fields.extend([
+                ("package".to_owned(), package.as_str().into()),
+                ("path".to_owned(), path_value(path)),
+            ]),
+            Self::FileConflict { path, packages } => fields.extend([
+                ("path".to_owned(), path_value(path)),
+                (
+                    "packages".to_owned(),
+                    Value::Array(packages.iter().map(|p| p.as_str().into()).collect()),
+                ),
+            ]),
+            Self::DanglingConventionLink { link, target }
+            | Self::UnpackagedConventionLink { link, target } => fields.extend([
+                ("link".to_owned(), path_value(link)),
+                ("target".to_owned(), path_value(target)),
+            ]),
+            Self::BadSymlinkChain {
+                package,
+                link,
+                target,
+                problem,
+            } => fields.extend([
+                ("package".to_owned(), package.as_str().into()),
+                ("link".to_owned(), path_value(link)),
+                ("target".to_owned(), path_value(target)),
+                ("chain".to_owned(), problem.name().into()),
+            ]),
+            Self::BrokenBootEntry {
+                path,
+                entry,
+                missing,
+                problem,
+            } => fields.extend([
+                ("path".to_owned(), path_value(path)),
+                ("entry".to_owned(), entry.as_str().into()),
+                ("missing".to_owned(), missing.as_str().into()),
+                ("problem".to_owned(), problem.name().into()),
+            ]),
+            Self::BrokenBootReference {
+                path,
+                missing,
+                reference,
+            } => fields.extend([
+                ("path".to_owned(), path_value(path)),
+                ("missing".to_owned(), missing.as_str().into()),
+                ("reference".to_owned(), reference.name().into()),
+            ]),
+            Self::UnsignedBootImage { path, signed } => fields.extend([
+                ("path".to_owned(), path_value(path)),
+                ("signed".to_owned(), (*signed).into()),
+            ]),
+            Self::MissingFirmware {
+                firmware,
+                module,
+                provider,
+            } => {
+                fields.push(("firmware".to_owned(), firmware.as_str().into()));
+                if let Some(module) = module {
+                    fields.push(("module".to_owned(), module.as_str().into()));
+                }
+                if let Some(provider) = provider {
+                    fields.push(("provider".to_owned(), provider.as_str().into()));
+                }
+            }
+            Self::AurPackage { package, status } => fields.extend([
+                ("package".to_owned(), package.as_str().into()),
+                ("status".to_owned(), status.name().into()),
+            ]),
+            Self::StaleBuild {
+                package,
+                built,
+                dependencies,
+            } => fields.extend([
+                ("package".to_owned(), package.as_str().into()),
+                ("built".to_owned(), (*built).into()),
+                (
+                    "dependencies".to_owned(),
+                    Value::Array(dependencies.iter().map(|d| d.as_str().into()).collect()),
+                ),
+            ]),
+            Self::LegacyRuntime { runtime, packages } => fields.extend([
+                ("runtime".to_owned(), runtime.as_str().into()),
+                (
+                    "packages".to_owned(),
+                    Value::Array(packages.iter().map(|p| p.as_str().into()).collect()),
+                ),
+            ]),
+            Self::VulnerablePackage {
+                package,
+                version,
+                advisory,
+                risk,
+                issue_type,
+                fixed,
+            } => fields.extend([
+                ("package".to_owned(), package.as_str().into()),
+                ("version".to_owned(), version.as_str().into()),
+                ("advisory".to_owned(), advisory.as_str().into()),
+                ("risk".to_owned(), risk.as_str().into()),
+                ("type".to_owned(), issue_type.as_str().into()),
+                (
+                    "fixed".to_owned(),
+                    fixed.as_deref().map_or(Value::Null, Into::into),
+                ),
+            ]),
+            Self::External { .. } => {}
+            Self::CheckSkipped { reason, .. } => {
+                fields.push(("reason".to_owned(), reason.name().into()));
+                match reason {
+                    SkipReason::Timeout(timeout) => {
+                        fields.push(("timeout".to_owned(), timeout.as_secs().into()));
+                    }
+                    SkipReason::Panic(message) => {
+                        fields.push(("error".to_owned(), message.as_str().into()));
+                    }
+                    SkipReason::DbBusy(packages) => fields.push((
+                        "packages".to_owned(),
+                        Value::Array(packages.iter().map(|p| p.as_str().into()).collect()),
+                    )),
+                    SkipReason::EarlyExit(_) | SkipReason::Offline => {}
+                }
+            }
+        }
+        if let Some(origin) = self.package_origin() {
+            fields.push(("package_origin".to_owned(), origin.name().into()));
+        }
+        if let Some(hint) = self.hint() {
+            fields.push(("hint".to_owned(), hint.into()));
+        }
+        Value::Object(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        let finding = Finding::BrokenServiceLink {
+            link: PathBuf::from("/etc/systemd/system/multi-user.target.wants/foo.service"),
+            package: None,
+        };
+        assert_eq!(finding.kind(), CheckKind::ServiceLinks);
+        assert_eq!(
+            finding.to_json().to_string(),
+            r#"{"schema_version":1,"check":"service-links","severity":"warning","message":"Systemd enabled service has broken link in /etc/systemd/system/multi-user.target.wants/foo.service, created by systemctl enable, remove it with systemctl disable","link":"/etc/systemd/system/multi-user.target.wants/foo.service"}"#
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! System health score of full scans, and its trend over time
+
+use std::{
+    fmt, fs,
+    io::{self, Write as _},
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    baseline::root_state_dir,
+    finding::{Finding, Severity},
+};
+
+/// History file name in state directory
+const HISTORY_FILENAME: &str = "health.history";
+
+/// Maximum number of scores kept in the history, older ones are dropped
+const MAX_ENTRIES: usize = 512;
+
+/// Age of the score a new one is compared with, if the history goes back that far
+const TREND_PERIOD: Duration = Duration::from_hours(24 * 7);
+
+/// Score of a system without findings
+const MAX_SCORE: u64 = 100;
+
+/// Score lost for a finding of a severity
+fn weight(severity: Severity) -> u64 {
+    match severity {
+        Severity::Info => 0,
+        Severity::Warning => 2,
+        Severity::Error => 10,
+    }
+}
+
+/// Health of the system at the time of a scan
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Health {
+    /// Unix time of the scan, in seconds
+    time: u64,
+    /// From 0 to [`MAX_SCORE`]
+    score: u64,
+    /// Number of findings with the warning or error severity, excluding skipped checks
+    issues: u64,
+}
+
+impl Health {
+    /// Compute the health of the system from the findings of a full scan
+    #[must_use]
+    pub fn new(findings: &[Finding]) -> Self {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        // Skipped checks say nothing about the system
+        let issues = findings
+            .iter()
+            .filter(|f| !matches!(f, Finding::CheckSkipped { .. }) && weight(f.severity()) > 0);
+        Self {
+            time,
+            score: MAX_SCORE.saturating_sub(issues.clone().map(|f| weight(f.severity())).sum()),
+            issues: issues.count() as u64,
+        }
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace().map(|f| f.parse().ok());
+        Some(Self {
+            time: fields.next()??,
+            score: fields.next()??,
+            issues: fields.next()??,
+        })
+    }
+}
+
+impl fmt::Display for Health {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.time, self.score, self.issues)
+    }
+}
+
+/// Scores of previous full scans, oldest first
+pub struct HealthHistory {
+    entries: Vec<Health>,
+}
+
+impl HealthHistory {
+    fn path() -> PathBuf {
+        root_state_dir().join(HISTORY_FILENAME)
+    }
+
+    /// Load history, which is empty on first run, invalid lines are ignored
+    ///
+    /// # Errors
+    ///
+    /// Fails if the history file exists but can not be read
+    pub fn load() -> anyhow::Result<Self> {
+        let entries = match fs::read_to_string(Self::path()) {
+            Ok(content) => content.lines().filter_map(Health::parse).collect(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self { entries })
+    }
+
+    /// Get the previous score a new one is compared with: the most recent one older than [`TREND_PERIOD`],
+    /// or the oldest one if the history is more recent
+    fn reference(&self, current: &Health) -> Option<&Health> {
+        let cutoff = current.time.saturating_sub(TREND_PERIOD.as_secs());
+        self.entries
+            .iter()
+            .rev()
+            .find(|h| h.time <= cutoff)
+            .or_else(|| self.entries.first())
+    }
+
+    /// Describe the trend of a new score compared to the history
+    #[must_use]
+    pub fn trend(&self, current: Health) -> Trend {
+        Trend {
+            current,
+            reference: self.reference(&current).copied(),
+        }
+    }
+
+    /// Append a new score, dropping the oldest ones over [`MAX_ENTRIES`]
+    ///
+    /// # Errors
+    ///
+    /// Fails if the history file can not be written
+    pub fn append(mut self, health: Health) -> anyhow::Result<()> {
+        self.entries.push(health);
+        let skip = self.entries.len().saturating_sub(MAX_ENTRIES);
+        let path = Self::path();
+        fs::create_dir_all(root_state_dir())?;
+        let tmp_path = path.with_extension("tmp");
+        let mut file = io::BufWriter::new(fs::File::create(&tmp_path)?);
+        for entry in &self.entries[skip..] {
+            writeln!(file, "{entry}")?;
+        }
+        file.into_inner()
+            .map_err(io::IntoInnerError::into_error)?
+            .sync_all()?;
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+}
+
+/// Health score of a scan, compared to a previous one
+pub struct Trend {
+    current: Health,
+    reference: Option<Health>,
+}
+
+impl fmt::Display for Trend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Health score: {}/{MAX_SCORE}", self.current.score)?;
+        let Some(reference) = self.reference else {
+            return Ok(());
+        };
+        let since = if self.current.time.saturating_sub(reference.time) >= TREND_PERIOD.as_secs() {
+            "last week"
+        } else {
+            "the first recorded scan"
+        };
+        match self.current.issues.cmp(&reference.issues) {
+            std::cmp::Ordering::Greater => write!(
+                f,
+                ", {} new issues since {since}",
+                self.current.issues - reference.issues
+            ),
+            std::cmp::Ordering::Less => write!(
+                f,
+                ", {} fewer issues since {since}",
+                reference.issues - self.current.issues
+            ),
+            std::cmp::Ordering::Equal => write!(f, ", unchanged since {since}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use super::*;
+    use crate::finding::PythonAction;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn health(time: u64, score: u64, issues: u64) -> Health {
+        Health {
+            time,
+            score,
+            issues,
+        }
+    }
+
+    #[test]
+    fn test_health() {
+        let findings = [
+            Finding::TruncatedFile {
+                package: Arc::new("foo".to_owned()),
+                file: Arc::new(PathBuf::from("/usr/bin/foo")),
+                size: 0,
+                expected: 1024,
+            },
+            Finding::BrokenPythonPackage {
+                package: "python-foo".to_owned(),
+                dirs: vec!["/usr/lib/python3.11".to_owned()],
+                files: 1,
+                size: 1024,
+                action: PythonAction::Rebuild,
+            },
+            Finding::PacnewFile {
+                path: PathBuf::from("/etc/foo.conf"),
+            },
+        ];
+        let current = Health::new(&findings);
+        assert_eq!((current.score, current.issues), (88, 2));
+        assert_eq!(Health::parse(&current.to_string()), Some(current));
+        assert_eq!(Health::parse("1 2"), None);
+    }
+
+    #[test]
+    fn test_trend() {
+        let history = HealthHistory {
+            entries: vec![
+                health(DAY, 100, 0),
+                health(3 * DAY, 95, 1),
+                health(9 * DAY, 90, 2),
+            ],
+        };
+        assert_eq!(
+            history.trend(health(10 * DAY, 70, 4)).to_string(),
+            "Health score: 70/100, 3 new issues since last week"
+        );
+        assert_eq!(
+            history.trend(health(5 * DAY, 100, 0)).to_string(),
+            "Health score: 100/100, unchanged since the first recorded scan"
+        );
+        assert_eq!(
+            HealthHistory { entries: vec![] }
+                .trend(health(DAY, 100, 0))
+                .to_string(),
+            "Health score: 100/100"
+        );
+    }
+}
@@ -0,0 +1,58 @@
+//! Remediation hints for missing libraries of well known families, often removed by upgrades
+
+/// Soname prefixes of a library family, and what to do when one of them is missing
+const RULES: [(&[&str], &str); 5] = [
+    (
+        &[
+            "libavcodec.so.",
+            "libavdevice.so.",
+            "libavfilter.so.",
+            "libavformat.so.",
+            "libavutil.so.",
+            "libpostproc.so.",
+            "libswresample.so.",
+            "libswscale.so.",
+        ],
+        "install the compatibility package of the FFmpeg version it was built against, like ffmpeg4.4, or rebuild it against ffmpeg",
+    ),
+    (
+        &["libicudata.so.", "libicui18n.so.", "libicuio.so.", "libicuuc.so."],
+        "ICU changes soname with every release, rebuild it against icu",
+    ),
+    (
+        &["libcrypto.so.1.1", "libssl.so.1.1"],
+        "install openssl-1.1, or rebuild it against openssl",
+    ),
+    (
+        &["libcrypto.so.1.0", "libssl.so.1.0"],
+        "install openssl-1.0 from the AUR, or rebuild it against openssl",
+    ),
+    (
+        &["libpng12.so.", "libpng14.so.", "libpng15.so."],
+        "install the libpng compatibility package of the same version from the AUR, like libpng12, or rebuild it against libpng",
+    ),
+];
+
+/// Get what to do about a missing library, if it is from a known family
+#[must_use]
+pub fn remediation_hint(soname: &str) -> Option<&'static str> {
+    RULES
+        .iter()
+        .find(|(prefixes, _)| prefixes.iter().any(|p| soname.starts_with(p)))
+        .map(|(_, hint)| *hint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remediation_hint() {
+        assert!(remediation_hint("libavcodec.so.58").is_some_and(|h| h.contains("ffmpeg4.4")));
+        assert!(remediation_hint("libicuuc.so.74").is_some_and(|h| h.contains("icu")));
+        assert!(remediation_hint("libssl.so.1.1").is_some_and(|h| h.contains("openssl-1.1")));
+        assert!(remediation_hint("libssl.so.1.0.0").is_some_and(|h| h.contains("openssl-1.0")));
+        assert_eq!(remediation_hint("libssl.so.3"), None);
+        assert_eq!(remediation_hint("libfoo.so.1"), None);
+    }
+}
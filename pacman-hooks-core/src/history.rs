@@ -0,0 +1,324 @@
+//! Package history from the pacman log and package cache, to explain where a missing library went
+
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+
+use crate::{
+    json::{ToJson, Value},
+    privileges, root,
+};
+
+/// Pacman log path
+const LOG_PATH: &str = "/var/log/pacman.log";
+
+/// Pacman package cache directory
+const CACHE_DIR: &str = "/var/cache/pacman/pkg";
+
+/// Library directory of package archives
+const LIBRARY_DIR: &str = "usr/lib/";
+
+/// Directory of package archives whose libraries ld.so.conf snippets may add to the search path
+const OPT_DIR: &str = "opt/";
+
+/// Maximum number of recent package changes searched, since each one needs a package archive listing
+const MAX_CHANGES: usize = 100;
+
+/// How a transaction replaced or removed a package version
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Change {
+    /// Package removed
+    Removed,
+    /// Package replaced by a newer version
+    Upgraded,
+    /// Package replaced by an older version
+    Downgraded,
+}
+
+impl Change {
+    /// Verb used in the pacman log
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Removed => "removed",
+            Self::Upgraded => "upgraded",
+            Self::Downgraded => "downgraded",
+        }
+    }
+}
+
+/// Package version that provided a library, and the transaction that replaced or removed it
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LibraryOrigin {
+    package: String,
+    version: String,
+    change: Change,
+    /// Day of the transaction, like `2024-03-05`
+    date: String,
+    /// Cached archive of the package version
+    archive: Option<PathBuf>,
+}
+
+impl LibraryOrigin {
+    /// Create an origin of a library, from a package version changed by a transaction on `date`
+    #[must_use]
+    pub fn new(
+        package: &str,
+        version: &str,
+        change: Change,
+        date: &str,
+        archive: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            package: package.to_owned(),
+            version: version.to_owned(),
+            change,
+            date: date.to_owned(),
+            archive,
+        }
+    }
+
+    /// Package name
+    #[must_use]
+    pub fn package(&self) -> &str {
+        &self.package
+    }
+
+    /// Package version that provided the library
+    #[must_use]
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// How the package version was replaced or removed
+    #[must_use]
+    pub fn change(&self) -> Change {
+        self.change
+    }
+
+    /// Day of the transaction, like `2024-03-05`
+    #[must_use]
+    pub fn date(&self) -> &str {
+        &self.date
+    }
+
+    /// Cached archive of the package version, that it can be reinstalled from
+    #[must_use]
+    pub fn archive(&self) -> Option<&Path> {
+        self.archive.as_deref()
+    }
+}
+
+impl fmt::Display for LibraryOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}, {} on {}",
+            self.package,
+            self.version,
+            self.change.name(),
+            self.date
+        )
+    }
+}
+
+impl ToJson for LibraryOrigin {
+    fn to_json(&self) -> Value {
+        Value::Object(vec![
+            ("package".to_owned(), self.package.as_str().into()),
+            ("version".to_owned(), self.version.as_str().into()),
+            ("change".to_owned(), self.change.name().into()),
+            ("date".to_owned(), self.date.as_str().into()),
+        ])
+    }
+}
+
+/// Parse pacman log lines like `[2024-03-05T18:12:01+0100] [ALPM] upgraded icu (74.2-1 -> 75.1-1)`,
+/// into changes replacing or removing a package version, most recent first
+fn parse_log(log: &str) -> Vec<LibraryOrigin> {
+    let mut changes: Vec<_> = log
+        .lines()
+        .filter_map(|line| {
+            let (timestamp, message) = line.strip_prefix('[')?.split_once("] [ALPM] ")?;
+            let (verb, rest) = message.split_once(' ')?;
+            let change = [Change::Removed, Change::Upgraded, Change::Downgraded]
+                .into_iter()
+                .find(|c| c.name() == verb)?;
+            let (package, versions) = rest.split_once(" (")?;
+            let version = versions.strip_suffix(')')?.split(" -> ").next()?;
+            Some(LibraryOrigin {
+                package: package.to_owned(),
+                version: version.to_owned(),
+                change,
+                // Old logs use "2019-01-02 10:00"
+                date: timestamp
+                    .split(['T', ' '])
+                    .next()
+                    .unwrap_or_default()
+                    .to_owned(),
+                archive: None,
+            })
+        })
+        .collect();
+    changes.reverse();
+    changes
+}
+
+/// Get the timestamp of the last completed transaction in pacman log lines
+fn parse_last_transaction(log: &str) -> Option<&str> {
+    log.lines().rev().find_map(|line| {
+        let (timestamp, message) = line.strip_prefix('[')?.split_once("] [ALPM] ")?;
+        (message == "transaction completed").then_some(timestamp)
+    })
+}
+
+/// Read the pacman log of the checked system, empty if there is none
+pub(crate) fn read_log() -> anyhow::Result<String> {
+    match fs::read_to_string(root::resolve(Path::new(LOG_PATH))) {
+        Ok(log) => Ok(log),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Get the timestamp of the last completed transaction of the checked system, as in the pacman log
+///
+/// # Errors
+///
+/// Fails if the pacman log can not be read
+pub fn last_transaction() -> anyhow::Result<Option<String>> {
+    Ok(parse_last_transaction(&read_log()?).map(ToOwned::to_owned))
+}
+
+/// Get archives of a package version among package cache files, of any architecture
+fn cached_archives<'a>(cached: &'a [PathBuf], package: &str, version: &str) -> Vec<&'a Path> {
+    let prefix = format!("{package}-{version}-");
+    cached
+        .iter()
+        .map(PathBuf::as_path)
+        .filter(|p| {
+            p.extension().is_none_or(|e| e != "sig")
+                && p.file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(|n| n.strip_prefix(&prefix))
+                    .is_some_and(|arch| !arch.contains('-') && arch.contains(".pkg.tar"))
+        })
+        .collect()
+}
+
+/// Get names of files directly in the library directory of a package archive, and of libraries below /opt,
+/// with bsdtar
+fn archive_libraries(archive: &Path) -> anyhow::Result<Vec<String>> {
+    let output = privileges::command("bsdtar")
+        .arg("-tf")
+        .arg(archive)
+        .output()
+        .context("Failed to run bsdtar")?;
+    if !output.status.success() {
+        anyhow::bail!("Failed to list files of {}", archive.display());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|l| match l.strip_prefix(LIBRARY_DIR) {
+            Some(name) => Some(name).filter(|n| !n.contains('/')),
+            None => l
+                .strip_prefix(OPT_DIR)?
+                .rsplit('/')
+                .next()
+                .filter(|n| n.contains(".so")),
+        })
+        .filter(|n| !n.is_empty())
+        .map(ToOwned::to_owned)
+        .collect())
+}
+
+/// Recent package changes of the checked system
+pub struct History {
+    /// Most recent first
+    changes: Vec<LibraryOrigin>,
+}
+
+impl History {
+    /// Read recent package changes from the pacman log
+    ///
+    /// # Errors
+    ///
+    /// Fails if the pacman log can not be read
+    pub fn load() -> anyhow::Result<Self> {
+        let mut changes = parse_log(&read_log()?);
+        changes.truncate(MAX_CHANGES);
+        Ok(Self { changes })
+    }
+
+    /// Find which recently replaced or removed package versions provided libraries, by soname,
+    /// from their archives still in the package cache
+    #[must_use]
+    pub fn library_origins(&self, sonames: &[String]) -> HashMap<String, LibraryOrigin> {
+        let cached: Vec<_> = root::resolve(Path::new(CACHE_DIR))
+            .read_dir()
+            .map(|entries| entries.filter_map(Result::ok).map(|e| e.path()).collect())
+            .unwrap_or_default();
+        let mut origins = HashMap::new();
+        for change in &self.changes {
+            if origins.len() == sonames.len() {
+                break;
+            }
+            for archive in cached_archives(&cached, &change.package, &change.version) {
+                let libraries = archive_libraries(archive).unwrap_or_else(|err| {
+                    log::debug!("{err:#}");
+                    Vec::new()
+                });
+                for soname in sonames.iter().filter(|s| libraries.contains(s)) {
+                    origins
+                        .entry(soname.to_owned())
+                        .or_insert_with(|| LibraryOrigin {
+                            archive: Some(archive.to_owned()),
+                            ..change.clone()
+                        });
+                }
+            }
+        }
+        origins
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log() {
+        let changes = parse_log(
+            "[2019-01-02 10:00] [ALPM] removed libbar (1:2.0-3)
+[2024-03-05T18:12:01+0100] [PACMAN] Running 'pacman -Syu'
+[2024-03-05T18:12:02+0100] [ALPM] upgraded icu (74.2-1 -> 75.1-1)
+[2024-03-05T18:12:02+0100] [ALPM] installed libfoo (1.0-1)
+",
+        );
+        assert_eq!(
+            changes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "icu 74.2-1, upgraded on 2024-03-05",
+                "libbar 1:2.0-3, removed on 2019-01-02"
+            ]
+        );
+    }
+    #[test]
+    fn test_parse_last_transaction() {
+        let log = "[2024-03-05T18:12:01+0100] [ALPM] transaction started
+[2024-03-05T18:12:02+0100] [ALPM] upgraded icu (74.2-1 -> 75.1-1)
+[2024-03-05T18:12:03+0100] [ALPM] transaction completed
+[2024-03-06T09:00:00+0100] [ALPM] transaction started
+[2024-03-06T09:00:01+0100] [ALPM] transaction interrupted
+";
+        assert_eq!(
+            parse_last_transaction(log),
+            Some("2024-03-05T18:12:03+0100")
+        );
+        assert_eq!(parse_last_transaction(""), None);
+    }
+}
@@ -0,0 +1,155 @@
+//! HTTP downloads and uploads, with curl
+
+use std::{fmt::Write as _, io::Write as _, process::Stdio, time::Duration};
+
+use anyhow::Context as _;
+
+use crate::{network, privileges};
+
+/// Maximum time to connect to a server, in seconds
+const CONNECT_TIMEOUT_SECS: &str = "5";
+
+/// Maximum time of a download, in seconds
+const DOWNLOAD_TIMEOUT_SECS: &str = "30";
+
+/// Percent encode a URL component
+pub(crate) fn url_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(char::from(b));
+        } else {
+            let _ = write!(encoded, "%{b:02X}");
+        }
+    }
+    encoded
+}
+
+/// Download content at `url`
+pub(crate) fn download(url: &str) -> anyhow::Result<Vec<u8>> {
+    if network::is_offline() {
+        anyhow::bail!("Network is offline, not downloading {url}");
+    }
+    log::debug!("Downloading {url}");
+    let output = privileges::command("curl")
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            // URLs may contain brackets, like AUR query arguments
+            "--globoff",
+            "--connect-timeout",
+            CONNECT_TIMEOUT_SECS,
+            "--max-time",
+            DOWNLOAD_TIMEOUT_SECS,
+            url,
+        ])
+        .output()
+        .context("Failed to run curl")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Download failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// Quote a string for a curl configuration file
+fn config_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Upload `body` as JSON to `url`, with `token` as bearer authentication, in at most `max_time`, and get the HTTP
+/// status code.
+///
+/// Options are passed to curl in a configuration file on its standard input, so that the token does not show up in
+/// process arguments.
+pub(crate) fn post_json(
+    url: &str,
+    token: Option<&str>,
+    body: &str,
+    max_time: Duration,
+) -> anyhow::Result<u16> {
+    if network::is_offline() {
+        anyhow::bail!("Network is offline, not uploading to {url}");
+    }
+    log::debug!("Uploading {} bytes to {url}", body.len());
+    let mut config = format!(
+        "url = {}\nheader = \"Content-Type: application/json\"\n",
+        config_string(url)
+    );
+    if let Some(token) = token {
+        let _ = writeln!(
+            config,
+            "header = {}",
+            config_string(&format!("Authorization: Bearer {token}"))
+        );
+    }
+    let _ = writeln!(config, "data-binary = {}", config_string(body));
+    let max_time = max_time.as_secs().to_string();
+    let mut child = privileges::command("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--config",
+            "-",
+            "--output",
+            "/dev/null",
+            "--write-out",
+            "%{http_code}",
+            "--connect-timeout",
+            CONNECT_TIMEOUT_SECS,
+            "--max-time",
+            &max_time,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run curl")?;
+    // curl reads its whole configuration before sending anything, so it cannot block on its output meanwhile
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(config.as_bytes())
+            .context("Failed to pass options to curl")?;
+    }
+    let output = child.wait_with_output().context("Failed to run curl")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Upload failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .context("Unexpected HTTP status code from curl")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_string() {
+        assert_eq!(
+            config_string("{\"a\":\"b\\\\c\nd\"}"),
+            r#""{\"a\":\"b\\\\c\nd\"}""#
+        );
+    }
+}
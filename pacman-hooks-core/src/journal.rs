@@ -0,0 +1,21 @@
+//! Journald priorities, for output read by systemd
+
+// Journald priority prefixes, see sd-daemon(3)
+/// Error priority
+pub const SD_ERR: &str = "<3>";
+/// Warning priority
+pub const SD_WARNING: &str = "<4>";
+/// Notice priority
+pub const SD_NOTICE: &str = "<5>";
+const SD_INFO: &str = "<6>";
+const SD_DEBUG: &str = "<7>";
+
+/// Get the priority prefix of a log level
+pub(crate) fn priority(level: log::Level) -> &'static str {
+    match level {
+        log::Level::Error => SD_ERR,
+        log::Level::Warn => SD_WARNING,
+        log::Level::Info => SD_INFO,
+        log::Level::Debug | log::Level::Trace => SD_DEBUG,
+    }
+}
@@ -0,0 +1,384 @@
+//! Minimal JSON serialization and parsing
+
+use std::fmt::{self, Write as _};
+
+/// JSON value
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// `null`
+    Null,
+    /// `true` or `false`
+    Bool(bool),
+    /// Number
+    Number(f64),
+    /// String
+    String(String),
+    /// Array
+    Array(Vec<Value>),
+    /// Object, fields are kept in insertion order
+    Object(Vec<(String, Value)>),
+}
+
+/// Conversion to a JSON value
+pub trait ToJson {
+    /// Convert to a JSON value
+    fn to_json(&self) -> Value;
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Self::String(s.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Self::String(s)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Self::Bool(b)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Self::Number(n)
+    }
+}
+
+impl From<u64> for Value {
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "counts and sizes are far below 2^53"
+    )]
+    fn from(n: u64) -> Self {
+        Self::Number(n as f64)
+    }
+}
+
+impl<T: ToJson> From<&[T]> for Value {
+    fn from(items: &[T]) -> Self {
+        Self::Array(items.iter().map(ToJson::to_json).collect())
+    }
+}
+
+/// Write a quoted and escaped JSON string
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", u32::from(c))?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Number(n) if n.is_finite() => write!(f, "{n}"),
+            // Infinity and NaN are not representable in JSON
+            Self::Null | Self::Number(_) => f.write_str("null"),
+            Self::String(s) => write_string(f, s),
+            Self::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_char(']')
+            }
+            Self::Object(fields) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+impl Value {
+    /// Get an object field
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        match self {
+            Self::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Get string value
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Get array items
+    #[must_use]
+    pub fn as_array(&self) -> Option<&[Self]> {
+        match self {
+            Self::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/// Maximum nesting of arrays and objects
+const MAX_DEPTH: usize = 64;
+
+/// Recursive descent JSON parser
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| matches!(b, b' ' | b'\t' | b'\n' | b'\r'))
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn next_byte(&mut self) -> anyhow::Result<u8> {
+        let b = *self
+            .bytes
+            .get(self.pos)
+            .ok_or_else(|| anyhow::anyhow!("Unexpected end of JSON"))?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn expect(&mut self, expected: u8) -> anyhow::Result<()> {
+        self.skip_whitespace();
+        let b = self.next_byte()?;
+        if b != expected {
+            anyhow::bail!(
+                "Expected {:?} at offset {}, got {:?}",
+                char::from(expected),
+                self.pos - 1,
+                char::from(b)
+            );
+        }
+        Ok(())
+    }
+
+    fn literal(&mut self, literal: &str, value: Value) -> anyhow::Result<Value> {
+        if !self.bytes[self.pos..].starts_with(literal.as_bytes()) {
+            anyhow::bail!("Invalid JSON literal at offset {}", self.pos);
+        }
+        self.pos += literal.len();
+        Ok(value)
+    }
+
+    fn value(&mut self, depth: usize) -> anyhow::Result<Value> {
+        if depth > MAX_DEPTH {
+            anyhow::bail!("JSON nesting too deep");
+        }
+        self.skip_whitespace();
+        match self.bytes.get(self.pos) {
+            Some(b'n') => self.literal("null", Value::Null),
+            Some(b't') => self.literal("true", Value::Bool(true)),
+            Some(b'f') => self.literal("false", Value::Bool(false)),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.bytes.get(self.pos) == Some(&b']') {
+                    self.pos += 1;
+                    return Ok(Value::Array(items));
+                }
+                loop {
+                    items.push(self.value(depth + 1)?);
+                    self.skip_whitespace();
+                    match self.next_byte()? {
+                        b',' => {}
+                        b']' => return Ok(Value::Array(items)),
+                        _ => anyhow::bail!("Expected ',' or ']' at offset {}", self.pos - 1),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.bytes.get(self.pos) == Some(&b'}') {
+                    self.pos += 1;
+                    return Ok(Value::Object(fields));
+                }
+                loop {
+                    self.expect(b'"')?;
+                    self.pos -= 1;
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    fields.push((key, self.value(depth + 1)?));
+                    self.skip_whitespace();
+                    match self.next_byte()? {
+                        b',' => {}
+                        b'}' => return Ok(Value::Object(fields)),
+                        _ => anyhow::bail!("Expected ',' or '}}' at offset {}", self.pos - 1),
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => anyhow::bail!("Unexpected character at offset {}", self.pos),
+            None => anyhow::bail!("Unexpected end of JSON"),
+        }
+    }
+
+    fn number(&mut self) -> anyhow::Result<Value> {
+        let start = self.pos;
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| matches!(b, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+        {
+            self.pos += 1;
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.pos])?;
+        Ok(Value::Number(text.parse().map_err(|e| {
+            anyhow::anyhow!("Invalid JSON number {text:?}: {e}")
+        })?))
+    }
+
+    fn hex4(&mut self) -> anyhow::Result<u32> {
+        let digits = self
+            .bytes
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| anyhow::anyhow!("Unexpected end of JSON"))?;
+        self.pos += 4;
+        Ok(u32::from_str_radix(std::str::from_utf8(digits)?, 16)?)
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        self.pos += 1;
+        let mut s = Vec::new();
+        loop {
+            match self.next_byte()? {
+                b'"' => return Ok(String::from_utf8(s)?),
+                b'\\' => {
+                    let c = match self.next_byte()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            if (0xd800..0xdc00).contains(&code) {
+                                // Surrogate pair
+                                if self.next_byte()? != b'\\' || self.next_byte()? != b'u' {
+                                    anyhow::bail!("Invalid JSON surrogate pair");
+                                }
+                                let low = self.hex4()?;
+                                code = 0x10000
+                                    + ((code - 0xd800) << 10)
+                                    + (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            char::from_u32(code)
+                                .ok_or_else(|| anyhow::anyhow!("Invalid JSON unicode escape"))?
+                        }
+                        b => anyhow::bail!("Invalid JSON escape {:?}", char::from(b)),
+                    };
+                    let mut buf = [0; 4];
+                    s.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                b => s.push(b),
+            }
+        }
+    }
+}
+
+/// Parse a JSON document
+///
+/// # Errors
+///
+/// Fails if `text` is not a valid JSON document
+pub fn parse(text: &str) -> anyhow::Result<Value> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        pos: 0,
+    };
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    if parser.pos != parser.bytes.len() {
+        anyhow::bail!("Trailing data after JSON value at offset {}", parser.pos);
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize() {
+        let value = Value::Object(vec![
+            ("name".to_owned(), "a \"b\"\n\u{1}".into()),
+            ("count".to_owned(), 3.0.into()),
+            ("ratio".to_owned(), 0.5.into()),
+            (
+                "items".to_owned(),
+                Value::Array(vec![Value::Null, true.into()]),
+            ),
+        ]);
+        assert_eq!(
+            value.to_string(),
+            r#"{"name":"a \"b\"\n\u0001","count":3,"ratio":0.5,"items":[null,true]}"#
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let value = parse(
+            r#" {"message": "caf\u00e9 \"x\" \ud83d\ude00", "n": -1.5e2, "list": [true, null, {}], "empty": []} "#,
+        )
+        .unwrap();
+        assert_eq!(
+            value.get("message").and_then(Value::as_str),
+            Some("café \"x\" 😀")
+        );
+        assert_eq!(value.get("n"), Some(&Value::Number(-150.0)));
+        assert_eq!(
+            value.get("list"),
+            Some(&Value::Array(vec![
+                true.into(),
+                Value::Null,
+                Value::Object(Vec::new())
+            ]))
+        );
+        assert_eq!(parse(&value.to_string()).unwrap(), value);
+
+        assert!(parse("[1, 2").is_err());
+        assert!(parse("{} {}").is_err());
+        assert!(parse("\"\\x\"").is_err());
+    }
+}
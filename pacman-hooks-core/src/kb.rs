@@ -0,0 +1,166 @@
+//! Knowledge base of known false positives, like packages bundling libraries on purpose
+
+use std::{fmt::Write as _, fs, io, path::PathBuf};
+
+use anyhow::Context as _;
+use glob::Pattern;
+
+use crate::{baseline::STATE_DIR, http};
+
+/// Knowledge base shipped with this version
+const BUILTIN: &str = include_str!("../known-bundled-libs.conf");
+
+/// Updated knowledge base file name in state directory
+const FILENAME: &str = "known-bundled-libs.conf";
+
+/// URL of the latest knowledge base, in the project repository
+const UPDATE_URL: &str =
+    "https://raw.githubusercontent.com/desbma/pacman-hooks/master/pacman-hooks-core/known-bundled-libs.conf";
+
+/// Packages whose missing sonames must not be reported
+#[derive(Default)]
+pub struct KnowledgeBase {
+    /// Package name pattern, and soname patterns
+    entries: Vec<(Pattern, Vec<Pattern>)>,
+}
+
+impl KnowledgeBase {
+    fn path() -> PathBuf {
+        PathBuf::from(STATE_DIR).join(FILENAME)
+    }
+
+    fn parse(content: &str) -> anyhow::Result<Self> {
+        let mut entries = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let mut words = line
+                .split('#')
+                .next()
+                .unwrap_or_default()
+                .split_whitespace();
+            let Some(package) = words.next() else {
+                continue;
+            };
+            let pattern = |p: &str| {
+                Pattern::new(p).with_context(|| format!("Line {}: invalid pattern {p:?}", i + 1))
+            };
+            let package = pattern(package)?;
+            let sonames = words.map(pattern).collect::<anyhow::Result<Vec<_>>>()?;
+            if sonames.is_empty() {
+                anyhow::bail!("Line {}: missing sonames for package {package}", i + 1);
+            }
+            entries.push((package, sonames));
+        }
+        Ok(Self { entries })
+    }
+
+    /// Load the updated knowledge base, or the built-in one if it was never updated or is invalid
+    #[must_use]
+    pub fn load() -> Self {
+        match fs::read_to_string(Self::path()) {
+            Ok(content) => match Self::parse(&content) {
+                Ok(kb) => return kb,
+                Err(err) => log::warn!("Ignoring invalid updated knowledge base: {err:#}"),
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => log::warn!("Failed to read updated knowledge base: {err}"),
+        }
+        Self::builtin()
+    }
+
+    fn builtin() -> Self {
+        Self::parse(BUILTIN).unwrap_or_else(|err| {
+            log::error!("Invalid built-in knowledge base: {err:#}");
+            Self::default()
+        })
+    }
+
+    /// Whether a missing soname of a package is a known false positive
+    #[must_use]
+    pub fn is_known(&self, package: &str, soname: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|(p, s)| p.matches(package) && s.iter().any(|s| s.matches(soname)))
+    }
+
+    /// Entries as package name pattern, and soname patterns
+    pub(crate) fn entries(&self) -> &[(Pattern, Vec<Pattern>)] {
+        &self.entries
+    }
+
+    /// Add an entry, unless it is already present.
+    ///
+    /// Returns whether it was added.
+    pub(crate) fn add(&mut self, package: Pattern, sonames: Vec<Pattern>) -> bool {
+        if self
+            .entries
+            .iter()
+            .any(|(p, s)| *p == package && *s == sonames)
+        {
+            return false;
+        }
+        self.entries.push((package, sonames));
+        true
+    }
+
+    /// Replace the updated knowledge base with these entries
+    pub(crate) fn save(&self) -> anyhow::Result<()> {
+        let mut content = String::new();
+        for (package, sonames) in &self.entries {
+            let sonames: Vec<_> = sonames.iter().map(Pattern::as_str).collect();
+            writeln!(content, "{package} {}", sonames.join(" "))?;
+        }
+        Self::write(&content)
+    }
+
+    fn write(content: &str) -> anyhow::Result<()> {
+        let path = Self::path();
+        fs::create_dir_all(STATE_DIR)?;
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Download the latest knowledge base from the project repository, and replace the updated one.
+    ///
+    /// Returns the number of entries.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the download fails, the downloaded knowledge base is invalid, or it can not be written
+    pub fn update() -> anyhow::Result<usize> {
+        log::info!("Downloading knowledge base from {UPDATE_URL}");
+        let content =
+            String::from_utf8(http::download(UPDATE_URL)?).context("Invalid knowledge base")?;
+        let kb = Self::parse(&content).context("Invalid knowledge base")?;
+        Self::write(&content)?;
+        Ok(kb.entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_known() {
+        let kb = KnowledgeBase::parse(
+            "# comment
+bcompare *
+* libffmpeg.so # inline comment
+wine* *.dll.so libwine.so.1
+",
+        )
+        .unwrap();
+        assert!(kb.is_known("bcompare", "libQt4Pas.so.5"));
+        assert!(kb.is_known("signal-desktop", "libffmpeg.so"));
+        assert!(!kb.is_known("signal-desktop", "libffmpeg.so.60"));
+        assert!(kb.is_known("wine-staging", "ntdll.dll.so"));
+        assert!(kb.is_known("wine", "libwine.so.1"));
+        assert!(!kb.is_known("foo", "libwine.so.1"));
+
+        assert!(KnowledgeBase::parse("foo\n").is_err());
+        assert!(KnowledgeBase::parse("foo [\n").is_err());
+        assert!(!KnowledgeBase::parse(BUILTIN).unwrap().entries.is_empty());
+    }
+}
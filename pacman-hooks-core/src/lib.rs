@@ -0,0 +1,47 @@
+//! Shared code for pacman hooks.
+//!
+//! Checks can be embedded in other tools with [`run_checks`], which returns [`Finding`]s,
+//! serializable to JSON with [`json::ToJson`].
+
+pub mod aur;
+pub mod backend;
+pub mod baseline;
+mod cache;
+pub mod cancel;
+pub mod checks;
+pub mod collect;
+pub mod config;
+pub mod elf;
+pub mod error;
+pub mod filesdb;
+pub mod finding;
+pub mod health;
+pub mod hints;
+pub mod history;
+mod http;
+pub mod journal;
+pub mod json;
+pub mod kb;
+pub mod localdb;
+pub mod logging;
+mod metadata;
+pub mod network;
+pub mod origins;
+pub mod output;
+pub mod overrides;
+pub mod privileges;
+mod progress;
+pub mod rebuild;
+pub mod reports;
+pub mod root;
+pub mod schema;
+pub mod security;
+pub mod selftest;
+mod sha256;
+pub mod suppressions;
+pub mod syncdb;
+pub mod timings;
+pub mod version;
+
+pub use checks::{run_checks, Check, CheckDoc};
+pub use finding::{CheckKind, Finding, Severity};
@@ -0,0 +1,602 @@
+//! Pacman local database access
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+
+use crate::{
+    error::{Classify as _, ErrorKind},
+    root,
+};
+
+/// Pacman lock file in the database directory, present during transactions
+const LOCK_FILENAME: &str = "db.lck";
+
+/// Files of a local database package entry, written when installing the package
+const ENTRY_FILES: [&str; 2] = ["desc", "files"];
+
+/// Maximum number of ancestor processes searched for pacman
+const MAX_ANCESTORS: usize = 16;
+
+/// Get the local database directory
+#[must_use]
+pub fn local_db_dir() -> PathBuf {
+    root::db_path().join("local")
+}
+
+/// Find the current entry directory of a package in the local database, `None` if it is not installed
+fn find_entry(package: &str) -> anyhow::Result<Option<PathBuf>> {
+    for entry in fs::read_dir(local_db_dir())? {
+        let entry = entry?;
+        if entry.file_name().to_str().and_then(package_name) == Some(package) {
+            return Ok(Some(entry.path()));
+        }
+    }
+    Ok(None)
+}
+
+/// Get the parent process id of a process
+fn parent_pid(pid: u32) -> Option<u32> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // The command name in parentheses may contain spaces
+    stat.rsplit_once(')')?
+        .1
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+/// Whether this process runs from a pacman hook, in which case pacman itself holds the database lock
+fn is_run_by_pacman() -> bool {
+    let mut pid = std::process::id();
+    for _ in 0..MAX_ANCESTORS {
+        match parent_pid(pid) {
+            Some(parent) if parent > 1 => pid = parent,
+            _ => return false,
+        }
+        if fs::read_to_string(format!("/proc/{pid}/comm")).is_ok_and(|c| c.trim_end() == "pacman") {
+            return true;
+        }
+    }
+    false
+}
+
+/// Get names of package entry directories missing some of their files, none if there is no local database
+fn incomplete_entries(dir: &Path) -> anyhow::Result<Vec<String>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut incomplete = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let path = entry.path();
+        if ENTRY_FILES.iter().any(|f| !path.join(f).exists()) {
+            incomplete.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    incomplete.sort_unstable();
+    Ok(incomplete)
+}
+
+/// Fail if a pacman transaction is modifying the local database, whose content would then be partial:
+/// if it is locked by a pacman process this one does not run from, or if package entries are incomplete
+///
+/// # Errors
+///
+/// Fails with [`ErrorKind::DbBusy`] if a transaction is modifying the database, or if it can not be read
+pub fn ensure_consistent() -> anyhow::Result<()> {
+    let lock = root::db_path().join(LOCK_FILENAME);
+    if lock.exists() && !is_run_by_pacman() {
+        return Err(anyhow::anyhow!(
+            "Local database is locked by a pacman transaction, run again when it completes, \
+             or remove {} if no pacman process is running",
+            lock.display()
+        ))
+        .classify(ErrorKind::DbBusy);
+    }
+    let incomplete = incomplete_entries(&local_db_dir()).classify(ErrorKind::PacmanQuery)?;
+    if !incomplete.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Local database entries {} are incomplete, run again when the pacman transaction writing them completes",
+            incomplete.join(", ")
+        ))
+        .classify(ErrorKind::DbBusy);
+    }
+    Ok(())
+}
+
+/// Type of an mtree entry
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum EntryType {
+    File,
+    Dir,
+    Link,
+    Other,
+}
+
+/// File metadata recorded in a package mtree
+pub struct MtreeEntry {
+    path: PathBuf,
+    kind: EntryType,
+    mode: u32,
+    /// Size in bytes, for regular files
+    size: Option<u64>,
+    /// Hexadecimal SHA-256 digest, for regular files
+    sha256: Option<String>,
+}
+
+impl MtreeEntry {
+    /// Installed file path
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether this entry is a regular file with any execute bit set
+    #[must_use]
+    pub fn is_executable_file(&self) -> bool {
+        self.kind == EntryType::File && (self.mode & 0o111) != 0
+    }
+
+    /// Recorded size in bytes, for regular files
+    #[must_use]
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    /// Recorded hexadecimal SHA-256 digest, for regular files
+    #[must_use]
+    pub fn sha256(&self) -> Option<&str> {
+        self.sha256.as_deref()
+    }
+}
+
+/// Index of the local database package entries
+pub struct LocalDb {
+    /// Package name to package entry directory
+    packages: HashMap<String, PathBuf>,
+}
+
+impl LocalDb {
+    /// Index package entries of the local database
+    ///
+    /// # Errors
+    ///
+    /// Fails if the local database directory can not be read
+    pub fn open() -> anyhow::Result<Self> {
+        let mut packages = HashMap::new();
+        for entry in fs::read_dir(local_db_dir())? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let dir_name = entry.file_name();
+            if let Some(name) = dir_name.to_str().and_then(package_name) {
+                packages.insert(name.to_owned(), entry.path());
+            }
+        }
+        Ok(Self { packages })
+    }
+
+    /// Read mtree entries of a package, return `None` if the package has no mtree file
+    ///
+    /// # Errors
+    ///
+    /// Fails if the mtree file can not be read or decompressed
+    pub fn mtree(&self, package: &str) -> anyhow::Result<Option<Vec<MtreeEntry>>> {
+        let Some(path) = self.packages.get(package).map(|d| d.join("mtree")) else {
+            return Ok(None);
+        };
+        let compressed = match fs::read(&path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let data = gunzip(&compressed).classify(ErrorKind::PacmanQuery)?;
+        Ok(Some(parse_mtree(&String::from_utf8_lossy(&data))))
+    }
+
+    /// Names of installed packages
+    pub fn packages(&self) -> impl Iterator<Item = &str> {
+        self.packages.keys().map(String::as_str)
+    }
+
+    /// Read a metadata file of a package entry, return `None` if the package is not installed.
+    ///
+    /// Upgrades rename package entries after their version, so an entry missing since the database was indexed is
+    /// searched again.
+    fn read_entry_file(&self, package: &str, name: &str) -> anyhow::Result<Option<String>> {
+        let Some(dir) = self.packages.get(package) else {
+            return Ok(None);
+        };
+        match fs::read_to_string(dir.join(name)) {
+            Ok(content) => return Ok(Some(content)),
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+            Err(_) => {}
+        }
+        // Removed since indexed
+        let Some(current_dir) = find_entry(package)? else {
+            return Ok(None);
+        };
+        fs::read_to_string(current_dir.join(name))
+            .map(Some)
+            .with_context(|| format!("Failed to read {name} of package {package:?} being written"))
+            .classify(ErrorKind::DbBusy)
+    }
+
+    /// Installed version of a package
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package entry can not be read
+    pub fn package_version(&self, package: &str) -> anyhow::Result<Option<String>> {
+        Ok(self.read_entry_file(package, "desc")?.and_then(|desc| {
+            parse_sections(&desc)
+                .get("VERSION")
+                .and_then(|v| v.first())
+                .map(|v| (*v).to_owned())
+        }))
+    }
+
+    /// Unix time of a date field of a package, like `BUILDDATE`
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package entry can not be read
+    pub fn package_date(&self, package: &str, section: &str) -> anyhow::Result<Option<u64>> {
+        Ok(self.read_entry_file(package, "desc")?.and_then(|desc| {
+            parse_sections(&desc)
+                .get(section)
+                .and_then(|v| v.first())
+                .and_then(|v| v.parse().ok())
+        }))
+    }
+
+    /// Configuration files of a package backed up on upgrade, as absolute paths
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package entry can not be read
+    pub fn package_backup_files(&self, package: &str) -> anyhow::Result<Vec<PathBuf>> {
+        Ok(self
+            .read_entry_file(package, "desc")?
+            .map(|desc| {
+                // Entries are <path>\t<md5sum>
+                parse_sections(&desc)
+                    .remove("BACKUP")
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|b| b.split('\t').next())
+                    .map(|f| Path::new("/").join(f))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Names of dependencies of a package
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package entry can not be read
+    pub fn package_dependencies(&self, package: &str) -> anyhow::Result<Vec<String>> {
+        self.dependency_names(package, "DEPENDS")
+    }
+
+    /// Names of optional dependencies of a package
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package entry can not be read
+    pub fn package_optional_dependencies(&self, package: &str) -> anyhow::Result<Vec<String>> {
+        self.dependency_names(package, "OPTDEPENDS")
+    }
+
+    /// Names a package provides besides its own
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package entry can not be read
+    pub fn package_provides(&self, package: &str) -> anyhow::Result<Vec<String>> {
+        self.dependency_names(package, "PROVIDES")
+    }
+
+    /// Names of dependencies of a package listed in a desc section
+    fn dependency_names(&self, package: &str, section: &str) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .read_entry_file(package, "desc")?
+            .map(|desc| {
+                parse_sections(&desc)
+                    .remove(section)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|d| dependency_name(d).to_owned())
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Files of a package as absolute paths, directories ending with a slash
+    ///
+    /// # Errors
+    ///
+    /// Fails if the package entry can not be read
+    pub fn files(&self, package: &str) -> anyhow::Result<Option<Vec<String>>> {
+        Ok(self.read_entry_file(package, "files")?.map(|files| {
+            parse_sections(&files)
+                .remove("FILES")
+                .unwrap_or_default()
+                .into_iter()
+                .map(|f| format!("/{f}"))
+                .collect()
+        }))
+    }
+}
+
+/// Get package name from a local database entry directory name
+#[must_use]
+pub fn package_name(dir_name: &str) -> Option<&str> {
+    // Directories are named <name>-<pkgver>-<pkgrel>
+    dir_name.rsplitn(3, '-').nth(2)
+}
+
+/// Get the package name of a dependency, without version constraint or optional dependency description
+#[must_use]
+pub fn dependency_name(dependency: &str) -> &str {
+    // Optional dependencies are "<name>: <description>", versions may contain an epoch
+    let dependency = dependency
+        .split_once(": ")
+        .map_or(dependency, |(d, _)| d)
+        .trim_end_matches(':');
+    dependency
+        .split(['<', '>', '='])
+        .next()
+        .unwrap_or_default()
+        .trim()
+}
+
+/// Parse `%SECTION%` headed values of desc and files entries
+pub(crate) fn parse_sections(text: &str) -> HashMap<&str, Vec<&str>> {
+    let mut sections = HashMap::new();
+    let mut current: Option<&mut Vec<&str>> = None;
+    for line in text.lines() {
+        if let Some(name) = line.strip_prefix('%').and_then(|l| l.strip_suffix('%')) {
+            current = Some(sections.entry(name).or_default());
+        } else if line.is_empty() {
+            current = None;
+        } else if let Some(values) = current.as_mut() {
+            values.push(line);
+        }
+    }
+    sections
+}
+
+/// Decompress gzip data
+pub(crate) fn gunzip(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    let header = data
+        .get(..10)
+        .ok_or_else(|| anyhow::anyhow!("Truncated gzip header"))?;
+    if header[..3] != [0x1f, 0x8b, 0x08] {
+        anyhow::bail!("Invalid gzip header");
+    }
+    let flags = header[3];
+    let mut offset = 10;
+    if flags & FEXTRA != 0 {
+        let len = data
+            .get(offset..offset + 2)
+            .map(|l| usize::from(u16::from_le_bytes([l[0], l[1]])))
+            .ok_or_else(|| anyhow::anyhow!("Truncated gzip header"))?;
+        offset += 2 + len;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let len = data
+                .get(offset..)
+                .and_then(|d| d.iter().position(|b| *b == 0))
+                .ok_or_else(|| anyhow::anyhow!("Truncated gzip header"))?;
+            offset += len + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        offset += 2;
+    }
+
+    let deflated = data
+        .get(offset..)
+        .ok_or_else(|| anyhow::anyhow!("Truncated gzip data"))?;
+    miniz_oxide::inflate::decompress_to_vec(deflated)
+        .map_err(|e| anyhow::anyhow!("Failed to decompress gzip data: {e}"))
+}
+
+/// Decode mtree `\ooo` octal escapes
+fn unescape(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            if let Some(c) = bytes
+                .get(i + 1..i + 4)
+                .and_then(|o| std::str::from_utf8(o).ok())
+                .and_then(|o| u8::from_str_radix(o, 8).ok())
+            {
+                decoded.push(c);
+                i += 4;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Parse mtree text, as generated by makepkg with bsdtar
+fn parse_mtree(text: &str) -> Vec<MtreeEntry> {
+    let mut defaults: HashMap<&str, &str> = HashMap::new();
+    let mut entries = Vec::new();
+
+    for line in text.lines() {
+        let mut tokens = line.split_whitespace();
+        let Some(first) = tokens.next() else {
+            continue;
+        };
+        if first.starts_with('#') {
+            continue;
+        }
+        if first == "/set" {
+            defaults.extend(tokens.filter_map(|t| t.split_once('=')));
+            continue;
+        }
+        if first == "/unset" {
+            for key in tokens {
+                defaults.remove(key);
+            }
+            continue;
+        }
+
+        let Some(relative) = first.strip_prefix("./") else {
+            continue;
+        };
+        if !relative.contains('/') && relative.starts_with('.') {
+            // Package metadata files (.PKGINFO, .BUILDINFO...), not installed
+            continue;
+        }
+
+        let mut keywords = defaults.clone();
+        keywords.extend(tokens.filter_map(|t| t.split_once('=')));
+        let kind = match keywords.get("type") {
+            Some(&"file") => EntryType::File,
+            Some(&"dir") => EntryType::Dir,
+            Some(&"link") => EntryType::Link,
+            _ => EntryType::Other,
+        };
+        let mode = keywords
+            .get("mode")
+            .and_then(|m| u32::from_str_radix(m, 8).ok())
+            .unwrap_or(0);
+        let size = keywords
+            .get("size")
+            .filter(|_| kind == EntryType::File)
+            .and_then(|s| s.parse().ok());
+        let sha256 = keywords
+            .get("sha256digest")
+            .filter(|_| kind == EntryType::File)
+            .map(|d| (*d).to_owned());
+        entries.push(MtreeEntry {
+            path: Path::new("/").join(unescape(relative)),
+            kind,
+            mode,
+            size,
+            sha256,
+        });
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mtree() {
+        let mtree = "#mtree
+/set type=file uid=0 gid=0 mode=644
+./.BUILDINFO time=1700000000.0 size=4523 md5digest=0 sha256digest=0
+./.PKGINFO time=1700000000.0 size=612 md5digest=0 sha256digest=0
+./usr time=1700000000.0 mode=755 type=dir
+./usr/bin/foo time=1700000000.0 mode=755 size=16432 md5digest=0 sha256digest=0
+./usr/bin/foo\\040bar time=1700000000.0 mode=755 size=16432 md5digest=0 sha256digest=0
+./usr/bin/foolink time=1700000000.0 mode=777 type=link link=foo
+./usr/share/foo/data time=1700000000.0 size=12 md5digest=0 sha256digest=0
+/set mode=755
+./usr/lib/libfoo.so.1 time=1700000000.0 size=12 md5digest=0 sha256digest=0
+";
+        let entries = parse_mtree(mtree);
+        assert_eq!(
+            entries
+                .iter()
+                .filter(|e| e.is_executable_file())
+                .map(MtreeEntry::path)
+                .collect::<Vec<_>>(),
+            [
+                Path::new("/usr/bin/foo"),
+                Path::new("/usr/bin/foo bar"),
+                Path::new("/usr/lib/libfoo.so.1"),
+            ]
+        );
+        assert_eq!(entries.len(), 6);
+        assert_eq!(entries[0].kind, EntryType::Dir);
+        assert_eq!(entries[3].kind, EntryType::Link);
+        assert_eq!(entries[1].size(), Some(16432));
+        assert_eq!(entries[0].size(), None);
+        assert_eq!(entries[1].sha256(), Some("0"));
+    }
+
+    #[test]
+    fn test_parse_sections() {
+        let desc = "%NAME%
+foo
+
+%VERSION%
+1:2.3-4
+
+%DEPENDS%
+glibc
+bar>=1.0
+";
+        let sections = parse_sections(desc);
+        assert_eq!(sections["VERSION"], ["1:2.3-4"]);
+        assert_eq!(sections["DEPENDS"], ["glibc", "bar>=1.0"]);
+        assert!(!sections.contains_key("LICENSE"));
+    }
+
+    #[test]
+    fn test_dependency_name() {
+        assert_eq!(dependency_name("glibc"), "glibc");
+        assert_eq!(dependency_name("bar>=1:1.0-2"), "bar");
+        assert_eq!(
+            dependency_name("libnotify: desktop notifications"),
+            "libnotify"
+        );
+        assert_eq!(dependency_name("ffmpeg>=6: video support"), "ffmpeg");
+        assert_eq!(dependency_name("foo:"), "foo");
+    }
+
+    #[test]
+    fn test_incomplete_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        for (entry, files) in [
+            ("foo-1.0-1", &["desc", "files", "mtree"][..]),
+            ("bar-2.0-1", &["desc"][..]),
+        ] {
+            fs::create_dir(dir.path().join(entry)).unwrap();
+            for file in files {
+                fs::write(dir.path().join(entry).join(file), "").unwrap();
+            }
+        }
+        fs::write(dir.path().join("ALPM_DB_VERSION"), "9").unwrap();
+        assert_eq!(incomplete_entries(dir.path()).unwrap(), ["bar-2.0-1"]);
+    }
+
+    #[test]
+    fn test_parent_pid() {
+        assert!(parent_pid(std::process::id()).is_some_and(|p| p > 0));
+        assert!(!is_run_by_pacman());
+    }
+}
@@ -0,0 +1,251 @@
+//! Logging with the check, package and file being processed attached to every record.
+//!
+//! Records are formatted before taking the output lock, and written at once, so that multi-line records
+//! from concurrent checks and their worker threads never interleave.
+
+use std::{
+    cell::RefCell,
+    fmt::Write as _,
+    fs,
+    io::{self, IsTerminal as _, Write as _},
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+    time::Instant,
+};
+
+use ansi_term::Colour::{Cyan, Purple, Red, Yellow};
+
+use crate::journal;
+
+thread_local! {
+    static CONTEXT: RefCell<LogContext> = RefCell::default();
+}
+
+/// What the current thread is processing, attached to its log records
+#[derive(Clone, Default)]
+pub struct LogContext {
+    check: Option<Arc<str>>,
+    package: Option<Arc<str>>,
+    file: Option<Arc<Path>>,
+}
+
+impl LogContext {
+    /// Get the context of the current thread, to set it in the worker threads it hands work to
+    #[must_use]
+    pub fn current() -> Self {
+        CONTEXT.with(|c| c.borrow().clone())
+    }
+
+    /// Set check name
+    #[must_use]
+    pub fn with_check(mut self, check: &str) -> Self {
+        self.check = Some(check.into());
+        self
+    }
+
+    /// Set package name
+    #[must_use]
+    pub fn with_package(mut self, package: &str) -> Self {
+        self.package = Some(package.into());
+        self
+    }
+
+    /// Set package file
+    #[must_use]
+    pub fn with_file(mut self, file: &Path) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Make this the context of the current thread, until the returned guard is dropped
+    pub fn enter(self) -> ContextGuard {
+        ContextGuard {
+            previous: CONTEXT.with(|c| c.replace(self)),
+        }
+    }
+
+    /// Append context fields to a log line, if there are any
+    fn write_to(&self, line: &mut String) {
+        let fields = [
+            ("check", self.check.as_deref().map(ToOwned::to_owned)),
+            ("package", self.package.as_deref().map(ToOwned::to_owned)),
+            (
+                "file",
+                self.file.as_deref().map(|f| f.display().to_string()),
+            ),
+        ];
+        let mut fields = fields
+            .iter()
+            .filter_map(|(k, v)| v.as_ref().map(|v| (k, v)))
+            .peekable();
+        if fields.peek().is_none() {
+            return;
+        }
+        line.push_str(" (");
+        for (i, (key, value)) in fields.enumerate() {
+            if i > 0 {
+                line.push_str(", ");
+            }
+            let _ = write!(line, "{key}={value}");
+        }
+        line.push(')');
+    }
+}
+
+/// Restores the previous context of the thread when dropped
+#[must_use]
+pub struct ContextGuard {
+    previous: LogContext,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        let previous = std::mem::take(&mut self.previous);
+        CONTEXT.with(|c| c.replace(previous));
+    }
+}
+
+/// How records are written to stderr
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum LogStyle {
+    /// Level and module, with colors if stderr is a terminal
+    Terminal,
+    /// Journald priority prefix on each line, without colors
+    Journal,
+}
+
+/// Logger writing to stderr, and all records including debug ones to a file if set
+pub struct Logger {
+    style: LogStyle,
+    colors: bool,
+    /// Maximum level written to stderr
+    level: log::LevelFilter,
+    /// Debug log file, and creation time of the logger its records are timed from
+    file: Option<(Mutex<fs::File>, Instant)>,
+}
+
+impl Logger {
+    /// Install as the global logger, creating or truncating debug log file `debug_file`
+    ///
+    /// # Errors
+    ///
+    /// Fails if the debug log file can not be created, or a global logger is already installed
+    pub fn init(style: LogStyle, debug_file: Option<&Path>) -> anyhow::Result<()> {
+        // Debug records are only shown in debug builds, unless written to a file
+        let level = if cfg!(debug_assertions) {
+            log::LevelFilter::Debug
+        } else {
+            log::LevelFilter::Info
+        };
+        let file = debug_file
+            .map(|path| fs::File::create(path).map(|f| (Mutex::new(f), Instant::now())))
+            .transpose()?;
+        let max_level = if file.is_some() {
+            log::LevelFilter::Debug
+        } else {
+            level
+        };
+        let logger = Self {
+            style,
+            colors: style == LogStyle::Terminal && io::stderr().is_terminal(),
+            level,
+            file,
+        };
+        // Without the std feature of log, its error does not implement Error
+        log::set_logger(Box::leak(Box::new(logger))).map_err(|err| anyhow::anyhow!("{err}"))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+
+    /// Format a record for stderr, with a trailing newline
+    fn format_stderr(&self, record: &log::Record<'_>, context: &str) -> String {
+        let message = format!("{}{context}", record.args());
+        match self.style {
+            LogStyle::Journal => {
+                let prefix = journal::priority(record.level());
+                message.lines().fold(String::new(), |mut text, line| {
+                    let _ = writeln!(text, "{prefix}{line}");
+                    text
+                })
+            }
+            LogStyle::Terminal => {
+                let level = format!("{:<5}", record.level());
+                let level = if self.colors {
+                    match record.level() {
+                        log::Level::Error => Red.paint(level).to_string(),
+                        log::Level::Warn => Yellow.paint(level).to_string(),
+                        log::Level::Info => Cyan.paint(level).to_string(),
+                        log::Level::Debug => Purple.paint(level).to_string(),
+                        log::Level::Trace => level,
+                    }
+                } else {
+                    level
+                };
+                format!("{level} [{}] {message}\n", record.target())
+            }
+        }
+    }
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        metadata.level() <= self.level || self.file.is_some()
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut context = String::new();
+        CONTEXT.with(|c| c.borrow().write_to(&mut context));
+        if record.level() <= self.level {
+            let text = self.format_stderr(record, &context);
+            let _ = io::stderr().lock().write_all(text.as_bytes());
+        }
+        if let Some((file, start)) = &self.file {
+            let text = format!(
+                "{:>10.3} {:<5} [{}] {:?} {}{context}\n",
+                start.elapsed().as_secs_f64(),
+                record.level(),
+                record.target(),
+                std::thread::current().name().unwrap_or("unnamed"),
+                record.args()
+            );
+            let _ = file
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .write_all(text.as_bytes());
+        }
+    }
+
+    // Records are written unbuffered
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> String {
+        let mut line = String::new();
+        LogContext::current().write_to(&mut line);
+        line
+    }
+
+    #[test]
+    fn test_log_context() {
+        assert_eq!(context(), "");
+        {
+            let _check = LogContext::current().with_check("foo").enter();
+            {
+                let _file = LogContext::current()
+                    .with_package("bar")
+                    .with_file(Path::new("/usr/bin/bar"))
+                    .enter();
+                assert_eq!(context(), " (check=foo, package=bar, file=/usr/bin/bar)");
+            }
+            assert_eq!(context(), " (check=foo)");
+        }
+        assert_eq!(context(), "");
+    }
+}
@@ -0,0 +1,80 @@
+//! Package metadata shared by checks, loaded on first use
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::{backend::PackageBackend, filesdb::FilesDb, history::History};
+
+/// Metadata caches, each loaded by the first check needing it, so that other checks pay no cost.
+///
+/// Caches are locked independently: checks running concurrently load different ones in parallel,
+/// and a check needing one being loaded waits for it instead of loading it again.
+/// Failed loads are not cached, and are retried by the next check.
+#[derive(Default)]
+pub(crate) struct Metadata {
+    /// Foreign packages, which needs reading all sync databases
+    foreign_packages: Mutex<Option<Arc<Vec<String>>>>,
+    files_db: Mutex<Option<Arc<Option<FilesDb>>>>,
+    history: Mutex<Option<Arc<History>>>,
+}
+
+/// Get the value of a cache, loading it if it is empty
+fn get_or_load<T>(
+    cache: &Mutex<Option<Arc<T>>>,
+    load: impl FnOnce() -> anyhow::Result<T>,
+) -> anyhow::Result<Arc<T>> {
+    // A check panicking while loading leaves the cache empty
+    let mut cached = cache.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(value) = cached.as_ref() {
+        return Ok(Arc::clone(value));
+    }
+    let value = Arc::new(load()?);
+    *cached = Some(Arc::clone(&value));
+    Ok(value)
+}
+
+impl Metadata {
+    /// Installed packages not found in the sync databases, sorted
+    pub(crate) fn foreign_packages(
+        &self,
+        backend: &dyn PackageBackend,
+    ) -> anyhow::Result<Arc<Vec<String>>> {
+        get_or_load(&self.foreign_packages, || backend.foreign_packages())
+    }
+
+    /// Files databases, `None` if none was downloaded
+    pub(crate) fn files_db(&self) -> anyhow::Result<Arc<Option<FilesDb>>> {
+        get_or_load(&self.files_db, FilesDb::open)
+    }
+
+    /// Recent package changes
+    pub(crate) fn history(&self) -> anyhow::Result<Arc<History>> {
+        get_or_load(&self.history, History::load)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn test_get_or_load() {
+        let cache = Mutex::new(None);
+        let loads = AtomicUsize::new(0);
+        assert!(get_or_load(&cache, || -> anyhow::Result<u32> {
+            loads.fetch_add(1, Ordering::Relaxed);
+            anyhow::bail!("failed")
+        })
+        .is_err());
+        for _ in 0..2 {
+            let value = get_or_load(&cache, || {
+                loads.fetch_add(1, Ordering::Relaxed);
+                Ok(42)
+            })
+            .unwrap();
+            assert_eq!(*value, 42);
+        }
+        assert_eq!(loads.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,142 @@
+//! Network availability, so that features needing it are skipped instead of stalling when offline
+
+use std::{
+    fs,
+    net::{TcpStream, ToSocketAddrs as _},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, OnceLock,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Address connected to by the reachability probe, the servers of the AUR and security tracker being behind it
+const PROBE_ADDRESS: &str = "archlinux.org:443";
+
+/// Whether network access was disabled
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the system has a default route, detected on first use
+static ROUTED: OnceLock<bool> = OnceLock::new();
+
+/// Timeout of the reachability probe, if enabled
+static PROBE_TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
+/// Whether the probe address was reachable, probed on first use
+static REACHABLE: OnceLock<bool> = OnceLock::new();
+
+/// Disable network access for the rest of the run
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Probe the network before using it, connecting to a well known address with a timeout, so that a route to
+/// nowhere, like a captive portal or a dead VPN, disables network features instead of stalling them
+pub fn enable_probe(timeout: Duration) {
+    // Already probed with the first timeout otherwise
+    let _ = PROBE_TIMEOUT.set(timeout);
+}
+
+/// Check if an address accepts TCP connections within `timeout`, including the time to resolve it
+fn probe(address: &str, timeout: Duration) -> anyhow::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let address = address.to_owned();
+    // Name resolution has no timeout of its own, the thread is left behind if it hangs
+    thread::Builder::new()
+        .name("network-probe".to_owned())
+        .spawn(move || {
+            let result = address
+                .to_socket_addrs()
+                .map_err(anyhow::Error::from)
+                .and_then(|mut addrs| {
+                    addrs
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("{address} has no address"))
+                })
+                .and_then(|addr| Ok(TcpStream::connect_timeout(&addr, timeout)?));
+            let _ = tx.send(result.map(drop));
+        })?;
+    rx.recv_timeout(timeout)
+        .map_err(|_| anyhow::anyhow!("No answer within {}ms", timeout.as_millis()))?
+}
+
+/// Check if a route table of `/proc/net` has a default route, on an interface other than loopback
+fn has_default_route(route_table: &str) -> bool {
+    route_table.lines().any(|line| {
+        let fields: Vec<_> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            // IPv4: interface, destination, gateway, flags, refcnt, use, metric, mask, ...
+            [iface, "00000000", _, _, _, _, _, "00000000", ..] => *iface != "lo",
+            // IPv6: destination, prefix length, source, prefix length, next hop, metric, refcnt, use, flags, interface
+            [destination, "00", _, _, _, _, _, _, _, iface]
+                if destination.bytes().all(|b| b == b'0') =>
+            {
+                *iface != "lo"
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Whether network access is unavailable, because it was disabled, the system has no default route, or the enabled
+/// probe failed
+pub fn is_offline() -> bool {
+    DISABLED.load(Ordering::Relaxed)
+        || !*ROUTED.get_or_init(|| {
+            let routed = ["/proc/net/route", "/proc/net/ipv6_route"]
+                .into_iter()
+                .filter_map(|p| fs::read_to_string(p).ok())
+                .any(|t| has_default_route(&t));
+            if !routed {
+                log::info!("No default network route, network features are disabled");
+            }
+            routed
+        })
+        || PROBE_TIMEOUT.get().is_some_and(|timeout| {
+            !*REACHABLE.get_or_init(|| match probe(PROBE_ADDRESS, *timeout) {
+                Ok(()) => true,
+                Err(err) => {
+                    log::info!(
+                        "Network probe of {PROBE_ADDRESS} failed: {err:#}, network features are disabled"
+                    );
+                    false
+                }
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_default_route() {
+        let ipv4 =
+            "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+eth0\t00000000\t0102A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0
+eth0\t0002A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0
+";
+        assert!(has_default_route(ipv4));
+        assert!(!has_default_route(
+            "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+eth0\t0002A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0
+"
+        ));
+
+        let ipv6 = "00000000000000000000000000000000 00 00000000000000000000000000000000 00 fe800000000000000000000000000001 00000400 00000001 00000000 00000003 wlan0
+00000000000000000000000000000000 00 00000000000000000000000000000000 00 00000000000000000000000000000000 ffffffff 00000001 00000000 00200200 lo
+";
+        assert!(has_default_route(ipv6));
+        assert!(!has_default_route(ipv6.lines().nth(1).unwrap()));
+    }
+
+    #[test]
+    fn test_probe() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        assert!(probe(&address, Duration::from_secs(5)).is_ok());
+        drop(listener);
+        assert!(probe(&address, Duration::from_secs(5)).is_err());
+    }
+}
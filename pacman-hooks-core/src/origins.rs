@@ -0,0 +1,144 @@
+//! Where packages of findings come from, since fixing a repository package and a foreign one differ completely
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    sync::{PoisonError, RwLock},
+};
+
+use crate::{aur::AurClient, finding::Finding, network, syncdb};
+
+/// Origins of the packages of the reported findings, by package
+static ORIGINS: RwLock<BTreeMap<String, PackageOrigin>> = RwLock::new(BTreeMap::new());
+
+/// Where an installed package comes from
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PackageOrigin {
+    /// Sync repository, like `core` or `extra`
+    Repository(String),
+    /// Foreign package of the AUR
+    Aur,
+    /// Foreign package of neither a repository nor the AUR, built locally
+    Local,
+    /// Foreign package, not looked up in the AUR because the network is unavailable
+    Foreign,
+}
+
+impl PackageOrigin {
+    /// Stable name, used in machine readable output: the repository name, `aur`, `local` or `foreign`
+    #[must_use]
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Repository(repository) => repository,
+            Self::Aur => "aur",
+            Self::Local => "local",
+            Self::Foreign => "foreign",
+        }
+    }
+
+    /// Whether the origin is selected by a filter name, `foreign` selecting all foreign packages
+    #[must_use]
+    pub fn matches(&self, filter: &str) -> bool {
+        self.name() == filter || (filter == "foreign" && !matches!(self, Self::Repository(_)))
+    }
+}
+
+impl fmt::Display for PackageOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Get the origin of packages, from the repositories providing them, in pacman.conf order,
+/// and the foreign packages found in the AUR, if it was looked up
+fn classify(
+    packages: &HashSet<&str>,
+    repositories: &HashMap<String, String>,
+    aur: Option<&HashSet<String>>,
+) -> HashMap<String, PackageOrigin> {
+    packages
+        .iter()
+        .map(|package| {
+            let origin = match (repositories.get(*package), aur) {
+                (Some(repository), _) => PackageOrigin::Repository(repository.clone()),
+                (None, Some(aur)) if aur.contains(*package) => PackageOrigin::Aur,
+                (None, Some(_)) => PackageOrigin::Local,
+                (None, None) => PackageOrigin::Foreign,
+            };
+            ((*package).to_owned(), origin)
+        })
+        .collect()
+}
+
+/// Look up the origin of the packages of `findings` not already known, to annotate them for the rest of the run
+pub fn resolve(findings: &[Finding]) {
+    let packages: HashSet<&str> = {
+        let known = ORIGINS.read().unwrap_or_else(PoisonError::into_inner);
+        findings
+            .iter()
+            .flat_map(Finding::packages)
+            .filter(|p| !known.contains_key(*p))
+            .collect()
+    };
+    if packages.is_empty() {
+        return;
+    }
+    let repositories = syncdb::package_repositories().unwrap_or_else(|err| {
+        log::warn!("Failed to read sync databases: {err:#}");
+        HashMap::new()
+    });
+    let foreign: Vec<String> = packages
+        .iter()
+        .filter(|p| !repositories.contains_key(**p))
+        .map(|p| (*p).to_owned())
+        .collect();
+    let aur = if foreign.is_empty() || network::is_offline() {
+        None
+    } else {
+        match AurClient::default().info(&foreign) {
+            Ok(aur_packages) => Some(aur_packages.into_keys().collect()),
+            Err(err) => {
+                log::warn!("Failed to look up foreign packages in the AUR: {err:#}");
+                None
+            }
+        }
+    };
+    ORIGINS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .extend(classify(&packages, &repositories, aur.as_ref()));
+}
+
+/// Origin of a package, if resolved
+pub(crate) fn get(package: &str) -> Option<PackageOrigin> {
+    ORIGINS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(package)
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let repositories = HashMap::from([("glibc".to_owned(), "core".to_owned())]);
+        let aur = HashSet::from(["foo-git".to_owned()]);
+        let packages = HashSet::from(["glibc", "foo-git", "bar"]);
+        let origins = classify(&packages, &repositories, Some(&aur));
+        assert_eq!(
+            origins["glibc"],
+            PackageOrigin::Repository("core".to_owned())
+        );
+        assert_eq!(origins["foo-git"], PackageOrigin::Aur);
+        assert_eq!(origins["bar"], PackageOrigin::Local);
+        assert!(origins["bar"].matches("foreign"));
+        assert!(!origins["glibc"].matches("foreign"));
+        assert_eq!(
+            classify(&packages, &repositories, None)["foo-git"],
+            PackageOrigin::Foreign
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Findings output
+
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
+};
+
+use ansi_term::Colour::{Red, Yellow};
+
+use crate::{baseline::Baseline, cancel, finding::Finding, journal, origins};
+
+/// Findings printed by default, so that a badly broken system does not flood the transaction output
+pub const DEFAULT_MAX_FINDINGS: usize = 500;
+
+/// Maximum number of printed findings
+static MAX_FINDINGS: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_FINDINGS);
+
+/// Print at most `max` findings for the rest of the run, or all of them if `None`.
+///
+/// Machine readable forms of findings, like saved reports, always include all of them.
+pub fn set_max_findings(max: Option<usize>) {
+    MAX_FINDINGS.store(max.unwrap_or(usize::MAX), Ordering::Relaxed);
+}
+
+/// Package origins of the printed findings, all findings are printed if unset
+static ORIGIN_FILTER: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Only print findings about packages of one of `origins` for the rest of the run,
+/// repository names, `aur`, `local` or `foreign`
+///
+/// # Errors
+///
+/// Fails if the origin filter was already set
+pub fn set_origin_filter(origins: Vec<String>) -> anyhow::Result<()> {
+    ORIGIN_FILTER
+        .set(origins)
+        .map_err(|_| anyhow::anyhow!("Origin filter already set"))
+}
+
+/// Whether a finding is printed with the origin filter
+fn selected(finding: &Finding) -> bool {
+    ORIGIN_FILTER.get().is_none_or(|filter| {
+        finding
+            .package_origin()
+            .is_some_and(|o| filter.iter().any(|f| o.matches(f)))
+    })
+}
+
+/// Print findings, those already in the baseline being reported with a lower priority,
+/// annotated with the origin of their package
+pub fn print_findings(findings: &[Finding], baseline: Option<&Baseline>) {
+    origins::resolve(findings);
+    // When writing to the journal, set priority instead of colors
+    let journal = baseline.is_some() || env::var_os("JOURNAL_STREAM").is_some();
+    let max = MAX_FINDINGS.load(Ordering::Relaxed);
+    let findings: Vec<_> = findings.iter().filter(|f| selected(f)).collect();
+    for finding in findings.iter().take(max) {
+        let known = baseline.is_some_and(|b| b.contains(finding));
+        let origin = finding
+            .package_origin()
+            .map(|o| format!(" [{o}]"))
+            .unwrap_or_default();
+        match (journal, known) {
+            (true, false) => println!("{}{finding}{origin}", journal::SD_WARNING),
+            (true, true) => println!("{}Known: {finding}{origin}", journal::SD_NOTICE),
+            (false, false) => println!("{}", Yellow.paint(format!("{finding}{origin}"))),
+            (false, true) => println!("Known: {finding}{origin}"),
+        }
+        if let Some(hint) = finding.hint() {
+            if journal {
+                println!("{}  Hint: {hint}", journal::SD_NOTICE);
+            } else {
+                println!("  Hint: {hint}");
+            }
+        }
+    }
+    if findings.len() > max {
+        let more = format!(
+            "… and {} more, run with --full-report",
+            findings.len() - max
+        );
+        if journal {
+            println!("{}{more}", journal::SD_NOTICE);
+        } else {
+            println!("{more}");
+        }
+    }
+    if let Some(signum) = cancel::signal() {
+        let marker = format!(
+            "Scan incomplete: interrupted by signal {signum}, only findings gathered so far were reported"
+        );
+        if journal {
+            println!("{}{marker}", journal::SD_WARNING);
+        } else {
+            println!("{}", Red.paint(marker));
+        }
+    }
+}
+
+/// Log findings as warnings, annotated with the origin of their package, for long running modes whose output is only
+/// read from the log, like the journal
+pub fn log_findings(findings: &[Finding]) {
+    origins::resolve(findings);
+    let max = MAX_FINDINGS.load(Ordering::Relaxed);
+    let findings: Vec<_> = findings.iter().filter(|f| selected(f)).collect();
+    for finding in findings.iter().take(max) {
+        let origin = finding
+            .package_origin()
+            .map(|o| format!(" [{o}]"))
+            .unwrap_or_default();
+        log::warn!("{finding}{origin}");
+        if let Some(hint) = finding.hint() {
+            log::info!("Hint: {hint}");
+        }
+    }
+    if findings.len() > max {
+        log::info!(
+            "… and {} more, run with --full-report",
+            findings.len() - max
+        );
+    }
+}
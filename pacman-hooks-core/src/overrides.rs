@@ -0,0 +1,174 @@
+//! Severity overrides of the configuration, for packages, paths or sonames that local policy treats differently
+
+use std::{path::PathBuf, str::FromStr, sync::OnceLock};
+
+use crate::finding::{Finding, Severity};
+
+/// Overrides of the configuration, if set
+static OVERRIDES: OnceLock<Vec<SeverityOverride>> = OnceLock::new();
+
+/// What a severity override applies to
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OverrideTarget {
+    /// Findings about a package
+    Package(String),
+    /// Findings about a file or directory in a directory, or the file itself
+    Path(PathBuf),
+    /// Findings about a library soname
+    Soname(String),
+}
+
+impl OverrideTarget {
+    fn matches(&self, finding: &Finding) -> bool {
+        match self {
+            Self::Package(package) => finding.packages().contains(&package.as_str()),
+            Self::Path(dir) => finding.paths().iter().any(|p| p.starts_with(dir)),
+            Self::Soname(soname) => finding.soname() == Some(soname.as_str()),
+        }
+    }
+}
+
+impl FromStr for OverrideTarget {
+    type Err = anyhow::Error;
+
+    /// Parse an absolute path, a soname like `libfoo.so.1`, or otherwise a package name
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with('/') {
+            Ok(Self::Path(PathBuf::from(s)))
+        } else if s.contains(".so") {
+            Ok(Self::Soname(s.to_owned()))
+        } else if !s.is_empty() {
+            Ok(Self::Package(s.to_owned()))
+        } else {
+            anyhow::bail!("Empty severity override target")
+        }
+    }
+}
+
+/// Severity to report findings about some targets with, instead of the one of their check
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SeverityOverride {
+    severity: Severity,
+    targets: Vec<OverrideTarget>,
+}
+
+impl SeverityOverride {
+    /// Severity set for matching findings
+    #[must_use]
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Packages, paths and sonames this override applies to
+    #[must_use]
+    pub fn targets(&self) -> &[OverrideTarget] {
+        &self.targets
+    }
+}
+
+impl FromStr for SeverityOverride {
+    type Err = anyhow::Error;
+
+    /// Parse a severity followed by space separated targets
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let severity = words
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Missing severity"))?
+            .parse()?;
+        let targets = words.map(str::parse).collect::<anyhow::Result<Vec<_>>>()?;
+        if targets.is_empty() {
+            anyhow::bail!("Missing packages, paths or sonames to override the severity of");
+        }
+        Ok(Self { severity, targets })
+    }
+}
+
+/// Apply `overrides` to findings for the rest of the run
+///
+/// # Errors
+///
+/// Fails if the overrides were already set
+pub fn set(overrides: Vec<SeverityOverride>) -> anyhow::Result<()> {
+    OVERRIDES
+        .set(overrides)
+        .map_err(|_| anyhow::anyhow!("Severity overrides already set"))
+}
+
+/// Severity of the last override of `overrides` matching `finding`, if any
+fn lookup(overrides: &[SeverityOverride], finding: &Finding) -> Option<Severity> {
+    overrides
+        .iter()
+        .rev()
+        .find(|o| o.targets.iter().any(|t| t.matches(finding)))
+        .map(|o| o.severity)
+}
+
+/// Severity overriding the one of `finding`, if any
+pub(crate) fn severity(finding: &Finding) -> Option<Severity> {
+    lookup(OVERRIDES.get()?, finding)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_overrides() {
+        let overrides: Vec<SeverityOverride> = [
+            "info /usr/lib/erlang libssl.so.1.1",
+            "error /usr/lib/erlang/bin erlang-nox",
+        ]
+        .iter()
+        .map(|o| o.parse().unwrap())
+        .collect();
+        assert_eq!(
+            overrides[0].targets(),
+            [
+                OverrideTarget::Path(PathBuf::from("/usr/lib/erlang")),
+                OverrideTarget::Soname("libssl.so.1.1".to_owned()),
+            ]
+        );
+        assert_eq!(
+            overrides[1].targets()[1],
+            OverrideTarget::Package("erlang-nox".to_owned())
+        );
+        assert!("info".parse::<SeverityOverride>().is_err());
+        assert!("fatal foo".parse::<SeverityOverride>().is_err());
+
+        let missing = |package: &str, file: &str, dependency: &str| Finding::MissingDependency {
+            package: Arc::new(package.to_owned()),
+            file: Arc::new(PathBuf::from(file)),
+            dependency: PathBuf::from(dependency),
+            origin: None,
+        };
+        let severity = |finding| lookup(&overrides, &finding);
+        assert_eq!(
+            severity(missing(
+                "erlang",
+                "/usr/lib/erlang/lib/crypto.so",
+                "libfoo.so.1"
+            )),
+            Some(Severity::Info)
+        );
+        // The last matching override wins
+        assert_eq!(
+            severity(missing("erlang", "/usr/lib/erlang/bin/beam", "libfoo.so.1")),
+            Some(Severity::Error)
+        );
+        assert_eq!(
+            severity(missing("foo", "/usr/bin/foo", "libssl.so.1.1")),
+            Some(Severity::Info)
+        );
+        assert_eq!(
+            severity(missing("foo", "/usr/lib/erlangfoo/bar", "libfoo.so.1")),
+            None
+        );
+        assert_eq!(
+            severity(missing("foo", "/usr/bin/foo", "libfoo.so.1")),
+            None
+        );
+    }
+}
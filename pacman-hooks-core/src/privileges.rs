@@ -0,0 +1,86 @@
+//! Privilege dropping for child processes
+
+use std::{
+    ffi::CString,
+    io,
+    mem::MaybeUninit,
+    os::unix::{fs, process::CommandExt},
+    path::Path,
+    process::Command,
+    ptr,
+    sync::OnceLock,
+};
+
+/// Unprivileged user and group ids to run child processes as
+static IDS: OnceLock<(libc::uid_t, libc::gid_t)> = OnceLock::new();
+
+/// Size of the buffer for `getpwnam_r` strings
+const PASSWD_BUF_SIZE: usize = 16384;
+
+/// Look up user and primary group ids of a user
+pub(crate) fn lookup_user(name: &str) -> anyhow::Result<(libc::uid_t, libc::gid_t)> {
+    let cname = CString::new(name)?;
+    let mut buf: Vec<libc::c_char> = vec![0; PASSWD_BUF_SIZE];
+    let mut pwd = MaybeUninit::<libc::passwd>::uninit();
+    let mut result = ptr::null_mut();
+    // SAFETY: all pointers are valid for the call duration, and buf length is passed
+    let ret = unsafe {
+        libc::getpwnam_r(
+            cname.as_ptr(),
+            pwd.as_mut_ptr(),
+            buf.as_mut_ptr(),
+            buf.len(),
+            &raw mut result,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::from_raw_os_error(ret).into());
+    }
+    if result.is_null() {
+        anyhow::bail!("Unknown user {name:?}");
+    }
+    // SAFETY: getpwnam_r found the user, so it initialized pwd
+    let pwd = unsafe { pwd.assume_init() };
+    Ok((pwd.pw_uid, pwd.pw_gid))
+}
+
+/// Run all further child processes as `user`, if running as root.
+///
+/// Data only readable by root must be read before, or from this process.
+///
+/// # Errors
+///
+/// Fails if `user` does not exist
+pub fn drop_for_children(user: Option<&str>) -> anyhow::Result<()> {
+    // SAFETY: no arguments, always succeeds
+    let euid = unsafe { libc::geteuid() };
+    let Some(user) = user.filter(|_| euid == 0) else {
+        return Ok(());
+    };
+    let (uid, gid) = lookup_user(user)?;
+    log::debug!("Running child processes as user {user:?} (uid {uid}, gid {gid})");
+    let _ = IDS.set((uid, gid));
+    Ok(())
+}
+
+/// Give a path to the user child processes run as, if privileges were dropped, so that they can write to it
+///
+/// # Errors
+///
+/// Fails if the owner of the path can not be changed
+pub fn give_to_children(path: &Path) -> io::Result<()> {
+    match IDS.get() {
+        Some((uid, gid)) => fs::chown(path, Some(*uid), Some(*gid)),
+        None => Ok(()),
+    }
+}
+
+/// Create a command for a child process, run without privileges if they were dropped
+pub fn command(program: &str) -> Command {
+    let mut command = Command::new(program);
+    if let Some((uid, gid)) = IDS.get() {
+        // Supplementary groups are cleared when spawning
+        command.uid(*uid).gid(*gid).current_dir("/");
+    }
+    command
+}
@@ -0,0 +1,67 @@
+//! Low contention progress reporting
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Duration,
+};
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+/// Interval between progress bar redraws
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Progress counter, cheap to increment from many threads.
+///
+/// Workers only increment an atomic counter, the progress bar is updated from it by a ticker thread,
+/// so that they never contend on the progress bar internal lock.
+pub(crate) struct Progress {
+    bar: ProgressBar,
+    count: AtomicU64,
+}
+
+impl Progress {
+    /// Create progress for `len` units, drawn on stderr if `visible` is set
+    pub(crate) fn new(len: u64, visible: bool) -> anyhow::Result<Self> {
+        let target = if visible {
+            ProgressDrawTarget::stderr()
+        } else {
+            ProgressDrawTarget::hidden()
+        };
+        let bar = ProgressBar::with_draw_target(Some(len), target);
+        // Units are weighted items of several checks, only their proportion is meaningful
+        bar.set_style(ProgressStyle::default_bar().template("Analyzing {wide_bar} {percent}%")?);
+        Ok(Self {
+            bar,
+            count: AtomicU64::new(0),
+        })
+    }
+
+    pub(crate) fn add(&self, units: u64) {
+        self.count.fetch_add(units, Ordering::Relaxed);
+    }
+
+    /// Run a function while drawing progress, and clear the progress bar when it returns
+    pub(crate) fn run<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let result = thread::scope(|scope| {
+            scope.spawn(move || {
+                while let Err(mpsc::RecvTimeoutError::Timeout) = stop_rx.recv_timeout(TICK_INTERVAL)
+                {
+                    self.bar.set_position(self.count.load(Ordering::Relaxed));
+                }
+            });
+            let result = f();
+            drop(stop_tx);
+            result
+        });
+        self.bar.finish_and_clear();
+        result
+    }
+}
@@ -0,0 +1,186 @@
+//! Remediation of packages broken by library changes: rebuild commands for an AUR helper,
+//! or downgrades of the libraries from the package cache
+
+use std::{
+    cmp::Ordering,
+    collections::BTreeSet,
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    aur::AurClient, backend::PackageBackend, finding::Finding, history::Change, network,
+    version::vercmp,
+};
+
+/// Placeholder replaced by package names in rebuild command templates
+const PACKAGES_PLACEHOLDER: &str = "{packages}";
+
+/// Rebuild command templates of supported AUR helpers
+const HELPER_TEMPLATES: [(&str, &str); 3] = [
+    ("paru", "paru -S --rebuild {packages}"),
+    ("yay", "yay -S --rebuild {packages}"),
+    ("aurutils", "aur sync --rebuild {packages}"),
+];
+
+/// Get the rebuild command template of an AUR helper, by name
+#[must_use]
+pub fn helper_template(helper: &str) -> Option<&'static str> {
+    HELPER_TEMPLATES
+        .iter()
+        .find(|(h, _)| *h == helper)
+        .map(|(_, t)| *t)
+}
+
+/// Get packages that a rebuild fixes, from findings about missing libraries, sorted
+pub fn broken_packages(findings: &[Finding]) -> Vec<String> {
+    findings
+        .iter()
+        .filter_map(|f| match f {
+            Finding::MissingDependency { package, .. }
+            | Finding::UpgradeBreakage { package, .. } => Some(package.as_str()),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+/// Build a rebuild command from a template, `None` if there is nothing to rebuild
+#[must_use]
+pub fn rebuild_command(template: &str, packages: &[String]) -> Option<String> {
+    (!packages.is_empty()).then(|| template.replace(PACKAGES_PLACEHOLDER, &packages.join(" ")))
+}
+
+/// Get packages with missing libraries that have a newer version in the AUR, likely rebuilt
+pub fn aur_updated_packages(backend: &dyn PackageBackend, packages: &[String]) -> Vec<String> {
+    if network::is_offline() {
+        return Vec::new();
+    }
+    let aur_packages = match AurClient::default().info(packages) {
+        Ok(aur_packages) => aur_packages,
+        Err(err) => {
+            log::warn!("Failed to get AUR package versions: {err:#}");
+            return Vec::new();
+        }
+    };
+    packages
+        .iter()
+        .filter(|p| {
+            let installed = backend.version(p).ok().flatten();
+            match (aur_packages.get(p.as_str()), installed) {
+                (Some(aur), Some(installed)) => {
+                    vercmp(aur.version(), &installed) == Ordering::Greater
+                }
+                _ => false,
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+/// Get cached archives of library package versions replaced by an upgrade, that missing libraries of packages
+/// not in `updated` were found in, sorted
+pub fn downgrade_archives(findings: &[Finding], updated: &[String]) -> Vec<PathBuf> {
+    findings
+        .iter()
+        .filter_map(|f| match f {
+            Finding::MissingDependency {
+                package,
+                origin: Some(origin),
+                ..
+            } if origin.change() == Change::Upgraded && !updated.contains(package) => {
+                origin.archive()
+            }
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+/// Build a command reinstalling package archives, `None` if there are none
+#[must_use]
+pub fn downgrade_command(archives: &[PathBuf]) -> Option<String> {
+    (!archives.is_empty()).then(|| {
+        let archives: Vec<_> = archives.iter().map(|a| a.to_string_lossy()).collect();
+        format!("pacman -U {}", archives.join(" "))
+    })
+}
+
+/// Write a rebuild command to an executable shell script
+///
+/// # Errors
+///
+/// Fails if the script can not be written
+pub fn write_script(path: &Path, command: &str) -> anyhow::Result<()> {
+    fs::write(path, format!("#!/bin/sh\nset -e\n{command}\n"))?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::history::LibraryOrigin;
+
+    #[test]
+    fn test_rebuild_command() {
+        let missing = |package: &str| Finding::MissingDependency {
+            package: Arc::new(package.to_owned()),
+            file: Arc::new(PathBuf::from("/usr/bin/foo")),
+            dependency: PathBuf::from("libicuuc.so.74"),
+            origin: None,
+        };
+        let findings = [
+            missing("foo-git"),
+            Finding::PacnewFile {
+                path: PathBuf::from("/etc/foo.conf"),
+            },
+            missing("bar"),
+            missing("foo-git"),
+        ];
+        let packages = broken_packages(&findings);
+        assert_eq!(packages, ["bar", "foo-git"]);
+        assert_eq!(
+            rebuild_command(helper_template("paru").unwrap(), &packages).as_deref(),
+            Some("paru -S --rebuild bar foo-git")
+        );
+        assert_eq!(rebuild_command("aur sync {packages}", &[]), None);
+        assert_eq!(helper_template("pacaur"), None);
+    }
+
+    #[test]
+    fn test_downgrade_command() {
+        let archive = PathBuf::from("/var/cache/pacman/pkg/icu-74.2-1-x86_64.pkg.tar.zst");
+        let missing = |package: &str, change| Finding::MissingDependency {
+            package: Arc::new(package.to_owned()),
+            file: Arc::new(PathBuf::from("/usr/bin/foo")),
+            dependency: PathBuf::from("libicuuc.so.74"),
+            origin: Some(LibraryOrigin::new(
+                "icu",
+                "74.2-1",
+                change,
+                "2024-03-05",
+                Some(archive.clone()),
+            )),
+        };
+        let findings = [
+            missing("foo", Change::Upgraded),
+            missing("bar", Change::Upgraded),
+            missing("baz", Change::Removed),
+        ];
+        let archives = downgrade_archives(&findings, &["bar".to_owned()]);
+        assert_eq!(archives, [archive]);
+        assert_eq!(
+            downgrade_command(&archives).as_deref(),
+            Some("pacman -U /var/cache/pacman/pkg/icu-74.2-1-x86_64.pkg.tar.zst")
+        );
+        assert!(downgrade_archives(&findings, &["foo".to_owned(), "bar".to_owned()]).is_empty());
+    }
+}
@@ -0,0 +1,295 @@
+//! Reports of previous full scans, with their provenance, to find out when a problem appeared
+
+use std::{
+    fmt, fs,
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context as _;
+
+use crate::{
+    baseline::root_state_dir,
+    finding::Finding,
+    history,
+    json::{self, ToJson, Value},
+};
+
+/// Reports directory name in state directory
+const REPORTS_DIRNAME: &str = "reports";
+
+/// Maximum number of reports kept, older ones are removed
+const MAX_REPORTS: usize = 20;
+
+/// Findings of a full scan, and how they were produced
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    /// Unix time of the scan, in seconds
+    time: u64,
+    /// Version of pacman-hooks that ran the scan
+    version: String,
+    /// Command line of the scan
+    command: String,
+    /// Checks that ran
+    checks: Vec<String>,
+    /// Timestamp of the last completed transaction before the scan, as in the pacman log
+    last_transaction: Option<String>,
+    /// Findings, in their JSON form
+    findings: Vec<Value>,
+}
+
+/// Format a Unix time as a UTC date and time
+pub(crate) fn format_time(time: u64) -> String {
+    let (days, secs) = (time / 86_400, time % 86_400);
+    // Civil date from days, with years starting in March so that leap days are last
+    let shifted = days + 719_468;
+    let era = shifted / 146_097;
+    let day_of_era = shifted % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Get a field of a finding in its JSON form
+fn finding_field<'a>(finding: &'a Value, key: &str) -> Option<&'a str> {
+    finding.get(key).and_then(Value::as_str)
+}
+
+impl Report {
+    /// Create the report of a full scan, run now by `command` with `checks`
+    pub fn new(findings: &[Finding], checks: Vec<String>, command: String) -> Self {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let last_transaction = history::last_transaction().unwrap_or_else(|err| {
+            log::warn!("Failed to read last transaction: {err:#}");
+            None
+        });
+        Self {
+            time,
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            command,
+            checks,
+            last_transaction,
+            findings: findings.iter().map(ToJson::to_json).collect(),
+        }
+    }
+
+    fn dir() -> PathBuf {
+        root_state_dir().join(REPORTS_DIRNAME)
+    }
+
+    /// Parse a report saved at Unix time `time`
+    fn from_json(time: u64, value: &Value) -> Option<Self> {
+        let string = |key| {
+            value
+                .get(key)
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned)
+        };
+        Some(Self {
+            time,
+            version: string("version")?,
+            command: string("command")?,
+            checks: value
+                .get("checks")?
+                .as_array()?
+                .iter()
+                .map(|c| c.as_str().map(ToOwned::to_owned))
+                .collect::<Option<_>>()?,
+            last_transaction: string("last_transaction"),
+            findings: value.get("findings")?.as_array()?.to_vec(),
+        })
+    }
+
+    /// Load a report file, named after its Unix time
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let time = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| anyhow::anyhow!("Unexpected report file name"))?;
+        let content = fs::read_to_string(path)?;
+        Self::from_json(time, &json::parse(&content)?)
+            .ok_or_else(|| anyhow::anyhow!("Invalid report"))
+    }
+
+    /// Get saved reports, most recent first, invalid ones are skipped with a warning
+    ///
+    /// # Errors
+    ///
+    /// Fails if the report directory exists but can not be read
+    pub fn list() -> anyhow::Result<Vec<Self>> {
+        let entries = match Self::dir().read_dir() {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut reports = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|e| e != "json") {
+                continue;
+            }
+            match Self::load(&path) {
+                Ok(report) => reports.push(report),
+                Err(err) => log::warn!("Failed to load report {}: {err:#}", path.display()),
+            }
+        }
+        reports.sort_unstable_by_key(|r| std::cmp::Reverse(r.time));
+        Ok(reports)
+    }
+
+    /// Save report, and remove the oldest ones over [`MAX_REPORTS`]
+    ///
+    /// # Errors
+    ///
+    /// Fails if the report can not be written
+    pub fn save(&self) -> anyhow::Result<()> {
+        let dir = Self::dir();
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.json", self.time));
+        let tmp_path = path.with_extension("tmp");
+        let mut file = io::BufWriter::new(fs::File::create(&tmp_path)?);
+        write!(file, "{}", self.to_json())?;
+        file.into_inner()
+            .map_err(io::IntoInnerError::into_error)?
+            .sync_all()?;
+        fs::rename(tmp_path, path)?;
+
+        let mut times: Vec<u64> = dir
+            .read_dir()?
+            .filter_map(Result::ok)
+            .filter_map(|e| {
+                let name = e.file_name();
+                name.to_str()?.strip_suffix(".json")?.parse().ok()
+            })
+            .collect();
+        times.sort_unstable_by(|a, b| b.cmp(a));
+        for time in times.iter().skip(MAX_REPORTS) {
+            let old_path = dir.join(format!("{time}.json"));
+            fs::remove_file(&old_path)
+                .with_context(|| format!("Failed to remove {}", old_path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// One line description, with the date and findings count
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let errors = self
+            .findings
+            .iter()
+            .filter(|f| finding_field(f, "severity") == Some("error"))
+            .count();
+        format!(
+            "{}, {} finding(s) including {errors} error(s), by {:?}",
+            format_time(self.time),
+            self.findings.len(),
+            self.command
+        )
+    }
+}
+
+impl ToJson for Report {
+    fn to_json(&self) -> Value {
+        Value::Object(vec![
+            ("time".to_owned(), self.time.into()),
+            ("version".to_owned(), self.version.as_str().into()),
+            ("command".to_owned(), self.command.as_str().into()),
+            (
+                "checks".to_owned(),
+                Value::Array(self.checks.iter().map(|c| c.as_str().into()).collect()),
+            ),
+            (
+                "last_transaction".to_owned(),
+                self.last_transaction
+                    .as_deref()
+                    .map_or(Value::Null, Into::into),
+            ),
+            ("findings".to_owned(), Value::Array(self.findings.clone())),
+        ])
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Scan of {}, by pacman-hooks {} with {:?}",
+            format_time(self.time),
+            self.version,
+            self.command
+        )?;
+        match &self.last_transaction {
+            Some(timestamp) => writeln!(f, "Last transaction: {timestamp}")?,
+            None => writeln!(f, "Last transaction: none in the pacman log")?,
+        }
+        writeln!(f, "Checks: {}", self.checks.join(", "))?;
+        if self.findings.is_empty() {
+            writeln!(f, "No findings")?;
+        }
+        for finding in &self.findings {
+            writeln!(
+                f,
+                "{}: {}",
+                finding_field(finding, "severity").unwrap_or("unknown"),
+                finding_field(finding, "message").unwrap_or_default()
+            )?;
+            if let Some(hint) = finding_field(finding, "hint") {
+                writeln!(f, "  Hint: {hint}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_time(951_782_400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_time(1_700_000_000), "2023-11-14 22:13:20 UTC");
+    }
+
+    #[test]
+    fn test_report() {
+        let report = Report {
+            time: 1_700_000_000,
+            version: "1.0.0".to_owned(),
+            command: "pacman-hooks check all --service".to_owned(),
+            checks: vec!["pacnew-files".to_owned()],
+            last_transaction: Some("2023-11-14T20:00:00+0100".to_owned()),
+            findings: vec![Finding::PacnewFile {
+                path: PathBuf::from("/etc/foo.conf"),
+            }
+            .to_json()],
+        };
+        let text = report.to_string();
+        assert!(text.contains("Last transaction: 2023-11-14T20:00:00+0100\n"));
+        assert!(text.contains("\ninfo: "));
+        let json = json::parse(&report.to_json().to_string()).unwrap();
+        assert_eq!(Report::from_json(report.time, &json), Some(report));
+        assert_eq!(Report::from_json(0, &Value::Object(vec![])), None);
+    }
+}
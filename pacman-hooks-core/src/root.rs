@@ -0,0 +1,209 @@
+//! Root directory of the checked system, like pacman `--root` and `--dbpath`
+
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Default pacman database directory, relative to the root
+pub const DEFAULT_DB_PATH: &str = "var/lib/pacman";
+
+/// Maximum number of symlinks followed when resolving a link, as the kernel does
+const MAX_LINK_DEPTH: usize = 40;
+
+/// Alternate root and database directories, if set
+static PATHS: OnceLock<(PathBuf, Option<PathBuf>)> = OnceLock::new();
+
+/// Check another system mounted at `root`, with its database in `db_path` if set,
+/// otherwise in the default location relative to `root`
+///
+/// # Errors
+///
+/// Fails if `root` is not a directory, or the root was already set
+pub fn set(root: PathBuf, db_path: Option<PathBuf>) -> anyhow::Result<()> {
+    if !root.is_dir() {
+        anyhow::bail!("Root {} is not a directory", root.display());
+    }
+    log::debug!("Checking system at {}", root.display());
+    PATHS
+        .set((root, db_path))
+        .map_err(|_| anyhow::anyhow!("Root directory already set"))
+}
+
+/// Root directory of the checked system
+pub fn dir() -> &'static Path {
+    PATHS.get().map_or(Path::new("/"), |(root, _)| root)
+}
+
+/// Whether the checked system is not the running one
+#[must_use]
+pub fn is_alternate() -> bool {
+    dir() != Path::new("/")
+}
+
+/// Database directory of the checked system
+pub fn db_path() -> PathBuf {
+    match PATHS.get() {
+        Some((_, Some(db_path))) => db_path.to_owned(),
+        _ => dir().join(DEFAULT_DB_PATH),
+    }
+}
+
+/// Pacman options selecting the checked system, empty for the running one
+pub fn pacman_args() -> Vec<OsString> {
+    let Some((root, db_path)) = PATHS.get() else {
+        return Vec::new();
+    };
+    let mut args = vec!["--root".into(), root.into()];
+    if let Some(db_path) = db_path {
+        args.extend(["--dbpath".into(), db_path.into()]);
+    }
+    args
+}
+
+fn resolve_in(root: &Path, path: &Path) -> PathBuf {
+    root.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+fn unresolve_in(root: &Path, path: &Path) -> PathBuf {
+    path.strip_prefix(root)
+        .map_or_else(|_| path.to_owned(), |p| Path::new("/").join(p))
+}
+
+/// Get the path to access an absolute path of the checked system
+#[must_use]
+pub fn resolve(path: &Path) -> PathBuf {
+    resolve_in(dir(), path)
+}
+
+/// Get the absolute path in the checked system of a path below its root, inverse of [`resolve`]
+#[must_use]
+pub fn unresolve(path: &Path) -> PathBuf {
+    unresolve_in(dir(), path)
+}
+
+/// Follow a symlink below the root, and the links it points to, to the path of the first non link,
+/// which may not exist, or `None` if there are too many links.
+///
+/// Absolute link targets are paths of the checked system.
+#[must_use]
+pub fn link_target(link: &Path) -> Option<PathBuf> {
+    let mut path = link.to_owned();
+    for _ in 0..MAX_LINK_DEPTH {
+        let next = match fs::read_link(&path) {
+            Ok(target) if target.is_absolute() => resolve(&target),
+            Ok(target) => path.parent().unwrap_or(&path).join(target),
+            Err(_) => return Some(path),
+        };
+        path = next;
+    }
+    None
+}
+
+/// Resolve every component of a path below the root, like `realpath`, to the canonical path below the root, which may
+/// not exist, or `None` if there are too many links.
+///
+/// Unlike [`link_target`], links of parent directories are also followed, and absolute link targets are paths of the
+/// checked system.
+#[must_use]
+pub fn canonicalize(path: &Path) -> Option<PathBuf> {
+    canonicalize_in(dir(), path)
+}
+
+fn canonicalize_in(root: &Path, path: &Path) -> Option<PathBuf> {
+    /// Component left to resolve, `None` for a parent directory
+    fn push_components(pending: &mut Vec<Option<OsString>>, path: &Path) {
+        pending.extend(
+            path.components()
+                .rev()
+                .filter_map(|component| match component {
+                    Component::Normal(name) => Some(Some(name.to_owned())),
+                    Component::ParentDir => Some(None),
+                    Component::RootDir | Component::CurDir | Component::Prefix(_) => None,
+                }),
+        );
+    }
+
+    let mut pending = Vec::new();
+    push_components(&mut pending, &unresolve_in(root, path));
+    let mut canonical = PathBuf::from("/");
+    let mut links = 0;
+    while let Some(component) = pending.pop() {
+        let Some(name) = component else {
+            canonical.pop();
+            continue;
+        };
+        let next = canonical.join(name);
+        match fs::read_link(resolve_in(root, &next)) {
+            Ok(target) => {
+                links += 1;
+                if links > MAX_LINK_DEPTH {
+                    return None;
+                }
+                if target.is_absolute() {
+                    canonical = PathBuf::from("/");
+                }
+                push_components(&mut pending, &target);
+            }
+            Err(_) => canonical = next,
+        }
+    }
+    Some(resolve_in(root, &canonical))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let root = Path::new("/mnt");
+        let lib = resolve_in(root, Path::new("/usr/lib/libc.so.6"));
+        assert_eq!(lib, Path::new("/mnt/usr/lib/libc.so.6"));
+        assert_eq!(unresolve_in(root, &lib), Path::new("/usr/lib/libc.so.6"));
+        assert_eq!(
+            resolve_in(Path::new("/"), Path::new("/usr/lib")),
+            Path::new("/usr/lib")
+        );
+        assert_eq!(
+            unresolve_in(Path::new("/"), Path::new("/usr/lib")),
+            Path::new("/usr/lib")
+        );
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("usr/lib/jvm/java-21/bin")).unwrap();
+        fs::write(root.join("usr/lib/jvm/java-21/bin/java"), "").unwrap();
+        symlink("java-21", root.join("usr/lib/jvm/default")).unwrap();
+        // Absolute targets are paths of the checked system, even below a link
+        symlink(
+            "/usr/lib/jvm/default/bin/java",
+            root.join("usr/lib/jvm/default-runtime"),
+        )
+        .unwrap();
+        symlink("../lib/jvm/default-runtime", root.join("usr/lib/java")).unwrap();
+        fs::create_dir(root.join("usr/bin")).unwrap();
+        symlink("../lib/java", root.join("usr/bin/java")).unwrap();
+        assert_eq!(
+            canonicalize_in(root, &root.join("usr/bin/java")),
+            Some(root.join("usr/lib/jvm/java-21/bin/java"))
+        );
+        assert_eq!(
+            canonicalize_in(
+                root,
+                &root.join("usr/lib/jvm/default/../default/bin/missing")
+            ),
+            Some(root.join("usr/lib/jvm/java-21/bin/missing"))
+        );
+
+        symlink("loop", root.join("usr/bin/loop")).unwrap();
+        assert_eq!(canonicalize_in(root, &root.join("usr/bin/loop")), None);
+    }
+}
@@ -0,0 +1,462 @@
+//! JSON Schema of findings, for consumers of their machine readable form.
+//!
+//! Fields are only added within a schema version, [`SCHEMA_VERSION`] is increased when a field is removed,
+//! renamed, or changes type or meaning.
+
+use crate::{
+    finding::{AurStatus, BootProblem, BootReference, ChainProblem, PythonAction, Severity},
+    history::Change,
+    json::Value,
+};
+
+/// Version of the JSON form of findings, in their `schema_version` field
+pub const SCHEMA_VERSION: u64 = 1;
+
+/// Build a JSON object from fields
+fn object<const N: usize>(fields: [(&str, Value); N]) -> Value {
+    Value::Object(fields.map(|(k, v)| (k.to_owned(), v)).into())
+}
+
+/// Schema of a field of type `json_type`
+fn field(json_type: &str, description: &str) -> Value {
+    object([
+        ("type", json_type.into()),
+        ("description", description.into()),
+    ])
+}
+
+/// Schema of a string field with one of the given values
+fn enum_field(values: &[&str], description: &str) -> Value {
+    object([
+        ("type", "string".into()),
+        (
+            "enum",
+            Value::Array(values.iter().map(|v| (*v).into()).collect()),
+        ),
+        ("description", description.into()),
+    ])
+}
+
+/// Fields of findings with a plain type: name, JSON type and description
+const PLAIN_FIELDS: [(&str, &str, &str); 44] = [
+    (
+        "check",
+        "string",
+        "Name of the built-in or custom check that produced the finding",
+    ),
+    ("message", "string", "Human readable description"),
+    ("package", "string", "Package name"),
+    ("file", "string", "Package file"),
+    ("dependency", "string", "Library file name"),
+    (
+        "declared",
+        "string",
+        "Declared dependency providing the library",
+    ),
+    (
+        "optional",
+        "string",
+        "Optional dependency providing the library",
+    ),
+    ("size", "integer", "File or directory size in bytes"),
+    (
+        "expected",
+        "integer",
+        "File size recorded in the package in bytes",
+    ),
+    (
+        "platform",
+        "string",
+        "Architecture, operating system or libc the file is built for",
+    ),
+    ("upgrade", "string", "Upgraded package removing the library"),
+    (
+        "dir",
+        "string",
+        "Python packages directory, the first one for packages with files in several",
+    ),
+    ("files", "integer", "Number of files"),
+    (
+        "interpreter",
+        "string",
+        "Missing Python interpreter of the script shebang",
+    ),
+    (
+        "link",
+        "string",
+        "Systemd enablement, convention, library soname or package symlink",
+    ),
+    (
+        "target",
+        "string",
+        "Final target of the convention or library soname symlink, or link of the package symlink chain with the problem",
+    ),
+    (
+        "library",
+        "string",
+        "Installed library a stale soname symlink should point to, library of /opt the file uses, or library lacking symbol versions",
+    ),
+    (
+        "provider",
+        "string",
+        "Package owning the library of /opt or the library lacking symbol versions, or repository package providing the missing firmware",
+    ),
+    (
+        "firmware",
+        "string",
+        "Firmware file, relative to the firmware directory",
+    ),
+    (
+        "module",
+        "string",
+        "Loaded kernel module that requested the firmware",
+    ),
+    ("soname", "string", "Library file name"),
+    ("path", "string", "File path"),
+    (
+        "entry",
+        "string",
+        "Mount point of the boot entry, or name of its encrypted device",
+    ),
+    (
+        "missing",
+        "string",
+        "Missing device, tool or key file of the boot entry, file referenced by the boot configuration, or unit file or program of the login unit or activated service",
+    ),
+    (
+        "unit",
+        "string",
+        "Systemd unit needed to log in, or enabled socket or path unit",
+    ),
+    (
+        "service",
+        "string",
+        "Systemd service activated by the socket or path unit",
+    ),
+    ("mode", "string", "File permission bits, in octal"),
+    (
+        "appeared",
+        "integer",
+        "Unix time of the transaction the unowned file appeared during",
+    ),
+    ("built", "integer", "Unix time the foreign package was built at"),
+    (
+        "runtime",
+        "string",
+        "Compatibility package of the legacy runtime",
+    ),
+    (
+        "loader",
+        "boolean",
+        "Whether the library is a dynamic loader",
+    ),
+    (
+        "writable",
+        "boolean",
+        "Whether users other than root can modify the library",
+    ),
+    (
+        "program",
+        "boolean",
+        "Whether the missing file is a program of the login unit or activated service, rather than the unit itself",
+    ),
+    (
+        "signed",
+        "boolean",
+        "Whether the boot image is signed, with a key that is not enrolled",
+    ),
+    (
+        "shadowed_package",
+        "string",
+        "Package of the shadowed library",
+    ),
+    ("shadowed_path", "string", "Path of the shadowed library"),
+    ("version", "string", "Installed package version"),
+    ("advisory", "string", "Security tracker vulnerability group"),
+    ("risk", "string", "Security tracker severity"),
+    ("type", "string", "Vulnerability type"),
+    (
+        "date",
+        "string",
+        "Day of the transaction that ran the failed scriptlet",
+    ),
+    (
+        "error",
+        "string",
+        "Panic message of the skipped check, or first error of the failed scriptlet",
+    ),
+    (
+        "package_origin",
+        "string",
+        "Repository of the package, or aur, local, or foreign when the AUR could not be looked up",
+    ),
+    (
+        "hint",
+        "string",
+        "What to do about a missing library of a well known family",
+    ),
+];
+
+/// Schema of the origin of a missing library
+fn origin_schema() -> Value {
+    object([
+        ("type", "object".into()),
+        (
+            "description",
+            "Package version that provided a missing library, and the transaction that removed it"
+                .into(),
+        ),
+        (
+            "properties",
+            object([
+                ("package", field("string", "Package name")),
+                ("version", field("string", "Package version")),
+                (
+                    "change",
+                    enum_field(
+                        &[Change::Removed, Change::Upgraded, Change::Downgraded].map(Change::name),
+                        "Transaction that replaced or removed the package",
+                    ),
+                ),
+                (
+                    "date",
+                    field("string", "Transaction date, as in the pacman log"),
+                ),
+            ]),
+        ),
+    ])
+}
+
+/// Schema of what a boot entry is missing
+fn problem_schema() -> Value {
+    enum_field(
+        &[
+            BootProblem::Device,
+            BootProblem::Fsck,
+            BootProblem::MountHelper,
+            BootProblem::KeyFile,
+        ]
+        .map(BootProblem::name),
+        "What the boot entry is missing",
+    )
+}
+
+/// Schema of what the file missing for a boot configuration is
+fn reference_schema() -> Value {
+    enum_field(
+        &[
+            BootReference::Image,
+            BootReference::Init,
+            BootReference::PlymouthTheme,
+        ]
+        .map(BootReference::name),
+        "What the file missing for the boot configuration is",
+    )
+}
+
+/// Schema of what is wrong with a package symlink chain
+fn chain_schema() -> Value {
+    enum_field(
+        &[
+            ChainProblem::Circular,
+            ChainProblem::TooDeep,
+            ChainProblem::UserDirectory,
+        ]
+        .map(ChainProblem::name),
+        "What is wrong with the package symlink chain",
+    )
+}
+
+/// Fields holding an array of strings, with their description
+const STRING_ARRAY_FIELDS: [(&str, &str); 4] = [
+    (
+        "dirs",
+        "Python packages directories ignored by installed interpreters",
+    ),
+    (
+        "packages",
+        "Packages owning the same file, needing the legacy runtime, or skipped by the check while the local database was busy",
+    ),
+    (
+        "versions",
+        "Symbol versions the file requires that its library does not define",
+    ),
+    (
+        "dependencies",
+        "Repository dependencies installed long after the foreign package was built",
+    ),
+];
+
+/// Schema of an array of strings
+fn string_array(description: &str) -> Value {
+    object([
+        ("type", "array".into()),
+        ("items", object([("type", "string".into())])),
+        ("description", description.into()),
+    ])
+}
+
+/// Get the JSON Schema of a finding, as serialized by [`crate::json::ToJson`]
+pub fn findings_schema() -> Value {
+    let mut properties = vec![
+        (
+            "schema_version".to_owned(),
+            object([("const", SCHEMA_VERSION.into())]),
+        ),
+        (
+            "severity".to_owned(),
+            enum_field(
+                &[Severity::Info, Severity::Warning, Severity::Error].map(Severity::name),
+                "How serious the finding is",
+            ),
+        ),
+        ("origin".to_owned(), origin_schema()),
+        (
+            "status".to_owned(),
+            enum_field(
+                &[
+                    AurStatus::NotFound,
+                    AurStatus::Orphaned,
+                    AurStatus::OutOfDate,
+                ]
+                .map(AurStatus::name),
+                "Status of the package in the AUR",
+            ),
+        ),
+        (
+            "action".to_owned(),
+            enum_field(
+                &[
+                    PythonAction::Rebuild,
+                    PythonAction::Reinstall,
+                    PythonAction::Remove,
+                ]
+                .map(PythonAction::name),
+                "Suggested fix for the Python package",
+            ),
+        ),
+        ("problem".to_owned(), problem_schema()),
+        ("chain".to_owned(), chain_schema()),
+        ("reference".to_owned(), reference_schema()),
+        (
+            "fixed".to_owned(),
+            object([
+                ("type", Value::Array(vec!["string".into(), "null".into()])),
+                (
+                    "description",
+                    "First version with the vulnerabilities fixed, null if there is none yet"
+                        .into(),
+                ),
+            ]),
+        ),
+        (
+            "reason".to_owned(),
+            enum_field(
+                &["timeout", "panic", "early-exit", "offline", "db-busy"],
+                "Why the check was skipped",
+            ),
+        ),
+        (
+            "timeout".to_owned(),
+            field("integer", "Timeout of the skipped check in seconds"),
+        ),
+    ];
+    properties.extend(
+        STRING_ARRAY_FIELDS
+            .iter()
+            .map(|(name, description)| ((*name).to_owned(), string_array(description))),
+    );
+    properties.extend(
+        PLAIN_FIELDS.iter().map(|(name, json_type, description)| {
+            ((*name).to_owned(), field(json_type, description))
+        }),
+    );
+    object([
+        (
+            "$schema",
+            "https://json-schema.org/draft/2020-12/schema".into(),
+        ),
+        ("title", "pacman-hooks finding".into()),
+        ("type", "object".into()),
+        (
+            "required",
+            Value::Array(
+                ["schema_version", "check", "severity", "message"]
+                    .map(Into::into)
+                    .into(),
+            ),
+        ),
+        ("properties", Value::Object(properties)),
+        // New fields can be added without changing the schema version
+        ("additionalProperties", true.into()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc, time::Duration};
+
+    use super::*;
+    use crate::{
+        finding::{Finding, PythonAction, SkipReason},
+        json::ToJson as _,
+    };
+
+    #[test]
+    fn test_findings_schema() {
+        let schema = findings_schema();
+        let Some(Value::Object(properties)) = schema.get("properties") else {
+            panic!();
+        };
+        let findings = [
+            Finding::TruncatedFile {
+                package: Arc::new("foo".to_owned()),
+                file: Arc::new(PathBuf::from("/usr/bin/foo")),
+                size: 0,
+                expected: 1024,
+            },
+            Finding::BrokenPythonPackage {
+                package: "python-foo".to_owned(),
+                dirs: vec!["/usr/lib/python3.11".to_owned()],
+                files: 1,
+                size: 1024,
+                action: PythonAction::Remove,
+            },
+            Finding::UnpackagedLibrary {
+                soname: "libfoo.so.1".to_owned(),
+                path: PathBuf::from("/usr/local/lib"),
+                writable: true,
+                shadowed_package: "foo".to_owned(),
+                shadowed_path: PathBuf::from("/usr/lib"),
+            },
+            Finding::VulnerablePackage {
+                package: "foo".to_owned(),
+                version: "1.0-1".to_owned(),
+                advisory: "AVG-1".to_owned(),
+                risk: "High".to_owned(),
+                issue_type: "denial of service".to_owned(),
+                fixed: None,
+            },
+            Finding::CheckSkipped {
+                check: "aur-packages".to_owned(),
+                reason: SkipReason::Timeout(Duration::from_mins(10)),
+            },
+            Finding::CheckSkipped {
+                check: "missing-dependencies".to_owned(),
+                reason: SkipReason::DbBusy(vec!["foo".to_owned()]),
+            },
+        ];
+        for finding in findings {
+            let Value::Object(fields) = finding.to_json() else {
+                panic!();
+            };
+            assert_eq!(
+                fields[0],
+                ("schema_version".to_owned(), SCHEMA_VERSION.into())
+            );
+            for (key, _) in fields {
+                assert!(properties.iter().any(|(k, _)| *k == key), "{key}");
+            }
+        }
+    }
+}
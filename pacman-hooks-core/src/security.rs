@@ -0,0 +1,207 @@
+//! Arch Linux security tracker advisories, like arch-audit
+
+use std::{
+    cmp::Ordering,
+    fs, io,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context as _;
+
+use crate::{
+    baseline::STATE_DIR,
+    http,
+    json::{self, Value},
+    network,
+    version::vercmp,
+};
+
+/// Security tracker feed of all vulnerability groups
+const FEED_URL: &str = "https://security.archlinux.org/all.json";
+
+/// Cached feed file name in state directory
+const CACHE_FILENAME: &str = "security-tracker.json";
+
+/// Time the cached feed is used without downloading it again
+const CACHE_TTL: Duration = Duration::from_hours(6);
+
+/// Vulnerability group of the security tracker
+#[derive(Debug)]
+pub struct Advisory {
+    /// Group identifier, like `AVG-1234`
+    name: String,
+    packages: Vec<String>,
+    status: String,
+    severity: String,
+    /// Vulnerability type, like `arbitrary code execution`
+    kind: String,
+    /// First version with the vulnerabilities fixed, if any
+    fixed: Option<String>,
+}
+
+impl Advisory {
+    fn from_json(value: &Value) -> Option<Self> {
+        let string = |key| {
+            value
+                .get(key)
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned)
+        };
+        let Some(Value::Array(packages)) = value.get("packages") else {
+            return None;
+        };
+        Some(Self {
+            name: string("name")?,
+            packages: packages
+                .iter()
+                .filter_map(Value::as_str)
+                .map(ToOwned::to_owned)
+                .collect(),
+            status: string("status")?,
+            severity: string("severity")?,
+            kind: string("type")?,
+            fixed: string("fixed"),
+        })
+    }
+
+    /// Group identifier, like `AVG-1234`
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Affected package names
+    #[must_use]
+    pub fn packages(&self) -> &[String] {
+        &self.packages
+    }
+
+    /// Tracker severity, like `High`
+    #[must_use]
+    pub fn severity(&self) -> &str {
+        &self.severity
+    }
+
+    /// Vulnerability type, like `arbitrary code execution`
+    #[must_use]
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// First version with the vulnerabilities fixed, if any
+    #[must_use]
+    pub fn fixed(&self) -> Option<&str> {
+        self.fixed.as_deref()
+    }
+
+    /// Whether a version of an affected package is vulnerable
+    #[must_use]
+    pub fn affects(&self, version: &str) -> bool {
+        self.status != "Not affected"
+            && self
+                .fixed
+                .as_deref()
+                .is_none_or(|f| vercmp(version, f) == Ordering::Less)
+    }
+}
+
+fn parse_advisories(feed: &str) -> anyhow::Result<Vec<Advisory>> {
+    let Value::Array(groups) = json::parse(feed)? else {
+        anyhow::bail!("Feed is not a JSON array");
+    };
+    Ok(groups.iter().filter_map(Advisory::from_json).collect())
+}
+
+fn cache_path() -> PathBuf {
+    PathBuf::from(STATE_DIR).join(CACHE_FILENAME)
+}
+
+/// Whether the feed was downloaded and cached
+#[must_use]
+pub fn has_cached_feed() -> bool {
+    cache_path().is_file()
+}
+
+/// Download the feed, and cache it
+fn download_feed() -> anyhow::Result<String> {
+    let feed = String::from_utf8(http::download(FEED_URL)?).context("Invalid feed")?;
+    // Only cache a valid feed
+    parse_advisories(&feed).context("Invalid feed")?;
+    let path = cache_path();
+    let tmp_path = path.with_extension("tmp");
+    let written = fs::create_dir_all(STATE_DIR)
+        .and_then(|()| fs::write(&tmp_path, &feed))
+        .and_then(|()| fs::rename(&tmp_path, &path));
+    if let Err(err) = written {
+        log::debug!("Failed to cache security feed in {}: {err}", path.display());
+    }
+    Ok(feed)
+}
+
+/// Load advisories from the cached feed if recent enough, downloading it otherwise.
+///
+/// If the download fails, or the network is offline, an outdated cached feed is used.
+///
+/// # Errors
+///
+/// Fails if the cached feed can not be read, and can not be downloaded either
+pub fn load_advisories() -> anyhow::Result<Vec<Advisory>> {
+    let path = cache_path();
+    let cached = match fs::read_to_string(&path) {
+        Ok(feed) => Some(feed),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err.into()),
+    };
+    let age = fs::metadata(&path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| SystemTime::now().duration_since(t).ok());
+    let feed = match cached {
+        Some(feed) if age.is_some_and(|a| a <= CACHE_TTL) || network::is_offline() => feed,
+        None if network::is_offline() => {
+            anyhow::bail!("Network is offline, and the security feed was never downloaded")
+        }
+        cached => match (download_feed(), cached) {
+            (Ok(feed), _) => feed,
+            (Err(err), Some(feed)) => {
+                log::warn!("Failed to update security feed, using cached one: {err:#}");
+                feed
+            }
+            (Err(err), None) => return Err(err),
+        },
+    };
+    parse_advisories(&feed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advisories() {
+        let advisories = parse_advisories(
+            r#"[
+  {"name": "AVG-1", "packages": ["openssl", "lib32-openssl"], "status": "Fixed", "severity": "High",
+   "type": "arbitrary code execution", "affected": "3.0.1-1", "fixed": "3.0.2-1", "ticket": null,
+   "issues": ["CVE-2022-0001"], "advisories": []},
+  {"name": "AVG-2", "packages": ["foo"], "status": "Vulnerable", "severity": "Low",
+   "type": "denial of service", "affected": "1.0-1", "fixed": null, "ticket": null,
+   "issues": [], "advisories": []},
+  {"name": "AVG-3", "packages": ["bar"], "status": "Not affected", "severity": "Unknown",
+   "type": "unknown", "affected": "1.0-1", "fixed": null, "ticket": null,
+   "issues": [], "advisories": []}
+]"#,
+        )
+        .unwrap();
+        let [fixed, unfixed, not_affected] = advisories.as_slice() else {
+            panic!();
+        };
+        assert_eq!(fixed.packages(), ["openssl", "lib32-openssl"]);
+        assert!(fixed.affects("3.0.1-1"));
+        assert!(!fixed.affects("3.0.2-1"));
+        assert!(!fixed.affects("1:3.0.0-1"));
+        assert!(unfixed.affects("2.0-1"));
+        assert!(!not_affected.affects("1.0-1"));
+    }
+}
@@ -0,0 +1,252 @@
+//! Validation of the environment checks run in, to catch setups where they silently do less than expected
+
+use std::{fmt, fs, path::Path};
+
+use crate::{
+    baseline::STATE_DIR,
+    config::{Config, CONFIG_PATH},
+    elf,
+    filesdb::FilesDb,
+    localdb::{self, LocalDb},
+    privileges, root,
+};
+
+/// File created in the state directory to test it is writable
+const PROBE_FILENAME: &str = ".self-test";
+
+/// Outcome of a probe
+#[derive(Debug, Eq, PartialEq)]
+pub enum Outcome {
+    /// Works as expected
+    Ok,
+    /// Checks run, with fewer features, and what to do about it
+    Degraded(String),
+    /// Checks fail or miss problems, and what to do about it
+    Failed(String),
+}
+
+/// Probe of something checks need
+pub struct Probe {
+    /// What is probed
+    name: &'static str,
+    outcome: Outcome,
+}
+
+impl Probe {
+    /// Outcome of the probe
+    #[must_use]
+    pub fn outcome(&self) -> &Outcome {
+        &self.outcome
+    }
+}
+
+impl fmt::Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.outcome {
+            Outcome::Ok => write!(f, "{}: ok", self.name),
+            Outcome::Degraded(fix) => write!(f, "{}: degraded, {fix}", self.name),
+            Outcome::Failed(fix) => write!(f, "{}: failed, {fix}", self.name),
+        }
+    }
+}
+
+/// Probe that the local database can be read, and has packages
+fn probe_local_db() -> Outcome {
+    let dir = localdb::local_db_dir();
+    match LocalDb::open() {
+        Ok(db) if db.packages().next().is_some() => Outcome::Ok,
+        Ok(_) => Outcome::Failed(format!(
+            "{} has no packages, pass the pacman database directory with --dbpath",
+            dir.display()
+        )),
+        Err(err) => Outcome::Failed(format!(
+            "unable to read {}: {err}, run as root or pass the pacman database directory with --dbpath, checks fall back to the slower pacman command",
+            dir.display()
+        )),
+    }
+}
+
+/// Probe that a files database was downloaded
+fn probe_files_db() -> Outcome {
+    match FilesDb::open() {
+        Ok(Some(_)) => Outcome::Ok,
+        Ok(None) => Outcome::Degraded(
+            "no files database was downloaded, run `pacman -Fy` or `pkgfile -u` so that packages providing missing libraries are suggested"
+                .to_owned(),
+        ),
+        Err(err) => Outcome::Degraded(format!(
+            "unable to read the files database: {err:#}, download it again with `pacman -Fy`"
+        )),
+    }
+}
+
+/// Probe that ldd runs, it confirms missing dependencies of the running system
+fn probe_ldd() -> Outcome {
+    match privileges::command("ldd").arg("--version").output() {
+        Ok(output) if output.status.success() => Outcome::Ok,
+        Ok(output) => Outcome::Failed(format!(
+            "ldd failed with {}, reinstall glibc",
+            output.status
+        )),
+        Err(err) => Outcome::Failed(format!(
+            "unable to run ldd: {err}, install glibc, missing dependencies are not confirmed otherwise"
+        )),
+    }
+}
+
+/// Probe that the dynamic loader of the checked system exists and can be read
+fn probe_loader() -> Outcome {
+    let loader = match elf::system_loader() {
+        Ok(Some(loader)) => loader,
+        Ok(None) => {
+            return Outcome::Failed("pacman is not dynamically linked, check --root".to_owned())
+        }
+        Err(err) => return Outcome::Failed(format!("{err:#}, check --root")),
+    };
+    match fs::File::open(root::resolve(&loader)) {
+        Ok(_) => Outcome::Ok,
+        Err(err) => Outcome::Failed(format!(
+            "unable to read {}: {err}, reinstall glibc",
+            loader.display()
+        )),
+    }
+}
+
+/// Probe that the configuration at `path` parses, and that its user exists
+fn probe_config(path: &Path) -> Outcome {
+    let user = match Config::load_from(path) {
+        Ok(config) => config.user().map(ToOwned::to_owned),
+        Err(err) => return Outcome::Failed(format!("{err:#}, fix {}", path.display())),
+    };
+    match user.map(|u| privileges::lookup_user(&u)).transpose() {
+        Ok(_) => Outcome::Ok,
+        Err(err) => Outcome::Failed(format!(
+            "{err:#}, fix User in {}, child processes run as the default user otherwise",
+            path.display()
+        )),
+    }
+}
+
+/// Probe that a file can be created in a state directory
+fn probe_state_dir(dir: &Path) -> Outcome {
+    let path = dir.join(PROBE_FILENAME);
+    match fs::create_dir_all(dir)
+        .and_then(|()| fs::write(&path, ""))
+        .and_then(|()| fs::remove_file(&path))
+    {
+        Ok(()) => Outcome::Ok,
+        Err(err) => Outcome::Failed(format!(
+            "unable to write to {}: {err}, run as root, otherwise baselines, caches and reports are not saved",
+            dir.display()
+        )),
+    }
+}
+
+/// Probe the environment checks run in
+#[must_use]
+pub fn run() -> Vec<Probe> {
+    probe_all(Path::new(CONFIG_PATH), Path::new(STATE_DIR))
+}
+
+/// Probe the environment checks run in, with the configuration at `config_path` and state in `state_dir`
+fn probe_all(config_path: &Path, state_dir: &Path) -> Vec<Probe> {
+    let mut probes = vec![
+        Probe {
+            name: "local database",
+            outcome: probe_local_db(),
+        },
+        Probe {
+            name: "files database",
+            outcome: probe_files_db(),
+        },
+    ];
+    // Dependencies of another root, or with the native resolver, are not confirmed with ldd
+    if !elf::is_native_only() {
+        probes.push(Probe {
+            name: "ldd",
+            outcome: probe_ldd(),
+        });
+    }
+    probes.extend([
+        Probe {
+            name: "dynamic loader",
+            outcome: probe_loader(),
+        },
+        Probe {
+            name: "configuration",
+            outcome: probe_config(config_path),
+        },
+        Probe {
+            name: "state directory",
+            outcome: probe_state_dir(state_dir),
+        },
+    ]);
+    probes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_state_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(probe_state_dir(&dir.path().join("state")), Outcome::Ok);
+        assert!(!dir.path().join("state").join(PROBE_FILENAME).exists());
+
+        let file = dir.path().join("file");
+        fs::write(&file, "").unwrap();
+        let probe = Probe {
+            name: "state directory",
+            outcome: probe_state_dir(&file.join("state")),
+        };
+        assert!(matches!(probe.outcome(), Outcome::Failed(_)));
+        assert!(probe.to_string().starts_with(&format!(
+            "state directory: failed, unable to write to {}/state: ",
+            file.display()
+        )));
+    }
+
+    #[test]
+    fn test_probe_all_invalid_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("pacman-hooks.conf");
+        fs::write(&config, "[check-broken-packages]\nUnknownOption = 1\n").unwrap();
+        let probes = probe_all(&config, &dir.path().join("state"));
+
+        let config_probe = probes.iter().find(|p| p.name == "configuration").unwrap();
+        assert_eq!(
+            config_probe.to_string(),
+            format!(
+                "configuration: failed, [config] Line 2: Unknown option \"UnknownOption\", fix {}",
+                config.display()
+            )
+        );
+        // The other probes still run
+        for name in [
+            "local database",
+            "files database",
+            "dynamic loader",
+            "state directory",
+        ] {
+            assert!(probes.iter().any(|p| p.name == name), "{name}");
+        }
+        let state_probe = probes.iter().find(|p| p.name == "state directory").unwrap();
+        assert_eq!(state_probe.outcome(), &Outcome::Ok);
+    }
+
+    #[test]
+    fn test_probe_config_unknown_user() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("pacman-hooks.conf");
+        assert_eq!(probe_config(&config), Outcome::Ok);
+
+        fs::write(
+            &config,
+            "[check-broken-packages]\nUser = pacman-hooks-missing-user\n",
+        )
+        .unwrap();
+        assert!(matches!(probe_config(&config), Outcome::Failed(fix)
+            if fix.starts_with("Unknown user \"pacman-hooks-missing-user\", fix User in ")));
+    }
+}
@@ -0,0 +1,182 @@
+//! Portable set of suppressions, the known false positives and the baseline of known findings, to distribute a
+//! common set to several systems
+//!
+//! Imported suppressions are merged with the local ones, so suppressions added on a system are kept.
+
+use anyhow::Context as _;
+use glob::Pattern;
+
+use crate::{
+    baseline::Baseline,
+    json::{self, Value},
+    kb::KnowledgeBase,
+};
+
+/// Version of the suppression format, in its `schema_version` field
+const SCHEMA_VERSION: u64 = 1;
+
+/// Suppressions added by an import
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Imported {
+    /// Known false positive entries
+    pub known_bundled_libs: usize,
+    /// Known findings
+    pub baseline: usize,
+}
+
+/// Build the JSON form of suppressions
+fn to_json(kb: &KnowledgeBase, baseline: &Baseline) -> Value {
+    let strings = |s: Vec<&str>| Value::Array(s.into_iter().map(Value::from).collect());
+    Value::Object(vec![
+        ("schema_version".to_owned(), SCHEMA_VERSION.into()),
+        (
+            "known_bundled_libs".to_owned(),
+            Value::Array(
+                kb.entries()
+                    .iter()
+                    .map(|(package, sonames)| {
+                        Value::Object(vec![
+                            ("package".to_owned(), package.as_str().into()),
+                            (
+                                "sonames".to_owned(),
+                                strings(sonames.iter().map(Pattern::as_str).collect()),
+                            ),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+        ("baseline".to_owned(), strings(baseline.messages())),
+    ])
+}
+
+/// Get the items of a string array field
+fn strings<'a>(value: &'a Value, key: &str) -> anyhow::Result<Vec<&'a str>> {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .and_then(|items| items.iter().map(Value::as_str).collect())
+        .ok_or_else(|| anyhow::anyhow!("Missing or invalid {key:?} string array"))
+}
+
+/// Merge the suppressions of a JSON value into local ones
+fn merge(
+    kb: &mut KnowledgeBase,
+    baseline: &mut Baseline,
+    value: &Value,
+) -> anyhow::Result<Imported> {
+    let version = value.get("schema_version");
+    if version != Some(&SCHEMA_VERSION.into()) {
+        anyhow::bail!("Unsupported schema version {version:?}, expected {SCHEMA_VERSION}");
+    }
+    let pattern = |p: &str| Pattern::new(p).with_context(|| format!("Invalid pattern {p:?}"));
+    let mut imported = Imported::default();
+    for entry in value
+        .get("known_bundled_libs")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow::anyhow!("Missing or invalid \"known_bundled_libs\" array"))?
+    {
+        let package = entry
+            .get("package")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow::anyhow!("Known false positive without package"))?;
+        let sonames = strings(entry, "sonames")?
+            .into_iter()
+            .map(pattern)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if sonames.is_empty() {
+            anyhow::bail!("Missing sonames for package {package:?}");
+        }
+        if kb.add(pattern(package)?, sonames) {
+            imported.known_bundled_libs += 1;
+        }
+    }
+    for message in strings(value, "baseline")? {
+        // Baseline files have a finding per line
+        if message.contains('\n') {
+            anyhow::bail!("Invalid multiline baseline finding {message:?}");
+        }
+        if baseline.add(message.to_owned()) {
+            imported.baseline += 1;
+        }
+    }
+    Ok(imported)
+}
+
+/// Export the known false positives in effect and the baseline, as JSON
+///
+/// # Errors
+///
+/// Fails if the baseline can not be read
+pub fn export() -> anyhow::Result<Value> {
+    Ok(to_json(&KnowledgeBase::load(), &Baseline::load()?))
+}
+
+/// Import suppressions exported as JSON, adding them to the local ones
+///
+/// # Errors
+///
+/// Fails if `content` is invalid, or the local suppressions can not be read or written
+pub fn import(content: &str) -> anyhow::Result<Imported> {
+    let value = json::parse(content).context("Invalid suppressions")?;
+    let mut kb = KnowledgeBase::load();
+    let mut baseline = Baseline::load()?;
+    let imported = merge(&mut kb, &mut baseline, &value).context("Invalid suppressions")?;
+    if imported.known_bundled_libs > 0 {
+        kb.save()?;
+    }
+    if imported.baseline > 0 {
+        baseline.save_known()?;
+    }
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let mut kb = KnowledgeBase::default();
+        kb.add(
+            Pattern::new("wine*").unwrap(),
+            vec![Pattern::new("*.dll.so").unwrap()],
+        );
+        let mut baseline = Baseline::default();
+        baseline.add("Local finding".to_owned());
+        let exported = to_json(&kb, &baseline);
+        assert_eq!(
+            exported.to_string(),
+            r#"{"schema_version":1,"known_bundled_libs":[{"package":"wine*","sonames":["*.dll.so"]}],"baseline":["Local finding"]}"#
+        );
+
+        let fleet = json::parse(
+            r#"{"schema_version":1,"known_bundled_libs":[{"package":"wine*","sonames":["*.dll.so"]},{"package":"bcompare","sonames":["*"]}],"baseline":["Fleet finding","Local finding"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            merge(&mut kb, &mut baseline, &fleet).unwrap(),
+            Imported {
+                known_bundled_libs: 1,
+                baseline: 1
+            }
+        );
+        assert!(kb.is_known("bcompare", "libQt4Pas.so.5"));
+        assert!(kb.is_known("wine", "ntdll.dll.so"));
+        assert_eq!(baseline.messages(), ["Fleet finding", "Local finding"]);
+        assert_eq!(
+            merge(&mut kb, &mut baseline, &fleet).unwrap(),
+            Imported::default()
+        );
+
+        for invalid in [
+            r#"{"schema_version":2,"known_bundled_libs":[],"baseline":[]}"#,
+            r#"{"schema_version":1,"baseline":[]}"#,
+            r#"{"schema_version":1,"known_bundled_libs":[{"package":"foo","sonames":[]}],"baseline":[]}"#,
+            r#"{"schema_version":1,"known_bundled_libs":[{"package":"foo [","sonames":["*"]}],"baseline":[]}"#,
+            r#"{"schema_version":1,"known_bundled_libs":[],"baseline":["a\nb"]}"#,
+        ] {
+            assert!(merge(&mut kb, &mut baseline, &json::parse(invalid).unwrap()).is_err());
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Pacman sync databases access
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{Classify as _, ErrorKind},
+    localdb, root,
+};
+
+/// Pacman configuration, declaring the sync repositories
+const PACMAN_CONF_PATH: &str = "/etc/pacman.conf";
+
+/// Tar block size
+const TAR_BLOCK_SIZE: usize = 512;
+
+/// Get names of repositories configured in pacman.conf
+pub(crate) fn repositories() -> anyhow::Result<Vec<String>> {
+    let conf = fs::read_to_string(root::resolve(Path::new(PACMAN_CONF_PATH)))?;
+    Ok(conf
+        .lines()
+        .filter_map(|l| l.trim().strip_prefix('[')?.strip_suffix(']'))
+        .filter(|s| *s != "options")
+        .map(ToOwned::to_owned)
+        .collect())
+}
+
+/// Directory of the sync databases
+pub(crate) fn sync_dir() -> PathBuf {
+    root::db_path().join("sync")
+}
+
+/// Read a database archive, decompressing it if needed, to get a tar archive
+pub(crate) fn read_archive(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let data = fs::read(path)?;
+    if data.starts_with(&[0x1f, 0x8b]) {
+        localdb::gunzip(&data).classify(ErrorKind::PacmanQuery)
+    } else if data.get(257..262) == Some(b"ustar") {
+        Ok(data)
+    } else {
+        Err(anyhow::anyhow!(
+            "Unsupported compression for {}",
+            path.display()
+        ))
+        .classify(ErrorKind::PacmanQuery)
+    }
+}
+
+/// Get names of all packages available in the configured repositories
+///
+/// # Errors
+///
+/// Fails if pacman.conf or a sync database can not be read
+pub fn package_names() -> anyhow::Result<HashSet<String>> {
+    Ok(package_repositories()?.into_keys().collect())
+}
+
+/// Get the repository of all packages available in the configured repositories,
+/// the first one in pacman.conf order for packages in several, as pacman installs them from
+///
+/// # Errors
+///
+/// Fails if pacman.conf or a sync database can not be read
+pub fn package_repositories() -> anyhow::Result<HashMap<String, String>> {
+    let mut packages = HashMap::new();
+    let sync_dir = sync_dir();
+    for repo in repositories()? {
+        let tar = read_archive(&sync_dir.join(format!("{repo}.db")))?;
+        for (path, _) in tar_entries(&tar).classify(ErrorKind::PacmanQuery)? {
+            if let Some(name) = path.split('/').next().and_then(localdb::package_name) {
+                packages
+                    .entry(name.to_owned())
+                    .or_insert_with(|| repo.clone());
+            }
+        }
+    }
+    Ok(packages)
+}
+
+/// Get names the repository versions of `packages` provide besides their own, like virtual packages and sonames.
+///
+/// Packages removed from the system are not in the local database anymore, their repository version is the only
+/// record left of what they provided.
+///
+/// # Errors
+///
+/// Fails if pacman.conf or a sync database can not be read
+pub fn package_provides(packages: &[String]) -> anyhow::Result<Vec<String>> {
+    let mut provides = Vec::new();
+    let sync_dir = sync_dir();
+    for repo in repositories()? {
+        let tar = read_archive(&sync_dir.join(format!("{repo}.db")))?;
+        for (path, data) in tar_entries(&tar).classify(ErrorKind::PacmanQuery)? {
+            let Some((dir, "desc")) = path.split_once('/') else {
+                continue;
+            };
+            if !localdb::package_name(dir).is_some_and(|n| packages.iter().any(|p| p == n)) {
+                continue;
+            }
+            let desc = String::from_utf8_lossy(data);
+            provides.extend(
+                localdb::parse_sections(&desc)
+                    .remove("PROVIDES")
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|p| localdb::dependency_name(p).to_owned()),
+            );
+        }
+    }
+    provides.sort_unstable();
+    provides.dedup();
+    Ok(provides)
+}
+
+/// Get paths and content of tar archive entries
+pub(crate) fn tar_entries(tar: &[u8]) -> anyhow::Result<Vec<(String, &[u8])>> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = tar.get(offset..offset + TAR_BLOCK_SIZE) {
+        if header.iter().all(|b| *b == 0) {
+            break;
+        }
+        let field = |range: std::ops::Range<usize>| {
+            let f = &header[range];
+            String::from_utf8_lossy(f.split(|b| *b == 0).next().unwrap_or_default()).into_owned()
+        };
+        let size = usize::from_str_radix(field(124..136).trim(), 8)
+            .map_err(|e| anyhow::anyhow!("Invalid tar entry size: {e}"))?;
+        let data_offset = offset + TAR_BLOCK_SIZE;
+        // Skip extended headers, whose names are not paths
+        if matches!(header[156], 0 | b'0' | b'5') {
+            let name = field(0..100);
+            let prefix = field(345..500);
+            let data = tar
+                .get(data_offset..data_offset + size)
+                .ok_or_else(|| anyhow::anyhow!("Truncated tar entry {name:?}"))?;
+            entries.push((
+                if prefix.is_empty() {
+                    name
+                } else {
+                    format!("{prefix}/{name}")
+                },
+                data,
+            ));
+        }
+        offset = data_offset + size.div_ceil(TAR_BLOCK_SIZE) * TAR_BLOCK_SIZE;
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tar_header(name: &str, size: usize, kind: u8) -> Vec<u8> {
+        let mut header = vec![0; TAR_BLOCK_SIZE];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        let size = format!("{size:011o}");
+        header[124..124 + size.len()].copy_from_slice(size.as_bytes());
+        header[156] = kind;
+        header[257..262].copy_from_slice(b"ustar");
+        header
+    }
+
+    #[test]
+    fn test_tar_entries() {
+        let mut tar = tar_header("foo-1.0-1/", 0, b'5');
+        tar.extend(tar_header("foo-1.0-1/desc", 600, b'0'));
+        tar.extend(vec![0; 2 * TAR_BLOCK_SIZE]);
+        tar.extend(tar_header("PaxHeader/bar", 10, b'x'));
+        tar.extend(vec![0; TAR_BLOCK_SIZE]);
+        tar.extend(tar_header("bar-2:3.4-5/", 0, b'5'));
+        tar.extend(vec![0; 2 * TAR_BLOCK_SIZE]);
+
+        let paths: Vec<_> = tar_entries(&tar)
+            .unwrap()
+            .into_iter()
+            .map(|(p, _)| p)
+            .collect();
+        assert_eq!(paths, ["foo-1.0-1/", "foo-1.0-1/desc", "bar-2:3.4-5/"]);
+        assert_eq!(
+            paths
+                .iter()
+                .filter_map(|p| p.split('/').next().and_then(localdb::package_name))
+                .collect::<HashSet<_>>(),
+            HashSet::from(["foo", "bar"])
+        );
+    }
+}
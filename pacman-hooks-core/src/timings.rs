@@ -0,0 +1,186 @@
+//! Time measurements of analysis phases, packages and files
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Number of slowest packages and files to report
+const SLOWEST_COUNT: usize = 10;
+
+/// Analysis phases
+#[derive(Clone, Copy)]
+pub(crate) enum Phase {
+    PackageEnumeration,
+    FileListing,
+    ElfAnalysis,
+    Systemd,
+    Python,
+    External,
+}
+
+impl Phase {
+    const ALL: [Self; 6] = [
+        Self::PackageEnumeration,
+        Self::FileListing,
+        Self::ElfAnalysis,
+        Self::Systemd,
+        Self::Python,
+        Self::External,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::PackageEnumeration => "package enumeration",
+            Self::FileListing => "file listing",
+            Self::ElfAnalysis => "ELF analysis",
+            Self::Systemd => "systemd",
+            Self::Python => "Python",
+            Self::External => "custom checks",
+        }
+    }
+}
+
+/// How an executable file of a package was handled by the analysis
+#[derive(Clone, Copy)]
+pub(crate) enum FileOutcome {
+    /// Listed in the package files
+    Listed,
+    /// Analyzed for missing dependencies
+    Analyzed,
+    /// Not analyzed, statically linked
+    SkippedStatic,
+    /// Not analyzed, in an excluded directory or for Windows
+    SkippedBlacklisted,
+}
+
+impl FileOutcome {
+    const ALL: [Self; 4] = [
+        Self::Listed,
+        Self::Analyzed,
+        Self::SkippedStatic,
+        Self::SkippedBlacklisted,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Listed => "listed",
+            Self::Analyzed => "analyzed",
+            Self::SkippedStatic => "static",
+            Self::SkippedBlacklisted => "excluded",
+        }
+    }
+}
+
+/// Time measurements, shared between worker threads
+pub struct Timings {
+    start: Instant,
+    phases: Mutex<[Duration; Phase::ALL.len()]>,
+    packages: Mutex<HashMap<Arc<String>, Duration>>,
+    files: Mutex<Vec<(Arc<String>, PathBuf, Duration)>>,
+    /// File counts of each package by outcome, if all packages are reported
+    file_outcomes: Option<Mutex<HashMap<Arc<String>, [usize; FileOutcome::ALL.len()]>>>,
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            phases: Mutex::new([Duration::ZERO; Phase::ALL.len()]),
+            packages: Mutex::new(HashMap::new()),
+            files: Mutex::new(Vec::new()),
+            file_outcomes: None,
+        }
+    }
+}
+
+impl Timings {
+    /// Create measurements also reporting file counts and time of every package, not only the slowest ones
+    #[must_use]
+    pub fn with_package_stats() -> Self {
+        Self {
+            file_outcomes: Some(Mutex::new(HashMap::new())),
+            ..Self::default()
+        }
+    }
+
+    /// Whether file counts are recorded, so that analysis can spend time classifying files for them
+    pub(crate) fn has_package_stats(&self) -> bool {
+        self.file_outcomes.is_some()
+    }
+
+    /// Count an executable file of a package
+    pub(crate) fn record_file_outcome(&self, package: &Arc<String>, outcome: FileOutcome) {
+        if let Some(Ok(mut file_outcomes)) = self.file_outcomes.as_ref().map(Mutex::lock) {
+            file_outcomes.entry(Arc::clone(package)).or_default()[outcome as usize] += 1;
+        }
+    }
+
+    /// Add time spent in a phase, phases running on several threads accumulate time of all threads
+    pub(crate) fn record_phase(&self, phase: Phase, duration: Duration) {
+        if let Ok(mut phases) = self.phases.lock() {
+            phases[phase as usize] += duration;
+        }
+    }
+
+    /// Add time spent for a package, either listing or analyzing its files
+    pub(crate) fn record_package(&self, package: &Arc<String>, duration: Duration) {
+        if let Ok(mut packages) = self.packages.lock() {
+            *packages.entry(Arc::clone(package)).or_default() += duration;
+        }
+    }
+
+    /// Add time spent analyzing a file, also accounted to its package
+    pub(crate) fn record_file(&self, package: &Arc<String>, file: &Path, duration: Duration) {
+        self.record_package(package, duration);
+        if let Ok(mut files) = self.files.lock() {
+            files.push((Arc::clone(package), file.to_owned(), duration));
+        }
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Total: {:.2?}", self.start.elapsed())?;
+        if let Ok(phases) = self.phases.lock() {
+            writeln!(f, "Phases (cumulated over threads):")?;
+            for phase in Phase::ALL {
+                writeln!(f, "  {:<20} {:.2?}", phase.name(), phases[phase as usize])?;
+            }
+        }
+        if let Ok(packages) = self.packages.lock() {
+            let mut packages: Vec<_> = packages.iter().collect();
+            packages.sort_unstable_by(|a, b| b.1.cmp(a.1));
+            if let Some(Ok(file_outcomes)) = self.file_outcomes.as_ref().map(Mutex::lock) {
+                writeln!(
+                    f,
+                    "Packages (executable files {}, time):",
+                    FileOutcome::ALL.map(FileOutcome::name).join(", ")
+                )?;
+                for (package, duration) in packages {
+                    write!(f, "  {package:<40}")?;
+                    for count in file_outcomes.get(package).copied().unwrap_or_default() {
+                        write!(f, " {count:>8}")?;
+                    }
+                    writeln!(f, " {duration:.2?}")?;
+                }
+            } else {
+                writeln!(f, "Slowest packages:")?;
+                for (package, duration) in packages.into_iter().take(SLOWEST_COUNT) {
+                    writeln!(f, "  {package:<40} {duration:.2?}")?;
+                }
+            }
+        }
+        if let Ok(mut files) = self.files.lock() {
+            files.sort_unstable_by_key(|e| std::cmp::Reverse(e.2));
+            writeln!(f, "Slowest files:")?;
+            for (package, file, duration) in files.iter().take(SLOWEST_COUNT) {
+                writeln!(f, "  {} ({package}) {duration:.2?}", file.display())?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+//! Package version comparison, like pacman `vercmp`
+
+use std::cmp::Ordering;
+
+/// Compare version segments, with the same algorithm as RPM and libalpm
+fn rpmvercmp(one: &str, two: &str) -> Ordering {
+    if one == two {
+        return Ordering::Equal;
+    }
+    let (one, two) = (one.as_bytes(), two.as_bytes());
+    let (mut pos1, mut pos2) = (0, 0);
+    while pos1 < one.len() && pos2 < two.len() {
+        let (separators_start1, separators_start2) = (pos1, pos2);
+        while pos1 < one.len() && !one[pos1].is_ascii_alphanumeric() {
+            pos1 += 1;
+        }
+        while pos2 < two.len() && !two[pos2].is_ascii_alphanumeric() {
+            pos2 += 1;
+        }
+        if pos1 >= one.len() || pos2 >= two.len() {
+            break;
+        }
+        // More separators is newer
+        let separators = (pos1 - separators_start1).cmp(&(pos2 - separators_start2));
+        if separators != Ordering::Equal {
+            return separators;
+        }
+
+        let is_num = one[pos1].is_ascii_digit();
+        let in_segment = |c: u8| {
+            if is_num {
+                c.is_ascii_digit()
+            } else {
+                c.is_ascii_alphabetic()
+            }
+        };
+        let (segment_start1, segment_start2) = (pos1, pos2);
+        while pos1 < one.len() && in_segment(one[pos1]) {
+            pos1 += 1;
+        }
+        while pos2 < two.len() && in_segment(two[pos2]) {
+            pos2 += 1;
+        }
+        let mut segment1 = &one[segment_start1..pos1];
+        let mut segment2 = &two[segment_start2..pos2];
+        if segment2.is_empty() {
+            // Numeric segments are newer than alpha ones
+            return if is_num {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+        if is_num {
+            let leading_zeros = |s: &[u8]| s.iter().take_while(|c| **c == b'0').count();
+            segment1 = &segment1[leading_zeros(segment1)..];
+            segment2 = &segment2[leading_zeros(segment2)..];
+            let lengths = segment1.len().cmp(&segment2.len());
+            if lengths != Ordering::Equal {
+                return lengths;
+            }
+        }
+        let ordering = segment1.cmp(segment2);
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    match (one.get(pos1), two.get(pos2)) {
+        (None, None) => Ordering::Equal,
+        // A remaining alpha part is older than nothing, anything else is newer
+        (None, Some(c)) if !c.is_ascii_alphabetic() => Ordering::Less,
+        (Some(c), _) if c.is_ascii_alphabetic() => Ordering::Less,
+        _ => Ordering::Greater,
+    }
+}
+
+/// Split a version into epoch, version and optional release
+fn parse_evr(evr: &str) -> (&str, &str, Option<&str>) {
+    let (epoch, version) = match evr.split_once(':') {
+        Some((epoch, version)) if epoch.bytes().all(|c| c.is_ascii_digit()) => {
+            (if epoch.is_empty() { "0" } else { epoch }, version)
+        }
+        _ => ("0", evr),
+    };
+    match version.rsplit_once('-') {
+        Some((version, release)) => (epoch, version, Some(release)),
+        None => (epoch, version, None),
+    }
+}
+
+/// Compare package versions of the form `[epoch:]version[-release]`
+#[must_use]
+pub fn vercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let (epoch_a, version_a, release_a) = parse_evr(a);
+    let (epoch_b, version_b, release_b) = parse_evr(b);
+    rpmvercmp(epoch_a, epoch_b)
+        .then_with(|| rpmvercmp(version_a, version_b))
+        .then_with(|| match (release_a, release_b) {
+            (Some(release_a), Some(release_b)) => rpmvercmp(release_a, release_b),
+            _ => Ordering::Equal,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vercmp() {
+        for (a, b, expected) in [
+            ("1.0-1", "1.0-1", Ordering::Equal),
+            ("1.0-1", "1.0-2", Ordering::Less),
+            ("1.0", "1.0-2", Ordering::Equal),
+            ("1.10", "1.9", Ordering::Greater),
+            ("1.0a", "1.0", Ordering::Less),
+            ("1.0.1", "1.0", Ordering::Greater),
+            ("1.0", "1.0.a", Ordering::Less),
+            ("1.001", "1.1", Ordering::Equal),
+            ("1.0alpha", "1.0beta", Ordering::Less),
+            ("1:1.0-1", "2.0-1", Ordering::Greater),
+            ("1.0..1", "1.0.1", Ordering::Greater),
+            ("1.0.r2.g1234-1", "1.0.1-1", Ordering::Less),
+        ] {
+            assert_eq!(vercmp(a, b), expected, "{a} vs {b}");
+            assert_eq!(vercmp(b, a), expected.reverse(), "{b} vs {a}");
+        }
+    }
+}
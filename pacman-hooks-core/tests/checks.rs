@@ -0,0 +1,187 @@
+//! End to end tests of the built-in checks, on a fake system root
+
+#![cfg(test)]
+#![expect(
+    unused_crate_dependencies,
+    reason = "tests do not use all library dependencies"
+)]
+
+mod fixture;
+
+use std::sync::Arc;
+
+use fixture::{elf, elf_rpath, FakeRoot};
+use pacman_hooks_core::{
+    backend::{MemoryBackend, PackageBackend},
+    checks::{builtin_checks, run_checks, select_checks},
+    elf::SonameResolver,
+    localdb::LocalDb,
+};
+
+/// Run built-in checks selected by `toggles` on `packages`, and get the sorted findings descriptions
+fn check(
+    backend: impl PackageBackend + 'static,
+    toggles: &[(&str, bool)],
+    packages: &[&str],
+) -> Vec<String> {
+    let toggles: Vec<_> = toggles.iter().map(|(n, e)| ((*n).to_owned(), *e)).collect();
+    let checks = select_checks(builtin_checks(), &toggles).unwrap();
+    let packages = packages.iter().map(|p| (*p).to_owned()).collect();
+    let backend: Arc<dyn PackageBackend> = Arc::new(backend);
+    let mut findings: Vec<String> = run_checks(&backend, checks, Some(packages), false, None, None)
+        .unwrap()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    findings.sort_unstable();
+    findings
+}
+
+fn setup_elf_package(root: &FakeRoot) {
+    root.write(
+        "/usr/bin/app",
+        &elf(&["libfound.so.1", "libmissing.so.1", "libffmpeg.so"], None),
+        0o755,
+    );
+    root.write("/usr/lib/libfound.so.1", &elf(&[], None), 0o755);
+    root.write(
+        "/usr/lib/app/bin/tool",
+        &elf(&["libprivate.so"], Some("$ORIGIN/../lib")),
+        0o755,
+    );
+    root.write("/usr/lib/app/lib/libprivate.so", &elf(&[], None), 0o644);
+    root.write("/usr/share/app/data", b"not an ELF file", 0o755);
+    // The DT_RPATH of the executable also applies to the dependencies of its libraries
+    root.write(
+        "/opt/app/bin/app",
+        &elf_rpath(&["liba.so"], "$ORIGIN/../lib"),
+        0o755,
+    );
+    root.write("/opt/app/lib/liba.so", &elf(&["libb.so"], None), 0o644);
+    root.write("/opt/app/lib/libb.so", &elf(&[], None), 0o644);
+    root.add_package(
+        "app",
+        "1.0-1",
+        &[
+            "/usr/bin/app",
+            "/usr/lib/app/bin/tool",
+            "/usr/lib/app/lib/libprivate.so",
+            "/usr/share/app/data",
+            "/opt/app/bin/app",
+            "/opt/app/lib/liba.so",
+            "/opt/app/lib/libb.so",
+        ],
+        &[],
+    );
+    root.add_package("found", "1.0-1", &["/usr/lib/libfound.so.1"], &[]);
+}
+
+fn setup_python_packages(root: &FakeRoot) {
+    root.mkdir("/usr/lib/python3.12/site-packages");
+    root.mkdir("/usr/lib/python3.11/site-packages");
+    root.write(
+        "/usr/lib/python3.11/site-packages/old.py",
+        &vec![0; 1 << 19],
+        0o644,
+    );
+    root.add_package("python", "3.12.4-1", &["/usr/lib/python3.12/"], &[]);
+    root.add_package(
+        "python-old",
+        "1.0-1",
+        &[
+            "/usr/lib/python3.11/",
+            "/usr/lib/python3.11/site-packages/",
+            "/usr/lib/python3.11/site-packages/old.py",
+        ],
+        &[],
+    );
+}
+
+fn setup_service_links(root: &FakeRoot) {
+    root.write("/usr/lib/systemd/system/present.service", b"", 0o644);
+    root.symlink(
+        "/etc/systemd/system/multi-user.target.wants/present.service",
+        "/usr/lib/systemd/system/present.service",
+    );
+    root.symlink(
+        "/etc/systemd/system/multi-user.target.wants/removed.service",
+        "/usr/lib/systemd/system/removed.service",
+    );
+    // Enabled by its package, whose unit was deleted
+    root.symlink(
+        "/etc/systemd/system/multi-user.target.wants/vendor.service",
+        "/usr/lib/systemd/system/vendor.service",
+    );
+    root.add_package(
+        "vendor",
+        "1.0-1",
+        &["/etc/systemd/system/multi-user.target.wants/vendor.service"],
+        &[],
+    );
+}
+
+fn setup_pacnew_files(root: &FakeRoot) {
+    root.write("/etc/app.conf", b"modified", 0o644);
+    root.write("/etc/app.conf.pacnew", b"new", 0o644);
+    root.write("/etc/other.conf", b"unmodified", 0o644);
+    root.add_package("conf", "1.0-1", &[], &["/etc/app.conf", "/etc/other.conf"]);
+}
+
+#[test]
+fn test_checks() {
+    // The root is set for the whole process, so all scenarios share it
+    let root = FakeRoot::new();
+    setup_elf_package(&root);
+    setup_python_packages(&root);
+    setup_service_links(&root);
+    setup_pacnew_files(&root);
+
+    let broken_link = root.path("/etc/systemd/system/multi-user.target.wants/removed.service");
+    let vendor_link = root.path("/etc/systemd/system/multi-user.target.wants/vendor.service");
+    assert_eq!(
+        check(
+        LocalDb::open().unwrap(),
+        &[("pacnew-files", true)],
+        &["app"],
+    ),
+        [
+            "Configuration file /etc/app.conf has a pending /etc/app.conf.pacnew update".to_owned(),
+            r#"File /usr/bin/app from package "app" is missing dependency libmissing.so.1"#.to_owned(),
+            r#"Package "python-old" has 1 file(s) (0.5 MiB) ignored by the current Python interpreter in "/usr/lib/python3.11", remove it, it has no other files and no package depends on it"#.to_owned(),
+            format!(
+                "Systemd enabled service has broken link in {}, created by systemctl enable, remove it with systemctl disable",
+                broken_link.display()
+            ),
+            format!(
+                r#"Systemd enabled service has broken link in {}, owned by package "vendor", reinstall it"#,
+                vendor_link.display()
+            ),
+        ]
+    );
+
+    // Libraries bundled by a package are not reported anyway, so the resolver is checked directly
+    assert!(SonameResolver::default()
+        .missing_dependencies(&root.path("/opt/app/bin/app"))
+        .is_empty());
+
+    // The mock backend gets the same results for the same package files
+    let backend = MemoryBackend::default().with_package(
+        "app",
+        "1.0-1",
+        true,
+        &[
+            "/usr/bin/app",
+            "/usr/lib/app/bin/tool",
+            "/usr/share/app/data",
+        ],
+    );
+    let findings = check(
+        backend,
+        &[("python-packages", false), ("service-links", false)],
+        &["app"],
+    );
+    assert_eq!(
+        findings,
+        [r#"File /usr/bin/app from package "app" is missing dependency libmissing.so.1"#]
+    );
+}
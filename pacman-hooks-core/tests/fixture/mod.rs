@@ -0,0 +1,170 @@
+//! Fake system root, with a pacman local database, and ELF files with controlled dependencies
+
+use std::{
+    fs,
+    os::unix::fs::{symlink, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+use pacman_hooks_core::root;
+use tempfile::TempDir;
+
+/// Temporary directory set up as the root of the checked system
+pub(crate) struct FakeRoot {
+    dir: TempDir,
+}
+
+impl FakeRoot {
+    /// Create the root, and check it instead of the running system, for the whole test process
+    pub(crate) fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("var/lib/pacman/local")).unwrap();
+        root::set(dir.path().to_owned(), None).unwrap();
+        Self { dir }
+    }
+
+    /// Get the path below the root of an absolute path of the checked system
+    pub(crate) fn path(&self, path: &str) -> PathBuf {
+        self.dir.path().join(path.trim_start_matches('/'))
+    }
+
+    /// Write a file, creating its parent directories
+    pub(crate) fn write(&self, path: &str, content: &[u8], mode: u32) {
+        let path = self.path(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    /// Create a directory and its parents
+    pub(crate) fn mkdir(&self, path: &str) {
+        fs::create_dir_all(self.path(path)).unwrap();
+    }
+
+    /// Create a symlink, with a target that is interpreted in the checked system
+    pub(crate) fn symlink(&self, link: &str, target: &str) {
+        let link = self.path(link);
+        fs::create_dir_all(link.parent().unwrap()).unwrap();
+        symlink(target, link).unwrap();
+    }
+
+    /// Add a package entry to the local database, files ending with a slash being directories
+    pub(crate) fn add_package(&self, name: &str, version: &str, files: &[&str], backup: &[&str]) {
+        let entry = self.path(&format!("/var/lib/pacman/local/{name}-{version}"));
+        fs::create_dir_all(&entry).unwrap();
+        let mut desc = format!("%NAME%\n{name}\n\n%VERSION%\n{version}\n\n");
+        if !backup.is_empty() {
+            desc.push_str("%BACKUP%\n");
+            for file in backup {
+                desc.push_str(rel(file));
+                desc.push_str("\td41d8cd98f00b204e9800998ecf8427e\n");
+            }
+            desc.push('\n');
+        }
+        fs::write(entry.join("desc"), desc).unwrap();
+        let mut list = String::from("%FILES%\n");
+        for file in files {
+            list.push_str(rel(file));
+            list.push('\n');
+        }
+        list.push('\n');
+        fs::write(entry.join("files"), list).unwrap();
+    }
+}
+
+/// Path relative to the root, as recorded by the local database
+fn rel(path: &str) -> &str {
+    Path::new(path)
+        .strip_prefix("/")
+        .ok()
+        .and_then(Path::to_str)
+        .unwrap_or(path)
+}
+
+/// `DT_RPATH` dynamic entry tag
+const DT_RPATH: u64 = 15;
+
+/// `DT_RUNPATH` dynamic entry tag
+const DT_RUNPATH: u64 = 29;
+
+/// Build a minimal x86-64 ELF shared object, with only the sections needed for dynamic dependency resolution
+pub(crate) fn elf(needed: &[&str], runpath: Option<&str>) -> Vec<u8> {
+    elf_with_search_path(needed, runpath.map(|r| (DT_RUNPATH, r)))
+}
+
+/// Build a minimal x86-64 ELF shared object like [`elf`], with a `DT_RPATH` instead of a `DT_RUNPATH`
+pub(crate) fn elf_rpath(needed: &[&str], rpath: &str) -> Vec<u8> {
+    elf_with_search_path(needed, Some((DT_RPATH, rpath)))
+}
+
+fn elf_with_search_path(needed: &[&str], search_path: Option<(u64, &str)>) -> Vec<u8> {
+    const EHDR_SIZE: usize = 64;
+    const SHDR_SIZE: usize = 64;
+    const DT_NULL: u64 = 0;
+    const DT_NEEDED: u64 = 1;
+    const SHT_STRTAB: u32 = 3;
+    const SHT_DYNAMIC: u32 = 6;
+
+    let mut dynstr = vec![0];
+    let mut dynamic = Vec::new();
+    let entries = needed.iter().map(|n| (DT_NEEDED, *n)).chain(search_path);
+    for (tag, value) in entries {
+        dynamic.push((tag, dynstr.len() as u64));
+        dynstr.extend_from_slice(value.as_bytes());
+        dynstr.push(0);
+    }
+    dynamic.push((DT_NULL, 0));
+    let shstrtab = b"\0.dynstr\0.dynamic\0.shstrtab\0";
+
+    let align = |n: usize| n.next_multiple_of(8);
+    let dynstr_offset = EHDR_SIZE;
+    let dynamic_offset = align(dynstr_offset + dynstr.len());
+    let dynamic_size = dynamic.len() * 16;
+    let shstrtab_offset = dynamic_offset + dynamic_size;
+    let shdrs_offset = align(shstrtab_offset + shstrtab.len());
+
+    let mut data = Vec::new();
+    // ELF header: 64-bit, little endian, shared object for x86-64
+    data.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    data.resize(16, 0);
+    data.extend_from_slice(&3_u16.to_le_bytes());
+    data.extend_from_slice(&62_u16.to_le_bytes());
+    data.extend_from_slice(&1_u32.to_le_bytes());
+    data.extend_from_slice(&0_u64.to_le_bytes());
+    data.extend_from_slice(&0_u64.to_le_bytes());
+    data.extend_from_slice(&(shdrs_offset as u64).to_le_bytes());
+    data.extend_from_slice(&0_u32.to_le_bytes());
+    for half in [EHDR_SIZE, 56, 0, SHDR_SIZE, 4, 3] {
+        data.extend_from_slice(&u16::try_from(half).unwrap().to_le_bytes());
+    }
+
+    data.extend_from_slice(&dynstr);
+    data.resize(dynamic_offset, 0);
+    for (tag, value) in &dynamic {
+        data.extend_from_slice(&tag.to_le_bytes());
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(shstrtab);
+    data.resize(shdrs_offset, 0);
+
+    // Section headers: null, .dynstr, .dynamic linked to .dynstr, .shstrtab
+    let sections = [
+        (0, 0, 0, 0, 0, 0_u64),
+        (1, SHT_STRTAB, dynstr_offset, dynstr.len(), 0, 0),
+        (9, SHT_DYNAMIC, dynamic_offset, dynamic_size, 1, 16),
+        (18, SHT_STRTAB, shstrtab_offset, shstrtab.len(), 0, 0),
+    ];
+    for (name, kind, offset, size, link, entsize) in sections {
+        data.extend_from_slice(&u32::try_from(name).unwrap().to_le_bytes());
+        data.extend_from_slice(&kind.to_le_bytes());
+        data.extend_from_slice(&0_u64.to_le_bytes());
+        data.extend_from_slice(&0_u64.to_le_bytes());
+        data.extend_from_slice(&(offset as u64).to_le_bytes());
+        data.extend_from_slice(&(size as u64).to_le_bytes());
+        data.extend_from_slice(&u32::try_from(link).unwrap().to_le_bytes());
+        data.extend_from_slice(&0_u32.to_le_bytes());
+        data.extend_from_slice(&8_u64.to_le_bytes());
+        data.extend_from_slice(&entsize.to_le_bytes());
+    }
+    data
+}
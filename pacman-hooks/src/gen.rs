@@ -0,0 +1,283 @@
+//! Generation of files to integrate with the system
+
+use std::{fmt::Write as _, fs, path::Path};
+
+use pacman_hooks_core::{checks::Check, finding::CheckKind};
+
+/// Name of generated systemd units
+const UNIT_NAME: &str = "check-broken-packages";
+
+const SERVICE_UNIT: &str = "[Unit]
+Description=Check for broken packages
+
+[Service]
+Type=oneshot
+ExecStart=/usr/bin/pacman-hooks check all --service
+Nice=19
+IOSchedulingClass=idle
+";
+
+const TIMER_UNIT: &str = "[Unit]
+Description=Check for broken packages periodically
+
+[Timer]
+OnCalendar=daily
+RandomizedDelaySec=1h
+Persistent=true
+
+[Install]
+WantedBy=timers.target
+";
+
+/// Write systemd service and timer units to periodically run in service mode
+pub(crate) fn write_systemd_units(dir: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)?;
+    for (extension, content) in [("service", SERVICE_UNIT), ("timer", TIMER_UNIT)] {
+        let path = dir.join(format!("{UNIT_NAME}.{extension}"));
+        fs::write(&path, content)?;
+        log::info!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+/// Pre transaction hook saving the files the unowned-files check compares with after the transaction
+const SNAPSHOT_HOOK: &str = "[Trigger]
+Operation = Install
+Operation = Upgrade
+Operation = Remove
+Type = Package
+Target = *
+
+[Action]
+Description = Saving files to detect unowned ones...
+Exec = /usr/bin/pacman-hooks snapshot
+When = PreTransaction
+";
+
+/// Pacman hook trigger of a check
+struct HookSpec {
+    /// Hook action description
+    description: String,
+    /// Transaction operations triggering the hook
+    operations: &'static [&'static str],
+    /// Packages triggering the hook
+    target: &'static str,
+    /// Whether the hook must run after the hooks of other packages, which pacman runs in file name order
+    late: bool,
+}
+
+/// Get the hook trigger of a check, only running it after transactions that can affect it
+#[expect(clippy::too_many_lines, reason = "one arm per check")]
+fn hook_spec(check: &str) -> HookSpec {
+    let (description, operations, target) = match check {
+        "missing-dependencies" => (
+            "Checking for packages with missing dependencies...",
+            &["Install", "Upgrade"][..],
+            "*",
+        ),
+        "python-packages" => (
+            "Checking for Python packages built for an older Python version...",
+            &["Upgrade"][..],
+            "python",
+        ),
+        "service-links" => (
+            "Checking for broken systemd service links...",
+            &["Upgrade", "Remove"][..],
+            "*",
+        ),
+        "login-units" => (
+            "Checking for login units with missing programs...",
+            &["Upgrade", "Remove"][..],
+            "*",
+        ),
+        "duplicate-libraries" => (
+            "Checking for libraries shadowed by another package...",
+            &["Install", "Upgrade", "Remove"][..],
+            "*",
+        ),
+        "library-permissions" => (
+            "Checking for libraries that users can not load...",
+            &["Install", "Upgrade"][..],
+            "*",
+        ),
+        "library-links" => (
+            "Checking for stale library links...",
+            &["Install", "Upgrade", "Remove"][..],
+            "*",
+        ),
+        "introspection-typelibs" => (
+            "Checking for introspection typelibs with missing libraries...",
+            &["Install", "Upgrade", "Remove"][..],
+            "*",
+        ),
+        "perl-modules" => (
+            "Checking for Perl modules linked against a removed Perl version...",
+            &["Upgrade"][..],
+            "perl",
+        ),
+        "symbol-versions" => (
+            "Checking for executables needing symbol versions their libraries lack...",
+            &["Install", "Upgrade"][..],
+            "*",
+        ),
+        "scriptlet-failures" => (
+            "Checking for failed scriptlets...",
+            &["Install", "Upgrade"][..],
+            "*",
+        ),
+        "unowned-files" => (
+            "Checking for new files owned by no package...",
+            &["Install", "Upgrade", "Remove"][..],
+            "*",
+        ),
+        "pacnew-files" => (
+            "Checking for pending pacnew files...",
+            &["Install", "Upgrade"][..],
+            "*",
+        ),
+        "file-checksums" => (
+            "Checking installed files against package checksums...",
+            &["Install", "Upgrade"][..],
+            "*",
+        ),
+        "file-conflicts" => (
+            "Checking for files owned by several packages...",
+            &["Install", "Upgrade"][..],
+            "*",
+        ),
+        "convention-links" => (
+            "Checking for broken convention symlinks...",
+            &["Upgrade", "Remove"][..],
+            "*",
+        ),
+        "symlink-chains" => (
+            "Checking for package symlinks in loops or pointing into user directories...",
+            &["Install", "Upgrade"][..],
+            "*",
+        ),
+        "boot-mounts" => (
+            "Checking for boot entries with missing devices or tools...",
+            &["Upgrade", "Remove"][..],
+            "*",
+        ),
+        "kernel-cmdline" => (
+            "Checking for boot configurations referencing removed files...",
+            &["Upgrade", "Remove"][..],
+            "*",
+        ),
+        // After the hooks building images, and the zz-sbctl.hook signing them
+        "secure-boot" => {
+            return HookSpec {
+                description: "Checking for boot images not signed for Secure Boot...".to_owned(),
+                operations: &["Install", "Upgrade"],
+                target: "*",
+                late: true,
+            }
+        }
+        "module-firmware" => (
+            "Checking for missing firmware of loaded kernel modules...",
+            &["Upgrade", "Remove"][..],
+            "*",
+        ),
+        "aur-packages" => (
+            "Checking for foreign packages with problems in the AUR...",
+            &["Install", "Upgrade"][..],
+            "*",
+        ),
+        "stale-builds" => (
+            "Checking for foreign packages built long before their dependencies...",
+            &["Upgrade"][..],
+            "*",
+        ),
+        "legacy-runtimes" => (
+            "Checking for packages needing legacy runtimes...",
+            &["Install", "Upgrade", "Remove"][..],
+            "*",
+        ),
+        "security-advisories" => (
+            "Checking for packages affected by security advisories...",
+            &["Install", "Upgrade"][..],
+            "*",
+        ),
+        _ => {
+            return HookSpec {
+                description: format!("Running custom check {check}..."),
+                operations: &["Install", "Upgrade", "Remove"],
+                target: "*",
+                late: false,
+            }
+        }
+    };
+    HookSpec {
+        description: description.to_owned(),
+        operations,
+        target,
+        late: false,
+    }
+}
+
+/// Build the pacman hook file content running a check
+fn hook(check: &str) -> String {
+    let spec = hook_spec(check);
+    let mut content = String::from("[Trigger]\n");
+    for operation in spec.operations {
+        let _ = writeln!(content, "Operation = {operation}");
+    }
+    let _ = write!(
+        content,
+        "Type = Package
+Target = {}
+
+[Action]
+Description = {}
+Exec = /usr/bin/pacman-hooks check {check} --no-wait
+When = PostTransaction
+",
+        spec.target, spec.description
+    );
+    content
+}
+
+/// Write a pacman hook for each check
+pub(crate) fn write_hooks(dir: &Path, checks: &[Box<dyn Check>]) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)?;
+    for check in checks {
+        let prefix = if hook_spec(check.name()).late {
+            "zzz-"
+        } else {
+            ""
+        };
+        let path = dir.join(format!("{prefix}pacman-hooks-{}.hook", check.name()));
+        fs::write(&path, hook(check.name()))?;
+        log::info!("Wrote {}", path.display());
+        if check.name() == CheckKind::UnownedFiles.name() {
+            let snapshot_path = dir.join("pacman-hooks-snapshot.hook");
+            fs::write(&snapshot_path, SNAPSHOT_HOOK)?;
+            log::info!("Wrote {}", snapshot_path.display());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hook() {
+        assert_eq!(
+            hook("python-packages"),
+            "[Trigger]
+Operation = Upgrade
+Type = Package
+Target = python
+
+[Action]
+Description = Checking for Python packages built for an older Python version...
+Exec = /usr/bin/pacman-hooks check python-packages --no-wait
+When = PostTransaction
+"
+        );
+        assert!(hook("foo").contains("Operation = Remove\n"));
+    }
+}
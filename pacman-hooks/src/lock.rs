@@ -0,0 +1,73 @@
+//! Lock to prevent concurrent executions
+
+use std::{
+    env,
+    fs::{File, OpenOptions, TryLockError},
+    io,
+    os::unix::fs::OpenOptionsExt as _,
+    path::{Path, PathBuf},
+};
+
+/// Directory of the lock file of root, that only root can write to, so that other users can not create the file
+const ROOT_LOCK_DIR: &str = "/run";
+
+/// Lock file name
+const LOCK_FILENAME: &str = "pacman-hooks.lock";
+
+/// Directory of the lock file of the current user, `None` if it has none
+fn lock_dir() -> Option<PathBuf> {
+    // SAFETY: no arguments, always succeeds
+    if unsafe { libc::geteuid() } == 0 {
+        Some(PathBuf::from(ROOT_LOCK_DIR))
+    } else {
+        env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from)
+    }
+}
+
+/// Open a lock file, creating it if it does not exist, only readable by its owner so that other users can not lock it
+fn open_lock_file(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .mode(0o600)
+        .open(path)
+}
+
+/// Exclusive run lock, released when dropped
+pub(crate) struct RunLock {
+    /// Locked file, `None` if no lock file could be opened
+    _file: Option<File>,
+}
+
+impl RunLock {
+    /// Take the run lock, waiting for other instances to release it if `wait` is set.
+    ///
+    /// The lock file of root is only used by root, and unprivileged users have their own, in their runtime directory,
+    /// so that they can not hold up runs of root, like the ones of pacman hooks. Without a lock file, the run continues
+    /// without lock.
+    ///
+    /// Returns `None` if another instance holds the lock and `wait` is not set.
+    pub(crate) fn acquire(wait: bool) -> anyhow::Result<Option<Self>> {
+        let file = lock_dir().and_then(|dir| {
+            let path = dir.join(LOCK_FILENAME);
+            open_lock_file(&path)
+                .inspect_err(|err| log::debug!("Failed to open lock file {path:?}: {err}"))
+                .ok()
+        });
+        let Some(file) = file else {
+            log::warn!("Unable to open a lock file, running without preventing concurrent runs");
+            return Ok(Some(Self { _file: None }));
+        };
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) if wait => {
+                log::warn!("Another instance is running, waiting for it to finish");
+                file.lock()?;
+            }
+            Err(TryLockError::WouldBlock) => return Ok(None),
+            Err(TryLockError::Error(err)) => return Err(err.into()),
+        }
+        Ok(Some(Self { _file: Some(file) }))
+    }
+}
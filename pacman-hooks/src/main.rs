@@ -0,0 +1,1391 @@
+//! Pacman hooks, checking for broken arch packages and system state
+
+use std::{
+    collections::HashSet,
+    env,
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, ExitCode},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use pacman_hooks_core::{
+    backend,
+    baseline::Baseline,
+    cancel,
+    checks::{self, plan_checks, run_checks, Check, Input},
+    collect,
+    config::{Config, DEFAULT_USER},
+    elf,
+    error::{Classify as _, ErrorKind},
+    filesdb::FilesDb,
+    finding::{CheckKind, Finding, Severity},
+    health::{Health, HealthHistory},
+    kb::KnowledgeBase,
+    localdb,
+    logging::{LogStyle, Logger},
+    network,
+    output::{self, print_findings, DEFAULT_MAX_FINDINGS},
+    overrides, privileges, rebuild,
+    reports::Report,
+    root, schema,
+    selftest::{self, Outcome},
+    suppressions, syncdb,
+    timings::Timings,
+};
+
+mod gen;
+mod lock;
+mod pkgfile;
+mod watch;
+
+use lock::RunLock;
+use watch::LocalDbWatcher;
+
+/// Directory of systemd-nspawn machine roots
+const MACHINES_DIR: &str = "/var/lib/machines";
+
+/// Short check names usable as subcommands, and the check they select
+const CHECK_ALIASES: [(&str, &str); 25] = [
+    ("broken", "missing-dependencies"),
+    ("python", "python-packages"),
+    ("systemd", "service-links"),
+    ("login", "login-units"),
+    ("duplicates", "duplicate-libraries"),
+    ("permissions", "library-permissions"),
+    ("ldconfig", "library-links"),
+    ("typelibs", "introspection-typelibs"),
+    ("perl", "perl-modules"),
+    ("symbols", "symbol-versions"),
+    ("scriptlets", "scriptlet-failures"),
+    ("unowned", "unowned-files"),
+    ("pacnew", "pacnew-files"),
+    ("checksums", "file-checksums"),
+    ("conflicts", "file-conflicts"),
+    ("links", "convention-links"),
+    ("symlinks", "symlink-chains"),
+    ("mounts", "boot-mounts"),
+    ("cmdline", "kernel-cmdline"),
+    ("secureboot", "secure-boot"),
+    ("firmware", "module-firmware"),
+    ("aur", "aur-packages"),
+    ("stale", "stale-builds"),
+    ("legacy", "legacy-runtimes"),
+    ("security", "security-advisories"),
+];
+
+/// Get the check name of a short check name, or other names unchanged
+fn check_name(name: String) -> String {
+    CHECK_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, check)| (*check).to_owned())
+}
+
+/// Checks selected on the command line
+enum Selection {
+    /// Checks enabled by the configuration and toggles
+    All,
+    /// A single check by name, run even if not enabled
+    Only(String),
+}
+
+/// Subcommand
+enum Subcommand {
+    /// Run checks
+    Check(Selection),
+    /// Check files of a package archive
+    CheckPkgFile(PathBuf),
+    /// Check files, installed by a package or not
+    CheckFiles(Vec<PathBuf>),
+    /// Generate systemd service and timer units in a directory
+    GenSystemd(PathBuf),
+    /// Generate pacman hooks for the enabled checks in a directory
+    GenHooks(PathBuf),
+    /// Download the latest known false positives
+    UpdateKb,
+    /// Print the known false positives and baseline as JSON
+    ExportSuppressions,
+    /// Add known false positives and baseline findings exported as JSON, from a file or stdin if `-`
+    ImportSuppressions(String),
+    /// Warn about foreign packages that upgrading packages will break
+    Predict,
+    /// Save the files of the directories watched for unowned files, before a transaction
+    Snapshot,
+    /// Print the JSON Schema of findings
+    Schema,
+    /// Print the documentation of a check
+    Describe(String),
+    /// Check that the tools and databases checks need are available
+    SelfTest,
+    /// List reports of previous full scans, or print one of them by number, 1 being the most recent
+    History(Option<usize>),
+}
+
+impl Subcommand {
+    /// Parse the subcommand and its positional arguments
+    fn parse(args: &mut impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        Ok(match args.next().as_deref() {
+            Some("check") => match args.next() {
+                Some(what) if what == "all" => Self::Check(Selection::All),
+                Some(what) if what == "pkgfile" => Self::CheckPkgFile(
+                    args.next()
+                        .map(PathBuf::from)
+                        .ok_or_else(|| anyhow::anyhow!("Missing package archive"))?,
+                ),
+                // Files are the positional arguments
+                Some(what) if what == "files" => Self::CheckFiles(Vec::new()),
+                Some(what) if !what.starts_with("--") => {
+                    Self::Check(Selection::Only(check_name(what)))
+                }
+                _ => anyhow::bail!("Missing check name, or all"),
+            },
+            Some("gen") => match args.next().as_deref() {
+                Some(what @ ("systemd" | "hooks")) => {
+                    let dir = args
+                        .next()
+                        .map_or_else(|| PathBuf::from("."), PathBuf::from);
+                    if what == "systemd" {
+                        Self::GenSystemd(dir)
+                    } else {
+                        Self::GenHooks(dir)
+                    }
+                }
+                Some(what) => anyhow::bail!("Unknown generation target {what:?}"),
+                None => anyhow::bail!("Missing generation target"),
+            },
+            Some("update-kb") => Self::UpdateKb,
+            Some("suppressions") => match args.next().as_deref() {
+                Some("export") => Self::ExportSuppressions,
+                Some("import") => Self::ImportSuppressions(
+                    args.next()
+                        .ok_or_else(|| anyhow::anyhow!("Missing suppressions file, or -"))?,
+                ),
+                Some(what) => anyhow::bail!("Unknown suppressions action {what:?}"),
+                None => anyhow::bail!("Missing suppressions action, export or import"),
+            },
+            Some("predict") => Self::Predict,
+            Some("snapshot") => Self::Snapshot,
+            Some("schema") => Self::Schema,
+            Some("describe") => Self::Describe(check_name(
+                args.next()
+                    .ok_or_else(|| anyhow::anyhow!("Missing check name"))?,
+            )),
+            Some("self-test") => Self::SelfTest,
+            Some("history") => Self::History(
+                args.next()
+                    .map(|n| {
+                        n.parse()
+                            .ok()
+                            .filter(|n| *n > 0)
+                            .ok_or_else(|| anyhow::anyhow!("Invalid report number {n:?}"))
+                    })
+                    .transpose()?,
+            ),
+            Some(command) => anyhow::bail!("Unknown subcommand {command:?}"),
+            None => anyhow::bail!("Missing subcommand"),
+        })
+    }
+}
+
+/// How checks are run
+#[derive(Eq, PartialEq)]
+enum Mode {
+    /// Single run, typically from the pacman hook
+    Default,
+    /// Watch the local database, and check packages as they are installed or upgraded
+    Watch,
+    /// Periodic non interactive run, only reporting findings that are new since the previous run
+    Service,
+}
+
+/// Transaction operation that ran the hook
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Operation {
+    Install,
+    Upgrade,
+    Remove,
+}
+
+impl Operation {
+    /// Parse an operation name, lowercase like in pacman hook triggers
+    fn parse(name: &str) -> anyhow::Result<Self> {
+        match name {
+            "install" => Ok(Self::Install),
+            "upgrade" => Ok(Self::Upgrade),
+            "remove" => Ok(Self::Remove),
+            _ => anyhow::bail!("Unknown operation {name:?}"),
+        }
+    }
+}
+
+/// How the exit code reports findings
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum ExitCodes {
+    /// Only new findings in service mode, with [`EXIT_NEW_FINDINGS`]
+    Default,
+    /// A bit set for each category of problems found
+    Categories,
+}
+
+/// Whether features needing network access run
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Network {
+    /// If the system has a default route
+    Auto,
+    /// Never
+    Offline,
+}
+
+/// How missing dependencies found by reading ELF files are confirmed
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Resolver {
+    /// With ldd, for the running system
+    Ldd,
+    /// They are not, ldd is never run
+    Native,
+}
+
+/// Analysis measurements printed after findings
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord)]
+enum Measurements {
+    /// None
+    Off,
+    /// Time spent in each analysis phase, and slowest packages and files
+    Timings,
+    /// Timings, with file counts and time of every package
+    PackageStats,
+}
+
+/// Command line arguments
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "independent command line flags"
+)]
+struct Args {
+    command: Subcommand,
+    /// Packages to check, foreign packages if empty
+    packages: Vec<String>,
+    /// File to read more packages to check from, `-` for stdin
+    packages_from: Option<String>,
+    measurements: Measurements,
+    /// Exit if another instance is running instead of waiting for it
+    no_wait: bool,
+    /// Describe what would be checked, without running checks
+    dry_run: bool,
+    exit_codes: ExitCodes,
+    network: Network,
+    /// Resolver set on the command line, `None` for the default one, confirming with ldd
+    resolver: Option<Resolver>,
+    /// Stop checks when findings of at least this severity are found
+    exit_early: Option<Severity>,
+    /// Transaction operation that ran the hook, if known
+    operation: Option<Operation>,
+    mode: Mode,
+    /// Checks to enable (`true`) or disable, in order
+    check_toggles: Vec<(String, bool)>,
+    /// Root directory of the system to check
+    root: Option<PathBuf>,
+    /// Pacman database directory of the system to check
+    db_path: Option<PathBuf>,
+    /// Staged package directory tree to check instead of installed packages
+    prefix_dir: Option<PathBuf>,
+    /// File to write all log records to, including debug ones
+    debug_log: Option<PathBuf>,
+    /// Check containers instead of this system
+    containers: bool,
+    /// Maximum number of printed findings, `None` to print all of them
+    max_findings: Option<usize>,
+    /// Package origins of the printed findings, all if empty
+    origins: Vec<String>,
+    /// Check the packages impacted by changes of the given packages, instead of the given packages
+    impact_of: bool,
+}
+
+impl Args {
+    #[expect(clippy::too_many_lines, reason = "one arm per option")]
+    fn parse<I>(args: I) -> anyhow::Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut args = args.into_iter();
+        let mut parsed = Self {
+            command: Subcommand::parse(&mut args)?,
+            packages: Vec::new(),
+            packages_from: None,
+            measurements: Measurements::Off,
+            no_wait: false,
+            dry_run: false,
+            exit_codes: ExitCodes::Default,
+            network: Network::Auto,
+            resolver: None,
+            exit_early: None,
+            operation: None,
+            mode: Mode::Default,
+            check_toggles: Vec::new(),
+            root: None,
+            db_path: None,
+            prefix_dir: None,
+            debug_log: None,
+            containers: false,
+            max_findings: Some(DEFAULT_MAX_FINDINGS),
+            origins: Vec::new(),
+            impact_of: false,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--enable" | "--disable" => {
+                    let check = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("Missing check name for {arg}"))?;
+                    parsed.check_toggles.push((check, arg == "--enable"));
+                }
+                "--debug-log" | "--packages-from" => {
+                    let file = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("Missing file for {arg}"))?;
+                    if arg == "--debug-log" {
+                        parsed.debug_log = Some(PathBuf::from(file));
+                    } else {
+                        parsed.packages_from = Some(file);
+                    }
+                }
+                "--root" | "--dbpath" | "--prefix-dir" => {
+                    let dir = args
+                        .next()
+                        .map(PathBuf::from)
+                        .ok_or_else(|| anyhow::anyhow!("Missing directory for {arg}"))?;
+                    match arg.as_str() {
+                        "--root" => parsed.root = Some(dir),
+                        "--dbpath" => parsed.db_path = Some(dir),
+                        _ => parsed.prefix_dir = Some(dir),
+                    }
+                }
+                "--operation" => {
+                    let operation = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("Missing operation for {arg}"))?;
+                    parsed.operation = Some(Operation::parse(&operation)?);
+                }
+                "--resolver" => {
+                    parsed.resolver = match args.next().as_deref() {
+                        Some("ldd") => Some(Resolver::Ldd),
+                        Some("native") => Some(Resolver::Native),
+                        Some(resolver) => anyhow::bail!("Unknown resolver {resolver:?}"),
+                        None => anyhow::bail!("Missing resolver for {arg}"),
+                    };
+                }
+                "--exit-early" => {
+                    parsed.exit_early = Some(
+                        args.next()
+                            .ok_or_else(|| anyhow::anyhow!("Missing severity for {arg}"))?
+                            .parse()?,
+                    );
+                }
+                "--max-findings" => {
+                    let max = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("Missing number for {arg}"))?;
+                    parsed.max_findings =
+                        Some(max.parse().ok().filter(|m| *m > 0).ok_or_else(|| {
+                            anyhow::anyhow!("Invalid number of findings {max:?}")
+                        })?);
+                }
+                "--full-report" => parsed.max_findings = None,
+                "--origin" => parsed.origins.push(
+                    args.next()
+                        .ok_or_else(|| anyhow::anyhow!("Missing package origin for {arg}"))?,
+                ),
+                "--timings" => {
+                    parsed.measurements = parsed.measurements.max(Measurements::Timings);
+                }
+                "--verbose" => parsed.measurements = Measurements::PackageStats,
+                "--no-wait" => parsed.no_wait = true,
+                "--offline" => parsed.network = Network::Offline,
+                "--dry-run" => parsed.dry_run = true,
+                "--containers" => parsed.containers = true,
+                "--impact-of" => parsed.impact_of = true,
+                "--category-exit-codes" => parsed.exit_codes = ExitCodes::Categories,
+                "--watch" => parsed.mode = Mode::Watch,
+                "--service" => parsed.mode = Mode::Service,
+                _ if arg.starts_with("--") => anyhow::bail!("Unknown option {arg:?}"),
+                _ => parsed.packages.push(arg),
+            }
+        }
+        parsed.validate()
+    }
+
+    /// Check combinations of parsed arguments, and move packages to the subcommand using them instead
+    fn validate(mut self) -> anyhow::Result<Self> {
+        if self.impact_of && self.operation.is_some() {
+            anyhow::bail!("--impact-of can not be used with --operation");
+        }
+        if let Subcommand::CheckFiles(files) = &mut self.command {
+            files.extend(self.packages.drain(..).map(PathBuf::from));
+            if files.is_empty() {
+                anyhow::bail!("Missing files to check");
+            }
+            // ldd may run code of the inspected files
+            if self.resolver == Some(Resolver::Ldd) {
+                anyhow::bail!("Files are only checked with the native resolver, --resolver ldd can not be used");
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// Exit code when new findings are reported in service mode, findings for a package archive,
+/// or findings with the severity of `--exit-early`
+const EXIT_NEW_FINDINGS: u8 = 2;
+
+/// Exit code base when interrupted by a signal, as done by shells
+const EXIT_SIGNAL_BASE: u8 = 128;
+
+/// Exit code bit with `--category-exit-codes`, when broken or shadowed library dependencies are found
+const EXIT_DEPENDENCIES: u8 = 8;
+
+/// Exit code bit with `--category-exit-codes`, when broken systemd service links are found
+const EXIT_SYSTEMD: u8 = 16;
+
+/// Exit code bit with `--category-exit-codes`, when broken Python packages are found
+const EXIT_PYTHON: u8 = 32;
+
+/// Exit code bit with `--category-exit-codes`, when other checks find problems
+const EXIT_OTHER: u8 = 64;
+
+/// Get exit code with a bit set for each category of reported findings, ignoring informational ones.
+///
+/// Bits are above error exit codes, and below the signal exit code base.
+fn category_exit_code(findings: &[&Finding]) -> u8 {
+    findings
+        .iter()
+        .filter(|f| f.severity() >= Severity::Warning)
+        .fold(0, |code, f| {
+            code | match f.kind() {
+                CheckKind::MissingDependencies
+                | CheckKind::DuplicateLibraries
+                | CheckKind::LibraryPermissions
+                | CheckKind::LibraryLinks
+                | CheckKind::IntrospectionTypelibs
+                | CheckKind::PerlModules
+                | CheckKind::SymbolVersions => EXIT_DEPENDENCIES,
+                CheckKind::ServiceLinks | CheckKind::LoginUnits => EXIT_SYSTEMD,
+                CheckKind::PythonPackages => EXIT_PYTHON,
+                CheckKind::ScriptletFailures
+                | CheckKind::UnownedFiles
+                | CheckKind::PacnewFiles
+                | CheckKind::FileChecksums
+                | CheckKind::FileConflicts
+                | CheckKind::ConventionLinks
+                | CheckKind::SymlinkChains
+                | CheckKind::BootMounts
+                | CheckKind::KernelCmdline
+                | CheckKind::SecureBoot
+                | CheckKind::ModuleFirmware
+                | CheckKind::AurPackages
+                | CheckKind::StaleBuilds
+                | CheckKind::LegacyRuntimes
+                | CheckKind::SecurityAdvisories
+                | CheckKind::External => EXIT_OTHER,
+            }
+        })
+}
+
+/// Combine the exit codes of the checks of two containers.
+///
+/// Category bits are combined, and the highest error code is kept, other exit codes are combined by keeping the
+/// highest one.
+fn combine_exit_codes(code: u8, other: u8, exit_codes: ExitCodes) -> u8 {
+    let error_mask = EXIT_DEPENDENCIES - 1;
+    match exit_codes {
+        ExitCodes::Categories if code < EXIT_SIGNAL_BASE && other < EXIT_SIGNAL_BASE => {
+            ((code | other) & !error_mask) | (code & error_mask).max(other & error_mask)
+        }
+        ExitCodes::Categories | ExitCodes::Default => code.max(other),
+    }
+}
+
+/// Get exit code for findings of a completed run, only counting findings not in the baseline of service mode
+fn findings_exit_code(
+    findings: &[Finding],
+    baseline: Option<&Baseline>,
+    exit_codes: ExitCodes,
+    exit_early: Option<Severity>,
+) -> ExitCode {
+    let reported: Vec<_> = findings
+        .iter()
+        .filter(|f| baseline.is_none_or(|b| !b.contains(f)))
+        .collect();
+    match exit_codes {
+        ExitCodes::Categories => ExitCode::from(category_exit_code(&reported)),
+        ExitCodes::Default if baseline.is_some() && !reported.is_empty() => {
+            ExitCode::from(EXIT_NEW_FINDINGS)
+        }
+        ExitCodes::Default
+            if exit_early.is_some_and(|s| {
+                reported
+                    .iter()
+                    .any(|f| !matches!(f, Finding::CheckSkipped { .. }) && f.severity() >= s)
+            }) =>
+        {
+            ExitCode::from(EXIT_NEW_FINDINGS)
+        }
+        ExitCodes::Default => ExitCode::SUCCESS,
+    }
+}
+
+/// Get exit code for an error, so that scripts can tell apart error causes
+fn error_exit_code(err: &anyhow::Error) -> u8 {
+    match ErrorKind::of(err) {
+        None => 1,
+        Some(ErrorKind::Config) => 3,
+        Some(ErrorKind::PacmanQuery) => 4,
+        Some(ErrorKind::ElfParse) => 5,
+        Some(ErrorKind::Io) => 6,
+        Some(ErrorKind::DbBusy) => 7,
+    }
+}
+
+/// Get checks to run
+fn select_checks(
+    config: &Config,
+    selection: &Selection,
+    check_toggles: &[(String, bool)],
+) -> anyhow::Result<Vec<Box<dyn Check>>> {
+    let all = checks::all_checks(config)?;
+    match selection {
+        Selection::All => checks::select_checks(
+            checks::order_checks(all, config.check_order())?,
+            check_toggles,
+        ),
+        Selection::Only(name) => {
+            let selected: Vec<_> = all.into_iter().filter(|c| c.name() == name).collect();
+            if selected.is_empty() {
+                return Err(anyhow::anyhow!("Unknown check {name:?}")).classify(ErrorKind::Config);
+            }
+            Ok(selected)
+        }
+    }
+}
+
+/// Get the documentation of a check, with whether the configuration enables it
+fn describe(config: &Config, name: &str) -> anyhow::Result<String> {
+    let check = select_checks(config, &Selection::Only(name.to_owned()), &[])?.remove(0);
+    let enabled = checks::select_checks(checks::all_checks(config)?, config.check_toggles())?
+        .iter()
+        .any(|c| c.name() == name);
+    let doc = check.doc();
+    let mut text = format!(
+        "{name} ({})\n\n{}\n",
+        if enabled { "enabled" } else { "disabled" },
+        doc.detects
+    );
+    for (title, items) in [
+        ("Caveats", &doc.caveats),
+        ("Configuration", &doc.config_keys),
+    ] {
+        if !items.is_empty() {
+            let _ = write!(text, "\n{title}:\n");
+            for item in items {
+                let _ = writeln!(text, "  - {item}");
+            }
+        }
+    }
+    if !doc.examples.is_empty() {
+        text.push_str("\nExample findings:\n");
+        for finding in &doc.examples {
+            let _ = writeln!(text, "  - {}: {finding}", finding.severity());
+        }
+    }
+    Ok(text)
+}
+
+/// Parse a package list, one package per line
+fn parse_package_list(list: &str) -> impl Iterator<Item = String> + '_ {
+    list.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(ToOwned::to_owned)
+}
+
+/// Read a package list from a file, or stdin if `source` is `-`
+fn read_package_list(source: &str) -> anyhow::Result<Vec<String>> {
+    let list = if source == "-" {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(source)?
+    };
+    Ok(parse_package_list(&list).collect())
+}
+
+/// Report findings of a package archive or staged directory tree
+fn report_tree_findings(findings: &[Finding]) -> ExitCode {
+    print_findings(findings, None);
+    if findings.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_NEW_FINDINGS)
+    }
+}
+
+/// Adapt checks to the transaction operation that ran the hook, and get the packages to check:
+/// installs only check the installed packages, upgrades check the upgraded packages and the packages
+/// depending on them, and removals check the packages depending on the removed ones or on what they provided,
+/// besides system wide checks.
+///
+/// Transaction targets are read from stdin if not given, as pacman passes them to hooks with `NeedsTargets`.
+/// Removed packages are also returned if missing dependencies are checked, to look up their libraries.
+fn operation_packages(
+    operation: Option<Operation>,
+    checks: &mut Vec<Box<dyn Check>>,
+    packages: Option<Vec<String>>,
+) -> anyhow::Result<(Option<Vec<String>>, Vec<String>)> {
+    let targets = match (operation, packages) {
+        (None, packages) => return Ok((packages, Vec::new())),
+        (_, Some(targets)) => targets,
+        (_, None) => read_package_list("-").context("Failed to read transaction targets")?,
+    };
+    if operation == Some(Operation::Install) {
+        checks.retain(|c| c.inputs().contains(&Input::Packages));
+        return Ok((Some(targets), Vec::new()));
+    }
+    // Removed packages are not in the local database anymore, what they provided is only known from the repositories
+    let provides = if operation == Some(Operation::Remove) {
+        syncdb::package_provides(&targets).unwrap_or_else(|err| {
+            log::warn!("Failed to get names provided by the removed packages: {err:#}");
+            Vec::new()
+        })
+    } else {
+        Vec::new()
+    };
+    let dependents = backend::reverse_dependencies(backend::open().as_ref(), &targets, &provides)
+        .context("Failed to get packages depending on the transaction targets")?;
+    log::debug!(
+        "Packages depending on the transaction targets: {}",
+        dependents.join(", ")
+    );
+    if operation == Some(Operation::Upgrade) {
+        // Unlike removed packages, upgraded ones, and the ones installed by the same transaction, are still installed
+        let mut changed = targets;
+        changed.extend(dependents);
+        changed.sort_unstable();
+        changed.dedup();
+        return Ok((Some(changed), Vec::new()));
+    }
+    let removed = if checks
+        .iter()
+        .any(|c| c.name() == CheckKind::MissingDependencies.name())
+    {
+        targets
+    } else {
+        Vec::new()
+    };
+    Ok((Some(dependents), removed))
+}
+
+/// Get the packages given on the command line and in the list of `packages_from`, `None` to check foreign packages,
+/// or the packages impacted by their changes with `impact_of`
+fn target_packages(
+    mut packages: Vec<String>,
+    packages_from: Option<&str>,
+    impact_of: bool,
+    checks: &mut Vec<Box<dyn Check>>,
+) -> anyhow::Result<Option<Vec<String>>> {
+    if let Some(source) = packages_from {
+        packages.extend(
+            read_package_list(source)
+                .with_context(|| format!("Failed to read package list from {source:?}"))?,
+        );
+    }
+    if impact_of {
+        return impacted_packages(checks, &packages).map(Some);
+    }
+    Ok((!packages.is_empty()).then_some(packages))
+}
+
+/// Get the installed packages using libraries of `changed` packages, upgraded or removed, and the installed
+/// changed packages, only keeping the checks analyzing them
+fn impacted_packages(
+    checks: &mut Vec<Box<dyn Check>>,
+    changed: &[String],
+) -> anyhow::Result<Vec<String>> {
+    if changed.is_empty() {
+        anyhow::bail!("Missing packages for --impact-of");
+    }
+    checks.retain(|c| c.inputs().contains(&Input::Packages));
+    let files_db = FilesDb::open()?;
+    let impacted = checks::impacted_packages(backend::open().as_ref(), files_db.as_ref(), changed)?;
+    log::info!(
+        "Checking {} package(s) impacted by changes of {}",
+        impacted.len(),
+        changed.join(", ")
+    );
+    Ok(impacted)
+}
+
+/// Add findings about executables of installed packages using libraries of removed packages,
+/// that checks did not already report
+fn add_removal_findings(findings: &mut Vec<Finding>, removed: &[String]) -> anyhow::Result<()> {
+    if removed.is_empty() {
+        return Ok(());
+    }
+    let Some(files_db) = FilesDb::open()? else {
+        log::warn!(
+            "No files database to find libraries of the removed packages, download it with pacman -Fy"
+        );
+        return Ok(());
+    };
+    let reported: HashSet<_> = findings.iter().map(ToString::to_string).collect();
+    let breakage = checks::removal_breakage(backend::open().as_ref(), &files_db, removed)?;
+    findings.extend(
+        breakage
+            .into_iter()
+            .filter(|f| !reported.contains(&f.to_string())),
+    );
+    Ok(())
+}
+
+/// Check a staged directory tree, named after the package, like a makepkg `$pkgdir`
+fn check_prefix_dir(dir: &Path, selection: &Selection) -> anyhow::Result<ExitCode> {
+    if let Selection::Only(name) = selection {
+        if name != CheckKind::MissingDependencies.name() {
+            anyhow::bail!("Check {name:?} can not run on a directory tree");
+        }
+    }
+    let dir = dir
+        .canonicalize()
+        .with_context(|| format!("Invalid directory {}", dir.display()))?;
+    let package = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let findings = checks::check_tree(&dir, &package)?;
+    Ok(report_tree_findings(&findings))
+}
+
+/// Check files given on the command line, relative to the current directory
+fn check_files(files: &[PathBuf]) -> anyhow::Result<ExitCode> {
+    let paths = files
+        .iter()
+        .map(|f| {
+            f.canonicalize()
+                .with_context(|| format!("Invalid file {}", f.display()))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let findings = checks::check_files(backend::open().as_ref(), &paths);
+    Ok(report_tree_findings(&findings))
+}
+
+/// Check packages as they are installed or upgraded
+fn watch(
+    config: &Config,
+    selection: &Selection,
+    check_toggles: &[(String, bool)],
+) -> anyhow::Result<()> {
+    let local_db_dir = localdb::local_db_dir();
+    let local_db_dir = local_db_dir.as_path();
+    let mut watcher = LocalDbWatcher::new(local_db_dir)
+        .with_context(|| format!("Failed to watch {}", local_db_dir.display()))?;
+    log::info!("Watching {} for package changes", local_db_dir.display());
+    loop {
+        let packages = match watcher.wait_changes(local_db_dir) {
+            Err(_) if cancel::requested() => return Ok(()),
+            r => r?,
+        };
+        if packages.is_empty() {
+            continue;
+        }
+        log::info!("Checking changed packages: {}", packages.join(", "));
+        let _lock = RunLock::acquire(true)
+            .context("Failed to take run lock")?
+            .ok_or_else(|| anyhow::anyhow!("Failed to take run lock"))?;
+        // Only check the changed packages
+        let mut checks = select_checks(config, selection, check_toggles)?;
+        checks.retain(|c| !c.inputs().contains(&Input::System));
+        let findings = run_checks(
+            &backend::open().into(),
+            checks,
+            Some(packages),
+            false,
+            None,
+            None,
+        )?;
+        output::log_findings(&findings);
+        if cancel::requested() {
+            return Ok(());
+        }
+    }
+}
+
+/// Print the command rebuilding packages broken by library changes, and write it to the configured script,
+/// which is removed if there is nothing to rebuild
+fn report_rebuild(config: &Config, findings: &[Finding]) -> anyhow::Result<()> {
+    let Some(template) = config.rebuild_command() else {
+        return Ok(());
+    };
+    let command = rebuild::rebuild_command(template, &rebuild::broken_packages(findings));
+    if let Some(command) = &command {
+        println!("Rebuild broken packages with: {command}");
+    }
+    let Some(path) = config.rebuild_script() else {
+        return Ok(());
+    };
+    match command {
+        Some(command) => {
+            rebuild::write_script(path, &command)
+                .with_context(|| format!("Failed to write rebuild script {}", path.display()))?;
+            println!("Rebuild command written to {}", path.display());
+        }
+        None => match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                return Err(err).with_context(|| {
+                    format!("Failed to remove rebuild script {}", path.display())
+                });
+            }
+            _ => {}
+        },
+    }
+    Ok(())
+}
+
+/// Whether an error comes from missing permissions to write state, which only runs as root have
+fn is_permission_denied(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::PermissionDenied)
+}
+
+/// Send a report to the configured collection service, if any, retrying failed uploads with `retry`
+fn collect_report(config: &Config, report: &Report, retry: bool) -> anyhow::Result<()> {
+    let Some(url) = config.collect_url() else {
+        return Ok(());
+    };
+    if network::is_offline() {
+        log::debug!("Network is offline, not sending report to {url}");
+        return Ok(());
+    }
+    let token = config
+        .collect_token_file()
+        .map(|path| {
+            fs::read_to_string(path)
+                .map(|t| t.trim().to_owned())
+                .with_context(|| format!("Failed to read token file {}", path.display()))
+        })
+        .transpose()?;
+    collect::send(url, token.as_deref(), report, retry)?;
+    log::info!("Report sent to {url}");
+    Ok(())
+}
+
+/// Save the report of a full scan, with its provenance, and send it to the collection service.
+///
+/// Failed uploads are only retried with `retry`, since waiting for the service would hold up interactive runs.
+fn record_report(config: &Config, findings: &[Finding], checks: Vec<String>, retry: bool) {
+    let command = env::args().collect::<Vec<_>>().join(" ");
+    let report = Report::new(findings, checks, command);
+    match report.save() {
+        Err(err) if is_permission_denied(&err) => log::debug!("Not saving report: {err}"),
+        Err(err) => log::warn!("Failed to save report: {err:#}"),
+        Ok(()) => {}
+    }
+    if let Err(err) = collect_report(config, &report, retry) {
+        log::warn!("Failed to send report: {err:#}");
+    }
+}
+
+/// List saved reports, or print one of them
+fn show_reports(number: Option<usize>) -> anyhow::Result<()> {
+    let reports = Report::list().context("Failed to list reports")?;
+    match number {
+        Some(number) => {
+            let report = reports.get(number - 1).ok_or_else(|| {
+                anyhow::anyhow!("No report {number}, {} are saved", reports.len())
+            })?;
+            print!("{report}");
+        }
+        None if reports.is_empty() => log::info!("No saved reports"),
+        None => {
+            for (i, report) in reports.iter().enumerate() {
+                println!("{}: {}", i + 1, report.summary());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print the health score of a full scan and its trend, and record it
+fn report_health(findings: &[Finding]) {
+    let current = Health::new(findings);
+    let history = match HealthHistory::load() {
+        Ok(history) => history,
+        Err(err) => {
+            log::warn!("Failed to load health history: {err:#}");
+            return;
+        }
+    };
+    println!("{}", history.trend(current));
+    match history.append(current) {
+        Err(err) if is_permission_denied(&err) => log::debug!("Not saving health score: {err}"),
+        Err(err) => log::warn!("Failed to save health history: {err:#}"),
+        Ok(()) => {}
+    }
+}
+
+/// Suggest downgrading upgraded libraries from the package cache, if enabled,
+/// for packages with missing libraries and no newer version in the AUR
+fn report_downgrade(config: &Config, findings: &[Finding]) {
+    if !config.suggest_downgrade() {
+        return;
+    }
+    let packages = rebuild::broken_packages(findings);
+    if packages.is_empty() {
+        return;
+    }
+    let updated = rebuild::aur_updated_packages(backend::open().as_ref(), &packages);
+    for package in &updated {
+        println!("Package {package:?} has a newer version in the AUR, upgrade it instead of downgrading libraries");
+    }
+    let archives = rebuild::downgrade_archives(findings, &updated);
+    let Some(command) = rebuild::downgrade_command(&archives) else {
+        return;
+    };
+    println!("Missing libraries can be restored by downgrading the packages that provided them, with: {command}");
+    println!(
+        "Warning: partial upgrades are unsupported, and may break packages needing the new versions. \
+         Add the downgraded packages to IgnorePkg until their dependents are rebuilt, then upgrade them again."
+    );
+}
+
+/// Save the files of the directories watched for unowned files, before a transaction
+fn snapshot(config: &Config) -> anyhow::Result<ExitCode> {
+    if let Some(count) = checks::snapshot_unowned_dirs(config).context("Failed to save snapshot")? {
+        log::debug!("Saved snapshot of {count} files");
+    } else {
+        log::debug!("Keeping previous snapshot, not compared yet");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Warn about foreign packages that upgrading packages will break, upgrade targets are read from stdin if not given.
+///
+/// This runs before the transaction, so it must not fail it.
+fn predict(config: &Config, mut targets: Vec<String>) -> anyhow::Result<ExitCode> {
+    if targets.is_empty() {
+        targets = read_package_list("-").context("Failed to read upgrade targets")?;
+    }
+    let Some(files_db) = FilesDb::open()? else {
+        log::warn!("No files database to predict breakage, download it with pacman -Fy");
+        return Ok(ExitCode::SUCCESS);
+    };
+    let findings = checks::predict_breakage(backend::open().as_ref(), &files_db, &targets)?;
+    print_findings(&findings, None);
+    report_rebuild(config, &findings)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Get root directories of Arch containers: systemd-nspawn machines with a pacman database, and configured ones
+fn container_roots(config: &Config) -> anyhow::Result<Vec<PathBuf>> {
+    let mut roots: Vec<PathBuf> = match fs::read_dir(MACHINES_DIR) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|e| e.path())
+            .filter(|p| p.join(root::DEFAULT_DB_PATH).join("local").is_dir())
+            .collect(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err).context("Failed to list machines"),
+    };
+    roots.sort_unstable();
+    for root in config.container_roots() {
+        if !roots.contains(root) {
+            roots.push(root.to_owned());
+        }
+    }
+    Ok(roots)
+}
+
+/// Run checks in each container, and get the highest exit code.
+///
+/// Each container is checked by a child process with its root, since the checked root is process wide.
+fn check_containers(config: &Config, args: &Args) -> anyhow::Result<ExitCode> {
+    if args.root.is_some() || args.db_path.is_some() {
+        anyhow::bail!("--containers can not be used with --root or --dbpath");
+    }
+    let roots = container_roots(config)?;
+    if roots.is_empty() {
+        log::info!("No Arch container found");
+        return Ok(ExitCode::SUCCESS);
+    }
+    let exe = env::current_exe().context("Failed to get executable path")?;
+    let child_args: Vec<String> = env::args()
+        .skip(1)
+        .filter(|a| a != "--containers")
+        .collect();
+    let mut code = 0;
+    for root in roots {
+        if cancel::requested() {
+            break;
+        }
+        log::info!("Checking container {}", root.display());
+        let status = Command::new(&exe)
+            .args(&child_args)
+            .arg("--root")
+            .arg(&root)
+            .status()
+            .context("Failed to run checks in container")?;
+        // A container that can not be checked does not prevent checking the others
+        let child_code = status.code().map_or(1, |c| u8::try_from(c).unwrap_or(1));
+        if child_code != 0 {
+            log::warn!(
+                "Checking container {} exited with code {child_code}",
+                root.display()
+            );
+        }
+        code = combine_exit_codes(code, child_code, args.exit_codes);
+    }
+    Ok(ExitCode::from(code))
+}
+
+/// Init logger, signal handlers, the checked system and privileges, and get the configuration, loaded once for the run
+fn init(args: &Args) -> anyhow::Result<anyhow::Result<Config>> {
+    // The watch mode runs as a service too, its findings being logged
+    let style = if args.mode == Mode::Service
+        || (args.mode == Mode::Watch && env::var_os("JOURNAL_STREAM").is_some())
+    {
+        LogStyle::Journal
+    } else {
+        LogStyle::Terminal
+    };
+    Logger::init(style, args.debug_log.as_deref()).context("Failed to init logger")?;
+
+    cancel::install_handlers().context("Failed to install signal handlers")?;
+
+    output::set_max_findings(args.max_findings);
+    if !args.origins.is_empty() {
+        output::set_origin_filter(args.origins.clone())?;
+    }
+
+    if args.network == Network::Offline {
+        network::disable();
+    }
+    if args.resolver == Some(Resolver::Native) {
+        elf::resolve_natively();
+    }
+    // Only pacman hooks pass these
+    if args.operation.is_some() || args.no_wait {
+        checks::set_default_timeout(checks::HOOK_TIMEOUT);
+    }
+
+    if args.root.is_some() || args.db_path.is_some() {
+        root::set(
+            args.root.clone().unwrap_or_else(|| PathBuf::from("/")),
+            args.db_path.clone(),
+        )?;
+    }
+
+    // Loaded once: its errors, or the ones of its user, only fail the subcommands using it, the other ones run as the
+    // default user
+    let config = Config::load()
+        .context("Failed to load configuration")
+        .and_then(|config| {
+            privileges::drop_for_children(config.user()).context("Failed to drop privileges")?;
+            Ok(config)
+        });
+    if config.is_err() {
+        if let Err(err) = privileges::drop_for_children(Some(DEFAULT_USER)) {
+            log::warn!("Failed to drop privileges: {err:#}");
+        }
+    }
+    Ok(config)
+}
+
+/// Run subcommands that do not use the configuration, and get their exit code, `None` for other subcommands
+fn run_standalone(args: &Args) -> anyhow::Result<Option<ExitCode>> {
+    match &args.command {
+        Subcommand::Check(selection) => args
+            .prefix_dir
+            .as_ref()
+            .map(|dir| check_prefix_dir(dir, selection))
+            .transpose(),
+        Subcommand::GenSystemd(dir) => {
+            gen::write_systemd_units(dir)?;
+            Ok(Some(ExitCode::SUCCESS))
+        }
+        Subcommand::GenHooks(_)
+        | Subcommand::Predict
+        | Subcommand::Snapshot
+        | Subcommand::Describe(_) => Ok(None),
+        Subcommand::CheckPkgFile(archive) => {
+            Ok(Some(report_tree_findings(&pkgfile::check(archive)?)))
+        }
+        Subcommand::CheckFiles(files) => check_files(files).map(Some),
+        Subcommand::Schema => {
+            println!("{}", schema::findings_schema());
+            Ok(Some(ExitCode::SUCCESS))
+        }
+        Subcommand::History(number) => {
+            show_reports(*number)?;
+            Ok(Some(ExitCode::SUCCESS))
+        }
+        Subcommand::SelfTest => {
+            let probes = selftest::run();
+            for probe in &probes {
+                println!("{probe}");
+            }
+            Ok(Some(
+                if probes
+                    .iter()
+                    .any(|p| matches!(p.outcome(), Outcome::Failed(_)))
+                {
+                    ExitCode::FAILURE
+                } else {
+                    ExitCode::SUCCESS
+                },
+            ))
+        }
+        Subcommand::UpdateKb => {
+            let count = KnowledgeBase::update().context("Failed to update knowledge base")?;
+            log::info!("Knowledge base updated with {count} entries");
+            Ok(Some(ExitCode::SUCCESS))
+        }
+        Subcommand::ExportSuppressions => {
+            println!(
+                "{}",
+                suppressions::export().context("Failed to export suppressions")?
+            );
+            Ok(Some(ExitCode::SUCCESS))
+        }
+        Subcommand::ImportSuppressions(source) => {
+            let content = if source == "-" {
+                io::read_to_string(io::stdin())?
+            } else {
+                fs::read_to_string(source)?
+            };
+            let imported =
+                suppressions::import(&content).context("Failed to import suppressions")?;
+            log::info!(
+                "Imported {} known false positives and {} baseline findings",
+                imported.known_bundled_libs,
+                imported.baseline
+            );
+            Ok(Some(ExitCode::SUCCESS))
+        }
+    }
+}
+
+/// Apply the process wide settings of the configuration
+fn apply_config(config: Config) -> anyhow::Result<Config> {
+    if config.offline() {
+        network::disable();
+    }
+    if let Some(timeout) = config.network_probe_timeout() {
+        network::enable_probe(timeout);
+    }
+    overrides::set(config.severity_overrides().to_vec())?;
+    Ok(config)
+}
+
+fn run() -> anyhow::Result<ExitCode> {
+    let args = Args::parse(env::args().skip(1))?;
+    let config = init(&args)?;
+    if let Some(exit_code) = run_standalone(&args)? {
+        return Ok(exit_code);
+    }
+    let selection = match &args.command {
+        Subcommand::Check(selection) => selection,
+        _ => &Selection::All,
+    };
+    let config = apply_config(config?)?;
+
+    match &args.command {
+        Subcommand::Predict => return predict(&config, args.packages),
+        Subcommand::Snapshot => return snapshot(&config),
+        Subcommand::Describe(name) => {
+            print!("{}", describe(&config, name)?);
+            return Ok(ExitCode::SUCCESS);
+        }
+        _ if args.containers => return check_containers(&config, &args),
+        _ => {}
+    }
+
+    let check_toggles: Vec<(String, bool)> = config
+        .check_toggles()
+        .iter()
+        .cloned()
+        .chain(args.check_toggles)
+        .collect();
+    let mut checks = select_checks(&config, selection, &check_toggles)?;
+
+    if let Subcommand::GenHooks(dir) = &args.command {
+        gen::write_hooks(dir, &checks)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let packages = target_packages(
+        args.packages,
+        args.packages_from.as_deref(),
+        args.impact_of,
+        &mut checks,
+    )?;
+    let (packages, removed) = operation_packages(args.operation, &mut checks, packages)?;
+
+    if args.dry_run {
+        print!(
+            "{}",
+            plan_checks(backend::open().as_ref(), checks, packages)?
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.mode == Mode::Watch {
+        watch(&config, selection, &check_toggles)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let Some(_lock) = RunLock::acquire(!args.no_wait).context("Failed to take run lock")? else {
+        log::info!("Another instance is running, exiting");
+        return Ok(ExitCode::SUCCESS);
+    };
+    // Outside of pacman hooks, a concurrent transaction would make findings unreliable
+    backend::retry_while_busy(localdb::ensure_consistent)?;
+    let timings = match args.measurements {
+        Measurements::Off => None,
+        Measurements::Timings => Some(Arc::new(Timings::default())),
+        Measurements::PackageStats => Some(Arc::new(Timings::with_package_stats())),
+    };
+
+    let service = args.mode == Mode::Service;
+    let baseline = service
+        .then(Baseline::load)
+        .transpose()
+        .context("Failed to load baseline")?;
+
+    let full_scan = matches!(selection, Selection::All) && packages.is_none();
+    let check_names: Vec<String> = checks.iter().map(|c| c.name().to_owned()).collect();
+    let mut findings = run_checks(
+        &backend::open().into(),
+        checks,
+        packages,
+        !service,
+        timings.as_ref(),
+        args.exit_early,
+    )?;
+    add_removal_findings(&mut findings, &removed)?;
+    print_findings(&findings, baseline.as_ref());
+    report_rebuild(&config, &findings)?;
+    report_downgrade(&config, &findings);
+
+    if let Some(timings) = &timings {
+        eprint!("{timings}");
+    }
+
+    if let Some(signum) = cancel::signal() {
+        // Partial findings must not replace the baseline
+        return Ok(ExitCode::from(
+            EXIT_SIGNAL_BASE.saturating_add(u8::try_from(signum).unwrap_or_default()),
+        ));
+    }
+
+    // Findings of a scan stopped early, or of packages skipped while the database was busy, must not replace the
+    // baseline either, nor be scored
+    let complete = !findings.iter().any(Finding::is_partial);
+    if full_scan && complete {
+        report_health(&findings);
+        record_report(&config, &findings, check_names, service);
+    }
+    if baseline.is_some() && complete {
+        Baseline::save(&findings).context("Failed to save baseline")?;
+    }
+    Ok(findings_exit_code(
+        &findings,
+        baseline.as_ref(),
+        args.exit_codes,
+        args.exit_early,
+    ))
+}
+
+fn main() -> ExitCode {
+    run().unwrap_or_else(|err| {
+        // Same output as when returning the error from main
+        eprintln!("Error: {err:?}");
+        ExitCode::from(error_exit_code(&err))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_package_list() {
+        assert_eq!(
+            parse_package_list("foo\n\n  bar-git \n").collect::<Vec<_>>(),
+            ["foo", "bar-git"]
+        );
+    }
+
+    #[test]
+    fn test_check_files_resolver() {
+        let args = |extra: &[&str]| {
+            Args::parse(
+                ["check", "files", "/usr/bin/foo"]
+                    .iter()
+                    .chain(extra)
+                    .map(|a| (*a).to_owned()),
+            )
+        };
+        assert!(args(&[]).is_ok());
+        assert!(args(&["--resolver", "native"]).is_ok());
+        assert!(args(&["--resolver", "ldd"]).is_err());
+    }
+
+    #[test]
+    fn test_category_exit_code() {
+        let missing = Finding::MissingDependency {
+            package: Arc::new("foo".to_owned()),
+            file: Arc::new(PathBuf::from("/usr/bin/foo")),
+            dependency: PathBuf::from("libfoo.so.1"),
+            origin: None,
+        };
+        let link = Finding::BrokenServiceLink {
+            link: PathBuf::from("/etc/systemd/system/multi-user.target.wants/foo.service"),
+            package: None,
+        };
+        let pacnew = Finding::PacnewFile {
+            path: PathBuf::from("/etc/foo.conf"),
+        };
+        assert_eq!(category_exit_code(&[]), 0);
+        assert_eq!(category_exit_code(&[&pacnew]), 0);
+        assert_eq!(
+            category_exit_code(&[&missing, &link, &pacnew, &missing]),
+            EXIT_DEPENDENCIES | EXIT_SYSTEMD
+        );
+        assert_eq!(
+            combine_exit_codes(
+                EXIT_DEPENDENCIES | 4,
+                EXIT_PYTHON | 1,
+                ExitCodes::Categories
+            ),
+            EXIT_DEPENDENCIES | EXIT_PYTHON | 4
+        );
+        assert_eq!(
+            combine_exit_codes(EXIT_NEW_FINDINGS, 7, ExitCodes::Default),
+            7
+        );
+    }
+
+    #[test]
+    fn test_describe() {
+        let config = Config::default();
+        let text = describe(&config, &check_name("pacnew".to_owned())).unwrap();
+        assert!(text.starts_with(
+            "pacnew-files (disabled)\n\nConfiguration files with a pending .pacnew update\n\nCaveats:\n  - "
+        ));
+        assert!(text.contains("\nExample findings:\n  - "));
+        assert!(!text.contains("Configuration:"));
+        assert!(describe(&config, "unowned-files")
+            .unwrap()
+            .starts_with("unowned-files (enabled)\n"));
+        assert!(describe(&config, "foo").is_err());
+    }
+}
@@ -0,0 +1,55 @@
+//! Inspection of built package archives, before they are installed
+
+use std::{
+    fs,
+    path::{self, Path},
+};
+
+use anyhow::Context as _;
+use pacman_hooks_core::{checks, finding::Finding, privileges};
+
+/// Get package name from .PKGINFO content
+fn package_name(pkginfo: &str) -> Option<&str> {
+    pkginfo.lines().find_map(|l| {
+        let (key, value) = l.split_once('=')?;
+        (key.trim() == "pkgname").then(|| value.trim())
+    })
+}
+
+/// Extract a package archive, and check its files against the libraries of the system
+pub(crate) fn check(archive: &Path) -> anyhow::Result<Vec<Finding>> {
+    let dir = tempfile::tempdir()?;
+    // The archive is untrusted, it is extracted without privileges
+    privileges::give_to_children(dir.path())?;
+    let status = privileges::command("bsdtar")
+        .args(["-x", "--no-same-owner", "-f"])
+        .arg(path::absolute(archive)?)
+        .arg("-C")
+        .arg(dir.path())
+        .status()
+        .context("Failed to run bsdtar")?;
+    if !status.success() {
+        anyhow::bail!("Failed to extract {}", archive.display());
+    }
+    let pkginfo = fs::read_to_string(dir.path().join(".PKGINFO"))
+        .with_context(|| format!("{} is not a package archive", archive.display()))?;
+    let package = package_name(&pkginfo)
+        .ok_or_else(|| anyhow::anyhow!("Missing package name in .PKGINFO"))?;
+    checks::check_tree(dir.path(), package)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_name() {
+        let pkginfo = "# Generated by makepkg 6.1.0
+pkgname = foo-git
+pkgbase = foo-git
+pkgver = 1.0-1
+";
+        assert_eq!(package_name(pkginfo), Some("foo-git"));
+        assert_eq!(package_name("pkgver = 1.0-1\n"), None);
+    }
+}
@@ -0,0 +1,133 @@
+//! Watch local database for package changes
+
+use std::{
+    collections::HashSet,
+    ffi::CString,
+    fs::File,
+    io::{self, Read},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::ffi::OsStrExt,
+    },
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use pacman_hooks_core::{cancel, localdb};
+
+/// Time without database changes after which a transaction is considered finished
+const QUIET_PERIOD: Duration = Duration::from_secs(5);
+
+/// Size of `struct inotify_event` without the name
+const EVENT_HEADER_SIZE: usize = 16;
+
+/// Inotify based watcher for new local database package entries
+pub(crate) struct LocalDbWatcher {
+    inotify: File,
+    db_lock_path: PathBuf,
+}
+
+impl LocalDbWatcher {
+    pub(crate) fn new(local_db_dir: &Path) -> anyhow::Result<Self> {
+        // SAFETY: no pointer arguments
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        // SAFETY: fd was just returned by inotify_init1, and is not owned by anything else
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let dir = CString::new(local_db_dir.as_os_str().as_bytes())?;
+        // SAFETY: fd is a valid inotify file descriptor, and dir a NUL terminated string
+        let wd = unsafe {
+            libc::inotify_add_watch(
+                fd.as_raw_fd(),
+                dir.as_ptr(),
+                libc::IN_CREATE | libc::IN_MOVED_TO | libc::IN_ONLYDIR,
+            )
+        };
+        if wd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        let db_lock_path = local_db_dir
+            .parent()
+            .ok_or_else(|| anyhow::anyhow!("Invalid local database path {local_db_dir:?}"))?
+            .join("db.lck");
+        Ok(Self {
+            inotify: File::from(fd),
+            db_lock_path,
+        })
+    }
+
+    /// Block until new package entries are added to the database, and the transaction that added them is finished.
+    ///
+    /// Returns the names of the added packages that are still installed.
+    pub(crate) fn wait_changes(&mut self, local_db_dir: &Path) -> anyhow::Result<Vec<String>> {
+        let mut entries = HashSet::new();
+        self.wait_readable(None)?;
+        self.read_events(&mut entries)?;
+        loop {
+            if self.wait_readable(Some(QUIET_PERIOD))? {
+                self.read_events(&mut entries)?;
+            } else if !self.db_lock_path.exists() {
+                break;
+            }
+        }
+
+        let mut packages: Vec<String> = entries
+            .into_iter()
+            .filter(|e| local_db_dir.join(e).is_dir())
+            .filter_map(|e| localdb::package_name(&e).map(ToOwned::to_owned))
+            .collect();
+        packages.sort_unstable();
+        packages.dedup();
+        Ok(packages)
+    }
+
+    /// Wait for events to be available, return false on timeout
+    fn wait_readable(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        let mut pollfd = libc::pollfd {
+            fd: self.inotify.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout_ms = timeout.map_or(-1, |t| i32::try_from(t.as_millis()).unwrap_or(i32::MAX));
+        loop {
+            // SAFETY: pollfd is a valid pollfd struct, and its count is 1
+            let ret = unsafe { libc::poll(&raw mut pollfd, 1, timeout_ms) };
+            if ret >= 0 {
+                return Ok(ret > 0);
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted || cancel::requested() {
+                return Err(err);
+            }
+        }
+    }
+
+    /// Read pending events, and add the names of created entries
+    fn read_events(&mut self, entries: &mut HashSet<String>) -> io::Result<()> {
+        let mut buf = [0; 4096];
+        let len = self.inotify.read(&mut buf)?;
+        let mut offset = 0;
+        while let Some(header) = buf.get(offset..offset + EVENT_HEADER_SIZE) {
+            if offset >= len {
+                break;
+            }
+            let name_len = u32::from_ne_bytes([header[12], header[13], header[14], header[15]]);
+            let name_start = offset + EVENT_HEADER_SIZE;
+            let name_end = name_start + name_len as usize;
+            if let Some(name) = buf.get(name_start..name_end.min(len)) {
+                let name = name.split(|b| *b == 0).next().unwrap_or_default();
+                if let Ok(name) = std::str::from_utf8(name) {
+                    if !name.is_empty() {
+                        entries.insert(name.to_owned());
+                    }
+                }
+            }
+            offset = name_end;
+        }
+        Ok(())
+    }
+}